@@ -13,6 +13,7 @@ use std::str::FromStr;
 use std::fs;
 use anyhow::{Result, Context};
 use raydium_amm::instruction::{self, AmmInstruction};
+use raydium_amm::process::{constants, helpers};
 use spl_token_2022::{
     extension::StateWithExtensions,
     state::Mint,
@@ -351,21 +352,39 @@ async fn init_amm_pool(
     println!("  Open Time: {}", open_time);
     println!("  Payer: {}", payer.pubkey());
     
-    // Generate keypairs for the pool
-    let amm_pool_keypair = Keypair::new();
-    let amm_authority_keypair = Keypair::new();
-    let amm_open_orders_keypair = Keypair::new();
-    let amm_target_orders_keypair = Keypair::new();
-    let amm_coin_vault_keypair = Keypair::new();
-    let amm_pc_vault_keypair = Keypair::new();
-    let amm_lp_mint_keypair = Keypair::new();
-    let pool_withdraw_queue_keypair = Keypair::new();
-    let lp_withdraw_queue_keypair = Keypair::new();
-    
     // Parse pubkeys
     let amm_program_pubkey = Pubkey::from_str(&amm_program_id)?;
     let coin_mint_pubkey = Pubkey::from_str(&coin_mint)?;
     let pc_mint_pubkey = Pubkey::from_str(&pc_mint)?;
+
+    // The pool accounts are created by the program at addresses derived from the mint pair
+    let market = Pubkey::default(); // no market, swap only pool
+    let fee_tier = 0; // default swap fee
+    let pool_key = helpers::get_pool_key(&market, &coin_mint_pubkey, &pc_mint_pubkey, fee_tier);
+    let derive = |seed: &[u8]| {
+        helpers::get_associated_address_and_bump_seed(
+            &amm_program_pubkey,
+            &pool_key,
+            seed,
+            &amm_program_pubkey,
+        )
+        .0
+    };
+    let amm_pool = derive(constants::AMM_ASSOCIATED_SEED);
+    let amm_open_orders = derive(constants::OPEN_ORDER_ASSOCIATED_SEED);
+    let amm_target_orders = derive(constants::TARGET_ASSOCIATED_SEED);
+    let amm_coin_vault = derive(constants::COIN_VAULT_ASSOCIATED_SEED);
+    let amm_pc_vault = derive(constants::PC_VAULT_ASSOCIATED_SEED);
+    let amm_lp_mint = derive(constants::LP_MINT_ASSOCIATED_SEED);
+    let amm_authority =
+        helpers::authority_id(&amm_program_pubkey, constants::AUTHORITY_AMM, nonce)?;
+    let amm_owner = constants::get_amm_owner_id()?;
+    let (amm_config, _) = helpers::get_associated_address_and_bump_seed(
+        &amm_owner,
+        &amm_owner,
+        constants::AMM_CONFIG_SEED,
+        &amm_program_pubkey,
+    );
     
     // Check if payer has enough SOL
     let payer_balance = rpc_client.get_balance(&payer.pubkey())?;
@@ -387,17 +406,7 @@ async fn init_amm_pool(
     );
     let payer_lp_ata = spl_associated_token_account::get_associated_token_address(
         &payer.pubkey(),
-        &amm_lp_mint_keypair.pubkey(),
-    );
-    
-    // Create AMM vault ATAs
-    let amm_coin_vault_ata = spl_associated_token_account::get_associated_token_address(
-        &amm_coin_vault_keypair.pubkey(),
-        &coin_mint_pubkey,
-    );
-    let amm_pc_vault_ata = spl_associated_token_account::get_associated_token_address(
-        &amm_pc_vault_keypair.pubkey(),
-        &pc_mint_pubkey,
+        &amm_lp_mint,
     );
     
     let mut instructions = vec![];
@@ -417,41 +426,22 @@ async fn init_amm_pool(
         &spl_token::id(),
     ));
     
-    instructions.push(ata_instruction::create_associated_token_account(
-        &payer.pubkey(),
-        &payer.pubkey(),
-        &amm_lp_mint_keypair.pubkey(),
-        &spl_token::id(),
-    ));
-    
-    // Create AMM accounts
-    let amm_account_size = 752; // Size of AmmInfo struct
-    let amm_lamports = rpc_client.get_minimum_balance_for_rent_exemption(amm_account_size)?;
-    
-    instructions.push(system_instruction::create_account(
-        &payer.pubkey(),
-        &amm_pool_keypair.pubkey(),
-        amm_lamports,
-        amm_account_size as u64,
-        &amm_program_pubkey,
-    ));
-    
     // Create initialize instruction using actual instruction builder
     let init_instruction = instruction::initialize2(
         &amm_program_pubkey,
-        &amm_pool_keypair.pubkey(),
-        &amm_authority_keypair.pubkey(),
-        &amm_open_orders_keypair.pubkey(),
-        &amm_lp_mint_keypair.pubkey(),
+        &amm_pool,
+        &amm_authority,
+        &amm_open_orders,
+        &amm_lp_mint,
         &coin_mint_pubkey,
         &pc_mint_pubkey,
-        &amm_coin_vault_ata,
-        &amm_pc_vault_ata,
-        &amm_target_orders_keypair.pubkey(),
-        &Pubkey::default(), // config
-        &Pubkey::default(), // create_fee_destination
+        &amm_coin_vault,
+        &amm_pc_vault,
+        &amm_target_orders,
+        &amm_config,
+        &constants::get_create_pool_fee_address_id()?, // lamports create pool fee
         &Pubkey::default(), // market_program
-        &Pubkey::default(), // market
+        &market,
         &payer.pubkey(),
         &payer_coin_ata,
         &payer_pc_ata,
@@ -460,6 +450,10 @@ async fn init_amm_pool(
         open_time,
         init_pc_amount,
         init_coin_amount,
+        0, // launch_fee_numerator, no launch fee
+        0, // launch_fee_decay_seconds
+        fee_tier,
+        None, // no token account, the create pool fee is paid in lamports
     )?;
     
     instructions.push(init_instruction);
@@ -471,18 +465,12 @@ async fn init_amm_pool(
         Some(&payer.pubkey()),
     );
     
-    transaction.sign(
-        &[payer, &amm_pool_keypair, &amm_authority_keypair, 
-          &amm_open_orders_keypair, &amm_target_orders_keypair, &amm_coin_vault_keypair,
-          &amm_pc_vault_keypair, &amm_lp_mint_keypair, &pool_withdraw_queue_keypair,
-          &lp_withdraw_queue_keypair],
-        recent_blockhash
-    );
+    transaction.sign(&[payer], recent_blockhash);
     
     println!("Sending initialization transaction...");
     let signature = rpc_client.send_and_confirm_transaction(&transaction)?;
     println!("Pool initialized successfully!");
-    println!("  Pool Address: {}", amm_pool_keypair.pubkey());
+    println!("  Pool Address: {}", amm_pool);
     println!("  Transaction: {}", signature);
     println!("  Explorer: https://explorer.solana.com/tx/{}?cluster=devnet", signature);
     
@@ -237,6 +237,63 @@ pub enum AmmError {
     /// Hook program not supported for auto-initialization
     #[error("Hook program not supported for auto-initialization")]
     HookProgramNotSupportedForAutoInit,
+    /// The observation account does not belong to the amm
+    #[error("Invalid observation account")]
+    InvalidObservationAccount,
+    /// No observation covers the requested twap window
+    #[error("Insufficient observations for the twap window")]
+    InsufficientObservations,
+    /// The pool invariant plus the flash fee does not hold after the flash loan callback
+    #[error("Flash loan not repaid")]
+    FlashLoanNotRepaid,
+    /// The flash loan callback program is not allowed
+    #[error("Invalid flash loan callback program")]
+    InvalidFlashLoanCallback,
+    /// The lp lock account does not match the amm or the owner
+    #[error("Invalid lp lock account")]
+    InvalidLpLockAccount,
+    /// The unlock end time is before the unlock start time or already passed
+    #[error("Invalid unlock schedule")]
+    InvalidUnlockSchedule,
+    /// The requested lp amount is not unlocked yet
+    #[error("Lp is still locked")]
+    LpStillLocked,
+    /// The allowlist account does not match the amm
+    #[error("Invalid allowlist account")]
+    InvalidAllowlistAccount,
+    /// The wallet is not registered in the allowlist of the launch phase
+    #[error("Wallet is not allowlisted")]
+    NotAllowlisted,
+    /// The swap exceeds the allowlist cap of the wallet
+    #[error("Allowlist cap exceeded")]
+    AllowlistCapExceeded,
+    /// The swap exceeds a circuit breaker limit of the pool
+    #[error("Circuit breaker tripped")]
+    CircuitBreakerTripped,
+    /// The AMM account layout is older than the instruction needs, run MigratePoolAccount first
+    #[error("AMM account needs MigratePoolAccount")]
+    OutdatedAmmAccount,
+    /// InvalidCreatePoolFeeAccount
+    #[error("Invalid create pool fee account")]
+    InvalidCreatePoolFeeAccount,
+    /// The signer is not the pool admin of the amm
+    #[error("Signer is not the pool admin")]
+    InvalidPoolAdmin,
+    /// The signer is not the fee admin of the amm
+    #[error("Signer is not the fee admin")]
+    InvalidFeeAdmin,
+    /// The signer is not the pause guardian of the amm
+    #[error("Signer is not the pause guardian")]
+    InvalidPauseGuardian,
+    /// The signer is not the crank operator of the amm
+    #[error("Signer is not the crank operator")]
+    InvalidCrankOperator,
+    /// The signer is not the whitelist admin of the amm
+    #[error("Signer is not the whitelist admin")]
+    InvalidWhitelistAdmin,
+    /// The pool still has lp supply or unclaimed pnl
+    #[error("Pool is not drained")]
+    PoolNotDrained,
 }
 
 impl From<AmmError> for ProgramError {
@@ -327,6 +384,25 @@ impl AmmError {
             AmmError::HookMetaListInvalid => msg!("Error: Hook meta list invalid"),
             AmmError::HookMetaListAutoInitFailed => msg!("Error: Auto-initialization of hook meta list failed"),
             AmmError::HookProgramNotSupportedForAutoInit => msg!("Error: Hook program not supported for auto-initialization"),
+            AmmError::InvalidObservationAccount => msg!("Error: Invalid observation account"),
+            AmmError::InsufficientObservations => msg!("Error: Insufficient observations for the twap window"),
+            AmmError::FlashLoanNotRepaid => msg!("Error: Flash loan not repaid"),
+            AmmError::InvalidFlashLoanCallback => msg!("Error: Invalid flash loan callback program"),
+            AmmError::InvalidLpLockAccount => msg!("Error: Invalid lp lock account"),
+            AmmError::InvalidUnlockSchedule => msg!("Error: Invalid unlock schedule"),
+            AmmError::LpStillLocked => msg!("Error: Lp is still locked"),
+            AmmError::InvalidAllowlistAccount => msg!("Error: Invalid allowlist account"),
+            AmmError::NotAllowlisted => msg!("Error: Wallet is not allowlisted"),
+            AmmError::AllowlistCapExceeded => msg!("Error: Allowlist cap exceeded"),
+            AmmError::CircuitBreakerTripped => msg!("Error: Circuit breaker tripped"),
+            AmmError::OutdatedAmmAccount => msg!("Error: AMM account needs MigratePoolAccount"),
+            AmmError::InvalidCreatePoolFeeAccount => msg!("Error: Invalid create pool fee account"),
+            AmmError::InvalidPoolAdmin => msg!("Error: Signer is not the pool admin"),
+            AmmError::InvalidFeeAdmin => msg!("Error: Signer is not the fee admin"),
+            AmmError::InvalidPauseGuardian => msg!("Error: Signer is not the pause guardian"),
+            AmmError::InvalidCrankOperator => msg!("Error: Signer is not the crank operator"),
+            AmmError::InvalidWhitelistAdmin => msg!("Error: Signer is not the whitelist admin"),
+            AmmError::PoolNotDrained => msg!("Error: Pool still has lp supply or unclaimed pnl"),
             AmmError::UnknownAmmError => msg!("Error: UnknownAmmError"),
         }
     }
@@ -3,7 +3,10 @@
 #![allow(clippy::too_many_arguments)]
 #![allow(deprecated)]
 
-use crate::state::{AmmParams, Fees, LastOrderDistance, SimulateParams};
+use crate::state::{
+    AmmParams, CircuitBreaker, CrankReward, DynamicFee, Fees, LastOrderDistance, PnlSplit,
+    RoleAssignment, SimulateParams,
+};
 use arrayref::array_ref;
 use solana_program::{
     instruction::{AccountMeta, Instruction},
@@ -35,6 +38,14 @@ pub struct InitializeInstruction2 {
     pub init_pc_amount: u64,
     /// init token coin amount
     pub init_coin_amount: u64,
+    /// swap fee numerator charged at open_time, decays to the configured swap fee.
+    /// 0 means no launch fee
+    pub launch_fee_numerator: u32,
+    /// number of seconds after open_time the launch fee decays over
+    pub launch_fee_decay_seconds: u32,
+    /// swap fee numerator of the pool, TEN_THOUSAND as denominator. 0 means the default fee.
+    /// Pools without a market derive their accounts from the mint pair and this fee tier
+    pub fee_tier: u32,
 }
 
 #[repr(C)]
@@ -82,6 +93,9 @@ pub struct SetParamsInstruction {
     pub new_pubkey: Option<Pubkey>,
     pub fees: Option<Fees>,
     pub last_order_distance: Option<LastOrderDistance>,
+    pub dynamic_fee: Option<DynamicFee>,
+    pub circuit_breaker: Option<CircuitBreaker>,
+    pub crank_reward: Option<CrankReward>,
 }
 
 #[repr(C)]
@@ -114,6 +128,17 @@ pub struct SimulateInstruction {
     pub param: u8,
     pub swap_base_in_value: Option<SwapInstructionBaseIn>,
     pub swap_base_out_value: Option<SwapInstructionBaseOut>,
+    pub fee_growth_value: Option<FeeGrowthSnapshot>,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct FeeGrowthSnapshot {
+    /// lp balance the fees are accrued to
+    pub lp_amount: u64,
+    /// Q64.64 fee growth per lp token when the lp balance was last changed
+    pub fee_growth_coin_per_lp: u128,
+    pub fee_growth_pc_per_lp: u128,
 }
 
 #[repr(C)]
@@ -122,6 +147,89 @@ pub struct AdminCancelOrdersInstruction {
     pub limit: u16,
 }
 
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct GetTwapInstruction {
+    /// twap window in seconds, ending at the last price update
+    pub window: u64,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct DepositSingleSidedInstruction {
+    /// Amount of the user source token, part of it is swapped at pool price
+    /// and the rest is deposited together with the swap output
+    pub amount_in: u64,
+    /// Minimum lp amount to mint
+    pub minimum_lp_out: u64,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct WithdrawSingleSidedInstruction {
+    /// Pool token amount to burn, the other token share is swapped back
+    /// into the pool at the current reserves
+    pub amount: u64,
+    /// Minimum amount of the user destination token
+    pub min_amount_out: u64,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct LockLpInstruction {
+    /// Pool token amount to lock
+    pub amount: u64,
+    /// Nothing is unlocked before this time
+    pub unlock_start_time: u64,
+    /// Everything is unlocked at this time, linear vesting in between.
+    /// Equal to unlock_start_time for a plain timelock
+    pub unlock_end_time: u64,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct UnlockLpInstruction {
+    /// Unlocked pool token amount to withdraw from the lock
+    pub amount: u64,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct LockLpPermanentInstruction {
+    /// Pool token amount to lock forever
+    pub amount: u64,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct InitializeAllowlistInstruction {
+    /// Root of the merkle tree of hash(wallet, cap) leaves
+    pub merkle_root: [u8; 32],
+    /// The pool opens to everyone at this time, the allowlist phase starts at the pool open time
+    pub allowlist_end_time: u64,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RegisterAllowlistWalletInstruction {
+    /// Coin amount the wallet can swap during the allowlist phase
+    pub cap: u64,
+    /// Merkle proof of the wallet leaf, sorted pair hashes from the leaf up
+    pub proof: Vec<[u8; 32]>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FlashLoanInstruction {
+    /// coin amount lent to the borrower
+    pub coin_amount: u64,
+    /// pc amount lent to the borrower
+    pub pc_amount: u64,
+    /// number of transfer hook accounts in front of the callback accounts, must be 0 as the
+    /// vaults are spl token accounts
+    pub hook_account_num: u8,
+    /// instruction data of the callback
+    pub callback_data: Vec<u8>,
+}
+
 /// Update config acccount params
 #[repr(C)]
 #[derive(Clone, Copy, Debug, Default, PartialEq)]
@@ -129,6 +237,9 @@ pub struct ConfigArgs {
     pub param: u8,
     pub owner: Option<Pubkey>,
     pub create_pool_fee: Option<u64>,
+    pub create_pool_fee_mint: Option<Pubkey>,
+    pub pnl_split: Option<PnlSplit>,
+    pub role: Option<RoleAssignment>,
 }
 
 /// Instructions supported by the AmmInfo program.
@@ -141,31 +252,50 @@ pub enum AmmInstruction {
     Initialize(InitializeInstruction),
 
     ///   Initializes a new AMM pool.
+    ///   The pool accounts are created by the program at addresses derived from
+    ///   `find_program_address(&[program_id, pool_key, SEED])`, pool_key is the market,
+    ///   or the hash of the sorted mint pair and the fee tier for pools without a market.
+    ///   The vaults and the lp mint are created with the spl token program, so both mints
+    ///   must be spl token mints. Token-2022 mints are rejected.
+    ///
+    ///   The accounts follow the `initialize2` builder. Clients built for the previous layout
+    ///   must drop the withdraw queue, market bids, asks, event queue, market vaults, vault
+    ///   signer, srm and referrer accounts, which the program no longer reads.
     ///
     ///   0. `[]` Spl Token program id
     ///   1. `[]` Associated Token program id
     ///   2. `[]` Sys program id
     ///   3. `[]` Rent program id
-    ///   4. `[writable]` New AMM Account to create.
+    ///   4. `[writable]` New AMM Account to create, with AMM_ASSOCIATED_SEED.
     ///   5. `[]` $authority derived from `create_program_address(&[AUTHORITY_AMM, &[nonce]])`.
-    ///   6. `[writable]` AMM open orders Account
-    ///   7. `[writable]` AMM lp mint Account
+    ///   6. `[writable]` AMM open orders Account to create, with OPEN_ORDER_ASSOCIATED_SEED.
+    ///      Only created for pools with a market.
+    ///   7. `[writable]` AMM lp mint Account to create, with LP_MINT_ASSOCIATED_SEED.
     ///   8. `[]` AMM coin mint Account
     ///   9. `[]` AMM pc mint Account
-    ///   10. `[writable]` AMM coin vault Account. Must be non zero, owned by $authority.
-    ///   11. `[writable]` AMM pc vault Account. Must be non zero, owned by $authority.
-    ///   12. `[writable]` AMM target orders Account. To store plan orders informations.
-    ///   13. `[]` AMM config Account, derived from `find_program_address(&[&&AMM_CONFIG_SEED])`.
-    ///   14. `[]` AMM create pool fee destination Account
+    ///   10. `[writable]` AMM coin vault Account to create, with COIN_VAULT_ASSOCIATED_SEED.
+    ///   11. `[writable]` AMM pc vault Account to create, with PC_VAULT_ASSOCIATED_SEED.
+    ///   12. `[writable]` AMM target orders Account to create, with TARGET_ASSOCIATED_SEED.
+    ///   13. `[]` AMM config Account, derived from `find_program_address(&[amm_owner, amm_owner, AMM_CONFIG_SEED])`.
+    ///   14. `[writable]` AMM create pool fee destination Account, CREATE_POOL_FEE_ADDRESS_ID for a
+    ///      lamports fee, or its associated token account of AmmConfig.create_pool_fee_mint.
     ///   15. `[]` Market program id
-    ///   16. `[writable]` Market Account. Market program is the owner.
-    ///   17. `[writable, signer]` User wallet Account
-    ///   18. `[]` User token coin Account
-    ///   19. '[]` User token pc Account
-    ///   20. `[writable]` User destination lp token ATA Account
+    ///   16. `[]` Market Account. Market program is the owner. Pubkey::default() for
+    ///      pools without a market. An OpenBook v1 market, MigrateToOpenBook moves the pool
+    ///      to an OpenBook v2 market.
+    ///   17. `[writable, signer]` User wallet Account, pays for the pool accounts
+    ///   18. `[writable]` User token coin Account
+    ///   19. `[writable]` User token pc Account
+    ///   20. `[writable]` User destination lp token ATA Account, created by the program
+    ///   21. `[writable]` Optional, user token Account of AmmConfig.create_pool_fee_mint paying
+    ///      the create pool fee. Only when the fee is charged in a token.
     Initialize2(InitializeInstruction2),
 
     ///   MonitorStep. To monitor place Amm order state machine turn around step by step.
+    ///   Anyone can crank it: it plans the fibonacci order ladder into the target orders,
+    ///   places it on the market and purges the stray amm orders, bounded by the plan, place
+    ///   and cancel order limits. Pools with auto compound on leave the pnl taken by the step,
+    ///   less the crank reward, in the vaults for the lp.
     ///
     ///   0. `[]` Spl Token program id
     ///   1. `[]` Rent program id
@@ -187,6 +317,8 @@ pub enum AmmInstruction {
     ///   17. `[writable]` Market asks Account
     ///   18. `[writable]` (optional) the (M)SRM account used for fee discounts
     ///   19. `[writable]` (optional) the referrer pc account used for settle back referrer
+    ///   20. `[writable]` (optional) the caller pc token account receiving the crank reward,
+    ///       paid from the pool pnl. The amm program id stands in for a missing 18 or 19
     MonitorStep(MonitorStepInstruction),
 
     ///   Deposit some tokens into the pool.  The output is a "pool" token representing ownership
@@ -222,17 +354,19 @@ pub enum AmmInstruction {
     ///   9. `[writable]` Market Account. Market program is the owner.
     ///   10. `[writable]` Market coin vault Account
     ///   11. `[writable]` Market pc vault Account
-    ///   12. '[]` Market vault signer Account
+    ///   12. '[]` Market vault signer Account, the market authority for an OpenBook v2 market
     ///   13. `[writable]` User lp token Account.
     ///   14. `[writable]` User token coin Account. user Account to credit.
     ///   15. `[writable]` User token pc Account. user Account to credit.
     ///   16. `[signer]` User wallet Account
-    ///   17. `[writable]` Market event queue Account
+    ///   17. `[writable]` Market event queue Account, the system program for an OpenBook v2 market
     ///   18. `[writable]` Market bids Account
     ///   19. `[writable]` Market asks Account
     Withdraw(WithdrawInstruction),
 
-    ///   Migrate the associated market from Serum to OpenBook.
+    ///   Migrate the pool to a new OpenBook v1 or v2 market. Cancels and settles the amm orders
+    ///   on the old OpenBook v1 market, closes the old open orders and opens new ones on the new
+    ///   market. Pools are bound to an OpenBook v2 market this way, Initialize2 takes v1 markets.
     ///
     ///   0. `[]` Spl Token program id
     ///   1. `[]` Sys program id
@@ -251,10 +385,17 @@ pub enum AmmInstruction {
     ///   14. `[writable]` Market coin vault Account
     ///   15. `[writable]` Market pc vault Account
     ///   16. '[]` Market vault signer Account
-    ///   17. '[writable]` AMM new open orders Account
-    ///   18. '[]` mew Market program id
-    ///   19. '[]` new Market market Account
-    ///   20. '[]` Admin Account
+    ///   17. `[writable]` AMM new open orders Account, derived from
+    ///       `find_program_address(&[program_id, new_market, OPEN_ORDER_ASSOCIATED_SEED])`.
+    ///       For an OpenBook v2 market the next open orders account of the indexer, derived
+    ///       from `find_program_address(&["OpenOrders", $authority, created_counter + 1])`.
+    ///   18. `[]` new Market program id, the OpenBook v1 or v2 program
+    ///   19. `[]` new Market Account
+    ///   20. `[writable, signer]` Admin Account, the pool admin. Pays the new open orders.
+    ///   21. `[]` AMM config Account, PDA of (amm_owner, amm_owner, AMM_CONFIG_SEED)
+    ///   22. `[writable]` (optional) OpenBook v2 open orders indexer of $authority, derived from
+    ///       `find_program_address(&["OpenOrdersIndexer", $authority])`. Required for a v2
+    ///       market, created when it does not exist yet.
     MigrateToOpenBook,
 
     ///   Set AMM params
@@ -274,11 +415,17 @@ pub enum AmmInstruction {
     ///   12. `[writable]` Market event queue Account
     ///   13. `[writable]` Market bids Account
     ///   14. `[writable]` Market asks Account
-    ///   15. `[signer]` Admin Account
-    ///   16. `[]` (optional) New AMM open orders Account to replace old AMM open orders Account
+    ///   15. `[signer]` Admin Account, holder of the AmmRole the param needs, see AmmParams::role.
+    ///       PoolRole overrides of roles other than PoolAdmin need the config holder of the
+    ///       role or the config pnl owner
+    ///   16. `[]` AMM config Account, PDA of (amm_owner, amm_owner, AMM_CONFIG_SEED)
+    ///   17. `[]` (optional) New AMM open orders Account to replace old AMM open orders Account
     SetParams(SetParamsInstruction),
 
-    ///   Withdraw Pnl from pool by protocol
+    ///   Withdraw the unclaimed pnl from pool by protocol, split by the amm config into
+    ///   the treasury and buyback shares, the lp rebate share stays in the vaults.
+    ///   Pools with auto compound on leave the whole pnl in the vaults instead, the pool
+    ///   accounts are checked either way
     ///
     ///   0. `[]` Spl Token program id
     ///   1. `[writable]` AMM Account
@@ -287,27 +434,31 @@ pub enum AmmInstruction {
     ///   4. `[writable]` AMM open orders Account
     ///   5. `[writable]` AMM coin vault account to withdraw FROM,
     ///   6. `[writable]` AMM pc vault account to withdraw FROM,
-    ///   7. `[writable]` User coin token Account to withdraw to
-    ///   8. `[writable]` User pc token Account to withdraw to
-    ///   9. `[signer]` User wallet account
+    ///   7. `[writable]` Treasury coin token Account, owned by the config treasury
+    ///   8. `[writable]` Treasury pc token Account, owned by the config treasury
+    ///   9. `[signer]` Pnl owner of the amm config
     ///   10. `[writable]` AMM target orders Account
     ///   11. `[]` Market program id
     ///   12. `[writable]` Market Account. Market program is the owner.
-    ///   13. `[writable]` Market event queue Account
+    ///   13. `[writable]` Market event queue Account, the system program for an OpenBook v2 market
     ///   14. `[writable]` Market coin vault Account
     ///   15. `[writable]` Market pc vault Account
-    ///   16. '[]` Market vault signer Account
-    ///   17. `[]` (optional) the referrer pc account used for settle back referrer
+    ///   16. '[]` Market vault signer Account, the market authority for an OpenBook v2 market
+    ///   17. `[writable]` Buyback coin token Account, the amm program id if the config has no buyback share
+    ///   18. `[writable]` Buyback pc token Account, the amm program id if the config has no buyback share
+    ///   19. `[]` (optional) the referrer pc account used for settle back referrer
     WithdrawPnl,
 
-    ///   Withdraw (M)SRM from the (M)SRM Account used for fee discounts by admin
+    ///   Withdraw (M)SRM from the (M)SRM Account used for fee discounts by the fee admin
     ///
-    ///   0. `[]` Spl Token program id
-    ///   1. `[]` AMM Account.
-    ///   2. `[signer]` Admin wallet Account
-    ///   3. `[]` $authority derived from `create_program_address(&[AUTHORITY_AMM, &[nonce]])`.
-    ///   4. `[writable]` the (M)SRM Account withdraw from
-    ///   5. `[writable]` the (M)SRM Account withdraw to
+    ///   0. `[]` AMM Account.
+    ///   1. `[]` $authority derived from `create_program_address(&[AUTHORITY_AMM, &[nonce]])`.
+    ///   2. `[writable]` the (M)SRM Account withdraw from, owned by $authority and none of
+    ///      the pool vaults
+    ///   3. `[writable]` the (M)SRM Account withdraw to
+    ///   4. `[]` Spl Token program id
+    ///   5. `[signer]` Fee admin of the pool, see AmmRole::FeeAdmin
+    ///   6. `[]` AMM config Account, PDA of (amm_owner, amm_owner, AMM_CONFIG_SEED)
     WithdrawSrm(WithdrawSrmInstruction),
 
     /// Swap coin or pc from pool, base amount_in with a slippage of minimum_amount_out
@@ -330,6 +481,8 @@ pub enum AmmInstruction {
     ///   15. `[writable]` User source token Account.
     ///   16. `[writable]` User destination token Account.
     ///   17. `[signer]` User wallet Account
+    ///   18. `[writable]` (optional)Allowlist entry Account of the user wallet, required during the
+    ///       allowlist phase. The AMM target orders Account must be passed along with it.
     SwapBaseIn(SwapInstructionBaseIn),
 
     ///   Continue Initializes a new Amm pool because of compute units limit.
@@ -357,16 +510,42 @@ pub enum AmmInstruction {
     ///   15. `[writable]` User source token Account.
     ///   16. `[writable]` User destination token Account.
     ///   17. `[signer]` User wallet Account
+    ///   18. `[writable]` (optional)Allowlist entry Account of the user wallet, required during the
+    ///       allowlist phase. The AMM target orders Account must be passed along with it.
     SwapBaseOut(SwapInstructionBaseOut),
 
     SimulateInfo(SimulateInstruction),
 
+    ///   Cancel the amm orders by admin, at most limit orders per call, lowest client order id
+    ///   first. The progress is kept in the target orders so a pool with many resting orders
+    ///   is unwound over several calls.
+    ///
+    ///   0. `[]` Spl Token program id
+    ///   1. `[]` AMM Account
+    ///   2. `[]` $authority derived from `create_program_address(&[AUTHORITY_AMM, &[nonce]])`.
+    ///   3. `[writable]` AMM open orders Account
+    ///   4. `[writable]` AMM target orders Account
+    ///   5. `[writable]` AMM coin vault Account
+    ///   6. `[writable]` AMM pc vault Account
+    ///   7. `[signer]` crank operator or AMM config cancel owner Account
+    ///   8. `[]` AMM config Account, derived from `find_program_address(&[&&AMM_CONFIG_SEED])`.
+    ///   9. `[]` Market program id
+    ///   10. `[writable]` Market Account. Market program is the owner.
+    ///   11. `[writable]` Market coin vault Account
+    ///   12. `[writable]` Market pc vault Account
+    ///   13. `[]` Market vault signer Account, the market authority for an OpenBook v2 market
+    ///   14. `[writable]` Market event queue Account, the system program for an OpenBook v2 market
+    ///   15. `[writable]` Market bids Account
+    ///   16. `[writable]` Market asks Account
+    ///   17. `[writable]` (optional) the (M)SRM account used for fee discounts
+    ///   18. `[writable]` (optional) the referrer pc account used for settle back referrer
     AdminCancelOrders(AdminCancelOrdersInstruction),
 
     /// Create amm config account by admin
     CreateConfigAccount,
 
-    /// Update amm config account by admin
+    /// Update amm config account by admin. param 0 and 1 set the pnl owner, 2 the create
+    /// pool fee, 3 the create pool fee mint, 4 the pnl split, 5 a role and 6 the cancel owner
     UpdateConfigAccount(ConfigArgs),
 
     // New Token-2022 instructions
@@ -382,6 +561,226 @@ pub enum AmmInstruction {
     
     /// Update whitelist authority
     UpdateWhitelistAuthority { new_authority: Pubkey },
+
+    /// Create the price observation account of an amm
+    ///
+    ///   0. `[]` Amm Account
+    ///   1. `[writable]` Observation Account, PDA of (amm, amm, OBSERVATION_ASSOCIATED_SEED)
+    ///   2. `[writable, signer]` Payer Account
+    ///   3. `[]` System program
+    InitializeObservation,
+
+    /// Record the current price cumulatives of an amm, permissionless
+    ///
+    ///   0. `[]` Amm Account
+    ///   1. `[writable]` Observation Account
+    RecordObservation,
+
+    /// Compute the time weighted average price over a window, written as return data
+    ///
+    ///   0. `[]` Amm Account
+    ///   1. `[]` Observation Account
+    GetTwap(GetTwapInstruction),
+
+    /// Lend coin and/or pc from the vaults and call back the borrower program.
+    /// After the callback each vault must hold what it lent plus the flash fee charged at
+    /// the swap fee rate, repaid in the token it lent. The outcome is written as AmmReturnData
+    ///
+    ///   0. `[]` Spl Token program id
+    ///   1. `[writable]` Amm Account
+    ///   2. `[]` $authority derived from `create_program_address(&[AUTHORITY_AMM, &[nonce]])`.
+    ///   3. `[writable]` Amm coin vault Account
+    ///   4. `[writable]` Amm pc vault Account
+    ///   5. `[]` Amm coin mint Account
+    ///   6. `[]` Amm pc mint Account
+    ///   7. `[writable]` Borrower coin token Account
+    ///   8. `[writable]` Borrower pc token Account
+    ///   9. `[]` Callback program
+    ///   10... Accounts forwarded to the callback
+    FlashLoan(FlashLoanInstruction),
+
+    /// Deposit liquidity with a single token, the optimal part of it is swapped
+    /// into the other token at pool price before depositing
+    ///
+    ///   0. `[]` Spl Token program id
+    ///   1. `[writable]` AMM Account
+    ///   2. `[]` $authority derived from `create_program_address(&[AUTHORITY_AMM, &[nonce]])`.
+    ///   3. `[]` AMM open_orders Account
+    ///   4. `[writable]` AMM target orders Account. To store plan orders infomations.
+    ///   5. `[writable]` AMM lp mint Account. Owned by $authority.
+    ///   6. `[writable]` AMM coin vault $authority can transfer amount,
+    ///   7. `[writable]` AMM pc vault $authority can transfer amount,
+    ///   8. `[]` Market Account. Market program is the owner.
+    ///   9. `[]` Market event queue Account
+    ///   10. `[writable]` User source token Account, coin or pc
+    ///   11. `[writable]` User lp token Account. To deposit the generated tokens, user is the owner.
+    ///   12. `[signer]` User wallet Account
+    ///   13. `[]` User source token mint Account
+    DepositSingleSided(DepositSingleSidedInstruction),
+
+    /// Withdraw liquidity into a single token, the share of the other token
+    /// is swapped back into the pool at the current reserves
+    ///
+    ///   0. `[]` Spl Token program id
+    ///   1. `[writable]` AMM Account
+    ///   2. `[]` $authority derived from `create_program_address(&[AUTHORITY_AMM, &[nonce]])`.
+    ///   3. `[writable]` AMM open_orders Account
+    ///   4. `[writable]` AMM target orders Account
+    ///   5. `[writable]` AMM lp mint Account. Owned by $authority.
+    ///   6. `[writable]` AMM coin vault Account to withdraw FROM,
+    ///   7. `[writable]` AMM pc vault Account to withdraw FROM,
+    ///   8. `[]` User destination token mint Account
+    ///   9. `[]` Market program id
+    ///   10. `[writable]` Market Account. Market program is the owner.
+    ///   11. `[writable]` Market coin vault Account
+    ///   12. `[writable]` Market pc vault Account
+    ///   13. '[]` Market vault signer Account, the market authority for an OpenBook v2 market
+    ///   14. `[writable]` User lp token Account.
+    ///   15. `[writable]` User destination token Account, coin or pc
+    ///   16. `[signer]` User wallet Account
+    ///   17. `[writable]` Market event queue Account, the system program for an OpenBook v2 market
+    ///   18. `[writable]` Market bids Account
+    ///   19. `[writable]` Market asks Account
+    WithdrawSingleSided(WithdrawSingleSidedInstruction),
+
+    /// Lock pool tokens of the owner until an unlock time or a linear vesting schedule.
+    /// The tokens are held by the lp token account of $authority.
+    ///
+    ///   0. `[]` Spl Token program id
+    ///   1. `[]` Associated Token program id
+    ///   2. `[]` Sys program id
+    ///   3. `[]` AMM Account
+    ///   4. `[]` $authority derived from `create_program_address(&[AUTHORITY_AMM, &[nonce]])`.
+    ///   5. `[]` AMM open_orders Account
+    ///   6. `[]` AMM coin vault Account
+    ///   7. `[]` AMM pc vault Account
+    ///   8. `[]` AMM lp mint Account
+    ///   9. `[]` Market Account. Market program is the owner.
+    ///   10. `[]` Market event queue Account
+    ///   11. `[writable]` Lp lock Account, PDA of the amm and the owner
+    ///   12. `[writable]` Associated lp token Account of $authority
+    ///   13. `[writable]` User lp token Account to lock FROM.
+    ///   14. `[writable, signer]` User wallet Account, pays for the lock account
+    LockLp(LockLpInstruction),
+
+    /// Claim the lp tokens earned by a locked position as fees since the last claim
+    ///
+    ///   0. `[]` Spl Token program id
+    ///   1. `[]` AMM Account
+    ///   2. `[]` $authority derived from `create_program_address(&[AUTHORITY_AMM, &[nonce]])`.
+    ///   3. `[]` AMM open_orders Account
+    ///   4. `[]` AMM coin vault Account
+    ///   5. `[]` AMM pc vault Account
+    ///   6. `[]` AMM lp mint Account
+    ///   7. `[]` Market Account. Market program is the owner.
+    ///   8. `[]` Market event queue Account
+    ///   9. `[writable]` Lp lock Account
+    ///   10. `[writable]` Associated lp token Account of $authority
+    ///   11. `[writable]` User lp token Account to claim TO.
+    ///   12. `[signer]` Lock owner wallet Account
+    ClaimLpLockFee,
+
+    /// Withdraw unlocked pool tokens from a lock. A lock left empty is closed and its
+    /// lamports returned to the owner.
+    ///
+    ///   0. `[]` Spl Token program id
+    ///   1. `[]` AMM Account
+    ///   2. `[]` $authority derived from `create_program_address(&[AUTHORITY_AMM, &[nonce]])`.
+    ///   3. `[]` AMM lp mint Account
+    ///   4. `[writable]` Lp lock Account
+    ///   5. `[writable]` Associated lp token Account of $authority
+    ///   6. `[writable]` User lp token Account to withdraw TO.
+    ///   7. `[writable, signer]` Lock owner wallet Account
+    UnlockLp(UnlockLpInstruction),
+
+    /// Lock pool tokens of the owner forever, the lock keeps earning swap fees.
+    /// The tokens are held by the lp token account of $authority. The pool must be
+    /// migrated to AMM_INFO_VERSION_V2, the lock earns the fee growth per lp token.
+    ///
+    ///   Accounts are the same as LockLp, the lock Account is derived with the
+    ///   permanent lock seed
+    LockLpPermanent(LockLpPermanentInstruction),
+
+    /// Claim the lp share of the swap fees, net of the protocol pnl, accrued to the locked
+    /// lp since the last claim.
+    /// The lock burns the lp worth the fees, so the locked liquidity stays the same.
+    ///
+    ///   0. `[]` Spl Token program id
+    ///   1. `[writable]` AMM Account
+    ///   2. `[]` $authority derived from `create_program_address(&[AUTHORITY_AMM, &[nonce]])`.
+    ///   3. `[]` AMM open_orders Account
+    ///   4. `[writable]` AMM target orders Account
+    ///   5. `[writable]` AMM coin vault Account to claim FROM,
+    ///   6. `[writable]` AMM pc vault Account to claim FROM,
+    ///   7. `[writable]` AMM lp mint Account
+    ///   8. `[]` AMM coin mint Account
+    ///   9. `[]` AMM pc mint Account
+    ///   10. `[]` Market Account. Market program is the owner.
+    ///   11. `[]` Market event queue Account
+    ///   12. `[writable]` Permanent lp lock Account
+    ///   13. `[writable]` Associated lp token Account of $authority
+    ///   14. `[writable]` User coin token Account to claim TO.
+    ///   15. `[writable]` User pc token Account to claim TO.
+    ///   16. `[signer]` Lock owner wallet Account
+    ClaimPermanentLockFee,
+
+    /// Initialize the allowlist of the launch phase by the whitelist admin, before the pool opens.
+    /// Between the pool open time and allowlist_end_time only registered wallets can swap.
+    ///
+    ///   0. `[writable]` AMM Account
+    ///   1. `[writable]` Allowlist Account, PDA of the amm
+    ///   2. `[writable, signer]` Whitelist admin wallet Account, pays for the allowlist account
+    ///   3. `[]` Sys program id
+    ///   4. `[]` AMM config Account, PDA of (amm_owner, amm_owner, AMM_CONFIG_SEED)
+    InitializeAllowlist(InitializeAllowlistInstruction),
+
+    /// Register a wallet of the allowlist with its merkle proof
+    ///
+    ///   0. `[]` AMM Account
+    ///   1. `[]` Allowlist Account
+    ///   2. `[writable]` Allowlist entry Account, PDA of the amm and the wallet
+    ///   3. `[writable, signer]` User wallet Account, pays for the entry account
+    ///   4. `[]` Sys program id
+    RegisterAllowlistWallet(RegisterAllowlistWalletInstruction),
+
+    /// Close a drained pool by the pool admin. The pool must be Disabled with no lp supply and
+    /// no unclaimed pnl, so pools with a permanent lp lock can not be closed. Leftovers of the
+    /// vaults go to the owner token accounts, the vaults, open orders, target orders, allowlist,
+    /// observation and AMM account are closed and their lamports returned to the owner.
+    /// Spl token mints can not be closed, so the lp mint stays and Initialize2 refuses the pool
+    /// key again: a closed pool of a market is not recreated on that market, a closed pool
+    /// without one is recreated with another fee tier.
+    ///
+    ///   0. `[]` Spl Token program id
+    ///   1. `[writable]` AMM Account
+    ///   2. `[]` $authority derived from `create_program_address(&[AUTHORITY_AMM, &[nonce]])`.
+    ///   3. `[writable]` AMM open_orders Account
+    ///   4. `[writable]` AMM target orders Account
+    ///   5. `[]` AMM lp mint Account
+    ///   6. `[writable]` AMM coin vault Account
+    ///   7. `[writable]` AMM pc vault Account
+    ///   8. `[]` AMM coin mint Account
+    ///   9. `[]` AMM pc mint Account
+    ///   10. `[]` Market program id
+    ///   11. `[]` Market Account. Market program is the owner.
+    ///   12. `[writable]` Owner coin token Account to receive the coin vault leftover
+    ///   13. `[writable]` Owner pc token Account to receive the pc vault leftover
+    ///   14. `[writable, signer]` AMM pool admin wallet Account, receives the lamports
+    ///   15. `[]` AMM config Account, PDA of (amm_owner, amm_owner, AMM_CONFIG_SEED)
+    ///   16. `[writable]` AMM allowlist Account, PDA of (amm, amm, ALLOWLIST_ASSOCIATED_SEED).
+    ///       Closed if it was initialized
+    ///   17. `[writable]` AMM observation Account, PDA of (amm, amm, OBSERVATION_ASSOCIATED_SEED).
+    ///       Closed if it was initialized
+    ClosePool,
+
+    /// Realloc the AMM account to the current layout version and upgrade its data.
+    /// Accounts that are already current are left as they are. Permissionless: anyone can
+    /// migrate a pool, the caller pays the rent of the grown account and does not get it back.
+    ///
+    ///   0. `[writable]` AMM Account
+    ///   1. `[writable, signer]` Payer wallet Account, pays the rent of the grown account
+    ///   2. `[]` Sys program id
+    MigratePoolAccount,
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -442,12 +841,29 @@ impl AmmInstruction {
                 let (nonce, rest) = Self::unpack_u8(rest)?;
                 let (open_time, rest) = Self::unpack_u64(rest)?;
                 let (init_pc_amount, rest) = Self::unpack_u64(rest)?;
-                let (init_coin_amount, _reset) = Self::unpack_u64(rest)?;
+                let (init_coin_amount, rest) = Self::unpack_u64(rest)?;
+                // the launch fee and fee tier are optional for clients built before they existed
+                let (launch_fee_numerator, launch_fee_decay_seconds, rest) = if rest.is_empty() {
+                    (0, 0, rest)
+                } else {
+                    let (launch_fee_numerator, rest) = Self::unpack_u32(rest)?;
+                    let (launch_fee_decay_seconds, rest) = Self::unpack_u32(rest)?;
+                    (launch_fee_numerator, launch_fee_decay_seconds, rest)
+                };
+                let fee_tier = if rest.is_empty() {
+                    0
+                } else {
+                    let (fee_tier, _rest) = Self::unpack_u32(rest)?;
+                    fee_tier
+                };
                 Self::Initialize2(InitializeInstruction2 {
                     nonce,
                     open_time,
                     init_pc_amount,
                     init_coin_amount,
+                    launch_fee_numerator,
+                    launch_fee_decay_seconds,
+                    fee_tier,
                 })
             }
             2 => {
@@ -505,6 +921,9 @@ impl AmmInstruction {
                                 new_pubkey: Some(Pubkey::new_from_array(*new_pubkey)),
                                 fees: None,
                                 last_order_distance: None,
+                                dynamic_fee: None,
+                                circuit_breaker: None,
+                                crank_reward: None,
                             })
                         } else {
                             return Err(ProgramError::InvalidInstructionData.into());
@@ -520,6 +939,9 @@ impl AmmInstruction {
                                 new_pubkey: None,
                                 fees: Some(fees),
                                 last_order_distance: None,
+                                dynamic_fee: None,
+                                circuit_breaker: None,
+                                crank_reward: None,
                             })
                         } else {
                             return Err(ProgramError::InvalidInstructionData.into());
@@ -538,6 +960,96 @@ impl AmmInstruction {
                                     last_order_numerator,
                                     last_order_denominator,
                                 }),
+                                dynamic_fee: None,
+                                circuit_breaker: None,
+                                crank_reward: None,
+                            })
+                        } else {
+                            return Err(ProgramError::InvalidInstructionData.into());
+                        }
+                    }
+                    AmmParams::DynamicFee => {
+                        if rest.len() >= 16 {
+                            let (min_fee_numerator, rest) = Self::unpack_u64(rest)?;
+                            let (max_fee_numerator, _rest) = Self::unpack_u64(rest)?;
+                            Self::SetParams(SetParamsInstruction {
+                                param,
+                                value: None,
+                                new_pubkey: None,
+                                fees: None,
+                                last_order_distance: None,
+                                dynamic_fee: Some(DynamicFee {
+                                    min_fee_numerator,
+                                    max_fee_numerator,
+                                }),
+                                circuit_breaker: None,
+                                crank_reward: None,
+                            })
+                        } else {
+                            return Err(ProgramError::InvalidInstructionData.into());
+                        }
+                    }
+                    AmmParams::CircuitBreaker => {
+                        if rest.len() >= 32 {
+                            let (max_price_impact_bps, rest) = Self::unpack_u64(rest)?;
+                            let (max_output_bps, rest) = Self::unpack_u64(rest)?;
+                            let (max_slot_volume_bps, rest) = Self::unpack_u64(rest)?;
+                            let (max_trips, _rest) = Self::unpack_u64(rest)?;
+                            Self::SetParams(SetParamsInstruction {
+                                param,
+                                value: None,
+                                new_pubkey: None,
+                                fees: None,
+                                last_order_distance: None,
+                                dynamic_fee: None,
+                                circuit_breaker: Some(CircuitBreaker {
+                                    max_price_impact_bps,
+                                    max_output_bps,
+                                    max_slot_volume_bps,
+                                    max_trips,
+                                }),
+                                crank_reward: None,
+                            })
+                        } else {
+                            return Err(ProgramError::InvalidInstructionData.into());
+                        }
+                    }
+                    AmmParams::CrankReward => {
+                        if rest.len() >= 24 {
+                            let (reward_pc, rest) = Self::unpack_u64(rest)?;
+                            let (epoch_cap, rest) = Self::unpack_u64(rest)?;
+                            let (slot_interval, _rest) = Self::unpack_u64(rest)?;
+                            Self::SetParams(SetParamsInstruction {
+                                param,
+                                value: None,
+                                new_pubkey: None,
+                                fees: None,
+                                last_order_distance: None,
+                                dynamic_fee: None,
+                                circuit_breaker: None,
+                                crank_reward: Some(CrankReward {
+                                    reward_pc,
+                                    epoch_cap,
+                                    slot_interval,
+                                }),
+                            })
+                        } else {
+                            return Err(ProgramError::InvalidInstructionData.into());
+                        }
+                    }
+                    AmmParams::PoolRole => {
+                        if rest.len() >= 40 {
+                            let (value, rest) = Self::unpack_u64(rest)?;
+                            let new_pubkey = array_ref![rest, 0, 32];
+                            Self::SetParams(SetParamsInstruction {
+                                param,
+                                value: Some(value),
+                                new_pubkey: Some(Pubkey::new_from_array(*new_pubkey)),
+                                fees: None,
+                                last_order_distance: None,
+                                dynamic_fee: None,
+                                circuit_breaker: None,
+                                crank_reward: None,
                             })
                         } else {
                             return Err(ProgramError::InvalidInstructionData.into());
@@ -552,6 +1064,9 @@ impl AmmInstruction {
                                 new_pubkey: None,
                                 fees: None,
                                 last_order_distance: None,
+                                dynamic_fee: None,
+                                circuit_breaker: None,
+                                crank_reward: None,
                             })
                         } else {
                             return Err(ProgramError::InvalidInstructionData.into());
@@ -587,11 +1102,15 @@ impl AmmInstruction {
             12 => {
                 let (param, rest) = Self::unpack_u8(rest)?;
                 match SimulateParams::from_u64(param as u64) {
-                    SimulateParams::PoolInfo | SimulateParams::RunCrankInfo => {
+                    SimulateParams::PoolInfo
+                    | SimulateParams::RunCrankInfo
+                    | SimulateParams::LpLockInfo
+                    | SimulateParams::LadderInfo => {
                         Self::SimulateInfo(SimulateInstruction {
                             param,
                             swap_base_in_value: None,
                             swap_base_out_value: None,
+                            fee_growth_value: None,
                         })
                     }
                     SimulateParams::SwapBaseInInfo => {
@@ -605,6 +1124,7 @@ impl AmmInstruction {
                             param,
                             swap_base_in_value: swap_base_in,
                             swap_base_out_value: None,
+                            fee_growth_value: None,
                         })
                     }
                     SimulateParams::SwapBaseOutInfo => {
@@ -618,6 +1138,22 @@ impl AmmInstruction {
                             param,
                             swap_base_in_value: None,
                             swap_base_out_value: swap_base_out,
+                            fee_growth_value: None,
+                        })
+                    }
+                    SimulateParams::FeeGrowthInfo => {
+                        let (lp_amount, rest) = Self::unpack_u64(rest)?;
+                        let (fee_growth_coin_per_lp, rest) = Self::unpack_u128(rest)?;
+                        let (fee_growth_pc_per_lp, _rest) = Self::unpack_u128(rest)?;
+                        Self::SimulateInfo(SimulateInstruction {
+                            param,
+                            swap_base_in_value: None,
+                            swap_base_out_value: None,
+                            fee_growth_value: Some(FeeGrowthSnapshot {
+                                lp_amount,
+                                fee_growth_coin_per_lp,
+                                fee_growth_pc_per_lp,
+                            }),
                         })
                     }
                 }
@@ -636,6 +1172,9 @@ impl AmmInstruction {
                             param,
                             owner: Some(Pubkey::new_from_array(*pubkey)),
                             create_pool_fee: None,
+                            create_pool_fee_mint: None,
+                            pnl_split: None,
+                            role: None,
                         })
                     }
                     2 => {
@@ -644,6 +1183,70 @@ impl AmmInstruction {
                             param,
                             owner: None,
                             create_pool_fee: Some(create_pool_fee),
+                            create_pool_fee_mint: None,
+                            pnl_split: None,
+                            role: None,
+                        })
+                    }
+                    3 => {
+                        let pubkey = array_ref![rest, 0, 32];
+                        Self::UpdateConfigAccount(ConfigArgs {
+                            param,
+                            owner: None,
+                            create_pool_fee: None,
+                            create_pool_fee_mint: Some(Pubkey::new_from_array(*pubkey)),
+                            pnl_split: None,
+                            role: None,
+                        })
+                    }
+                    4 => {
+                        if rest.len() < 88 {
+                            return Err(ProgramError::InvalidInstructionData.into());
+                        }
+                        let treasury = Pubkey::new_from_array(*array_ref![rest, 0, 32]);
+                        let buyback = Pubkey::new_from_array(*array_ref![rest, 32, 32]);
+                        let (treasury_bps, rest) = Self::unpack_u64(&rest[64..])?;
+                        let (buyback_bps, rest) = Self::unpack_u64(rest)?;
+                        let (lp_rebate_bps, _rest) = Self::unpack_u64(rest)?;
+                        Self::UpdateConfigAccount(ConfigArgs {
+                            param,
+                            owner: None,
+                            create_pool_fee: None,
+                            create_pool_fee_mint: None,
+                            pnl_split: Some(PnlSplit {
+                                treasury,
+                                buyback,
+                                treasury_bps,
+                                buyback_bps,
+                                lp_rebate_bps,
+                            }),
+                            role: None,
+                        })
+                    }
+                    5 => {
+                        let (role, rest) = Self::unpack_u64(rest)?;
+                        if rest.len() < 32 {
+                            return Err(ProgramError::InvalidInstructionData.into());
+                        }
+                        let holder = Pubkey::new_from_array(*array_ref![rest, 0, 32]);
+                        Self::UpdateConfigAccount(ConfigArgs {
+                            param,
+                            owner: None,
+                            create_pool_fee: None,
+                            create_pool_fee_mint: None,
+                            pnl_split: None,
+                            role: Some(RoleAssignment { role, holder }),
+                        })
+                    }
+                    6 => {
+                        let cancel_owner = array_ref![rest, 0, 32];
+                        Self::UpdateConfigAccount(ConfigArgs {
+                            param,
+                            owner: Some(Pubkey::new_from_array(*cancel_owner)),
+                            create_pool_fee: None,
+                            create_pool_fee_mint: None,
+                            pnl_split: None,
+                            role: None,
                         })
                     }
                     _ => {
@@ -775,6 +1378,82 @@ impl AmmInstruction {
                     new_authority: Pubkey::new_from_array(*new_authority) 
                 }
             }
+            21 => Self::InitializeObservation,
+            22 => Self::RecordObservation,
+            23 => {
+                let (window, _rest) = Self::unpack_u64(rest)?;
+                Self::GetTwap(GetTwapInstruction { window })
+            }
+            24 => {
+                let (coin_amount, rest) = Self::unpack_u64(rest)?;
+                let (pc_amount, rest) = Self::unpack_u64(rest)?;
+                let (hook_account_num, rest) = Self::unpack_u8(rest)?;
+                Self::FlashLoan(FlashLoanInstruction {
+                    coin_amount,
+                    pc_amount,
+                    hook_account_num,
+                    callback_data: rest.to_vec(),
+                })
+            }
+            25 => {
+                let (amount_in, rest) = Self::unpack_u64(rest)?;
+                let (minimum_lp_out, _rest) = Self::unpack_u64(rest)?;
+                Self::DepositSingleSided(DepositSingleSidedInstruction {
+                    amount_in,
+                    minimum_lp_out,
+                })
+            }
+            26 => {
+                let (amount, rest) = Self::unpack_u64(rest)?;
+                let (min_amount_out, _rest) = Self::unpack_u64(rest)?;
+                Self::WithdrawSingleSided(WithdrawSingleSidedInstruction {
+                    amount,
+                    min_amount_out,
+                })
+            }
+            27 => {
+                let (amount, rest) = Self::unpack_u64(rest)?;
+                let (unlock_start_time, rest) = Self::unpack_u64(rest)?;
+                let (unlock_end_time, _rest) = Self::unpack_u64(rest)?;
+                Self::LockLp(LockLpInstruction {
+                    amount,
+                    unlock_start_time,
+                    unlock_end_time,
+                })
+            }
+            28 => Self::ClaimLpLockFee,
+            29 => {
+                let (amount, _rest) = Self::unpack_u64(rest)?;
+                Self::UnlockLp(UnlockLpInstruction { amount })
+            }
+            30 => {
+                let (amount, _rest) = Self::unpack_u64(rest)?;
+                Self::LockLpPermanent(LockLpPermanentInstruction { amount })
+            }
+            31 => Self::ClaimPermanentLockFee,
+            32 => {
+                let (merkle_root, rest) = Self::unpack_bytes32(rest)?;
+                let (allowlist_end_time, _rest) = Self::unpack_u64(rest)?;
+                Self::InitializeAllowlist(InitializeAllowlistInstruction {
+                    merkle_root,
+                    allowlist_end_time,
+                })
+            }
+            33 => {
+                let (cap, mut rest) = Self::unpack_u64(rest)?;
+                if rest.len() % 32 != 0 {
+                    return Err(ProgramError::InvalidInstructionData.into());
+                }
+                let mut proof = Vec::with_capacity(rest.len() / 32);
+                while !rest.is_empty() {
+                    let (node, next) = Self::unpack_bytes32(rest)?;
+                    proof.push(node);
+                    rest = next;
+                }
+                Self::RegisterAllowlistWallet(RegisterAllowlistWalletInstruction { cap, proof })
+            }
+            34 => Self::ClosePool,
+            35 => Self::MigratePoolAccount,
             _ => return Err(ProgramError::InvalidInstructionData.into()),
         })
     }
@@ -821,6 +1500,20 @@ impl AmmInstruction {
         }
     }
 
+    fn unpack_u128(input: &[u8]) -> Result<(u128, &[u8]), ProgramError> {
+        if input.len() >= 16 {
+            let (amount, rest) = input.split_at(16);
+            let amount = amount
+                .get(..16)
+                .and_then(|slice| slice.try_into().ok())
+                .map(u128::from_le_bytes)
+                .ok_or(ProgramError::InvalidInstructionData)?;
+            Ok((amount, rest))
+        } else {
+            Err(ProgramError::InvalidInstructionData.into())
+        }
+    }
+
     fn unpack_u32(input: &[u8]) -> Result<(u32, &[u8]), ProgramError> {
         if input.len() >= 4 {
             let (value, rest) = input.split_at(4);
@@ -835,6 +1528,15 @@ impl AmmInstruction {
         }
     }
 
+    fn unpack_bytes32(input: &[u8]) -> Result<([u8; 32], &[u8]), ProgramError> {
+        if input.len() >= 32 {
+            let (value, rest) = input.split_at(32);
+            Ok((*array_ref![value, 0, 32], rest))
+        } else {
+            Err(ProgramError::InvalidInstructionData.into())
+        }
+    }
+
     /// Packs a [AmmInstruction](enum.AmmInstruction.html) into a byte buffer.
     pub fn pack(&self) -> Result<Vec<u8>, ProgramError> {
         let mut buf = Vec::with_capacity(size_of::<Self>());
@@ -849,12 +1551,18 @@ impl AmmInstruction {
                 open_time,
                 init_pc_amount,
                 init_coin_amount,
+                launch_fee_numerator,
+                launch_fee_decay_seconds,
+                fee_tier,
             }) => {
                 buf.push(1);
                 buf.push(*nonce);
                 buf.extend_from_slice(&open_time.to_le_bytes());
                 buf.extend_from_slice(&init_pc_amount.to_le_bytes());
                 buf.extend_from_slice(&init_coin_amount.to_le_bytes());
+                buf.extend_from_slice(&launch_fee_numerator.to_le_bytes());
+                buf.extend_from_slice(&launch_fee_decay_seconds.to_le_bytes());
+                buf.extend_from_slice(&fee_tier.to_le_bytes());
             }
             Self::MonitorStep(MonitorStepInstruction {
                 plan_order_limit,
@@ -901,6 +1609,9 @@ impl AmmInstruction {
                 new_pubkey,
                 fees,
                 last_order_distance,
+                dynamic_fee,
+                circuit_breaker,
+                crank_reward,
             }) => {
                 buf.push(6);
                 buf.push(*param);
@@ -929,8 +1640,43 @@ impl AmmInstruction {
                         buf.extend_from_slice(&distance.last_order_numerator.to_le_bytes());
                         buf.extend_from_slice(&distance.last_order_denominator.to_le_bytes());
                     }
-                    _ => {
-                        let value = match value {
+                    AmmParams::DynamicFee => {
+                        let dynamic_fee = match dynamic_fee {
+                            Some(a) => a,
+                            None => return Err(ProgramError::InvalidInstructionData.into()),
+                        };
+                        buf.extend_from_slice(&dynamic_fee.min_fee_numerator.to_le_bytes());
+                        buf.extend_from_slice(&dynamic_fee.max_fee_numerator.to_le_bytes());
+                    }
+                    AmmParams::CircuitBreaker => {
+                        let circuit_breaker = match circuit_breaker {
+                            Some(a) => a,
+                            None => return Err(ProgramError::InvalidInstructionData.into()),
+                        };
+                        buf.extend_from_slice(&circuit_breaker.max_price_impact_bps.to_le_bytes());
+                        buf.extend_from_slice(&circuit_breaker.max_output_bps.to_le_bytes());
+                        buf.extend_from_slice(&circuit_breaker.max_slot_volume_bps.to_le_bytes());
+                        buf.extend_from_slice(&circuit_breaker.max_trips.to_le_bytes());
+                    }
+                    AmmParams::CrankReward => {
+                        let crank_reward = match crank_reward {
+                            Some(a) => a,
+                            None => return Err(ProgramError::InvalidInstructionData.into()),
+                        };
+                        buf.extend_from_slice(&crank_reward.reward_pc.to_le_bytes());
+                        buf.extend_from_slice(&crank_reward.epoch_cap.to_le_bytes());
+                        buf.extend_from_slice(&crank_reward.slot_interval.to_le_bytes());
+                    }
+                    AmmParams::PoolRole => {
+                        let (value, new_pubkey) = match (value, new_pubkey) {
+                            (Some(value), Some(new_pubkey)) => (value, new_pubkey),
+                            _ => return Err(ProgramError::InvalidInstructionData.into()),
+                        };
+                        buf.extend_from_slice(&value.to_le_bytes());
+                        buf.extend_from_slice(&new_pubkey.to_bytes());
+                    }
+                    _ => {
+                        let value = match value {
                             Some(a) => a,
                             None => return Err(ProgramError::InvalidInstructionData.into()),
                         };
@@ -969,11 +1715,15 @@ impl AmmInstruction {
                 param,
                 swap_base_in_value,
                 swap_base_out_value,
+                fee_growth_value,
             }) => {
                 buf.push(12);
                 buf.push(*param);
                 match SimulateParams::from_u64(*param as u64) {
-                    SimulateParams::PoolInfo | SimulateParams::RunCrankInfo => {}
+                    SimulateParams::PoolInfo
+                    | SimulateParams::RunCrankInfo
+                    | SimulateParams::LpLockInfo
+                    | SimulateParams::LadderInfo => {}
                     SimulateParams::SwapBaseInInfo => {
                         let swap_base_in = match swap_base_in_value {
                             Some(a) => a,
@@ -990,6 +1740,15 @@ impl AmmInstruction {
                         buf.extend_from_slice(&swap_base_out.max_amount_in.to_le_bytes());
                         buf.extend_from_slice(&swap_base_out.amount_out.to_le_bytes());
                     }
+                    SimulateParams::FeeGrowthInfo => {
+                        let fee_growth = match fee_growth_value {
+                            Some(a) => a,
+                            None => return Err(ProgramError::InvalidInstructionData.into()),
+                        };
+                        buf.extend_from_slice(&fee_growth.lp_amount.to_le_bytes());
+                        buf.extend_from_slice(&fee_growth.fee_growth_coin_per_lp.to_le_bytes());
+                        buf.extend_from_slice(&fee_growth.fee_growth_pc_per_lp.to_le_bytes());
+                    }
                 }
             }
             Self::AdminCancelOrders(AdminCancelOrdersInstruction { limit }) => {
@@ -1003,6 +1762,9 @@ impl AmmInstruction {
                 param,
                 owner,
                 create_pool_fee,
+                create_pool_fee_mint,
+                pnl_split,
+                role,
             }) => {
                 buf.push(15);
                 buf.push(*param);
@@ -1027,6 +1789,47 @@ impl AmmInstruction {
                         };
                         buf.extend_from_slice(&create_pool_fee.to_le_bytes());
                     }
+                    3 => {
+                        // Pubkey::default() switches the fee back to lamports
+                        let create_pool_fee_mint = match create_pool_fee_mint {
+                            Some(create_pool_fee_mint) => create_pool_fee_mint,
+                            None => return Err(ProgramError::InvalidInstructionData.into()),
+                        };
+                        buf.extend_from_slice(&create_pool_fee_mint.to_bytes());
+                    }
+                    4 => {
+                        let pnl_split = match pnl_split {
+                            Some(pnl_split) => pnl_split,
+                            None => return Err(ProgramError::InvalidInstructionData.into()),
+                        };
+                        buf.extend_from_slice(&pnl_split.treasury.to_bytes());
+                        buf.extend_from_slice(&pnl_split.buyback.to_bytes());
+                        buf.extend_from_slice(&pnl_split.treasury_bps.to_le_bytes());
+                        buf.extend_from_slice(&pnl_split.buyback_bps.to_le_bytes());
+                        buf.extend_from_slice(&pnl_split.lp_rebate_bps.to_le_bytes());
+                    }
+                    5 => {
+                        // Pubkey::default() unassigns the role, pools fall back to the amm owner
+                        let role = match role {
+                            Some(role) => role,
+                            None => return Err(ProgramError::InvalidInstructionData.into()),
+                        };
+                        buf.extend_from_slice(&role.role.to_le_bytes());
+                        buf.extend_from_slice(&role.holder.to_bytes());
+                    }
+                    6 => {
+                        let cancel_owner = match owner {
+                            Some(cancel_owner) => {
+                                if *cancel_owner == Pubkey::default() {
+                                    return Err(ProgramError::InvalidInstructionData.into());
+                                } else {
+                                    cancel_owner
+                                }
+                            }
+                            None => return Err(ProgramError::InvalidInstructionData.into()),
+                        };
+                        buf.extend_from_slice(&cancel_owner.to_bytes());
+                    }
                     _ => return Err(ProgramError::InvalidInstructionData.into()),
                 }
             }
@@ -1090,6 +1893,89 @@ impl AmmInstruction {
                 buf.push(20);
                 buf.extend_from_slice(&new_authority.to_bytes());
             },
+            Self::InitializeObservation => {
+                buf.push(21);
+            }
+            Self::RecordObservation => {
+                buf.push(22);
+            }
+            Self::GetTwap(GetTwapInstruction { window }) => {
+                buf.push(23);
+                buf.extend_from_slice(&window.to_le_bytes());
+            }
+            Self::FlashLoan(FlashLoanInstruction {
+                coin_amount,
+                pc_amount,
+                hook_account_num,
+                callback_data,
+            }) => {
+                buf.push(24);
+                buf.extend_from_slice(&coin_amount.to_le_bytes());
+                buf.extend_from_slice(&pc_amount.to_le_bytes());
+                buf.push(*hook_account_num);
+                buf.extend_from_slice(callback_data);
+            }
+            Self::DepositSingleSided(DepositSingleSidedInstruction {
+                amount_in,
+                minimum_lp_out,
+            }) => {
+                buf.push(25);
+                buf.extend_from_slice(&amount_in.to_le_bytes());
+                buf.extend_from_slice(&minimum_lp_out.to_le_bytes());
+            }
+            Self::WithdrawSingleSided(WithdrawSingleSidedInstruction {
+                amount,
+                min_amount_out,
+            }) => {
+                buf.push(26);
+                buf.extend_from_slice(&amount.to_le_bytes());
+                buf.extend_from_slice(&min_amount_out.to_le_bytes());
+            }
+            Self::LockLp(LockLpInstruction {
+                amount,
+                unlock_start_time,
+                unlock_end_time,
+            }) => {
+                buf.push(27);
+                buf.extend_from_slice(&amount.to_le_bytes());
+                buf.extend_from_slice(&unlock_start_time.to_le_bytes());
+                buf.extend_from_slice(&unlock_end_time.to_le_bytes());
+            }
+            Self::ClaimLpLockFee => {
+                buf.push(28);
+            }
+            Self::UnlockLp(UnlockLpInstruction { amount }) => {
+                buf.push(29);
+                buf.extend_from_slice(&amount.to_le_bytes());
+            }
+            Self::LockLpPermanent(LockLpPermanentInstruction { amount }) => {
+                buf.push(30);
+                buf.extend_from_slice(&amount.to_le_bytes());
+            }
+            Self::ClaimPermanentLockFee => {
+                buf.push(31);
+            }
+            Self::InitializeAllowlist(InitializeAllowlistInstruction {
+                merkle_root,
+                allowlist_end_time,
+            }) => {
+                buf.push(32);
+                buf.extend_from_slice(merkle_root);
+                buf.extend_from_slice(&allowlist_end_time.to_le_bytes());
+            }
+            Self::RegisterAllowlistWallet(RegisterAllowlistWalletInstruction { cap, proof }) => {
+                buf.push(33);
+                buf.extend_from_slice(&cap.to_le_bytes());
+                for node in proof.iter() {
+                    buf.extend_from_slice(node);
+                }
+            }
+            Self::ClosePool => {
+                buf.push(34);
+            }
+            Self::MigratePoolAccount => {
+                buf.push(35);
+            }
 
         }
         Ok(buf)
@@ -1120,16 +2006,23 @@ pub fn initialize2(
     open_time: u64,
     init_pc_amount: u64,
     init_coin_amount: u64,
+    launch_fee_numerator: u32,
+    launch_fee_decay_seconds: u32,
+    fee_tier: u32,
+    user_token_fee: Option<&Pubkey>,
 ) -> Result<Instruction, ProgramError> {
     let init_data = AmmInstruction::Initialize2(InitializeInstruction2 {
         nonce,
         open_time,
         init_pc_amount,
         init_coin_amount,
+        launch_fee_numerator,
+        launch_fee_decay_seconds,
+        fee_tier,
     });
     let data = init_data.pack()?;
 
-    let accounts = vec![
+    let mut accounts = vec![
         // spl & sys
         AccountMeta::new_readonly(spl_token::id(), false),
         AccountMeta::new_readonly(spl_associated_token_account::id(), false),
@@ -1156,6 +2049,9 @@ pub fn initialize2(
         AccountMeta::new(*user_token_pc, false),
         AccountMeta::new(*user_token_lp, false),
     ];
+    if let Some(user_token_fee) = user_token_fee {
+        accounts.push(AccountMeta::new(*user_token_fee, false));
+    }
 
     Ok(Instruction {
         program_id: *amm_program,
@@ -1444,10 +2340,12 @@ pub fn migrate_to_openbook(
     new_market: &Pubkey,
 
     admin: &Pubkey,
+    amm_config: &Pubkey,
+    new_open_orders_indexer: Option<&Pubkey>,
 ) -> Result<Instruction, ProgramError> {
     let data = AmmInstruction::MigrateToOpenBook.pack()?;
 
-    let accounts = vec![
+    let mut accounts = vec![
         // spl token
         AccountMeta::new_readonly(spl_token::id(), false),
         AccountMeta::new_readonly(solana_program::system_program::id(), false),
@@ -1474,7 +2372,11 @@ pub fn migrate_to_openbook(
         AccountMeta::new_readonly(*new_market, false),
         // admin
         AccountMeta::new(*admin, true),
+        AccountMeta::new_readonly(*amm_config, false),
     ];
+    if let Some(new_open_orders_indexer) = new_open_orders_indexer {
+        accounts.push(AccountMeta::new(*new_open_orders_indexer, false));
+    }
 
     Ok(Instruction {
         program_id: *amm_program,
@@ -1492,9 +2394,9 @@ pub fn withdrawpnl(
     amm_open_orders: &Pubkey,
     amm_coin_vault: &Pubkey,
     amm_pc_vault: &Pubkey,
-    user_token_coin: &Pubkey,
-    user_token_pc: &Pubkey,
-    user_owner: &Pubkey,
+    treasury_token_coin: &Pubkey,
+    treasury_token_pc: &Pubkey,
+    pnl_owner: &Pubkey,
     amm_target_orders: &Pubkey,
     market_program: &Pubkey,
     market: &Pubkey,
@@ -1502,6 +2404,8 @@ pub fn withdrawpnl(
     market_coin_vault: &Pubkey,
     market_pc_vault: &Pubkey,
     market_vault_signer: &Pubkey,
+    buyback_token_coin: Option<Pubkey>,
+    buyback_token_pc: Option<Pubkey>,
 ) -> Result<Instruction, ProgramError> {
     let data = AmmInstruction::WithdrawPnl.pack()?;
 
@@ -1515,9 +2419,9 @@ pub fn withdrawpnl(
         AccountMeta::new(*amm_open_orders, false),
         AccountMeta::new(*amm_coin_vault, false),
         AccountMeta::new(*amm_pc_vault, false),
-        AccountMeta::new(*user_token_coin, false),
-        AccountMeta::new(*user_token_pc, false),
-        AccountMeta::new_readonly(*user_owner, true),
+        AccountMeta::new(*treasury_token_coin, false),
+        AccountMeta::new(*treasury_token_pc, false),
+        AccountMeta::new_readonly(*pnl_owner, true),
         AccountMeta::new(*amm_target_orders, false),
         // serum
         AccountMeta::new_readonly(*market_program, false),
@@ -1526,6 +2430,9 @@ pub fn withdrawpnl(
         AccountMeta::new(*market_coin_vault, false),
         AccountMeta::new(*market_pc_vault, false),
         AccountMeta::new_readonly(*market_vault_signer, false),
+        // buyback, the amm program id as placeholder
+        AccountMeta::new(buyback_token_coin.unwrap_or(*amm_program), false),
+        AccountMeta::new(buyback_token_pc.unwrap_or(*amm_program), false),
     ];
 
     Ok(Instruction {
@@ -1541,6 +2448,7 @@ pub fn set_params(
     amm_pool: &Pubkey,
     amm_authority: &Pubkey,
     admin: &Pubkey,
+    amm_config: &Pubkey,
     param: u8,
     value: Option<u64>,
     new_pubkey: Option<Pubkey>,
@@ -1559,6 +2467,9 @@ pub fn set_params(
     new_amm_open_orders: Option<Pubkey>,
     fees: Option<Fees>,
     last_order_distance: Option<LastOrderDistance>,
+    dynamic_fee: Option<DynamicFee>,
+    circuit_breaker: Option<CircuitBreaker>,
+    crank_reward: Option<CrankReward>,
 ) -> Result<Instruction, ProgramError> {
     let data = AmmInstruction::SetParams(SetParamsInstruction {
         param,
@@ -1566,6 +2477,9 @@ pub fn set_params(
         new_pubkey,
         fees,
         last_order_distance,
+        dynamic_fee,
+        circuit_breaker,
+        crank_reward,
     })
     .pack()?;
 
@@ -1590,6 +2504,7 @@ pub fn set_params(
         AccountMeta::new(*market_asks, false),
         // admin
         AccountMeta::new_readonly(*admin, true),
+        AccountMeta::new_readonly(*amm_config, false),
     ];
     if param == AmmParams::UpdateOpenOrder.into_u64() as u8 {
         accounts.push(AccountMeta::new_readonly(
@@ -1624,6 +2539,7 @@ pub fn monitor_step(
     market_bids: &Pubkey,
     market_asks: &Pubkey,
     referrer_token_pc: Option<Pubkey>,
+    crank_reward_pc: Option<Pubkey>,
 
     plan_order_limit: u16,
     place_order_limit: u16,
@@ -1660,7 +2576,12 @@ pub fn monitor_step(
         AccountMeta::new(*market_asks, false),
     ];
 
-    if let Some(token_srm) = amm_token_srm {
+    if let Some(crank_reward_pc) = crank_reward_pc {
+        // the amm program id stands in for a missing optional account
+        accounts.push(AccountMeta::new(amm_token_srm.unwrap_or(*amm_program), false));
+        accounts.push(AccountMeta::new(referrer_token_pc.unwrap_or(*amm_program), false));
+        accounts.push(AccountMeta::new(crank_reward_pc, false));
+    } else if let Some(token_srm) = amm_token_srm {
         accounts.push(AccountMeta::new(token_srm, false));
         if let Some(referrer_pc) = referrer_token_pc {
             accounts.push(AccountMeta::new(referrer_pc, false));
@@ -1722,6 +2643,7 @@ pub fn simulate_get_pool_info(
         param: SimulateParams::PoolInfo as u8,
         swap_base_in_value: None,
         swap_base_out_value: None,
+        fee_growth_value: None,
     })
     .pack()?;
 
@@ -1774,6 +2696,7 @@ pub fn simulate_swap_base_in(
             minimum_amount_out: 0,
         }),
         swap_base_out_value: None,
+        fee_growth_value: None,
     })
     .pack()?;
 
@@ -1827,7 +2750,7 @@ pub fn simulate_swap_base_out(
         swap_base_out_value: Some(SwapInstructionBaseOut {
             max_amount_in: 0,
             amount_out,
-        }),
+        }),        fee_growth_value: None,
     })
     .pack()?;
 
@@ -1876,6 +2799,7 @@ pub fn simulate_run_crank(
         param: SimulateParams::RunCrankInfo as u8,
         swap_base_in_value: None,
         swap_base_out_value: None,
+        fee_growth_value: None,
     })
     .pack()?;
 
@@ -1943,7 +2867,7 @@ pub fn admin_cancel_orders(
         AccountMeta::new(*amm_coin_vault, false),
         AccountMeta::new(*amm_pc_vault, false),
         AccountMeta::new_readonly(*amm_cancel_owner, true),
-        AccountMeta::new(*amm_config, false),
+        AccountMeta::new_readonly(*amm_config, false),
         // market
         AccountMeta::new_readonly(*market_program, false),
         AccountMeta::new(*market, false),
@@ -2009,3 +2933,738 @@ pub fn update_config_account(
         data,
     })
 }
+
+/// Creates an 'initialize_observation' instruction.
+pub fn initialize_observation(
+    amm_program: &Pubkey,
+    amm_pool: &Pubkey,
+    observation: &Pubkey,
+    payer: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = AmmInstruction::InitializeObservation.pack()?;
+    let accounts = vec![
+        AccountMeta::new_readonly(*amm_pool, false),
+        AccountMeta::new(*observation, false),
+        AccountMeta::new(*payer, true),
+        AccountMeta::new_readonly(solana_program::system_program::id(), false),
+    ];
+    Ok(Instruction {
+        program_id: *amm_program,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a 'record_observation' instruction.
+pub fn record_observation(
+    amm_program: &Pubkey,
+    amm_pool: &Pubkey,
+    observation: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = AmmInstruction::RecordObservation.pack()?;
+    let accounts = vec![
+        AccountMeta::new_readonly(*amm_pool, false),
+        AccountMeta::new(*observation, false),
+    ];
+    Ok(Instruction {
+        program_id: *amm_program,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a 'get_twap' instruction.
+pub fn get_twap(
+    amm_program: &Pubkey,
+    amm_pool: &Pubkey,
+    observation: &Pubkey,
+    window: u64,
+) -> Result<Instruction, ProgramError> {
+    let data = AmmInstruction::GetTwap(GetTwapInstruction { window }).pack()?;
+    let accounts = vec![
+        AccountMeta::new_readonly(*amm_pool, false),
+        AccountMeta::new_readonly(*observation, false),
+    ];
+    Ok(Instruction {
+        program_id: *amm_program,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a 'flash_loan' instruction.
+pub fn flash_loan(
+    amm_program: &Pubkey,
+    token_program: &Pubkey,
+    amm_pool: &Pubkey,
+    amm_authority: &Pubkey,
+    amm_coin_vault: &Pubkey,
+    amm_pc_vault: &Pubkey,
+    amm_coin_mint: &Pubkey,
+    amm_pc_mint: &Pubkey,
+    borrower_coin: &Pubkey,
+    borrower_pc: &Pubkey,
+    callback_program: &Pubkey,
+    hook_accounts: Vec<AccountMeta>,
+    callback_accounts: Vec<AccountMeta>,
+    coin_amount: u64,
+    pc_amount: u64,
+    callback_data: Vec<u8>,
+) -> Result<Instruction, ProgramError> {
+    let data = AmmInstruction::FlashLoan(FlashLoanInstruction {
+        coin_amount,
+        pc_amount,
+        hook_account_num: hook_accounts
+            .len()
+            .try_into()
+            .map_err(|_| ProgramError::InvalidArgument)?,
+        callback_data,
+    })
+    .pack()?;
+
+    let mut accounts = vec![
+        // spl token
+        AccountMeta::new_readonly(*token_program, false),
+        // amm
+        AccountMeta::new(*amm_pool, false),
+        AccountMeta::new_readonly(*amm_authority, false),
+        AccountMeta::new(*amm_coin_vault, false),
+        AccountMeta::new(*amm_pc_vault, false),
+        AccountMeta::new_readonly(*amm_coin_mint, false),
+        AccountMeta::new_readonly(*amm_pc_mint, false),
+        // borrower
+        AccountMeta::new(*borrower_coin, false),
+        AccountMeta::new(*borrower_pc, false),
+        AccountMeta::new_readonly(*callback_program, false),
+    ];
+    accounts.extend(hook_accounts);
+    accounts.extend(callback_accounts);
+
+    Ok(Instruction {
+        program_id: *amm_program,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a 'deposit_single_sided' instruction.
+pub fn deposit_single_sided(
+    amm_program: &Pubkey,
+    amm_pool: &Pubkey,
+    amm_authority: &Pubkey,
+    amm_open_orders: &Pubkey,
+    amm_target_orders: &Pubkey,
+    amm_lp_mint: &Pubkey,
+    amm_coin_vault: &Pubkey,
+    amm_pc_vault: &Pubkey,
+    market: &Pubkey,
+    market_event_queue: &Pubkey,
+    user_token_source: &Pubkey,
+    user_token_lp: &Pubkey,
+    user_owner: &Pubkey,
+    source_mint: &Pubkey,
+    amount_in: u64,
+    minimum_lp_out: u64,
+) -> Result<Instruction, ProgramError> {
+    let data = AmmInstruction::DepositSingleSided(DepositSingleSidedInstruction {
+        amount_in,
+        minimum_lp_out,
+    })
+    .pack()?;
+
+    let accounts = vec![
+        // spl token
+        AccountMeta::new_readonly(spl_token::id(), false),
+        // amm
+        AccountMeta::new(*amm_pool, false),
+        AccountMeta::new_readonly(*amm_authority, false),
+        AccountMeta::new_readonly(*amm_open_orders, false),
+        AccountMeta::new(*amm_target_orders, false),
+        AccountMeta::new(*amm_lp_mint, false),
+        AccountMeta::new(*amm_coin_vault, false),
+        AccountMeta::new(*amm_pc_vault, false),
+        // market
+        AccountMeta::new_readonly(*market, false),
+        AccountMeta::new_readonly(*market_event_queue, false),
+        // user
+        AccountMeta::new(*user_token_source, false),
+        AccountMeta::new(*user_token_lp, false),
+        AccountMeta::new_readonly(*user_owner, true),
+        AccountMeta::new_readonly(*source_mint, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *amm_program,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a 'withdraw_single_sided' instruction.
+pub fn withdraw_single_sided(
+    amm_program: &Pubkey,
+    amm_pool: &Pubkey,
+    amm_authority: &Pubkey,
+    amm_open_orders: &Pubkey,
+    amm_target_orders: &Pubkey,
+    amm_lp_mint: &Pubkey,
+    amm_coin_vault: &Pubkey,
+    amm_pc_vault: &Pubkey,
+    dest_mint: &Pubkey,
+    market_program: &Pubkey,
+    market: &Pubkey,
+    market_coin_vault: &Pubkey,
+    market_pc_vault: &Pubkey,
+    market_vault_signer: &Pubkey,
+    user_token_lp: &Pubkey,
+    user_token_dest: &Pubkey,
+    user_owner: &Pubkey,
+    market_event_queue: &Pubkey,
+    market_bids: &Pubkey,
+    market_asks: &Pubkey,
+    amount: u64,
+    min_amount_out: u64,
+) -> Result<Instruction, ProgramError> {
+    let data = AmmInstruction::WithdrawSingleSided(WithdrawSingleSidedInstruction {
+        amount,
+        min_amount_out,
+    })
+    .pack()?;
+
+    let accounts = vec![
+        // spl token
+        AccountMeta::new_readonly(spl_token::id(), false),
+        // amm
+        AccountMeta::new(*amm_pool, false),
+        AccountMeta::new_readonly(*amm_authority, false),
+        AccountMeta::new(*amm_open_orders, false),
+        AccountMeta::new(*amm_target_orders, false),
+        AccountMeta::new(*amm_lp_mint, false),
+        AccountMeta::new(*amm_coin_vault, false),
+        AccountMeta::new(*amm_pc_vault, false),
+        AccountMeta::new_readonly(*dest_mint, false),
+        // market
+        AccountMeta::new_readonly(*market_program, false),
+        AccountMeta::new(*market, false),
+        AccountMeta::new(*market_coin_vault, false),
+        AccountMeta::new(*market_pc_vault, false),
+        AccountMeta::new_readonly(*market_vault_signer, false),
+        // user
+        AccountMeta::new(*user_token_lp, false),
+        AccountMeta::new(*user_token_dest, false),
+        AccountMeta::new_readonly(*user_owner, true),
+        AccountMeta::new(*market_event_queue, false),
+        AccountMeta::new(*market_bids, false),
+        AccountMeta::new(*market_asks, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *amm_program,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a 'lock_lp' instruction.
+pub fn lock_lp(
+    amm_program: &Pubkey,
+    amm_pool: &Pubkey,
+    amm_authority: &Pubkey,
+    amm_open_orders: &Pubkey,
+    amm_coin_vault: &Pubkey,
+    amm_pc_vault: &Pubkey,
+    amm_lp_mint: &Pubkey,
+    market: &Pubkey,
+    market_event_queue: &Pubkey,
+    lp_lock: &Pubkey,
+    user_token_lp: &Pubkey,
+    user_owner: &Pubkey,
+    amount: u64,
+    unlock_start_time: u64,
+    unlock_end_time: u64,
+) -> Result<Instruction, ProgramError> {
+    let data = AmmInstruction::LockLp(LockLpInstruction {
+        amount,
+        unlock_start_time,
+        unlock_end_time,
+    })
+    .pack()?;
+
+    let accounts = vec![
+        // spl token
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new_readonly(spl_associated_token_account::id(), false),
+        AccountMeta::new_readonly(solana_program::system_program::id(), false),
+        // amm
+        AccountMeta::new_readonly(*amm_pool, false),
+        AccountMeta::new_readonly(*amm_authority, false),
+        AccountMeta::new_readonly(*amm_open_orders, false),
+        AccountMeta::new_readonly(*amm_coin_vault, false),
+        AccountMeta::new_readonly(*amm_pc_vault, false),
+        AccountMeta::new_readonly(*amm_lp_mint, false),
+        // market
+        AccountMeta::new_readonly(*market, false),
+        AccountMeta::new_readonly(*market_event_queue, false),
+        // lock
+        AccountMeta::new(*lp_lock, false),
+        AccountMeta::new(
+            spl_associated_token_account::get_associated_token_address(
+                amm_authority,
+                amm_lp_mint,
+            ),
+            false,
+        ),
+        // user
+        AccountMeta::new(*user_token_lp, false),
+        AccountMeta::new(*user_owner, true),
+    ];
+
+    Ok(Instruction {
+        program_id: *amm_program,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a 'claim_lp_lock_fee' instruction.
+pub fn claim_lp_lock_fee(
+    amm_program: &Pubkey,
+    amm_pool: &Pubkey,
+    amm_authority: &Pubkey,
+    amm_open_orders: &Pubkey,
+    amm_coin_vault: &Pubkey,
+    amm_pc_vault: &Pubkey,
+    amm_lp_mint: &Pubkey,
+    market: &Pubkey,
+    market_event_queue: &Pubkey,
+    lp_lock: &Pubkey,
+    user_token_lp: &Pubkey,
+    user_owner: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = AmmInstruction::ClaimLpLockFee.pack()?;
+
+    let accounts = vec![
+        // spl token
+        AccountMeta::new_readonly(spl_token::id(), false),
+        // amm
+        AccountMeta::new_readonly(*amm_pool, false),
+        AccountMeta::new_readonly(*amm_authority, false),
+        AccountMeta::new_readonly(*amm_open_orders, false),
+        AccountMeta::new_readonly(*amm_coin_vault, false),
+        AccountMeta::new_readonly(*amm_pc_vault, false),
+        AccountMeta::new_readonly(*amm_lp_mint, false),
+        // market
+        AccountMeta::new_readonly(*market, false),
+        AccountMeta::new_readonly(*market_event_queue, false),
+        // lock
+        AccountMeta::new(*lp_lock, false),
+        AccountMeta::new(
+            spl_associated_token_account::get_associated_token_address(
+                amm_authority,
+                amm_lp_mint,
+            ),
+            false,
+        ),
+        // user
+        AccountMeta::new(*user_token_lp, false),
+        AccountMeta::new_readonly(*user_owner, true),
+    ];
+
+    Ok(Instruction {
+        program_id: *amm_program,
+        accounts,
+        data,
+    })
+}
+
+/// Creates an 'unlock_lp' instruction.
+pub fn unlock_lp(
+    amm_program: &Pubkey,
+    amm_pool: &Pubkey,
+    amm_authority: &Pubkey,
+    amm_lp_mint: &Pubkey,
+    lp_lock: &Pubkey,
+    user_token_lp: &Pubkey,
+    user_owner: &Pubkey,
+    amount: u64,
+) -> Result<Instruction, ProgramError> {
+    let data = AmmInstruction::UnlockLp(UnlockLpInstruction { amount }).pack()?;
+
+    let accounts = vec![
+        // spl token
+        AccountMeta::new_readonly(spl_token::id(), false),
+        // amm
+        AccountMeta::new_readonly(*amm_pool, false),
+        AccountMeta::new_readonly(*amm_authority, false),
+        AccountMeta::new_readonly(*amm_lp_mint, false),
+        // lock
+        AccountMeta::new(*lp_lock, false),
+        AccountMeta::new(
+            spl_associated_token_account::get_associated_token_address(
+                amm_authority,
+                amm_lp_mint,
+            ),
+            false,
+        ),
+        // user
+        AccountMeta::new(*user_token_lp, false),
+        AccountMeta::new(*user_owner, true),
+    ];
+
+    Ok(Instruction {
+        program_id: *amm_program,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a 'simulate_get_lp_lock_info' instruction.
+pub fn simulate_get_lp_lock_info(
+    amm_program: &Pubkey,
+    amm_pool: &Pubkey,
+    amm_authority: &Pubkey,
+    amm_open_orders: &Pubkey,
+    amm_coin_vault: &Pubkey,
+    amm_pc_vault: &Pubkey,
+    amm_lp_mint: &Pubkey,
+    market: &Pubkey,
+    market_event_queue: &Pubkey,
+    lp_lock: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = AmmInstruction::SimulateInfo(SimulateInstruction {
+        param: SimulateParams::LpLockInfo as u8,
+        swap_base_in_value: None,
+        swap_base_out_value: None,
+        fee_growth_value: None,
+    })
+    .pack()?;
+
+    let accounts = vec![
+        // amm
+        AccountMeta::new_readonly(*amm_pool, false),
+        AccountMeta::new_readonly(*amm_authority, false),
+        AccountMeta::new_readonly(*amm_open_orders, false),
+        AccountMeta::new_readonly(*amm_coin_vault, false),
+        AccountMeta::new_readonly(*amm_pc_vault, false),
+        AccountMeta::new_readonly(*amm_lp_mint, false),
+        // market
+        AccountMeta::new_readonly(*market, false),
+        AccountMeta::new_readonly(*market_event_queue, false),
+        // lock
+        AccountMeta::new_readonly(*lp_lock, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *amm_program,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a 'lock_lp_permanent' instruction.
+pub fn lock_lp_permanent(
+    amm_program: &Pubkey,
+    amm_pool: &Pubkey,
+    amm_authority: &Pubkey,
+    amm_open_orders: &Pubkey,
+    amm_coin_vault: &Pubkey,
+    amm_pc_vault: &Pubkey,
+    amm_lp_mint: &Pubkey,
+    market: &Pubkey,
+    market_event_queue: &Pubkey,
+    lp_lock: &Pubkey,
+    user_token_lp: &Pubkey,
+    user_owner: &Pubkey,
+    amount: u64,
+) -> Result<Instruction, ProgramError> {
+    let mut instruction = lock_lp(
+        amm_program,
+        amm_pool,
+        amm_authority,
+        amm_open_orders,
+        amm_coin_vault,
+        amm_pc_vault,
+        amm_lp_mint,
+        market,
+        market_event_queue,
+        lp_lock,
+        user_token_lp,
+        user_owner,
+        amount,
+        u64::MAX,
+        u64::MAX,
+    )?;
+    instruction.data =
+        AmmInstruction::LockLpPermanent(LockLpPermanentInstruction { amount }).pack()?;
+    Ok(instruction)
+}
+
+/// Creates a 'claim_permanent_lock_fee' instruction.
+pub fn claim_permanent_lock_fee(
+    amm_program: &Pubkey,
+    amm_pool: &Pubkey,
+    amm_authority: &Pubkey,
+    amm_open_orders: &Pubkey,
+    amm_target_orders: &Pubkey,
+    amm_coin_vault: &Pubkey,
+    amm_pc_vault: &Pubkey,
+    amm_lp_mint: &Pubkey,
+    amm_coin_mint: &Pubkey,
+    amm_pc_mint: &Pubkey,
+    market: &Pubkey,
+    market_event_queue: &Pubkey,
+    lp_lock: &Pubkey,
+    user_token_coin: &Pubkey,
+    user_token_pc: &Pubkey,
+    user_owner: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = AmmInstruction::ClaimPermanentLockFee.pack()?;
+
+    let accounts = vec![
+        // spl token
+        AccountMeta::new_readonly(spl_token::id(), false),
+        // amm
+        AccountMeta::new(*amm_pool, false),
+        AccountMeta::new_readonly(*amm_authority, false),
+        AccountMeta::new_readonly(*amm_open_orders, false),
+        AccountMeta::new(*amm_target_orders, false),
+        AccountMeta::new(*amm_coin_vault, false),
+        AccountMeta::new(*amm_pc_vault, false),
+        AccountMeta::new(*amm_lp_mint, false),
+        AccountMeta::new_readonly(*amm_coin_mint, false),
+        AccountMeta::new_readonly(*amm_pc_mint, false),
+        // market
+        AccountMeta::new_readonly(*market, false),
+        AccountMeta::new_readonly(*market_event_queue, false),
+        // lock
+        AccountMeta::new(*lp_lock, false),
+        AccountMeta::new(
+            spl_associated_token_account::get_associated_token_address(
+                amm_authority,
+                amm_lp_mint,
+            ),
+            false,
+        ),
+        // user
+        AccountMeta::new(*user_token_coin, false),
+        AccountMeta::new(*user_token_pc, false),
+        AccountMeta::new_readonly(*user_owner, true),
+    ];
+
+    Ok(Instruction {
+        program_id: *amm_program,
+        accounts,
+        data,
+    })
+}
+
+/// Creates an 'initialize_allowlist' instruction.
+pub fn initialize_allowlist(
+    amm_program: &Pubkey,
+    amm_pool: &Pubkey,
+    allowlist: &Pubkey,
+    whitelist_admin: &Pubkey,
+    amm_config: &Pubkey,
+    merkle_root: [u8; 32],
+    allowlist_end_time: u64,
+) -> Result<Instruction, ProgramError> {
+    let data = AmmInstruction::InitializeAllowlist(InitializeAllowlistInstruction {
+        merkle_root,
+        allowlist_end_time,
+    })
+    .pack()?;
+
+    let accounts = vec![
+        AccountMeta::new(*amm_pool, false),
+        AccountMeta::new(*allowlist, false),
+        AccountMeta::new(*whitelist_admin, true),
+        AccountMeta::new_readonly(solana_program::system_program::id(), false),
+        AccountMeta::new_readonly(*amm_config, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *amm_program,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a 'register_allowlist_wallet' instruction.
+pub fn register_allowlist_wallet(
+    amm_program: &Pubkey,
+    amm_pool: &Pubkey,
+    allowlist: &Pubkey,
+    allowlist_entry: &Pubkey,
+    user_owner: &Pubkey,
+    cap: u64,
+    proof: Vec<[u8; 32]>,
+) -> Result<Instruction, ProgramError> {
+    let data = AmmInstruction::RegisterAllowlistWallet(RegisterAllowlistWalletInstruction {
+        cap,
+        proof,
+    })
+    .pack()?;
+
+    let accounts = vec![
+        AccountMeta::new_readonly(*amm_pool, false),
+        AccountMeta::new_readonly(*allowlist, false),
+        AccountMeta::new(*allowlist_entry, false),
+        AccountMeta::new(*user_owner, true),
+        AccountMeta::new_readonly(solana_program::system_program::id(), false),
+    ];
+
+    Ok(Instruction {
+        program_id: *amm_program,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a 'close_pool' instruction.
+pub fn close_pool(
+    amm_program: &Pubkey,
+    amm_pool: &Pubkey,
+    amm_authority: &Pubkey,
+    amm_open_orders: &Pubkey,
+    amm_target_orders: &Pubkey,
+    amm_lp_mint: &Pubkey,
+    amm_coin_vault: &Pubkey,
+    amm_pc_vault: &Pubkey,
+    amm_coin_mint: &Pubkey,
+    amm_pc_mint: &Pubkey,
+    market_program: &Pubkey,
+    market: &Pubkey,
+    owner_token_coin: &Pubkey,
+    owner_token_pc: &Pubkey,
+    amm_owner: &Pubkey,
+    amm_config: &Pubkey,
+    amm_allowlist: &Pubkey,
+    amm_observation: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = AmmInstruction::ClosePool.pack()?;
+
+    let accounts = vec![
+        // spl token
+        AccountMeta::new_readonly(spl_token::id(), false),
+        // amm
+        AccountMeta::new(*amm_pool, false),
+        AccountMeta::new_readonly(*amm_authority, false),
+        AccountMeta::new(*amm_open_orders, false),
+        AccountMeta::new(*amm_target_orders, false),
+        AccountMeta::new_readonly(*amm_lp_mint, false),
+        AccountMeta::new(*amm_coin_vault, false),
+        AccountMeta::new(*amm_pc_vault, false),
+        AccountMeta::new_readonly(*amm_coin_mint, false),
+        AccountMeta::new_readonly(*amm_pc_mint, false),
+        // market
+        AccountMeta::new_readonly(*market_program, false),
+        AccountMeta::new_readonly(*market, false),
+        // owner
+        AccountMeta::new(*owner_token_coin, false),
+        AccountMeta::new(*owner_token_pc, false),
+        AccountMeta::new(*amm_owner, true),
+        AccountMeta::new_readonly(*amm_config, false),
+        // pool accounts closed with it
+        AccountMeta::new(*amm_allowlist, false),
+        AccountMeta::new(*amm_observation, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *amm_program,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a 'migrate_pool_account' instruction.
+pub fn migrate_pool_account(
+    amm_program: &Pubkey,
+    amm_pool: &Pubkey,
+    payer: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = AmmInstruction::MigratePoolAccount.pack()?;
+
+    let accounts = vec![
+        AccountMeta::new(*amm_pool, false),
+        AccountMeta::new(*payer, true),
+        AccountMeta::new_readonly(solana_program::system_program::id(), false),
+    ];
+
+    Ok(Instruction {
+        program_id: *amm_program,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a 'simulate_get_fee_growth_info' instruction.
+pub fn simulate_get_fee_growth_info(
+    amm_program: &Pubkey,
+    amm_pool: &Pubkey,
+    lp_amount: u64,
+    fee_growth_coin_per_lp: u128,
+    fee_growth_pc_per_lp: u128,
+) -> Result<Instruction, ProgramError> {
+    let data = AmmInstruction::SimulateInfo(SimulateInstruction {
+        param: SimulateParams::FeeGrowthInfo as u8,
+        swap_base_in_value: None,
+        swap_base_out_value: None,
+        fee_growth_value: Some(FeeGrowthSnapshot {
+            lp_amount,
+            fee_growth_coin_per_lp,
+            fee_growth_pc_per_lp,
+        }),
+    })
+    .pack()?;
+
+    let accounts = vec![
+        // amm
+        AccountMeta::new_readonly(*amm_pool, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *amm_program,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a 'simulate_get_ladder_info' instruction.
+pub fn simulate_get_ladder_info(
+    amm_program: &Pubkey,
+    amm_pool: &Pubkey,
+    amm_authority: &Pubkey,
+    amm_open_orders: &Pubkey,
+    amm_target_orders: &Pubkey,
+    amm_coin_vault: &Pubkey,
+    amm_pc_vault: &Pubkey,
+    market: &Pubkey,
+    market_event_queue: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = AmmInstruction::SimulateInfo(SimulateInstruction {
+        param: SimulateParams::LadderInfo as u8,
+        swap_base_in_value: None,
+        swap_base_out_value: None,
+        fee_growth_value: None,
+    })
+    .pack()?;
+
+    let accounts = vec![
+        // amm
+        AccountMeta::new_readonly(*amm_pool, false),
+        AccountMeta::new_readonly(*amm_authority, false),
+        AccountMeta::new_readonly(*amm_open_orders, false),
+        AccountMeta::new_readonly(*amm_target_orders, false),
+        AccountMeta::new_readonly(*amm_coin_vault, false),
+        AccountMeta::new_readonly(*amm_pc_vault, false),
+        // market
+        AccountMeta::new_readonly(*market, false),
+        AccountMeta::new_readonly(*market_event_queue, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *amm_program,
+        accounts,
+        data,
+    })
+}
@@ -1032,4 +1032,255 @@ impl Invokers {
         }
         solana_program::program::invoke_signed(&ix, &accounts, signers)
     }
-}
\ No newline at end of file
+
+    /// Issue an OpenBook v2 `PlaceOrder` instruction, post only.
+    pub fn invoke_openbook_v2_place_order<'a>(
+        market_program: AccountInfo<'a>,
+        open_orders: AccountInfo<'a>,
+        open_orders_owner: AccountInfo<'a>,
+        payer: AccountInfo<'a>,
+        market: AccountInfo<'a>,
+        bids: AccountInfo<'a>,
+        asks: AccountInfo<'a>,
+        event_heap: AccountInfo<'a>,
+        market_vault: AccountInfo<'a>,
+        token_program: AccountInfo<'a>,
+        amm_seed: &[u8],
+        nonce: u8,
+        side: serum_dex::matching::Side,
+        price_lots: u64,
+        max_base_lots: u64,
+        max_quote_lots_including_fees: u64,
+        client_order_id: u64,
+    ) -> Result<(), ProgramError> {
+        let authority_signature_seeds = [amm_seed, &[nonce]];
+        let signers = &[&authority_signature_seeds[..]];
+
+        let ix = crate::openbook_v2::place_order(
+            market_program.key,
+            open_orders_owner.key,
+            open_orders.key,
+            payer.key,
+            market.key,
+            bids.key,
+            asks.key,
+            event_heap.key,
+            market_vault.key,
+            token_program.key,
+            side,
+            price_lots,
+            max_base_lots,
+            max_quote_lots_including_fees,
+            client_order_id,
+            u8::MAX,
+        );
+        let accounts = [
+            market_program,
+            open_orders_owner,
+            open_orders,
+            payer,
+            market,
+            bids,
+            asks,
+            event_heap,
+            market_vault,
+            token_program,
+        ];
+        solana_program::program::invoke_signed(&ix, &accounts, signers)
+    }
+
+    /// Issue an OpenBook v2 `CancelOrderByClientOrderId` instruction.
+    pub fn invoke_openbook_v2_cancel_order_by_client_order_id<'a>(
+        market_program: AccountInfo<'a>,
+        market: AccountInfo<'a>,
+        bids: AccountInfo<'a>,
+        asks: AccountInfo<'a>,
+        open_orders: AccountInfo<'a>,
+        open_orders_owner: AccountInfo<'a>,
+        amm_seed: &[u8],
+        nonce: u8,
+        client_order_id: u64,
+    ) -> Result<(), ProgramError> {
+        let authority_signature_seeds = [amm_seed, &[nonce]];
+        let signers = &[&authority_signature_seeds[..]];
+
+        let ix = crate::openbook_v2::cancel_order_by_client_order_id(
+            market_program.key,
+            open_orders_owner.key,
+            open_orders.key,
+            market.key,
+            bids.key,
+            asks.key,
+            client_order_id,
+        );
+        let accounts = [market_program, open_orders_owner, open_orders, market, bids, asks];
+        solana_program::program::invoke_signed(&ix, &accounts, signers)
+    }
+
+    /// Issue an OpenBook v2 `CancelAllOrders` instruction, for one side or both.
+    pub fn invoke_openbook_v2_cancel_all_orders<'a>(
+        market_program: AccountInfo<'a>,
+        market: AccountInfo<'a>,
+        bids: AccountInfo<'a>,
+        asks: AccountInfo<'a>,
+        open_orders: AccountInfo<'a>,
+        open_orders_owner: AccountInfo<'a>,
+        amm_seed: &[u8],
+        nonce: u8,
+        side: Option<serum_dex::matching::Side>,
+    ) -> Result<(), ProgramError> {
+        let authority_signature_seeds = [amm_seed, &[nonce]];
+        let signers = &[&authority_signature_seeds[..]];
+
+        let ix = crate::openbook_v2::cancel_all_orders(
+            market_program.key,
+            open_orders_owner.key,
+            open_orders.key,
+            market.key,
+            bids.key,
+            asks.key,
+            side,
+            u8::MAX,
+        );
+        let accounts = [market_program, open_orders_owner, open_orders, market, bids, asks];
+        solana_program::program::invoke_signed(&ix, &accounts, signers)
+    }
+
+    /// Issue an OpenBook v2 `CreateOpenOrdersIndexer` instruction.
+    pub fn invoke_openbook_v2_create_open_orders_indexer<'a>(
+        market_program: AccountInfo<'a>,
+        payer: AccountInfo<'a>,
+        owner: AccountInfo<'a>,
+        open_orders_indexer: AccountInfo<'a>,
+        system_program: AccountInfo<'a>,
+        amm_seed: &[u8],
+        nonce: u8,
+    ) -> Result<(), ProgramError> {
+        let authority_signature_seeds = [amm_seed, &[nonce]];
+        let signers = &[&authority_signature_seeds[..]];
+
+        let ix = crate::openbook_v2::create_open_orders_indexer(
+            market_program.key,
+            payer.key,
+            owner.key,
+            open_orders_indexer.key,
+            system_program.key,
+        );
+        let accounts = [market_program, payer, owner, open_orders_indexer, system_program];
+        solana_program::program::invoke_signed(&ix, &accounts, signers)
+    }
+
+    /// Issue an OpenBook v2 `CreateOpenOrdersAccount` instruction.
+    pub fn invoke_openbook_v2_create_open_orders_account<'a>(
+        market_program: AccountInfo<'a>,
+        payer: AccountInfo<'a>,
+        owner: AccountInfo<'a>,
+        open_orders_indexer: AccountInfo<'a>,
+        open_orders: AccountInfo<'a>,
+        market: AccountInfo<'a>,
+        system_program: AccountInfo<'a>,
+        amm_seed: &[u8],
+        nonce: u8,
+    ) -> Result<(), ProgramError> {
+        let authority_signature_seeds = [amm_seed, &[nonce]];
+        let signers = &[&authority_signature_seeds[..]];
+
+        let ix = crate::openbook_v2::create_open_orders_account(
+            market_program.key,
+            payer.key,
+            owner.key,
+            open_orders_indexer.key,
+            open_orders.key,
+            market.key,
+            system_program.key,
+            crate::openbook_v2::OPEN_ORDERS_ACCOUNT_NAME,
+        );
+        let accounts = [
+            market_program,
+            payer,
+            owner,
+            open_orders_indexer,
+            open_orders,
+            market,
+            system_program,
+        ];
+        solana_program::program::invoke_signed(&ix, &accounts, signers)
+    }
+
+    /// Issue an OpenBook v2 `SettleFunds` instruction.
+    pub fn invoke_openbook_v2_settle_funds<'a>(
+        market_program: AccountInfo<'a>,
+        market: AccountInfo<'a>,
+        open_orders: AccountInfo<'a>,
+        owner: AccountInfo<'a>, //open_orders.owner
+        market_authority: AccountInfo<'a>,
+        base_vault: AccountInfo<'a>,
+        quote_vault: AccountInfo<'a>,
+        coin_wallet: AccountInfo<'a>,
+        pc_wallet: AccountInfo<'a>,
+        spl_token_program: AccountInfo<'a>,
+        system_program: AccountInfo<'a>,
+        referrer_pc_wallet: Option<&AccountInfo<'a>>,
+        amm_seed: &[u8],
+        nonce: u8,
+    ) -> Result<(), ProgramError> {
+        let authority_signature_seeds = [amm_seed, &[nonce]];
+        let signers = &[&authority_signature_seeds[..]];
+
+        let ix = crate::openbook_v2::settle_funds(
+            market_program.key,
+            owner.key,
+            open_orders.key,
+            market.key,
+            market_authority.key,
+            base_vault.key,
+            quote_vault.key,
+            coin_wallet.key,
+            pc_wallet.key,
+            referrer_pc_wallet.map(|referrer| referrer.key),
+            spl_token_program.key,
+            system_program.key,
+        );
+        let mut accounts = vec![
+            market_program,
+            owner,
+            open_orders,
+            market,
+            market_authority,
+            base_vault,
+            quote_vault,
+            coin_wallet,
+            pc_wallet,
+            spl_token_program,
+            system_program,
+        ];
+        if let Some(referrer_pc_account) = referrer_pc_wallet {
+            accounts.push(referrer_pc_account.clone());
+        }
+        solana_program::program::invoke_signed(&ix, &accounts, signers)
+    }
+
+    /// Issue the flash loan callback of the borrower program, forwarding the
+    /// borrower accounts with their signer and writable flags
+    pub fn invoke_flash_loan_callback<'a>(
+        callback_program: AccountInfo<'a>,
+        callback_accounts: &[AccountInfo<'a>],
+        data: Vec<u8>,
+    ) -> Result<(), ProgramError> {
+        let ix = Instruction {
+            program_id: *callback_program.key,
+            accounts: callback_accounts
+                .iter()
+                .map(|account| AccountMeta {
+                    pubkey: *account.key,
+                    is_signer: account.is_signer,
+                    is_writable: account.is_writable,
+                })
+                .collect(),
+            data,
+        };
+        let mut accounts = callback_accounts.to_vec();
+        accounts.push(callback_program);
+        solana_program::program::invoke(&ix, &accounts)
+    }
+}
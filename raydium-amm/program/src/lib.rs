@@ -6,6 +6,7 @@ pub mod instruction;
 pub mod invokers;
 pub mod log;
 pub mod math;
+pub mod openbook_v2;
 pub mod process;
 pub mod state;
 
@@ -92,6 +93,51 @@ pub fn process_instruction(
         crate::instruction::AmmInstruction::UpdateWhitelistAuthority { new_authority } => {
             crate::process::whitelist::process_update_whitelist_authority(program_id, accounts, new_authority)
         }
+        crate::instruction::AmmInstruction::InitializeObservation => {
+            crate::process::process_initialize_observation(program_id, accounts)
+        }
+        crate::instruction::AmmInstruction::RecordObservation => {
+            crate::process::process_record_observation(program_id, accounts)
+        }
+        crate::instruction::AmmInstruction::GetTwap(get_twap) => {
+            crate::process::process_get_twap(program_id, accounts, get_twap)
+        }
+        crate::instruction::AmmInstruction::FlashLoan(flash_loan) => {
+            crate::process::process_flash_loan(program_id, accounts, flash_loan)
+        }
+        crate::instruction::AmmInstruction::DepositSingleSided(deposit) => {
+            crate::process::process_deposit_single_sided(program_id, accounts, deposit)
+        }
+        crate::instruction::AmmInstruction::WithdrawSingleSided(withdraw) => {
+            crate::process::process_withdraw_single_sided(program_id, accounts, withdraw)
+        }
+        crate::instruction::AmmInstruction::LockLp(lock) => {
+            crate::process::process_lock_lp(program_id, accounts, lock)
+        }
+        crate::instruction::AmmInstruction::ClaimLpLockFee => {
+            crate::process::process_claim_lp_lock_fee(program_id, accounts)
+        }
+        crate::instruction::AmmInstruction::UnlockLp(unlock) => {
+            crate::process::process_unlock_lp(program_id, accounts, unlock)
+        }
+        crate::instruction::AmmInstruction::LockLpPermanent(lock) => {
+            crate::process::process_lock_lp_permanent(program_id, accounts, lock)
+        }
+        crate::instruction::AmmInstruction::ClaimPermanentLockFee => {
+            crate::process::process_claim_permanent_lock_fee(program_id, accounts)
+        }
+        crate::instruction::AmmInstruction::InitializeAllowlist(init) => {
+            crate::process::process_initialize_allowlist(program_id, accounts, init)
+        }
+        crate::instruction::AmmInstruction::RegisterAllowlistWallet(register) => {
+            crate::process::process_register_allowlist_wallet(program_id, accounts, register)
+        }
+        crate::instruction::AmmInstruction::ClosePool => {
+            crate::process::process_close_pool(program_id, accounts)
+        }
+        crate::instruction::AmmInstruction::MigratePoolAccount => {
+            crate::process::process_migrate_pool_account(program_id, accounts)
+        }
     }
 }
 
@@ -2,11 +2,14 @@ use arrform::{arrform, ArrForm};
 use serde::{Deserialize, Serialize};
 use solana_program::{
     msg,
+    program::{get_return_data, set_return_data},
     // entrypoint::ProgramResult,
     pubkey::Pubkey,
 };
 
 pub const LOG_SIZE: usize = 256;
+/// version of AmmReturnData, bumped whenever a field is appended
+pub const RETURN_DATA_VERSION: u8 = 1;
 
 #[macro_export]
 macro_rules! check_assert_eq {
@@ -37,6 +40,10 @@ pub enum LogType {
     Withdraw,
     SwapBaseIn,
     SwapBaseOut,
+    WithdrawSingleSided,
+    WithdrawPnl,
+    CompoundPnl,
+    FlashLoan,
 }
 
 impl LogType {
@@ -47,6 +54,10 @@ impl LogType {
             2 => LogType::Withdraw,
             3 => LogType::SwapBaseIn,
             4 => LogType::SwapBaseOut,
+            5 => LogType::WithdrawSingleSided,
+            6 => LogType::WithdrawPnl,
+            7 => LogType::CompoundPnl,
+            8 => LogType::FlashLoan,
             _ => unreachable!(),
         }
     }
@@ -58,6 +69,10 @@ impl LogType {
             LogType::Withdraw => 2u8,
             LogType::SwapBaseIn => 3u8,
             LogType::SwapBaseOut => 4u8,
+            LogType::WithdrawSingleSided => 5u8,
+            LogType::WithdrawPnl => 6u8,
+            LogType::CompoundPnl => 7u8,
+            LogType::FlashLoan => 8u8,
         }
     }
 }
@@ -73,6 +88,9 @@ pub struct InitLog {
     pub pc_amount: u64,
     pub coin_amount: u64,
     pub market: Pubkey,
+    // create pool fee paid, lamports when the mint is Pubkey::default()
+    pub create_pool_fee: u64,
+    pub create_pool_fee_mint: Pubkey,
 }
 
 #[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
@@ -144,6 +162,109 @@ pub struct SwapBaseOutLog {
     pub deduct_in: u64,
 }
 
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct WithdrawSingleSidedLog {
+    pub log_type: u8,
+    // input
+    pub withdraw_lp: u64,
+    pub minimum_out: u64,
+    pub direction: u64,
+    // user info
+    pub user_lp: u64,
+    // pool info
+    pub pool_coin: u64,
+    pub pool_pc: u64,
+    pub pool_lp: u64,
+    // calc result
+    pub out_coin: u64,
+    pub out_pc: u64,
+    pub swap_out: u64,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct WithdrawPnlLog {
+    pub log_type: u8,
+    // payouts
+    pub treasury_coin: u64,
+    pub treasury_pc: u64,
+    pub buyback_coin: u64,
+    pub buyback_pc: u64,
+    pub lp_rebate_coin: u64,
+    pub lp_rebate_pc: u64,
+    // lifetime claimed after the instruction
+    pub claimed_coin: u64,
+    pub claimed_pc: u64,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct CompoundPnlLog {
+    pub log_type: u8,
+    // pnl left to the lp
+    pub compound_coin: u64,
+    pub compound_pc: u64,
+    // lifetime compounded after the instruction
+    pub compounded_coin: u64,
+    pub compounded_pc: u64,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct FlashLoanLog {
+    pub log_type: u8,
+    // lent amounts
+    pub coin_amount: u64,
+    pub pc_amount: u64,
+    // flash fees left in the vaults
+    pub coin_fee: u64,
+    pub pc_fee: u64,
+    // pool info after the loan is repaid
+    pub pool_coin: u64,
+    pub pool_pc: u64,
+}
+
+/// Outcome of a swap, deposit or withdraw, written with set_return_data for cpi callers
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct AmmReturnData {
+    pub version: u8,
+    /// LogType of the instruction
+    pub log_type: u8,
+    // user amounts
+    pub coin_in: u64,
+    pub pc_in: u64,
+    pub coin_out: u64,
+    pub pc_out: u64,
+    pub coin_fee: u64,
+    pub pc_fee: u64,
+    pub lp_mint: u64,
+    pub lp_burn: u64,
+    // pool info after the instruction
+    pub pool_coin: u64,
+    pub pool_pc: u64,
+    pub pool_lp: u64,
+}
+
+pub fn encode_return_data(mut data: AmmReturnData) {
+    data.version = RETURN_DATA_VERSION;
+    let bytes = bincode::serialize(&data).unwrap();
+    set_return_data(&bytes);
+}
+
+/// Decode AmmReturnData bytes, fields appended by newer versions are ignored
+pub fn decode_return_data(data: &[u8]) -> Option<AmmReturnData> {
+    match data.first() {
+        Some(&version) if version != 0 => bincode::deserialize(data).ok(),
+        _ => None,
+    }
+}
+
+/// Read the AmmReturnData left by the last cpi into the amm program
+pub fn get_amm_return_data(amm_program: &Pubkey) -> Option<AmmReturnData> {
+    let (program_id, data) = get_return_data()?;
+    if program_id != *amm_program {
+        return None;
+    }
+    decode_return_data(&data)
+}
+
 pub fn encode_ray_log<T: Serialize>(log: T) {
     // encode
     let bytes = bincode::serialize(&log).unwrap();
@@ -178,5 +299,21 @@ pub fn decode_ray_log(log: &str) {
             let log: SwapBaseOutLog = bincode::deserialize(&bytes).unwrap();
             println!("{:?}", log);
         }
+        LogType::WithdrawSingleSided => {
+            let log: WithdrawSingleSidedLog = bincode::deserialize(&bytes).unwrap();
+            println!("{:?}", log);
+        }
+        LogType::WithdrawPnl => {
+            let log: WithdrawPnlLog = bincode::deserialize(&bytes).unwrap();
+            println!("{:?}", log);
+        }
+        LogType::CompoundPnl => {
+            let log: CompoundPnlLog = bincode::deserialize(&bytes).unwrap();
+            println!("{:?}", log);
+        }
+        LogType::FlashLoan => {
+            let log: FlashLoanLog = bincode::deserialize(&bytes).unwrap();
+            println!("{:?}", log);
+        }
     }
 }
@@ -4,7 +4,14 @@
 #![allow(clippy::unknown_clippy_lints)]
 #![allow(clippy::manual_range_contains)]
 
-use crate::{error::AmmError, state::AmmInfo};
+use crate::{
+    error::AmmError,
+    openbook_v2::{MarketV2, OpenOrdersV2},
+    state::{
+        AmmInfo, DYNAMIC_FEE_VOLATILITY_CAP, TEN_THOUSAND, VOLATILITY_EMA_ALPHA,
+        VOLATILITY_PRECISION,
+    },
+};
 use num_traits::CheckedDiv;
 use serum_dex::{
     matching::Side,
@@ -320,6 +327,28 @@ impl Calculator {
         Ok((total_pc_without_take_pnl, total_coin_without_take_pnl))
     }
 
+    pub fn calc_total_without_take_pnl_openbook_v2(
+        pc_amount: u64,
+        coin_amount: u64,
+        open_orders: &OpenOrdersV2,
+        amm: &AmmInfo,
+        market: &MarketV2,
+    ) -> Result<(u64, u64), AmmError> {
+        let (pc_total_in_market, coin_total_in_market) = open_orders.native_totals(market)?;
+
+        let total_pc_without_take_pnl = pc_amount
+            .checked_add(pc_total_in_market)
+            .ok_or(AmmError::CheckedAddOverflow)?
+            .checked_sub(amm.state_data.need_take_pnl_pc)
+            .ok_or(AmmError::CheckedSubOverflow)?;
+        let total_coin_without_take_pnl = coin_amount
+            .checked_add(coin_total_in_market)
+            .ok_or(AmmError::CheckedAddOverflow)?
+            .checked_sub(amm.state_data.need_take_pnl_coin)
+            .ok_or(AmmError::CheckedSubOverflow)?;
+        Ok((total_pc_without_take_pnl, total_coin_without_take_pnl))
+    }
+
     pub fn calc_total_without_take_pnl_no_orderbook<'a>(
         pc_amount: u64,
         coin_amount: u64,
@@ -452,6 +481,329 @@ impl Calculator {
         }
         return amount_in;
     }
+
+    /// Scale the swap fee linearly between min and max with the volatility ema,
+    /// reaching max once the ema hits DYNAMIC_FEE_VOLATILITY_CAP
+    pub fn calc_dynamic_fee_numerator(
+        min_fee_numerator: u64,
+        max_fee_numerator: u64,
+        volatility_ema: u64,
+    ) -> u64 {
+        let volatility = std::cmp::min(volatility_ema, DYNAMIC_FEE_VOLATILITY_CAP);
+        let fee_range = max_fee_numerator.saturating_sub(min_fee_numerator);
+        let extra_fee = U128::from(fee_range)
+            .checked_mul(volatility.into())
+            .unwrap()
+            .checked_div(DYNAMIC_FEE_VOLATILITY_CAP.into())
+            .unwrap()
+            .as_u64();
+        min_fee_numerator.checked_add(extra_fee).unwrap()
+    }
+
+    /// Launch fee after elapsed_seconds, decays linearly from launch_fee_numerator
+    /// to base_fee_numerator over decay_seconds
+    pub fn calc_launch_fee_numerator(
+        launch_fee_numerator: u64,
+        base_fee_numerator: u64,
+        elapsed_seconds: u64,
+        decay_seconds: u64,
+    ) -> u64 {
+        if elapsed_seconds >= decay_seconds || launch_fee_numerator <= base_fee_numerator {
+            return base_fee_numerator;
+        }
+        let fee_decay = U128::from(launch_fee_numerator - base_fee_numerator)
+            .checked_mul(elapsed_seconds.into())
+            .unwrap()
+            .checked_div(decay_seconds.into())
+            .unwrap()
+            .as_u64();
+        launch_fee_numerator.checked_sub(fee_decay).unwrap()
+    }
+
+    /// Fold the price move of one swap into the volatility ema.
+    /// The price move is |p1 - p0| / p0 with p = pc / coin, VOLATILITY_PRECISION as denominator
+    pub fn calc_volatility_ema(
+        volatility_ema: u64,
+        pc_before: u64,
+        coin_before: u64,
+        pc_after: u64,
+        coin_after: u64,
+    ) -> u64 {
+        if pc_before == 0 || coin_after == 0 {
+            return volatility_ema;
+        }
+        // p1 / p0 = (pc_after * coin_before) / (coin_after * pc_before)
+        let price_after = U256::from(pc_after)
+            .checked_mul(coin_before.into())
+            .unwrap();
+        let price_before = U256::from(pc_before)
+            .checked_mul(coin_after.into())
+            .unwrap();
+        let price_diff = if price_after > price_before {
+            price_after - price_before
+        } else {
+            price_before - price_after
+        };
+        let price_move = std::cmp::min(
+            price_diff
+                .checked_mul(VOLATILITY_PRECISION.into())
+                .unwrap()
+                .checked_div(price_before)
+                .unwrap(),
+            U256::from(VOLATILITY_PRECISION),
+        )
+        .as_u64();
+        // ema = ema * (1 - alpha) + price_move * alpha
+        U128::from(volatility_ema)
+            .checked_mul((TEN_THOUSAND - VOLATILITY_EMA_ALPHA).into())
+            .unwrap()
+            .checked_add(
+                U128::from(price_move)
+                    .checked_mul(VOLATILITY_EMA_ALPHA.into())
+                    .unwrap(),
+            )
+            .unwrap()
+            .checked_div(TEN_THOUSAND.into())
+            .unwrap()
+            .as_u64()
+    }
+
+    /// Price impact of a constant product swap, TEN_THOUSAND as denominator.
+    /// amount_out / spot_out = reserve_in / (reserve_in + amount_in),
+    /// so the impact is amount_in / (reserve_in + amount_in)
+    pub fn calc_price_impact(
+        amount_in: U128,
+        total_pc_without_take_pnl: U128,
+        total_coin_without_take_pnl: U128,
+        swap_direction: SwapDirection,
+    ) -> u64 {
+        let reserve_in = match swap_direction {
+            SwapDirection::Coin2PC => total_coin_without_take_pnl,
+            SwapDirection::PC2Coin => total_pc_without_take_pnl,
+        };
+        let denominator = reserve_in.checked_add(amount_in).unwrap();
+        if denominator.is_zero() {
+            return 0;
+        }
+        amount_in
+            .checked_mul(TEN_THOUSAND.into())
+            .unwrap()
+            .checked_div(denominator)
+            .unwrap()
+            .as_u64()
+    }
+
+    /// Swap output as a fraction of the output reserve, TEN_THOUSAND as denominator, rounded up
+    pub fn calc_output_bps(amount_out: u64, reserve_out: u64) -> u64 {
+        if reserve_out == 0 {
+            return TEN_THOUSAND;
+        }
+        U128::from(amount_out)
+            .checked_mul(TEN_THOUSAND.into())
+            .unwrap()
+            .checked_ceil_div(reserve_out.into())
+            .unwrap()
+            .0
+            .as_u64()
+    }
+
+    /// Part of a single sided deposit to swap so that the rest matches the pool ratio after the swap.
+    /// Solves (amount_in - s) / (reserve_in + s) = out(s) / (reserve_out - out(s)) with the swap fee:
+    /// s = (sqrt(r^2 * (2d - n)^2 + 4 * (d - n) * d * a * r) - r * (2d - n)) / (2 * (d - n))
+    pub fn calc_zap_in_swap_amount(
+        amount_in: u64,
+        reserve_in: u64,
+        swap_fee_numerator: u64,
+        swap_fee_denominator: u64,
+    ) -> u64 {
+        let r = U256::from(reserve_in);
+        let a = U256::from(amount_in);
+        let d = U256::from(swap_fee_denominator);
+        let n = U256::from(swap_fee_numerator);
+        let two_d_minus_n = d.checked_mul(2.into()).unwrap().checked_sub(n).unwrap();
+        let d_minus_n = d.checked_sub(n).unwrap();
+        let r_mul_two_d_minus_n = r.checked_mul(two_d_minus_n).unwrap();
+        let sqrt = r_mul_two_d_minus_n
+            .checked_mul(r_mul_two_d_minus_n)
+            .unwrap()
+            .checked_add(
+                d_minus_n
+                    .checked_mul(d)
+                    .unwrap()
+                    .checked_mul(a)
+                    .unwrap()
+                    .checked_mul(r)
+                    .unwrap()
+                    .checked_mul(4.into())
+                    .unwrap(),
+            )
+            .unwrap()
+            .integer_sqrt();
+        let swap_amount = sqrt
+            .checked_sub(r_mul_two_d_minus_n)
+            .unwrap()
+            .checked_div(d_minus_n.checked_mul(2.into()).unwrap())
+            .unwrap();
+        std::cmp::min(swap_amount, a).as_u64()
+    }
+
+    /// Flash fee of a borrowed amount, charged at the swap fee rate and rounded up
+    pub fn calc_flash_fee(amount: u64, swap_fee_numerator: u64, swap_fee_denominator: u64) -> u64 {
+        U128::from(amount)
+            .checked_mul(swap_fee_numerator.into())
+            .unwrap()
+            .checked_ceil_div(swap_fee_denominator.into())
+            .unwrap()
+            .0
+            .as_u64()
+    }
+
+    /// Whether the pool repaid a flash loan: each side must get back what it lent plus its
+    /// flash fee, in the token it lent. Repaying in the other token would be a swap
+    pub fn flash_loan_repaid(
+        pc_before: u64,
+        coin_before: u64,
+        pc_after: u64,
+        coin_after: u64,
+        pc_fee: u64,
+        coin_fee: u64,
+    ) -> bool {
+        match (
+            pc_after.checked_sub(pc_fee),
+            coin_after.checked_sub(coin_fee),
+        ) {
+            (Some(pc_after), Some(coin_after)) => pc_after >= pc_before && coin_after >= coin_before,
+            _ => false,
+        }
+    }
+
+    /// Q64.64 price of numerator in units of denominator, saturating at u128::MAX.
+    /// Returns 0 while the denominator side of the pool is empty
+    pub fn calc_price_x64(numerator: u64, denominator: u64) -> u128 {
+        if denominator == 0 {
+            return 0;
+        }
+        let price = (U256::from(numerator) << 64)
+            .checked_div(denominator.into())
+            .unwrap();
+        if price > U256::from(u128::MAX) {
+            u128::MAX
+        } else {
+            price.as_u128()
+        }
+    }
+
+    /// Time weighted average Q64.64 price between two cumulative snapshots.
+    /// The cumulatives wrap, so the delta is taken with wrapping_sub
+    pub fn calc_twap_x64(
+        cumulative_start: u128,
+        cumulative_end: u128,
+        elapsed: u64,
+    ) -> Option<u128> {
+        if elapsed == 0 {
+            return None;
+        }
+        Some(cumulative_end.wrapping_sub(cumulative_start) / u128::from(elapsed))
+    }
+
+    /// Q64.64 sqrt(pc * coin) backing one lp token, grows as swap fees stay in the pool
+    pub fn calc_liquidity_per_lp_x64(total_pc: u64, total_coin: u64, lp_amount: u64) -> u128 {
+        if lp_amount == 0 {
+            return 0;
+        }
+        let liquidity = U256::from(total_pc)
+            .checked_mul(total_coin.into())
+            .unwrap()
+            .integer_sqrt();
+        let liquidity_per_lp = (liquidity << 64).checked_div(lp_amount.into()).unwrap();
+        if liquidity_per_lp > U256::from(u128::MAX) {
+            u128::MAX
+        } else {
+            liquidity_per_lp.as_u128()
+        }
+    }
+
+    /// Q64.64 growth per lp token of a fee shared by lp_amount lp tokens
+    pub fn calc_fee_growth_x64(fee: u64, lp_amount: u64) -> u128 {
+        if lp_amount == 0 {
+            return 0;
+        }
+        (u128::from(fee) << 64) / u128::from(lp_amount)
+    }
+
+    /// Fees accrued to lp_balance lp tokens since the fee growth snapshot, saturating at
+    /// u64::MAX. The fee growth wraps, so the delta is taken with wrapping_sub
+    pub fn calc_fee_growth_accrued(
+        lp_balance: u64,
+        fee_growth_snapshot_x64: u128,
+        fee_growth_x64: u128,
+    ) -> u64 {
+        let accrued = U256::from(fee_growth_x64.wrapping_sub(fee_growth_snapshot_x64))
+            .checked_mul(lp_balance.into())
+            .unwrap()
+            >> 64;
+        if accrued > U256::from(u64::MAX) {
+            u64::MAX
+        } else {
+            accrued.as_u64()
+        }
+    }
+
+    /// Lp tokens worth the liquidity growth of a locked position since the last claim,
+    /// the rest keeps the liquidity locked at the last claim
+    pub fn calc_lock_fee_lp(
+        locked_amount: u64,
+        last_liquidity_per_lp_x64: u128,
+        liquidity_per_lp_x64: u128,
+    ) -> u64 {
+        if liquidity_per_lp_x64 <= last_liquidity_per_lp_x64 {
+            return 0;
+        }
+        let liquidity_per_lp = U256::from(liquidity_per_lp_x64);
+        let keep = U256::from(locked_amount)
+            .checked_mul(last_liquidity_per_lp_x64.into())
+            .unwrap()
+            .checked_add(liquidity_per_lp - U256::one())
+            .unwrap()
+            .checked_div(liquidity_per_lp)
+            .unwrap()
+            .as_u64();
+        locked_amount.saturating_sub(keep)
+    }
+
+    /// Lp worth the claimed fees at the pool price, rounded up.
+    /// At the pool price both sides of the pool have the same value, so the lp worth
+    /// an amount is half of its share of the pool side
+    pub fn calc_lock_fee_burn_lp(
+        coin_fee: u64,
+        pc_fee: u64,
+        total_coin: u64,
+        total_pc: u64,
+        lp_amount: u64,
+    ) -> u64 {
+        if total_coin == 0 || total_pc == 0 {
+            return 0;
+        }
+        // coin_fee * lp / (2 * total_coin) + pc_fee * lp / (2 * total_pc)
+        let numerator = U256::from(coin_fee)
+            .checked_mul(total_pc.into())
+            .unwrap()
+            .checked_add(U256::from(pc_fee).checked_mul(total_coin.into()).unwrap())
+            .unwrap()
+            .checked_mul(lp_amount.into())
+            .unwrap();
+        let denominator = U256::from(total_coin)
+            .checked_mul(total_pc.into())
+            .unwrap()
+            .checked_mul(2.into())
+            .unwrap();
+        numerator
+            .checked_add(denominator - U256::one())
+            .unwrap()
+            .checked_div(denominator)
+            .unwrap()
+            .as_u64()
+    }
 }
 
 /// The invariant calculator.
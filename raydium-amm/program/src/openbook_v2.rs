@@ -0,0 +1,457 @@
+//! OpenBook v2 market backend
+//!
+//! OpenBook v2 is an anchor program, the accounts are read at their fixed offsets and the
+//! instructions are packed by hand so the program does not depend on the anchor crates.
+
+use crate::error::AmmError;
+use arrayref::array_ref;
+use serum_dex::matching::Side;
+use solana_program::{
+    account_info::AccountInfo,
+    instruction::{AccountMeta, Instruction},
+    program_error::ProgramError,
+    pubkey::Pubkey,
+};
+
+pub const MARKET_DISCRIMINATOR: [u8; 8] = [219, 190, 213, 55, 0, 227, 198, 154];
+pub const OPEN_ORDERS_ACCOUNT_DISCRIMINATOR: [u8; 8] = [255, 194, 78, 123, 16, 105, 208, 165];
+pub const OPEN_ORDERS_INDEXER_DISCRIMINATOR: [u8; 8] = [195, 83, 128, 213, 204, 91, 19, 150];
+
+const PLACE_ORDER_DISCRIMINATOR: [u8; 8] = [51, 194, 155, 175, 109, 130, 96, 106];
+const CANCEL_ORDER_BY_CLIENT_ORDER_ID_DISCRIMINATOR: [u8; 8] =
+    [115, 178, 201, 8, 175, 183, 123, 119];
+const CANCEL_ALL_ORDERS_DISCRIMINATOR: [u8; 8] = [196, 83, 243, 171, 17, 100, 160, 143];
+const SETTLE_FUNDS_DISCRIMINATOR: [u8; 8] = [238, 64, 163, 96, 75, 171, 16, 33];
+const CREATE_OPEN_ORDERS_INDEXER_DISCRIMINATOR: [u8; 8] = [64, 64, 153, 255, 217, 71, 249, 133];
+const CREATE_OPEN_ORDERS_ACCOUNT_DISCRIMINATOR: [u8; 8] = [204, 181, 175, 222, 40, 125, 188, 71];
+
+pub const OPEN_ORDERS_SEED: &[u8] = b"OpenOrders";
+pub const OPEN_ORDERS_INDEXER_SEED: &[u8] = b"OpenOrdersIndexer";
+/// Name of the open orders accounts the amm creates
+pub const OPEN_ORDERS_ACCOUNT_NAME: &str = "raydium amm";
+
+/// discriminator + Market
+pub const MARKET_LEN: usize = 8 + 840;
+/// discriminator + OpenOrdersAccount
+pub const OPEN_ORDERS_ACCOUNT_LEN: usize = 8 + 1256;
+/// Max orders an open orders account holds
+pub const MAX_OPEN_ORDERS: usize = 24;
+
+/// PlaceOrderType::PostOnly
+const ORDER_TYPE_POST_ONLY: u8 = 2;
+/// SelfTradeBehavior::DecrementTake
+const SELF_TRADE_DECREMENT_TAKE: u8 = 0;
+
+/// The Market fields the amm uses
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct MarketV2 {
+    pub market_authority: Pubkey,
+    pub bids: Pubkey,
+    pub asks: Pubkey,
+    pub event_heap: Pubkey,
+    pub quote_lot_size: u64,
+    pub base_lot_size: u64,
+    pub base_mint: Pubkey,
+    pub quote_mint: Pubkey,
+    pub base_vault: Pubkey,
+    pub quote_vault: Pubkey,
+}
+
+impl MarketV2 {
+    pub fn unpack(data: &[u8]) -> Result<Self, ProgramError> {
+        if data.len() < MARKET_LEN || data[..8] != MARKET_DISCRIMINATOR {
+            return Err(AmmError::InvalidMarket.into());
+        }
+        let quote_lot_size = i64::from_le_bytes(*array_ref![data, 448, 8]);
+        let base_lot_size = i64::from_le_bytes(*array_ref![data, 456, 8]);
+        if quote_lot_size <= 0 || base_lot_size <= 0 {
+            return Err(AmmError::InvalidMarket.into());
+        }
+        Ok(Self {
+            market_authority: Pubkey::new_from_array(*array_ref![data, 16, 32]),
+            bids: Pubkey::new_from_array(*array_ref![data, 200, 32]),
+            asks: Pubkey::new_from_array(*array_ref![data, 232, 32]),
+            event_heap: Pubkey::new_from_array(*array_ref![data, 264, 32]),
+            quote_lot_size: quote_lot_size as u64,
+            base_lot_size: base_lot_size as u64,
+            base_mint: Pubkey::new_from_array(*array_ref![data, 576, 32]),
+            quote_mint: Pubkey::new_from_array(*array_ref![data, 608, 32]),
+            base_vault: Pubkey::new_from_array(*array_ref![data, 640, 32]),
+            quote_vault: Pubkey::new_from_array(*array_ref![data, 680, 32]),
+        })
+    }
+
+    pub fn load_checked(market: &AccountInfo, program_id: &Pubkey) -> Result<Self, ProgramError> {
+        if market.owner != program_id {
+            return Err(AmmError::InvalidMarket.into());
+        }
+        Self::unpack(&market.try_borrow_data()?)
+    }
+}
+
+/// Open orders indexer of an owner, `create_open_orders_account` numbers the accounts of the
+/// owner from created_counter + 1
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct OpenOrdersIndexerV2 {
+    pub created_counter: u32,
+}
+
+impl OpenOrdersIndexerV2 {
+    pub fn unpack(data: &[u8]) -> Result<Self, ProgramError> {
+        // discriminator, bump, created_counter
+        if data.len() < 8 + 1 + 4 || data[..8] != OPEN_ORDERS_INDEXER_DISCRIMINATOR {
+            return Err(AmmError::InvalidOpenOrders.into());
+        }
+        Ok(Self {
+            created_counter: u32::from_le_bytes(*array_ref![data, 9, 4]),
+        })
+    }
+
+    /// The indexer of the owner, or an empty one when it is not created yet
+    pub fn load_or_default(
+        indexer: &AccountInfo,
+        program_id: &Pubkey,
+        owner: &Pubkey,
+    ) -> Result<Self, ProgramError> {
+        if *indexer.key != open_orders_indexer_address(program_id, owner) {
+            return Err(AmmError::InvalidOpenOrders.into());
+        }
+        if indexer.data_is_empty() {
+            return Ok(Self::default());
+        }
+        if indexer.owner != program_id {
+            return Err(AmmError::InvalidOpenOrders.into());
+        }
+        Self::unpack(&indexer.try_borrow_data()?)
+    }
+
+    /// The open orders account `create_open_orders_account` creates next for the owner
+    pub fn next_open_orders_account(
+        &self,
+        program_id: &Pubkey,
+        owner: &Pubkey,
+    ) -> Result<Pubkey, AmmError> {
+        let account_num = self
+            .created_counter
+            .checked_add(1)
+            .ok_or(AmmError::CheckedAddOverflow)?;
+        Ok(Pubkey::find_program_address(
+            &[OPEN_ORDERS_SEED, owner.as_ref(), &account_num.to_le_bytes()],
+            program_id,
+        )
+        .0)
+    }
+}
+
+pub fn open_orders_indexer_address(program_id: &Pubkey, owner: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[OPEN_ORDERS_INDEXER_SEED, owner.as_ref()], program_id).0
+}
+
+/// An order resting on the book, as recorded in the open orders account
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct OpenOrderV2 {
+    pub client_order_id: u64,
+    pub price_lots: i64,
+    pub side: Side,
+}
+
+/// The OpenOrdersAccount fields the amm uses
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct OpenOrdersV2 {
+    pub owner: Pubkey,
+    pub market: Pubkey,
+    pub bids_base_lots: u64,
+    pub asks_base_lots: u64,
+    pub base_free_native: u64,
+    pub quote_free_native: u64,
+    pub locked_maker_fees: u64,
+    pub bids_quote_lots: u64,
+    pub orders: Vec<OpenOrderV2>,
+}
+
+impl OpenOrdersV2 {
+    pub fn unpack(data: &[u8]) -> Result<Self, ProgramError> {
+        if data.len() < OPEN_ORDERS_ACCOUNT_LEN || data[..8] != OPEN_ORDERS_ACCOUNT_DISCRIMINATOR {
+            return Err(AmmError::InvalidOpenOrders.into());
+        }
+        // the position starts at 144, the orders at 304
+        let lots = |offset: usize| -> u64 {
+            std::cmp::max(i64::from_le_bytes(*array_ref![data, offset, 8]), 0) as u64
+        };
+        let native = |offset: usize| -> u64 { u64::from_le_bytes(*array_ref![data, offset, 8]) };
+        let mut orders = Vec::new();
+        for i in 0..MAX_OPEN_ORDERS {
+            let order = array_ref![data, 304 + i * 40, 40];
+            // is_free
+            if order[32] != 0 {
+                continue;
+            }
+            orders.push(OpenOrderV2 {
+                client_order_id: u64::from_le_bytes(*array_ref![order, 16, 8]),
+                price_lots: i64::from_le_bytes(*array_ref![order, 24, 8]),
+                // side_and_tree: BidFixed, AskFixed, BidOraclePegged, AskOraclePegged
+                side: if order[33] % 2 == 0 { Side::Bid } else { Side::Ask },
+            });
+        }
+        Ok(Self {
+            owner: Pubkey::new_from_array(*array_ref![data, 8, 32]),
+            market: Pubkey::new_from_array(*array_ref![data, 40, 32]),
+            bids_base_lots: lots(144),
+            asks_base_lots: lots(152),
+            base_free_native: native(160),
+            quote_free_native: native(168),
+            locked_maker_fees: native(176),
+            bids_quote_lots: lots(232),
+            orders,
+        })
+    }
+
+    pub fn load_checked(
+        open_orders: &AccountInfo,
+        program_id: &Pubkey,
+    ) -> Result<Self, ProgramError> {
+        if open_orders.owner != program_id {
+            return Err(AmmError::InvalidOpenOrders.into());
+        }
+        Self::unpack(&open_orders.try_borrow_data()?)
+    }
+
+    /// base and quote native amounts the open orders account would hold with every order
+    /// cancelled. Fills still waiting in the event heap are not included
+    pub fn native_totals(&self, market: &MarketV2) -> Result<(u64, u64), AmmError> {
+        let coin = self
+            .asks_base_lots
+            .checked_mul(market.base_lot_size)
+            .and_then(|locked| locked.checked_add(self.base_free_native))
+            .ok_or(AmmError::CheckedAddOverflow)?;
+        let pc = self
+            .bids_quote_lots
+            .checked_mul(market.quote_lot_size)
+            .and_then(|locked| locked.checked_add(self.quote_free_native))
+            .and_then(|pc| pc.checked_add(self.locked_maker_fees))
+            .ok_or(AmmError::CheckedAddOverflow)?;
+        Ok((pc, coin))
+    }
+}
+
+fn side_to_u8(side: Side) -> u8 {
+    match side {
+        Side::Bid => 0,
+        Side::Ask => 1,
+    }
+}
+
+/// `place_order` as a post only order, user_token_account pays pc for a bid and coin for an ask
+pub fn place_order(
+    program_id: &Pubkey,
+    signer: &Pubkey,
+    open_orders_account: &Pubkey,
+    user_token_account: &Pubkey,
+    market: &Pubkey,
+    bids: &Pubkey,
+    asks: &Pubkey,
+    event_heap: &Pubkey,
+    market_vault: &Pubkey,
+    token_program: &Pubkey,
+    side: Side,
+    price_lots: u64,
+    max_base_lots: u64,
+    max_quote_lots_including_fees: u64,
+    client_order_id: u64,
+    limit: u8,
+) -> Instruction {
+    // lots are i64 in v2, anything above i64::MAX means no limit
+    let to_lots = |value: u64| std::cmp::min(value, i64::MAX as u64) as i64;
+    let mut data = Vec::with_capacity(8 + 44);
+    data.extend_from_slice(&PLACE_ORDER_DISCRIMINATOR);
+    data.push(side_to_u8(side));
+    data.extend_from_slice(&to_lots(price_lots).to_le_bytes());
+    data.extend_from_slice(&to_lots(max_base_lots).to_le_bytes());
+    data.extend_from_slice(&to_lots(max_quote_lots_including_fees).to_le_bytes());
+    data.extend_from_slice(&client_order_id.to_le_bytes());
+    data.push(ORDER_TYPE_POST_ONLY);
+    // no expiry
+    data.extend_from_slice(&0u64.to_le_bytes());
+    data.push(SELF_TRADE_DECREMENT_TAKE);
+    data.push(limit);
+
+    // anchor takes the program id for an optional account that is not passed
+    let accounts = vec![
+        AccountMeta::new_readonly(*signer, true),
+        AccountMeta::new(*open_orders_account, false),
+        // open_orders_admin
+        AccountMeta::new_readonly(*program_id, false),
+        AccountMeta::new(*user_token_account, false),
+        AccountMeta::new(*market, false),
+        AccountMeta::new(*bids, false),
+        AccountMeta::new(*asks, false),
+        AccountMeta::new(*event_heap, false),
+        AccountMeta::new(*market_vault, false),
+        // oracle_a, oracle_b
+        AccountMeta::new_readonly(*program_id, false),
+        AccountMeta::new_readonly(*program_id, false),
+        AccountMeta::new_readonly(*token_program, false),
+    ];
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    }
+}
+
+fn cancel_accounts(
+    signer: &Pubkey,
+    open_orders_account: &Pubkey,
+    market: &Pubkey,
+    bids: &Pubkey,
+    asks: &Pubkey,
+) -> Vec<AccountMeta> {
+    vec![
+        AccountMeta::new_readonly(*signer, true),
+        AccountMeta::new(*open_orders_account, false),
+        AccountMeta::new_readonly(*market, false),
+        AccountMeta::new(*bids, false),
+        AccountMeta::new(*asks, false),
+    ]
+}
+
+/// `cancel_order_by_client_order_id`
+pub fn cancel_order_by_client_order_id(
+    program_id: &Pubkey,
+    signer: &Pubkey,
+    open_orders_account: &Pubkey,
+    market: &Pubkey,
+    bids: &Pubkey,
+    asks: &Pubkey,
+    client_order_id: u64,
+) -> Instruction {
+    let mut data = Vec::with_capacity(8 + 8);
+    data.extend_from_slice(&CANCEL_ORDER_BY_CLIENT_ORDER_ID_DISCRIMINATOR);
+    data.extend_from_slice(&client_order_id.to_le_bytes());
+    Instruction {
+        program_id: *program_id,
+        accounts: cancel_accounts(signer, open_orders_account, market, bids, asks),
+        data,
+    }
+}
+
+/// `cancel_all_orders` of one side, or both sides when side is None
+pub fn cancel_all_orders(
+    program_id: &Pubkey,
+    signer: &Pubkey,
+    open_orders_account: &Pubkey,
+    market: &Pubkey,
+    bids: &Pubkey,
+    asks: &Pubkey,
+    side: Option<Side>,
+    limit: u8,
+) -> Instruction {
+    let mut data = Vec::with_capacity(8 + 3);
+    data.extend_from_slice(&CANCEL_ALL_ORDERS_DISCRIMINATOR);
+    match side {
+        Some(side) => {
+            data.push(1);
+            data.push(side_to_u8(side));
+        }
+        None => data.push(0),
+    }
+    data.push(limit);
+    Instruction {
+        program_id: *program_id,
+        accounts: cancel_accounts(signer, open_orders_account, market, bids, asks),
+        data,
+    }
+}
+
+/// `settle_funds`, the owner also pays the penalty of orders taken from the book
+pub fn settle_funds(
+    program_id: &Pubkey,
+    owner: &Pubkey,
+    open_orders_account: &Pubkey,
+    market: &Pubkey,
+    market_authority: &Pubkey,
+    market_base_vault: &Pubkey,
+    market_quote_vault: &Pubkey,
+    user_base_account: &Pubkey,
+    user_quote_account: &Pubkey,
+    referrer_account: Option<&Pubkey>,
+    token_program: &Pubkey,
+    system_program: &Pubkey,
+) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new(*owner, true),
+        // penalty_payer
+        AccountMeta::new(*owner, true),
+        AccountMeta::new(*open_orders_account, false),
+        AccountMeta::new(*market, false),
+        AccountMeta::new_readonly(*market_authority, false),
+        AccountMeta::new(*market_base_vault, false),
+        AccountMeta::new(*market_quote_vault, false),
+        AccountMeta::new(*user_base_account, false),
+        AccountMeta::new(*user_quote_account, false),
+        match referrer_account {
+            Some(referrer) => AccountMeta::new(*referrer, false),
+            None => AccountMeta::new_readonly(*program_id, false),
+        },
+        AccountMeta::new_readonly(*token_program, false),
+        AccountMeta::new_readonly(*system_program, false),
+    ];
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: SETTLE_FUNDS_DISCRIMINATOR.to_vec(),
+    }
+}
+
+/// `create_open_orders_indexer`
+pub fn create_open_orders_indexer(
+    program_id: &Pubkey,
+    payer: &Pubkey,
+    owner: &Pubkey,
+    open_orders_indexer: &Pubkey,
+    system_program: &Pubkey,
+) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new(*payer, true),
+        AccountMeta::new_readonly(*owner, true),
+        AccountMeta::new(*open_orders_indexer, false),
+        AccountMeta::new_readonly(*system_program, false),
+    ];
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: CREATE_OPEN_ORDERS_INDEXER_DISCRIMINATOR.to_vec(),
+    }
+}
+
+/// `create_open_orders_account` without a delegate
+pub fn create_open_orders_account(
+    program_id: &Pubkey,
+    payer: &Pubkey,
+    owner: &Pubkey,
+    open_orders_indexer: &Pubkey,
+    open_orders_account: &Pubkey,
+    market: &Pubkey,
+    system_program: &Pubkey,
+    name: &str,
+) -> Instruction {
+    let mut data = Vec::with_capacity(8 + 4 + name.len());
+    data.extend_from_slice(&CREATE_OPEN_ORDERS_ACCOUNT_DISCRIMINATOR);
+    data.extend_from_slice(&(name.len() as u32).to_le_bytes());
+    data.extend_from_slice(name.as_bytes());
+    let accounts = vec![
+        AccountMeta::new(*payer, true),
+        AccountMeta::new_readonly(*owner, true),
+        // delegate_account
+        AccountMeta::new_readonly(*program_id, false),
+        AccountMeta::new(*open_orders_indexer, false),
+        AccountMeta::new(*open_orders_account, false),
+        AccountMeta::new_readonly(*market, false),
+        AccountMeta::new_readonly(*system_program, false),
+    ];
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    }
+}
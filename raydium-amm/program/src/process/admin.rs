@@ -2,6 +2,7 @@
 use solana_program::{
     account_info::{next_account_info, AccountInfo},
     entrypoint::ProgramResult,
+    program_error::ProgramError,
     pubkey::Pubkey,
     msg,
 };
@@ -9,36 +10,79 @@ use crate::{
     error::AmmError,
     instruction::AdminCancelOrdersInstruction,
     invokers::Invokers,
-    state::AmmInfo,
+    state::{AmmConfig, AmmInfo, AmmRole, MarketBackend, TargetOrders, MAX_ORDER_LIMIT},
 };
-use crate::process::constants::AUTHORITY_AMM;
-use crate::process::helpers::{authority_id, load_serum_market_order, get_amm_orders};
+use crate::check_assert_eq;
+use crate::log::log_keys_mismatch;
+use crate::process::constants::{get_referrer_pc_wallet_id, AUTHORITY_AMM};
+use crate::process::helpers::{
+    authority_id, check_amm_role, get_amm_orders, load_openbook_v2_market_order,
+    load_serum_market_order, market_backend, unpack_token_account,
+};
+
+/// [token_program_info, amm_info, amm_authority_info, amm_open_orders_info, amm_target_orders_info, amm_coin_vault_info, amm_pc_vault_info, cancel_owner_info, amm_config_info, market_program_info, market_info, market_coin_vault_info, market_pc_vault_info, market_vault_signer, market_event_queue_info, market_bids_info, market_asks_info] = 17 accounts
+const ADMIN_CANCEL_ORDERS_ACCOUNT_LEN: usize = 17;
+
+/// The client order ids the next admin cancel takes: at most limit ids above
+/// cancelled_client_id, lowest first. The bool is true when they are the last ones
+pub fn admin_cancel_batch(
+    client_order_ids: &[u64],
+    cancelled_client_id: u64,
+    limit: usize,
+) -> (Vec<u64>, bool) {
+    let mut pending = client_order_ids
+        .iter()
+        .copied()
+        .filter(|client_order_id| *client_order_id > cancelled_client_id)
+        .collect::<Vec<u64>>();
+    pending.sort_unstable();
+    let done = pending.len() <= limit;
+    pending.truncate(limit);
+    (pending, done)
+}
 
 pub fn process_admin_cancel_orders(
     program_id: &Pubkey,
     accounts: &[AccountInfo],
-    _cancel: AdminCancelOrdersInstruction,
+    cancel: AdminCancelOrdersInstruction,
 ) -> ProgramResult {
+    if accounts.len() < ADMIN_CANCEL_ORDERS_ACCOUNT_LEN {
+        return Err(AmmError::WrongAccountsNumber.into());
+    }
     let account_info_iter = &mut accounts.iter();
+    let token_program_info = next_account_info(account_info_iter)?;
     let amm_info = next_account_info(account_info_iter)?;
     let amm_authority_info = next_account_info(account_info_iter)?;
     let amm_open_orders_info = next_account_info(account_info_iter)?;
+    let amm_target_orders_info = next_account_info(account_info_iter)?;
+    let amm_coin_vault_info = next_account_info(account_info_iter)?;
+    let amm_pc_vault_info = next_account_info(account_info_iter)?;
+    let cancel_owner_info = next_account_info(account_info_iter)?;
+    let amm_config_info = next_account_info(account_info_iter)?;
     let market_program_info = next_account_info(account_info_iter)?;
     let market_info = next_account_info(account_info_iter)?;
-    let market_bids_info = next_account_info(account_info_iter)?;
-    let market_asks_info = next_account_info(account_info_iter)?;
-    let market_event_queue_info = next_account_info(account_info_iter)?;
     let market_coin_vault_info = next_account_info(account_info_iter)?;
     let market_pc_vault_info = next_account_info(account_info_iter)?;
     let market_vault_signer = next_account_info(account_info_iter)?;
-    let token_program_info = next_account_info(account_info_iter)?;
-    let user_wallet_info = next_account_info(account_info_iter)?;
+    let market_event_queue_info = next_account_info(account_info_iter)?;
+    let market_bids_info = next_account_info(account_info_iter)?;
+    let market_asks_info = next_account_info(account_info_iter)?;
+    let _srm_token_info = next_account_info(account_info_iter).ok();
+    let referrer_pc_info = next_account_info(account_info_iter).ok();
 
-    if !user_wallet_info.is_signer {
+    if !cancel_owner_info.is_signer {
         return Err(AmmError::InvalidSignAccount.into());
     }
+    if cancel.limit == 0 {
+        return Err(AmmError::InvalidInput.into());
+    }
+
+    // Check token program
+    if *token_program_info.key != spl_token::id() {
+        return Err(AmmError::InvalidSplTokenProgram.into());
+    }
 
-    let amm = AmmInfo::load_mut_checked(&amm_info, program_id)?;
+    let amm = AmmInfo::load_checked(&amm_info, program_id)?;
 
     // Check authority
     let authority = authority_id(program_id, AUTHORITY_AMM, amm.nonce as u8)?;
@@ -46,124 +90,195 @@ pub fn process_admin_cancel_orders(
         return Err(AmmError::InvalidProgramAddress.into());
     }
 
-    // Check if user is owner
-    if *user_wallet_info.key != amm.amm_owner {
-        return Err(AmmError::InvalidOwner.into());
-    }
-
-    // Check token program
-    if *token_program_info.key != spl_token::id() {
-        return Err(AmmError::InvalidSplTokenProgram.into());
+    // The crank operator of the pool or the cancel owner of the amm config
+    let not_crank_operator: ProgramError = AmmRole::CrankOperator.error().into();
+    match check_amm_role(
+        program_id,
+        AmmRole::CrankOperator,
+        cancel_owner_info,
+        amm_info,
+        amm_config_info,
+    ) {
+        Err(e) if e == not_crank_operator => {
+            let amm_config = AmmConfig::load_checked(amm_config_info, program_id)?;
+            if *cancel_owner_info.key != amm_config.cancel_owner {
+                return Err(e);
+            }
+        }
+        result => result?,
     }
 
-    // Check market accounts
-    if *market_info.key != amm.market {
-        return Err(AmmError::InvalidMarket.into());
-    }
+    // Check amm and market accounts
+    check_assert_eq!(
+        *amm_coin_vault_info.key,
+        amm.coin_vault,
+        "coin_vault",
+        AmmError::InvalidCoinVault
+    );
+    check_assert_eq!(
+        *amm_pc_vault_info.key,
+        amm.pc_vault,
+        "pc_vault",
+        AmmError::InvalidPCVault
+    );
+    check_assert_eq!(
+        *market_program_info.key,
+        amm.market_program,
+        "market_program",
+        AmmError::InvalidMarketProgram
+    );
+    check_assert_eq!(*market_info.key, amm.market, "market", AmmError::InvalidMarket);
     if *amm_open_orders_info.key != amm.open_orders {
         return Err(AmmError::InvalidOpenOrders.into());
     }
+    if *amm_target_orders_info.key != amm.target_orders {
+        return Err(AmmError::InvalidTargetOrders.into());
+    }
+    // The referrer receives part of the settled fees, it must belong to the referrer wallet
+    let referrer_pc_wallet = match referrer_pc_info {
+        Some(referrer_pc_info) => {
+            let referrer_pc_token =
+                unpack_token_account(referrer_pc_info, token_program_info.key)?;
+            check_assert_eq!(
+                referrer_pc_token.owner,
+                get_referrer_pc_wallet_id()?,
+                "referrer_pc_owner",
+                AmmError::InvalidOwner
+            );
+            Some(referrer_pc_info)
+        }
+        None => None,
+    };
 
-    // Load market state
-    let (market_state, open_orders) = load_serum_market_order(
-        market_info,
-        amm_open_orders_info,
-        amm_authority_info,
-        &amm,
-        false,
-    )?;
-
-    let bids_orders = market_state.load_bids_checked(&market_bids_info)?;
-    let asks_orders = market_state.load_asks_checked(&market_asks_info)?;
-    let (bids, asks) = get_amm_orders(&open_orders, bids_orders, asks_orders)?;
+    let mut target_orders =
+        TargetOrders::load_mut_checked(&amm_target_orders_info, program_id, amm_info.key)?;
 
-    // Cancel all bids
-    if !bids.is_empty() {
-        let mut amm_order_ids_vec = Vec::new();
-        let mut order_ids = [0u64; 8];
-        let mut count = 0;
-        
-        for order in bids.into_iter() {
-            order_ids[count] = order.client_order_id();
-            count += 1;
-            if count == 8 {
-                amm_order_ids_vec.push(order_ids);
-                order_ids = [0u64; 8];
-                count = 0;
-            }
+    // The amm orders on the book, cancelled lowest client order id first
+    let backend = market_backend(&amm.market_program)?;
+    let client_order_ids = match backend {
+        MarketBackend::OpenBookV1 => {
+            let (market_state, open_orders) = load_serum_market_order(
+                market_info,
+                amm_open_orders_info,
+                amm_authority_info,
+                &amm,
+                false,
+            )?;
+            let bids_orders = market_state.load_bids_checked(&market_bids_info)?;
+            let asks_orders = market_state.load_asks_checked(&market_asks_info)?;
+            let (bids, asks) = get_amm_orders(&open_orders, bids_orders, asks_orders)?;
+            bids.iter()
+                .chain(asks.iter())
+                .map(|order| order.client_order_id())
+                .collect::<Vec<u64>>()
         }
-        if count != 0 {
-            amm_order_ids_vec.push(order_ids);
+        MarketBackend::OpenBookV2 => {
+            let (_, open_orders) = load_openbook_v2_market_order(
+                market_info,
+                amm_open_orders_info,
+                amm_authority_info,
+                &amm,
+            )?;
+            open_orders
+                .orders
+                .iter()
+                .map(|order| order.client_order_id)
+                .collect::<Vec<u64>>()
         }
-        
-        for ids in amm_order_ids_vec.iter() {
-            Invokers::invoke_dex_cancel_orders_by_client_order_ids(
+    };
+    let (batch, done) = admin_cancel_batch(
+        &client_order_ids,
+        target_orders.admin_cancel_client_id,
+        cancel.limit as usize,
+    );
+
+    // Cancel the batch and settle the cancelled orders back to the amm vaults
+    match backend {
+        MarketBackend::OpenBookV1 => {
+            for ids in batch.chunks(8) {
+                let mut order_ids = [0u64; 8];
+                order_ids[..ids.len()].copy_from_slice(ids);
+                Invokers::invoke_dex_cancel_orders_by_client_order_ids(
+                    market_program_info.clone(),
+                    market_info.clone(),
+                    market_bids_info.clone(),
+                    market_asks_info.clone(),
+                    amm_open_orders_info.clone(),
+                    amm_authority_info.clone(),
+                    market_event_queue_info.clone(),
+                    AUTHORITY_AMM,
+                    amm.nonce as u8,
+                    order_ids,
+                )?;
+            }
+            Invokers::invoke_dex_settle_funds(
                 market_program_info.clone(),
                 market_info.clone(),
-                market_bids_info.clone(),
-                market_asks_info.clone(),
                 amm_open_orders_info.clone(),
                 amm_authority_info.clone(),
-                market_event_queue_info.clone(),
+                market_coin_vault_info.clone(),
+                market_pc_vault_info.clone(),
+                amm_coin_vault_info.clone(),
+                amm_pc_vault_info.clone(),
+                market_vault_signer.clone(),
+                token_program_info.clone(),
+                referrer_pc_wallet,
                 AUTHORITY_AMM,
                 amm.nonce as u8,
-                *ids,
             )?;
         }
-    }
-
-    // Cancel all asks
-    if !asks.is_empty() {
-        let mut amm_order_ids_vec = Vec::new();
-        let mut order_ids = [0u64; 8];
-        let mut count = 0;
-        
-        for order in asks.into_iter() {
-            order_ids[count] = order.client_order_id();
-            count += 1;
-            if count == 8 {
-                amm_order_ids_vec.push(order_ids);
-                order_ids = [0u64; 8];
-                count = 0;
+        // the market authority comes in the vault signer slot, the system program in the
+        // event queue slot
+        MarketBackend::OpenBookV2 => {
+            for client_order_id in batch.iter() {
+                Invokers::invoke_openbook_v2_cancel_order_by_client_order_id(
+                    market_program_info.clone(),
+                    market_info.clone(),
+                    market_bids_info.clone(),
+                    market_asks_info.clone(),
+                    amm_open_orders_info.clone(),
+                    amm_authority_info.clone(),
+                    AUTHORITY_AMM,
+                    amm.nonce as u8,
+                    *client_order_id,
+                )?;
             }
-        }
-        if count != 0 {
-            amm_order_ids_vec.push(order_ids);
-        }
-        
-        for ids in amm_order_ids_vec.iter() {
-            Invokers::invoke_dex_cancel_orders_by_client_order_ids(
+            Invokers::invoke_openbook_v2_settle_funds(
                 market_program_info.clone(),
                 market_info.clone(),
-                market_bids_info.clone(),
-                market_asks_info.clone(),
                 amm_open_orders_info.clone(),
                 amm_authority_info.clone(),
+                market_vault_signer.clone(),
+                market_coin_vault_info.clone(),
+                market_pc_vault_info.clone(),
+                amm_coin_vault_info.clone(),
+                amm_pc_vault_info.clone(),
+                token_program_info.clone(),
                 market_event_queue_info.clone(),
+                referrer_pc_wallet,
                 AUTHORITY_AMM,
                 amm.nonce as u8,
-                *ids,
             )?;
         }
     }
 
-    // Settle funds
-    Invokers::invoke_dex_settle_funds(
-        market_program_info.clone(),
-        market_info.clone(),
-        amm_open_orders_info.clone(),
-        amm_authority_info.clone(),
-        market_coin_vault_info.clone(),
-        market_pc_vault_info.clone(),
-        market_coin_vault_info.clone(),
-        market_pc_vault_info.clone(),
-        market_vault_signer.clone(),
-        token_program_info.clone(),
-        None,
-        AUTHORITY_AMM,
-        amm.nonce as u8,
-    )?;
+    // Persist the progress, the next call goes on after the last cancelled order
+    if done {
+        target_orders.admin_cancel_client_id = 0;
+        target_orders.replace_buy_client_id = [0u64; MAX_ORDER_LIMIT];
+        target_orders.replace_sell_client_id = [0u64; MAX_ORDER_LIMIT];
+        target_orders.valid_buy_order_num = 0;
+        target_orders.valid_sell_order_num = 0;
+    } else if let Some(last) = batch.last() {
+        target_orders.admin_cancel_client_id = *last;
+    }
 
-    msg!("Admin orders cancelled successfully");
+    let admin_cancel_client_id = target_orders.admin_cancel_client_id;
+    msg!(
+        "admin cancelled:{}, done:{}, cancelled up to:{}",
+        batch.len(),
+        done,
+        admin_cancel_client_id
+    );
     Ok(())
-} 
\ No newline at end of file
+}
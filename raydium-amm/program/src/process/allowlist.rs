@@ -0,0 +1,159 @@
+//! Handles the launch allowlist instruction logic for Raydium AMM
+use crate::{
+    error::AmmError,
+    instruction::{InitializeAllowlistInstruction, RegisterAllowlistWalletInstruction},
+    process::constants::{ALLOWLIST_ASSOCIATED_SEED, ALLOWLIST_ENTRY_ASSOCIATED_SEED},
+    process::helpers::{check_amm_role, get_associated_address_and_bump_seed},
+    process::initialize::create_pool_account,
+    state::{AmmInfo, AmmRole, AmmStatus, Allowlist, AllowlistEntry, Loadable},
+};
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    clock::Clock,
+    entrypoint::ProgramResult,
+    msg,
+    pubkey::Pubkey,
+    sysvar::Sysvar,
+};
+use std::mem::size_of;
+
+/// [amm_info, allowlist_info, whitelist_admin_info, system_program_info, amm_config_info] = 5 accounts
+const INITIALIZE_ALLOWLIST_ACCOUNT_LEN: usize = 5;
+/// [amm_info, allowlist_info, allowlist_entry_info, user_wallet_info, system_program_info] = 5 accounts
+const REGISTER_ALLOWLIST_WALLET_ACCOUNT_LEN: usize = 5;
+
+pub fn process_initialize_allowlist(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    init: InitializeAllowlistInstruction,
+) -> ProgramResult {
+    if accounts.len() != INITIALIZE_ALLOWLIST_ACCOUNT_LEN {
+        return Err(AmmError::WrongAccountsNumber.into());
+    }
+    let account_info_iter = &mut accounts.iter();
+    let amm_info = next_account_info(account_info_iter)?;
+    let allowlist_info = next_account_info(account_info_iter)?;
+    let whitelist_admin_info = next_account_info(account_info_iter)?;
+    let system_program_info = next_account_info(account_info_iter)?;
+    let amm_config_info = next_account_info(account_info_iter)?;
+
+    if !whitelist_admin_info.is_signer {
+        return Err(AmmError::InvalidSignAccount.into());
+    }
+    if *system_program_info.key != solana_program::system_program::id() {
+        return Err(AmmError::InvalidSystemProgram.into());
+    }
+    check_amm_role(
+        program_id,
+        AmmRole::WhitelistAdmin,
+        whitelist_admin_info,
+        amm_info,
+        amm_config_info,
+    )?;
+    let mut amm = AmmInfo::load_mut_checked(&amm_info, program_id)?;
+    // the allowlist can only be set up before the pool opens
+    if amm.status != AmmStatus::WaitingTrade.into_u64()
+        || (Clock::get()?.unix_timestamp as u64) >= amm.state_data.pool_open_time
+    {
+        return Err(AmmError::InvalidStatus.into());
+    }
+    if init.allowlist_end_time <= amm.state_data.pool_open_time {
+        return Err(AmmError::InvalidInput.into());
+    }
+
+    let (allowlist_address, bump_seed) = get_associated_address_and_bump_seed(
+        amm_info.key,
+        amm_info.key,
+        ALLOWLIST_ASSOCIATED_SEED,
+        program_id,
+    );
+    if *allowlist_info.key != allowlist_address || !allowlist_info.data_is_empty() {
+        return Err(AmmError::InvalidAllowlistAccount.into());
+    }
+
+    create_pool_account(
+        whitelist_admin_info,
+        allowlist_info,
+        system_program_info,
+        size_of::<Allowlist>(),
+        program_id,
+        &[
+            amm_info.key.as_ref(),
+            amm_info.key.as_ref(),
+            ALLOWLIST_ASSOCIATED_SEED,
+            &[bump_seed],
+        ],
+    )?;
+
+    let mut allowlist = Allowlist::load_mut(&allowlist_info)?;
+    allowlist.amm_id = *amm_info.key;
+    allowlist.merkle_root = init.merkle_root;
+    amm.allowlist_end_time = init.allowlist_end_time;
+    msg!("allowlist_end_time:{}", init.allowlist_end_time);
+    Ok(())
+}
+
+pub fn process_register_allowlist_wallet(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    register: RegisterAllowlistWalletInstruction,
+) -> ProgramResult {
+    if accounts.len() != REGISTER_ALLOWLIST_WALLET_ACCOUNT_LEN {
+        return Err(AmmError::WrongAccountsNumber.into());
+    }
+    let account_info_iter = &mut accounts.iter();
+    let amm_info = next_account_info(account_info_iter)?;
+    let allowlist_info = next_account_info(account_info_iter)?;
+    let allowlist_entry_info = next_account_info(account_info_iter)?;
+    let user_wallet_info = next_account_info(account_info_iter)?;
+    let system_program_info = next_account_info(account_info_iter)?;
+
+    if !user_wallet_info.is_signer {
+        return Err(AmmError::InvalidSignAccount.into());
+    }
+    if *system_program_info.key != solana_program::system_program::id() {
+        return Err(AmmError::InvalidSystemProgram.into());
+    }
+    let amm = AmmInfo::load_checked(&amm_info, program_id)?;
+    if !amm.allowlist_active(Clock::get()?.unix_timestamp as u64) {
+        return Err(AmmError::InvalidStatus.into());
+    }
+    let allowlist = Allowlist::load_checked(&allowlist_info, program_id, amm_info.key)?;
+    if !allowlist.verify(user_wallet_info.key, register.cap, &register.proof) {
+        return Err(AmmError::NotAllowlisted.into());
+    }
+
+    let (allowlist_entry_address, bump_seed) = get_associated_address_and_bump_seed(
+        amm_info.key,
+        user_wallet_info.key,
+        ALLOWLIST_ENTRY_ASSOCIATED_SEED,
+        program_id,
+    );
+    if *allowlist_entry_info.key != allowlist_entry_address
+        || !allowlist_entry_info.data_is_empty()
+    {
+        return Err(AmmError::InvalidAllowlistAccount.into());
+    }
+
+    // a funded entry address would otherwise lock the wallet out of the allowlist phase
+    create_pool_account(
+        user_wallet_info,
+        allowlist_entry_info,
+        system_program_info,
+        size_of::<AllowlistEntry>(),
+        program_id,
+        &[
+            amm_info.key.as_ref(),
+            user_wallet_info.key.as_ref(),
+            ALLOWLIST_ENTRY_ASSOCIATED_SEED,
+            &[bump_seed],
+        ],
+    )?;
+
+    let mut allowlist_entry = AllowlistEntry::load_mut(&allowlist_entry_info)?;
+    allowlist_entry.amm_id = *amm_info.key;
+    allowlist_entry.wallet = *user_wallet_info.key;
+    allowlist_entry.cap = register.cap;
+    allowlist_entry.used = 0;
+    Ok(())
+}
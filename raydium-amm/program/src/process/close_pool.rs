@@ -0,0 +1,226 @@
+//! Handles the close pool instruction logic for Raydium AMM
+use crate::{
+    error::AmmError,
+    invokers::Invokers,
+    process::constants::{
+        ALLOWLIST_ASSOCIATED_SEED, AUTHORITY_AMM, OBSERVATION_ASSOCIATED_SEED,
+    },
+    process::helpers::{
+        authority_id, check_amm_role, close_program_account,
+        get_associated_address_and_bump_seed, unpack_mint, unpack_token_account,
+    },
+    state::{AmmInfo, AmmRole},
+};
+use crate::check_assert_eq;
+use crate::log::log_keys_mismatch;
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    msg,
+    pubkey::Pubkey,
+};
+
+/// [token_program_info, amm_info, amm_authority_info, amm_open_orders_info, amm_target_orders_info, amm_lp_mint_info, amm_coin_vault_info, amm_pc_vault_info, amm_coin_mint_info, amm_pc_mint_info, market_program_info, market_info, owner_coin_info, owner_pc_info, amm_owner_info, amm_config_info, amm_allowlist_info, amm_observation_info] = 18 accounts
+const CLOSE_POOL_ACCOUNT_LEN: usize = 18;
+
+pub fn process_close_pool(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    if accounts.len() != CLOSE_POOL_ACCOUNT_LEN {
+        return Err(AmmError::WrongAccountsNumber.into());
+    }
+    let account_info_iter = &mut accounts.iter();
+    let token_program_info = next_account_info(account_info_iter)?;
+    let amm_info = next_account_info(account_info_iter)?;
+    let amm_authority_info = next_account_info(account_info_iter)?;
+    let amm_open_orders_info = next_account_info(account_info_iter)?;
+    let amm_target_orders_info = next_account_info(account_info_iter)?;
+    let amm_lp_mint_info = next_account_info(account_info_iter)?;
+    let amm_coin_vault_info = next_account_info(account_info_iter)?;
+    let amm_pc_vault_info = next_account_info(account_info_iter)?;
+    let amm_coin_mint_info = next_account_info(account_info_iter)?;
+    let amm_pc_mint_info = next_account_info(account_info_iter)?;
+    let market_program_info = next_account_info(account_info_iter)?;
+    let market_info = next_account_info(account_info_iter)?;
+    let owner_coin_info = next_account_info(account_info_iter)?;
+    let owner_pc_info = next_account_info(account_info_iter)?;
+    let amm_owner_info = next_account_info(account_info_iter)?;
+    let amm_config_info = next_account_info(account_info_iter)?;
+    let amm_allowlist_info = next_account_info(account_info_iter)?;
+    let amm_observation_info = next_account_info(account_info_iter)?;
+
+    if !amm_owner_info.is_signer {
+        return Err(AmmError::InvalidSignAccount.into());
+    }
+    if *token_program_info.key != spl_token::id() {
+        return Err(AmmError::InvalidSplTokenProgram.into());
+    }
+    check_amm_role(
+        program_id,
+        AmmRole::PoolAdmin,
+        amm_owner_info,
+        amm_info,
+        amm_config_info,
+    )?;
+    let amm = AmmInfo::load_checked(&amm_info, program_id)?;
+    let nonce = amm.nonce as u8;
+    if *amm_authority_info.key != authority_id(program_id, AUTHORITY_AMM, nonce)? {
+        return Err(AmmError::InvalidProgramAddress.into());
+    }
+    check_assert_eq!(
+        *amm_open_orders_info.key,
+        amm.open_orders,
+        "open_orders",
+        AmmError::InvalidOpenOrders
+    );
+    check_assert_eq!(
+        *amm_target_orders_info.key,
+        amm.target_orders,
+        "target_orders",
+        AmmError::InvalidTargetOrders
+    );
+    check_assert_eq!(
+        *amm_lp_mint_info.key,
+        amm.lp_mint,
+        "lp_mint",
+        AmmError::InvalidPoolMint
+    );
+    check_assert_eq!(
+        *amm_coin_vault_info.key,
+        amm.coin_vault,
+        "coin_vault",
+        AmmError::InvalidCoinVault
+    );
+    check_assert_eq!(
+        *amm_pc_vault_info.key,
+        amm.pc_vault,
+        "pc_vault",
+        AmmError::InvalidPCVault
+    );
+    check_assert_eq!(
+        *amm_coin_mint_info.key,
+        amm.coin_vault_mint,
+        "coin_mint",
+        AmmError::InvalidCoinMint
+    );
+    check_assert_eq!(
+        *amm_pc_mint_info.key,
+        amm.pc_vault_mint,
+        "pc_mint",
+        AmmError::InvalidPCMint
+    );
+    check_assert_eq!(
+        *market_program_info.key,
+        amm.market_program,
+        "market_program",
+        AmmError::InvalidMarketProgram
+    );
+    check_assert_eq!(*market_info.key, amm.market, "market", AmmError::InvalidMarket);
+    if amm_target_orders_info.owner != program_id {
+        return Err(AmmError::InvalidTargetOrders.into());
+    }
+    // the allowlist and the observation accounts of the pool are closed with it
+    let (amm_allowlist, _) = get_associated_address_and_bump_seed(
+        amm_info.key,
+        amm_info.key,
+        ALLOWLIST_ASSOCIATED_SEED,
+        program_id,
+    );
+    if *amm_allowlist_info.key != amm_allowlist {
+        return Err(AmmError::InvalidAllowlistAccount.into());
+    }
+    let (amm_observation, _) = get_associated_address_and_bump_seed(
+        amm_info.key,
+        amm_info.key,
+        OBSERVATION_ASSOCIATED_SEED,
+        program_id,
+    );
+    if *amm_observation_info.key != amm_observation {
+        return Err(AmmError::InvalidObservationAccount.into());
+    }
+
+    // no lp holder, lp lock or pnl owner has a claim on the vaults left
+    let lp_mint = unpack_mint(&amm_lp_mint_info, token_program_info.key)?;
+    if let Err(e) = amm.check_closable(lp_mint.supply) {
+        let (status, lp_amount, need_take_pnl_coin, need_take_pnl_pc) = (
+            amm.status,
+            amm.lp_amount,
+            amm.state_data.need_take_pnl_coin,
+            amm.state_data.need_take_pnl_pc,
+        );
+        msg!(
+            "status:{}, lp supply:{}, lp_amount:{}, need_take_pnl coin:{}, pc:{}",
+            status,
+            lp_mint.supply,
+            lp_amount,
+            need_take_pnl_coin,
+            need_take_pnl_pc
+        );
+        return Err(e.into());
+    }
+    drop(amm);
+
+    // the rounding left in the vaults goes to the owner, token accounts must be empty to close
+    let amm_coin_vault = unpack_token_account(&amm_coin_vault_info, token_program_info.key)?;
+    let amm_pc_vault = unpack_token_account(&amm_pc_vault_info, token_program_info.key)?;
+    for (vault_info, vault_amount, mint_info, owner_token_info) in [
+        (
+            amm_coin_vault_info,
+            amm_coin_vault.amount,
+            amm_coin_mint_info,
+            owner_coin_info,
+        ),
+        (
+            amm_pc_vault_info,
+            amm_pc_vault.amount,
+            amm_pc_mint_info,
+            owner_pc_info,
+        ),
+    ] {
+        if vault_amount != 0 {
+            Invokers::token_transfer_with_authority(
+                program_id,
+                token_program_info.clone(),
+                vault_info.clone(),
+                owner_token_info.clone(),
+                amm_authority_info.clone(),
+                AUTHORITY_AMM,
+                nonce,
+                vault_amount,
+                mint_info.clone(),
+                &[],
+            )?;
+        }
+        Invokers::token_close_with_authority(
+            token_program_info.clone(),
+            vault_info.clone(),
+            amm_owner_info.clone(),
+            amm_authority_info.clone(),
+            AUTHORITY_AMM,
+            nonce,
+        )?;
+    }
+
+    // the open orders must hold no orders and no unsettled funds to be closed,
+    // pools that never had one on the market skip it
+    if amm_open_orders_info.owner == market_program_info.key {
+        Invokers::invoke_dex_close_open_orders(
+            market_program_info.clone(),
+            amm_open_orders_info.clone(),
+            amm_authority_info.clone(),
+            amm_owner_info.clone(),
+            market_info.clone(),
+            AUTHORITY_AMM,
+            nonce,
+        )?;
+    }
+
+    // spl token mints can not be closed, the lp mint stays with no supply
+    for companion_info in [amm_allowlist_info, amm_observation_info] {
+        if companion_info.owner == program_id {
+            close_program_account(companion_info, amm_owner_info)?;
+        }
+    }
+    close_program_account(amm_target_orders_info, amm_owner_info)?;
+    close_program_account(amm_info, amm_owner_info)?;
+    msg!("close pool:{}", amm_info.key);
+    Ok(())
+}
@@ -8,7 +8,7 @@ use solana_program::{
 use crate::{
     error::AmmError,
     instruction::ConfigArgs,
-    state::AmmConfig,
+    state::{AmmConfig, AmmRole, TEN_THOUSAND},
 };
 use crate::process::constants::AMM_CONFIG_SEED;
 use crate::process::helpers::get_associated_address_and_bump_seed;
@@ -80,13 +80,41 @@ pub fn process_update_config(
         return Err(AmmError::InvalidOwner.into());
     }
 
-    // Update config parameters
+    // Update config parameters, param 6 sets the cancel owner
     if let Some(owner) = config_args.owner {
-        config.pnl_owner = owner;
+        if config_args.param == 6 {
+            config.cancel_owner = owner;
+        } else {
+            config.pnl_owner = owner;
+        }
     }
     if let Some(create_pool_fee) = config_args.create_pool_fee {
         config.create_pool_fee = create_pool_fee;
     }
+    if let Some(create_pool_fee_mint) = config_args.create_pool_fee_mint {
+        config.create_pool_fee_mint = create_pool_fee_mint;
+    }
+    if let Some(pnl_split) = config_args.pnl_split {
+        // the shares must cover the whole pnl
+        let total_bps = pnl_split
+            .treasury_bps
+            .checked_add(pnl_split.buyback_bps)
+            .and_then(|bps| bps.checked_add(pnl_split.lp_rebate_bps));
+        if total_bps != Some(TEN_THOUSAND) {
+            return Err(AmmError::InvalidInput.into());
+        }
+        if pnl_split.buyback_bps != 0 && pnl_split.buyback == Pubkey::default() {
+            return Err(AmmError::InvalidInput.into());
+        }
+        config.pnl_treasury = pnl_split.treasury;
+        config.pnl_buyback = pnl_split.buyback;
+        config.pnl_treasury_bps = pnl_split.treasury_bps;
+        config.pnl_buyback_bps = pnl_split.buyback_bps;
+        config.pnl_lp_rebate_bps = pnl_split.lp_rebate_bps;
+    }
+    if let Some(role) = config_args.role {
+        config.set_role(AmmRole::from_u64(role.role)?, role.holder);
+    }
 
     msg!("Config updated successfully");
     Ok(())
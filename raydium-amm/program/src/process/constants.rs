@@ -6,6 +6,11 @@ pub const COIN_VAULT_ASSOCIATED_SEED: &[u8] = b"coin_vault_associated_seed";
 pub const PC_VAULT_ASSOCIATED_SEED: &[u8] = b"pc_vault_associated_seed";
 pub const LP_MINT_ASSOCIATED_SEED: &[u8] = b"lp_mint_associated_seed";
 pub const AMM_CONFIG_SEED: &[u8] = b"amm_config_account_seed";
+pub const OBSERVATION_ASSOCIATED_SEED: &[u8] = b"observation_associated_seed";
+pub const LP_LOCK_ASSOCIATED_SEED: &[u8] = b"lp_lock_associated_seed";
+pub const LP_PERMANENT_LOCK_ASSOCIATED_SEED: &[u8] = b"lp_permanent_lock_associated_seed";
+pub const ALLOWLIST_ASSOCIATED_SEED: &[u8] = b"allowlist_associated_seed";
+pub const ALLOWLIST_ENTRY_ASSOCIATED_SEED: &[u8] = b"allowlist_entry_associated_seed";
 
 use solana_program::pubkey::Pubkey;
 use std::str::FromStr;
@@ -36,6 +41,14 @@ pub const OPENBOOK_PROGRAM_ID: &str = "EoTcMgcDRTJVZDMZWBoU6rhYHZfkNTVEAfz3uUJRc
 #[cfg(not(any(feature = "testnet", feature = "devnet")))]
 pub const OPENBOOK_PROGRAM_ID: &str = "srmqPvymJeFKQ4zGQed1GFppgkRHL9kaELCbyksJtPX";
 
+// OpenBook v2 program IDs for different networks
+#[cfg(feature = "testnet")]
+pub const OPENBOOK_V2_PROGRAM_ID: &str = "opnb2LAfJYbRMAHHvqjCwQxanZn7ReEHp1k81EohpZb";
+#[cfg(feature = "devnet")]
+pub const OPENBOOK_V2_PROGRAM_ID: &str = "opnb2LAfJYbRMAHHvqjCwQxanZn7ReEHp1k81EohpZb";
+#[cfg(not(any(feature = "testnet", feature = "devnet")))]
+pub const OPENBOOK_V2_PROGRAM_ID: &str = "opnb2LAfJYbRMAHHvqjCwQxanZn7ReEHp1k81EohpZb";
+
 // Create pool fee address IDs for different networks
 #[cfg(feature = "testnet")]
 pub const CREATE_POOL_FEE_ADDRESS_ID: &str = "3TRTX4dXUpp2eqxi3tvQDFYUV7SdDJjcPE3Y4mbtftaX";
@@ -59,6 +72,11 @@ pub fn get_openbook_program_id() -> Result<Pubkey, AmmError> {
         .map_err(|_| AmmError::InvalidProgramAddress)
 }
 
+pub fn get_openbook_v2_program_id() -> Result<Pubkey, AmmError> {
+    Pubkey::from_str(OPENBOOK_V2_PROGRAM_ID)
+        .map_err(|_| AmmError::InvalidProgramAddress)
+}
+
 pub fn get_create_pool_fee_address_id() -> Result<Pubkey, AmmError> {
     Pubkey::from_str(CREATE_POOL_FEE_ADDRESS_ID)
         .map_err(|_| AmmError::InvalidProgramAddress)
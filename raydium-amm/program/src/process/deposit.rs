@@ -11,14 +11,17 @@ use crate::{
     instruction::DepositInstruction,
     invokers::Invokers,
     math::{Calculator, RoundDirection, InvariantToken, InvariantPool, U128},
-    state::{AmmInfo, AmmStatus, TargetOrders},
+    state::{AmmInfo, AmmStatus, MarketBackend, TargetOrders},
 };
 use crate::process::constants::AUTHORITY_AMM;
-use crate::process::helpers::{identity, authority_id, unpack_token_account, load_serum_market_order, calc_take_pnl};
+use crate::process::helpers::{
+    identity, authority_id, unpack_token_account, load_serum_market_order, calc_take_pnl,
+    calc_pool_total_without_take_pnl, market_backend,
+};
 use crate::process::args::{DepositLog, LogType};
 use serum_dex::state::ToAlignedBytes;
 use crate::check_assert_eq;
-use crate::log::{log_keys_mismatch, encode_ray_log};
+use crate::log::{log_keys_mismatch, encode_ray_log, encode_return_data, AmmReturnData};
 
 
 /// The number of accounts expected for a deposit instruction.
@@ -118,32 +121,54 @@ pub fn process_deposit(
             "open_orders",
             AmmError::InvalidOpenOrders
         );
-        let (market_state, open_orders) = load_serum_market_order(
-            market_info,
-            amm_open_orders_info,
-            amm_authority_info,
-            &amm,
-            false,
-        )?;
-        if identity(market_state.coin_mint) != amm_coin_vault.mint.to_aligned_bytes()
-            || identity(market_state.coin_mint) != user_source_coin.mint.to_aligned_bytes()
-        {
-            return Err(AmmError::InvalidCoinMint.into());
-        }
-        if identity(market_state.pc_mint) != amm_pc_vault.mint.to_aligned_bytes()
-            || identity(market_state.pc_mint) != user_source_pc.mint.to_aligned_bytes()
-        {
-            return Err(AmmError::InvalidPCMint.into());
+        match market_backend(&amm.market_program)? {
+            MarketBackend::OpenBookV1 => {
+                let (market_state, open_orders) = load_serum_market_order(
+                    market_info,
+                    amm_open_orders_info,
+                    amm_authority_info,
+                    &amm,
+                    false,
+                )?;
+                if identity(market_state.coin_mint) != amm_coin_vault.mint.to_aligned_bytes()
+                    || identity(market_state.coin_mint) != user_source_coin.mint.to_aligned_bytes()
+                {
+                    return Err(AmmError::InvalidCoinMint.into());
+                }
+                if identity(market_state.pc_mint) != amm_pc_vault.mint.to_aligned_bytes()
+                    || identity(market_state.pc_mint) != user_source_pc.mint.to_aligned_bytes()
+                {
+                    return Err(AmmError::InvalidPCMint.into());
+                }
+                Calculator::calc_total_without_take_pnl(
+                    amm_pc_vault.amount,
+                    amm_coin_vault.amount,
+                    &open_orders,
+                    &amm,
+                    &market_state,
+                    &market_event_queue_info,
+                    &amm_open_orders_info,
+                )?
+            }
+            MarketBackend::OpenBookV2 => {
+                // the v2 market mints were checked against the vaults when the pool was bound
+                if amm_coin_vault.mint != user_source_coin.mint {
+                    return Err(AmmError::InvalidCoinMint.into());
+                }
+                if amm_pc_vault.mint != user_source_pc.mint {
+                    return Err(AmmError::InvalidPCMint.into());
+                }
+                calc_pool_total_without_take_pnl(
+                    &amm,
+                    amm_pc_vault.amount,
+                    amm_coin_vault.amount,
+                    market_info,
+                    market_event_queue_info,
+                    amm_open_orders_info,
+                    amm_authority_info,
+                )?
+            }
         }
-        Calculator::calc_total_without_take_pnl(
-            amm_pc_vault.amount,
-            amm_coin_vault.amount,
-            &open_orders,
-            &amm,
-            &market_state,
-            &market_event_queue_info,
-            &amm_open_orders_info,
-        )?
     } else {
         Calculator::calc_total_without_take_pnl_no_orderbook(
             amm_pc_vault.amount,
@@ -169,6 +194,11 @@ pub fn process_deposit(
         x1.as_u128().into(),
         y1.as_u128().into(),
     )?;
+    amm.update_price_cumulative(
+        total_pc_without_take_pnl,
+        total_coin_without_take_pnl,
+        Clock::get()?.unix_timestamp as u64,
+    );
     let invariant = InvariantToken {
         token_coin: total_coin_without_take_pnl,
         token_pc: total_pc_without_take_pnl,
@@ -361,6 +391,16 @@ pub fn process_deposit(
         .checked_sub(U128::from(delta_y))
         .unwrap()
         .as_u128();
+    encode_return_data(AmmReturnData {
+        log_type: crate::log::LogType::Deposit.into_u8(),
+        coin_in: deduct_coin_amount,
+        pc_in: deduct_pc_amount,
+        lp_mint: mint_lp_amount,
+        pool_coin: total_coin_without_take_pnl.checked_add(deduct_coin_amount).unwrap(),
+        pool_pc: total_pc_without_take_pnl.checked_add(deduct_pc_amount).unwrap(),
+        pool_lp: amm.lp_amount,
+        ..Default::default()
+    });
     amm.recent_epoch = Clock::get()?.epoch;
     Ok(())
 }
\ No newline at end of file
@@ -0,0 +1,377 @@
+//! Handles the single sided deposit (zap-in) instruction logic for Raydium AMM
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    clock::Clock,
+    entrypoint::ProgramResult,
+    pubkey::Pubkey,
+    sysvar::Sysvar,
+};
+use crate::{
+    error::AmmError,
+    instruction::DepositSingleSidedInstruction,
+    invokers::Invokers,
+    math::{Calculator, CheckedCeilDiv, InvariantPool, RoundDirection, SwapDirection, U128},
+    state::{AmmInfo, AmmStatus, TargetOrders},
+};
+use crate::process::constants::AUTHORITY_AMM;
+use crate::process::helpers::{
+    accrue_fee_growth, authority_id, unpack_token_account, calc_pool_total_without_take_pnl,
+    calc_take_pnl, check_circuit_breaker,
+};
+use crate::process::args::{DepositLog, LogType};
+use crate::check_assert_eq;
+use crate::log::{log_keys_mismatch, encode_ray_log, encode_return_data, AmmReturnData};
+
+/// The number of accounts expected for a single sided deposit instruction.
+/// This is based on the order of next_account_info calls in the function:
+/// [token_program_info, amm_info, amm_authority_info, amm_open_orders_info, amm_target_orders_info, amm_lp_mint_info, amm_coin_vault_info, amm_pc_vault_info, market_info, market_event_queue_info, user_source_info, user_dest_lp_info, source_owner_info, source_mint_info]
+/// = 14 accounts
+const ACCOUNT_LEN: usize = 14;
+
+pub fn process_deposit_single_sided(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    deposit: DepositSingleSidedInstruction,
+) -> ProgramResult {
+    if accounts.len() != ACCOUNT_LEN {
+        return Err(AmmError::WrongAccountsNumber.into());
+    }
+    let account_info_iter = &mut accounts.iter();
+    let token_program_info = next_account_info(account_info_iter)?;
+    let amm_info = next_account_info(account_info_iter)?;
+    let amm_authority_info = next_account_info(account_info_iter)?;
+    let amm_open_orders_info = next_account_info(account_info_iter)?;
+    let amm_target_orders_info = next_account_info(account_info_iter)?;
+    let amm_lp_mint_info = next_account_info(account_info_iter)?;
+    let amm_coin_vault_info = next_account_info(account_info_iter)?;
+    let amm_pc_vault_info = next_account_info(account_info_iter)?;
+    let market_info = next_account_info(account_info_iter)?;
+    let market_event_queue_info = next_account_info(account_info_iter)?;
+    let user_source_info = next_account_info(account_info_iter)?;
+    let user_dest_lp_info = next_account_info(account_info_iter)?;
+    let source_owner_info = next_account_info(account_info_iter)?;
+    let source_mint_info = next_account_info(account_info_iter)?;
+
+    let mut amm = AmmInfo::load_mut_checked(&amm_info, program_id)?;
+    if deposit.amount_in == 0 {
+        return Err(AmmError::InvalidInput.into());
+    }
+    if !source_owner_info.is_signer {
+        return Err(AmmError::InvalidSignAccount.into());
+    }
+    // the deposit swaps part of the input, so both permissions are required
+    let status = AmmStatus::from_u64(amm.status);
+    if !status.deposit_permission()
+        || !status.swap_permission()
+        || (amm.status == AmmStatus::WaitingTrade.into_u64()
+            && (Clock::get()?.unix_timestamp as u64) < amm.state_data.pool_open_time)
+    {
+        return Err(AmmError::InvalidStatus.into());
+    }
+    // the internal swap has no allowlist cap
+    if amm.allowlist_active(Clock::get()?.unix_timestamp as u64) {
+        return Err(AmmError::NotAllowlisted.into());
+    }
+    if *amm_authority_info.key != authority_id(program_id, AUTHORITY_AMM, amm.nonce as u8)? {
+        return Err(AmmError::InvalidProgramAddress.into());
+    }
+    let enable_orderbook = AmmStatus::from_u64(amm.status).orderbook_permission();
+    let spl_token_program_id = token_program_info.key;
+    check_assert_eq!(
+        *amm_coin_vault_info.key,
+        amm.coin_vault,
+        "coin_vault",
+        AmmError::InvalidCoinVault
+    );
+    check_assert_eq!(
+        *amm_pc_vault_info.key,
+        amm.pc_vault,
+        "pc_vault",
+        AmmError::InvalidPCVault
+    );
+    if *user_source_info.key == amm.coin_vault || *user_source_info.key == amm.pc_vault {
+        return Err(AmmError::InvalidUserToken.into());
+    }
+    check_assert_eq!(
+        *amm_lp_mint_info.key,
+        amm.lp_mint,
+        "lp_mint",
+        AmmError::InvalidPoolMint
+    );
+    check_assert_eq!(
+        *amm_target_orders_info.key,
+        amm.target_orders,
+        "target_orders",
+        AmmError::InvalidTargetOrders
+    );
+    let amm_coin_vault = unpack_token_account(&amm_coin_vault_info, spl_token_program_id)?;
+    let amm_pc_vault = unpack_token_account(&amm_pc_vault_info, spl_token_program_id)?;
+    let user_source = unpack_token_account(&user_source_info, spl_token_program_id)?;
+    let swap_direction = if user_source.mint == amm_coin_vault.mint {
+        SwapDirection::Coin2PC
+    } else if user_source.mint == amm_pc_vault.mint {
+        SwapDirection::PC2Coin
+    } else {
+        return Err(AmmError::InvalidUserToken.into());
+    };
+    if *source_mint_info.key != user_source.mint {
+        return Err(AmmError::InvalidUserToken.into());
+    }
+    if user_source.amount < deposit.amount_in {
+        return Err(AmmError::InsufficientFunds.into());
+    }
+
+    let mut target_orders =
+        TargetOrders::load_mut_checked(&amm_target_orders_info, program_id, amm_info.key)?;
+    let (mut total_pc_without_take_pnl, mut total_coin_without_take_pnl) = if enable_orderbook {
+        check_assert_eq!(
+            *market_info.key,
+            amm.market,
+            "market",
+            AmmError::InvalidMarket
+        );
+        check_assert_eq!(
+            *amm_open_orders_info.key,
+            amm.open_orders,
+            "open_orders",
+            AmmError::InvalidOpenOrders
+        );
+        calc_pool_total_without_take_pnl(
+            &amm,
+            amm_pc_vault.amount,
+            amm_coin_vault.amount,
+            market_info,
+            market_event_queue_info,
+            amm_open_orders_info,
+            amm_authority_info,
+        )?
+    } else {
+        Calculator::calc_total_without_take_pnl_no_orderbook(
+            amm_pc_vault.amount,
+            amm_coin_vault.amount,
+            &amm,
+        )?
+    };
+    let x1 = Calculator::normalize_decimal_v2(
+        total_pc_without_take_pnl,
+        amm.pc_decimals,
+        amm.sys_decimal_value,
+    );
+    let y1 = Calculator::normalize_decimal_v2(
+        total_coin_without_take_pnl,
+        amm.coin_decimals,
+        amm.sys_decimal_value,
+    );
+    let (delta_x, delta_y) = calc_take_pnl(
+        &target_orders,
+        &mut amm,
+        &mut total_pc_without_take_pnl,
+        &mut total_coin_without_take_pnl,
+        x1.as_u128().into(),
+        y1.as_u128().into(),
+    )?;
+    amm.update_price_cumulative(
+        total_pc_without_take_pnl,
+        total_coin_without_take_pnl,
+        Clock::get()?.unix_timestamp as u64,
+    );
+    if amm.lp_amount == 0 {
+        return Err(AmmError::NotAllowZeroLP.into());
+    }
+
+    // swap the optimal part of the input at pool price
+    let (reserve_in, reserve_out) = match swap_direction {
+        SwapDirection::Coin2PC => (total_coin_without_take_pnl, total_pc_without_take_pnl),
+        SwapDirection::PC2Coin => (total_pc_without_take_pnl, total_coin_without_take_pnl),
+    };
+    let swap_fee_numerator = amm.swap_fee_numerator(Clock::get()?.unix_timestamp as u64);
+    let swap_amount_in = Calculator::calc_zap_in_swap_amount(
+        deposit.amount_in,
+        reserve_in,
+        swap_fee_numerator,
+        amm.fees.swap_fee_denominator,
+    );
+    let swap_fee = U128::from(swap_amount_in)
+        .checked_mul(swap_fee_numerator.into())
+        .unwrap()
+        .checked_ceil_div(amm.fees.swap_fee_denominator.into())
+        .unwrap()
+        .0;
+    let swap_in_after_deduct_fee = U128::from(swap_amount_in).checked_sub(swap_fee).unwrap();
+    let swap_amount_out = Calculator::swap_token_amount_base_in(
+        swap_in_after_deduct_fee,
+        total_pc_without_take_pnl.into(),
+        total_coin_without_take_pnl.into(),
+        swap_direction,
+    )
+    .as_u64();
+    if swap_amount_out >= reserve_out {
+        return Err(AmmError::InsufficientFunds.into());
+    }
+    check_circuit_breaker(
+        &mut amm,
+        swap_in_after_deduct_fee,
+        swap_amount_out,
+        total_pc_without_take_pnl,
+        total_coin_without_take_pnl,
+        swap_direction,
+        Clock::get()?.slot,
+    )?;
+
+    // deposit the rest of the input together with the swap output
+    let deposit_in = deposit.amount_in.checked_sub(swap_amount_in).unwrap();
+    let reserve_in_after_swap = reserve_in.checked_add(swap_amount_in).unwrap();
+    let reserve_out_after_swap = reserve_out.checked_sub(swap_amount_out).unwrap();
+    let mint_lp_by_in = InvariantPool {
+        token_input: deposit_in,
+        token_total: reserve_in_after_swap,
+    }
+    .exchange_token_to_pool(amm.lp_amount, RoundDirection::Floor)
+    .ok_or(AmmError::CalculationExRateFailure)?;
+    let mint_lp_by_out = InvariantPool {
+        token_input: swap_amount_out,
+        token_total: reserve_out_after_swap,
+    }
+    .exchange_token_to_pool(amm.lp_amount, RoundDirection::Floor)
+    .ok_or(AmmError::CalculationExRateFailure)?;
+    let mint_lp_amount = std::cmp::min(mint_lp_by_in, mint_lp_by_out);
+
+    let (deposit_coin_amount, deposit_pc_amount, coin_fee, pc_fee, base_side) =
+        match swap_direction {
+            SwapDirection::Coin2PC => (deposit.amount_in, 0, swap_fee.as_u64(), 0, 0),
+            SwapDirection::PC2Coin => (0, deposit.amount_in, 0, swap_fee.as_u64(), 1),
+        };
+    encode_ray_log(DepositLog {
+        log_type: LogType::Deposit.into_u8(),
+        max_coin: deposit_coin_amount,
+        max_pc: deposit_pc_amount,
+        base: base_side,
+        pool_coin: total_coin_without_take_pnl,
+        pool_pc: total_pc_without_take_pnl,
+        pool_lp: amm.lp_amount,
+        calc_pnl_x: target_orders.calc_pnl_x,
+        calc_pnl_y: target_orders.calc_pnl_y,
+        deduct_coin: deposit_coin_amount,
+        deduct_pc: deposit_pc_amount,
+        mint_lp: mint_lp_amount,
+    });
+    if mint_lp_amount == 0 {
+        return Err(AmmError::InvalidInput.into());
+    }
+    if mint_lp_amount < deposit.minimum_lp_out {
+        return Err(AmmError::ExceededSlippage.into());
+    }
+
+    let amm_vault_info = match swap_direction {
+        SwapDirection::Coin2PC => amm_coin_vault_info,
+        SwapDirection::PC2Coin => amm_pc_vault_info,
+    };
+    Invokers::token_transfer(
+        program_id,
+        token_program_info.clone(),
+        user_source_info.clone(),
+        amm_vault_info.clone(),
+        source_owner_info.clone(),
+        deposit.amount_in,
+        source_mint_info.clone(),
+        &[],
+    )?;
+    Invokers::token_mint_to(
+        token_program_info.clone(),
+        amm_lp_mint_info.clone(),
+        user_dest_lp_info.clone(),
+        amm_authority_info.clone(),
+        AUTHORITY_AMM,
+        amm.nonce as u8,
+        mint_lp_amount,
+    )?;
+    // the fee of the internal swap goes to the lp held before this deposit
+    let lp_amount_before = amm.lp_amount;
+    amm.lp_amount = amm.lp_amount.checked_add(mint_lp_amount).unwrap();
+
+    // the internal swap is accounted like a regular one
+    match swap_direction {
+        SwapDirection::Coin2PC => {
+            amm.state_data.swap_coin_in_amount = amm
+                .state_data
+                .swap_coin_in_amount
+                .checked_add(swap_amount_in.into())
+                .unwrap();
+            amm.state_data.swap_pc_out_amount = amm
+                .state_data
+                .swap_pc_out_amount
+                .checked_add(swap_amount_out.into())
+                .unwrap();
+            amm.state_data.swap_acc_coin_fee = amm
+                .state_data
+                .swap_acc_coin_fee
+                .checked_add(coin_fee)
+                .unwrap();
+        }
+        SwapDirection::PC2Coin => {
+            amm.state_data.swap_pc_in_amount = amm
+                .state_data
+                .swap_pc_in_amount
+                .checked_add(swap_amount_in.into())
+                .unwrap();
+            amm.state_data.swap_coin_out_amount = amm
+                .state_data
+                .swap_coin_out_amount
+                .checked_add(swap_amount_out.into())
+                .unwrap();
+            amm.state_data.swap_acc_pc_fee = amm
+                .state_data
+                .swap_acc_pc_fee
+                .checked_add(pc_fee)
+                .unwrap();
+        }
+    };
+    let pc_after = total_pc_without_take_pnl
+        .checked_add(deposit_pc_amount)
+        .unwrap();
+    let coin_after = total_coin_without_take_pnl
+        .checked_add(deposit_coin_amount)
+        .unwrap();
+    amm.update_volatility_ema(
+        total_pc_without_take_pnl,
+        total_coin_without_take_pnl,
+        pc_after,
+        coin_after,
+    );
+    target_orders.calc_pnl_x = x1
+        .checked_add(Calculator::normalize_decimal_v2(
+            deposit_pc_amount,
+            amm.pc_decimals,
+            amm.sys_decimal_value,
+        ))
+        .unwrap()
+        .checked_sub(U128::from(delta_x))
+        .unwrap()
+        .as_u128();
+    target_orders.calc_pnl_y = y1
+        .checked_add(Calculator::normalize_decimal_v2(
+            deposit_coin_amount,
+            amm.coin_decimals,
+            amm.sys_decimal_value,
+        ))
+        .unwrap()
+        .checked_sub(U128::from(delta_y))
+        .unwrap()
+        .as_u128();
+    encode_return_data(AmmReturnData {
+        log_type: crate::log::LogType::Deposit.into_u8(),
+        coin_in: deposit_coin_amount,
+        pc_in: deposit_pc_amount,
+        coin_fee,
+        pc_fee,
+        lp_mint: mint_lp_amount,
+        pool_coin: coin_after,
+        pool_pc: pc_after,
+        pool_lp: amm.lp_amount,
+        ..Default::default()
+    });
+    amm.recent_epoch = Clock::get()?.epoch;
+    drop(amm);
+    accrue_fee_growth(program_id, amm_info, coin_fee, pc_fee, lp_amount_before)?;
+    Ok(())
+}
@@ -0,0 +1,233 @@
+//! Handles the flash loan instruction logic for Raydium AMM
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    clock::Clock,
+    entrypoint::ProgramResult,
+    msg,
+    pubkey::Pubkey,
+    sysvar::Sysvar,
+};
+use crate::{
+    error::AmmError,
+    instruction::FlashLoanInstruction,
+    invokers::Invokers,
+    math::Calculator,
+    state::{AmmInfo, AmmStatus},
+};
+use crate::process::constants::AUTHORITY_AMM;
+use crate::process::helpers::{accrue_fee_growth, authority_id, unpack_token_account};
+use crate::check_assert_eq;
+use crate::log::{
+    encode_ray_log, encode_return_data, log_keys_mismatch, AmmReturnData, FlashLoanLog, LogType,
+};
+
+/// The number of accounts expected for a flash loan instruction, without the hook and callback accounts.
+/// This is based on the order of next_account_info calls in the function:
+/// [token_program_info, amm_info, amm_authority_info, amm_coin_vault_info, amm_pc_vault_info, amm_coin_mint_info, amm_pc_mint_info, borrower_coin_info, borrower_pc_info, callback_program_info]
+/// = 10 accounts
+const ACCOUNT_LEN: usize = 10;
+
+pub fn process_flash_loan(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    flash_loan: FlashLoanInstruction,
+) -> ProgramResult {
+    let hook_account_num = flash_loan.hook_account_num as usize;
+    if accounts.len() < ACCOUNT_LEN + hook_account_num {
+        return Err(AmmError::WrongAccountsNumber.into());
+    }
+    let account_info_iter = &mut accounts.iter();
+    let token_program_info = next_account_info(account_info_iter)?;
+    let amm_info = next_account_info(account_info_iter)?;
+    let amm_authority_info = next_account_info(account_info_iter)?;
+    let amm_coin_vault_info = next_account_info(account_info_iter)?;
+    let amm_pc_vault_info = next_account_info(account_info_iter)?;
+    let amm_coin_mint_info = next_account_info(account_info_iter)?;
+    let amm_pc_mint_info = next_account_info(account_info_iter)?;
+    let borrower_coin_info = next_account_info(account_info_iter)?;
+    let borrower_pc_info = next_account_info(account_info_iter)?;
+    let callback_program_info = next_account_info(account_info_iter)?;
+    let (hook_accounts, callback_accounts) =
+        accounts[ACCOUNT_LEN..].split_at(hook_account_num);
+
+    // the vaults are spl token accounts, so there are no transfer hooks to forward
+    if *token_program_info.key != spl_token::id() {
+        return Err(AmmError::InvalidSplTokenProgram.into());
+    }
+    if hook_account_num != 0 {
+        return Err(AmmError::InvalidInput.into());
+    }
+    // the runtime rejects reentrancy through another program, but allows the amm to call itself
+    if *callback_program_info.key == *program_id
+        || *callback_program_info.key == *token_program_info.key
+    {
+        return Err(AmmError::InvalidFlashLoanCallback.into());
+    }
+    if flash_loan.coin_amount == 0 && flash_loan.pc_amount == 0 {
+        return Err(AmmError::InvalidInput.into());
+    }
+
+    let amm = AmmInfo::load_checked(&amm_info, program_id)?;
+    if !AmmStatus::from_u64(amm.status).swap_permission()
+        || (amm.status == AmmStatus::WaitingTrade.into_u64()
+            && (Clock::get()?.unix_timestamp as u64) < amm.state_data.pool_open_time)
+    {
+        return Err(AmmError::InvalidStatus.into());
+    }
+    if *amm_authority_info.key != authority_id(program_id, AUTHORITY_AMM, amm.nonce as u8)? {
+        return Err(AmmError::InvalidProgramAddress.into());
+    }
+    check_assert_eq!(
+        *amm_coin_vault_info.key,
+        amm.coin_vault,
+        "coin_vault",
+        AmmError::InvalidCoinVault
+    );
+    check_assert_eq!(
+        *amm_pc_vault_info.key,
+        amm.pc_vault,
+        "pc_vault",
+        AmmError::InvalidPCVault
+    );
+    check_assert_eq!(
+        *amm_coin_mint_info.key,
+        amm.coin_vault_mint,
+        "coin_mint",
+        AmmError::InvalidCoinMint
+    );
+    check_assert_eq!(
+        *amm_pc_mint_info.key,
+        amm.pc_vault_mint,
+        "pc_mint",
+        AmmError::InvalidPCMint
+    );
+    if *borrower_coin_info.key == amm.coin_vault || *borrower_pc_info.key == amm.pc_vault {
+        return Err(AmmError::InvalidUserToken.into());
+    }
+
+    let spl_token_program_id = token_program_info.key;
+    let amm_coin_vault = unpack_token_account(&amm_coin_vault_info, spl_token_program_id)?;
+    let amm_pc_vault = unpack_token_account(&amm_pc_vault_info, spl_token_program_id)?;
+    let (pc_before, coin_before) = Calculator::calc_total_without_take_pnl_no_orderbook(
+        amm_pc_vault.amount,
+        amm_coin_vault.amount,
+        &amm,
+    )?;
+    if flash_loan.coin_amount >= coin_before || flash_loan.pc_amount >= pc_before {
+        return Err(AmmError::InsufficientFunds.into());
+    }
+    let swap_fee_numerator = amm.swap_fee_numerator(Clock::get()?.unix_timestamp as u64);
+    let swap_fee_denominator = amm.fees.swap_fee_denominator;
+    let coin_fee = Calculator::calc_flash_fee(
+        flash_loan.coin_amount,
+        swap_fee_numerator,
+        swap_fee_denominator,
+    );
+    let pc_fee = Calculator::calc_flash_fee(
+        flash_loan.pc_amount,
+        swap_fee_numerator,
+        swap_fee_denominator,
+    );
+    let nonce = amm.nonce as u8;
+    // the callback may pass the amm account along, release the borrow before the cpi
+    drop(amm);
+
+    if flash_loan.coin_amount != 0 {
+        Invokers::token_transfer_with_authority(
+            program_id,
+            token_program_info.clone(),
+            amm_coin_vault_info.clone(),
+            borrower_coin_info.clone(),
+            amm_authority_info.clone(),
+            AUTHORITY_AMM,
+            nonce,
+            flash_loan.coin_amount,
+            amm_coin_mint_info.clone(),
+            hook_accounts,
+        )?;
+    }
+    if flash_loan.pc_amount != 0 {
+        Invokers::token_transfer_with_authority(
+            program_id,
+            token_program_info.clone(),
+            amm_pc_vault_info.clone(),
+            borrower_pc_info.clone(),
+            amm_authority_info.clone(),
+            AUTHORITY_AMM,
+            nonce,
+            flash_loan.pc_amount,
+            amm_pc_mint_info.clone(),
+            hook_accounts,
+        )?;
+    }
+
+    Invokers::invoke_flash_loan_callback(
+        callback_program_info.clone(),
+        callback_accounts,
+        flash_loan.callback_data,
+    )?;
+
+    let mut amm = AmmInfo::load_mut_checked(&amm_info, program_id)?;
+    // the loan must come back in the tokens lent, the price of the pool does not move
+    let amm_coin_vault = unpack_token_account(&amm_coin_vault_info, spl_token_program_id)?;
+    let amm_pc_vault = unpack_token_account(&amm_pc_vault_info, spl_token_program_id)?;
+    let (pc_after, coin_after) = Calculator::calc_total_without_take_pnl_no_orderbook(
+        amm_pc_vault.amount,
+        amm_coin_vault.amount,
+        &amm,
+    )?;
+    if !Calculator::flash_loan_repaid(pc_before, coin_before, pc_after, coin_after, pc_fee, coin_fee)
+    {
+        msg!(
+            "flash loan: before pc:{}, coin:{}, after pc:{}, coin:{}, fee pc:{}, coin:{}",
+            pc_before,
+            coin_before,
+            pc_after,
+            coin_after,
+            pc_fee,
+            coin_fee
+        );
+        return Err(AmmError::FlashLoanNotRepaid.into());
+    }
+
+    amm.update_price_cumulative(pc_before, coin_before, Clock::get()?.unix_timestamp as u64);
+
+    // flash fees stay in the vaults and are accounted as swap fees
+    amm.state_data.swap_acc_coin_fee = amm
+        .state_data
+        .swap_acc_coin_fee
+        .checked_add(coin_fee)
+        .unwrap();
+    amm.state_data.swap_acc_pc_fee = amm
+        .state_data
+        .swap_acc_pc_fee
+        .checked_add(pc_fee)
+        .unwrap();
+    encode_ray_log(FlashLoanLog {
+        log_type: LogType::FlashLoan.into_u8(),
+        coin_amount: flash_loan.coin_amount,
+        pc_amount: flash_loan.pc_amount,
+        coin_fee,
+        pc_fee,
+        pool_coin: coin_after,
+        pool_pc: pc_after,
+    });
+    encode_return_data(AmmReturnData {
+        log_type: LogType::FlashLoan.into_u8(),
+        coin_in: flash_loan.coin_amount + (coin_after - coin_before),
+        pc_in: flash_loan.pc_amount + (pc_after - pc_before),
+        coin_out: flash_loan.coin_amount,
+        pc_out: flash_loan.pc_amount,
+        coin_fee,
+        pc_fee,
+        pool_coin: coin_after,
+        pool_pc: pc_after,
+        pool_lp: amm.lp_amount,
+        ..Default::default()
+    });
+    amm.recent_epoch = Clock::get()?.epoch;
+    let lp_amount = amm.lp_amount;
+    drop(amm);
+    accrue_fee_growth(program_id, amm_info, coin_fee, pc_fee, lp_amount)?;
+    Ok(())
+}
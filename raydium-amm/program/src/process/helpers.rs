@@ -3,14 +3,23 @@ use solana_program::{
     pubkey::Pubkey,
     program_error::ProgramError,
     entrypoint::ProgramResult,
+    hash::hashv,
+    msg,
 };
 
-use crate::state::{AmmInfo, TargetOrders, AmmStatus};
-use crate::math::{U256, U128, InvariantPool, RoundDirection};
+use crate::state::{
+    AmmConfig, AmmInfo, AmmRole, AllowlistEntry, TargetOrders, AmmStatus, MarketBackend,
+    AMM_INFO_VERSION_V2,
+};
+use crate::log::{encode_ray_log, CompoundPnlLog, LogType};
+use crate::math::{Calculator, CheckedCeilDiv, U256, U128, InvariantPool, RoundDirection, SwapDirection};
 use crate::error::AmmError;
-use crate::instruction::WithdrawInstruction;
+use crate::instruction::{WithdrawInstruction, WithdrawSingleSidedInstruction};
 use crate::invokers::Invokers;
-use crate::process::constants::AUTHORITY_AMM;
+use crate::openbook_v2::{MarketV2, OpenOrdersV2};
+use crate::process::constants::{
+    get_amm_owner_id, get_openbook_v2_program_id, AMM_CONFIG_SEED, AUTHORITY_AMM,
+};
 use serum_dex::state::{MarketState, OpenOrders, ToAlignedBytes};
 use std::cell::Ref;
 use serum_dex::critbit::{LeafNode, Slab, SlabView};
@@ -77,6 +86,230 @@ pub fn load_serum_market_order<'a>(
     Ok((Box::new(*market_state), Box::new(*open_orders)))
 }
 
+/// Market backend of the market program a pool is bound to
+pub fn market_backend(market_program: &Pubkey) -> Result<MarketBackend, AmmError> {
+    if *market_program == get_openbook_v2_program_id()? {
+        Ok(MarketBackend::OpenBookV2)
+    } else {
+        Ok(MarketBackend::OpenBookV1)
+    }
+}
+
+/// load_serum_market_order for a pool bound to an OpenBook v2 market
+pub fn load_openbook_v2_market_order<'a>(
+    market_acc: &AccountInfo<'a>,
+    open_orders_acc: &AccountInfo<'a>,
+    authority_acc: &AccountInfo<'a>,
+    amm: &AmmInfo,
+) -> Result<(Box<MarketV2>, Box<OpenOrdersV2>), ProgramError> {
+    let market = MarketV2::load_checked(market_acc, &amm.market_program)?;
+    let open_orders = OpenOrdersV2::load_checked(open_orders_acc, &amm.market_program)?;
+    if open_orders.market != *market_acc.key {
+        return Err(AmmError::InvalidMarket.into());
+    }
+    if open_orders.owner != *authority_acc.key {
+        return Err(AmmError::InvalidOwner.into());
+    }
+    if *open_orders_acc.key != amm.open_orders {
+        return Err(AmmError::InvalidOpenOrders.into());
+    }
+    Ok((Box::new(market), Box::new(open_orders)))
+}
+
+/// Pool totals without the pnl to take, including the funds in the market when the
+/// orderbook is enabled, whichever market backend the pool is bound to
+pub fn calc_pool_total_without_take_pnl<'a>(
+    amm: &AmmInfo,
+    pc_amount: u64,
+    coin_amount: u64,
+    market_info: &AccountInfo<'a>,
+    market_event_queue_info: &AccountInfo<'a>,
+    amm_open_orders_info: &AccountInfo<'a>,
+    amm_authority_info: &AccountInfo<'a>,
+) -> Result<(u64, u64), ProgramError> {
+    if !AmmStatus::from_u64(amm.status).orderbook_permission() {
+        return Ok(Calculator::calc_total_without_take_pnl_no_orderbook(
+            pc_amount,
+            coin_amount,
+            amm,
+        )?);
+    }
+    match market_backend(&amm.market_program)? {
+        MarketBackend::OpenBookV1 => {
+            let (market_state, open_orders) = load_serum_market_order(
+                market_info,
+                amm_open_orders_info,
+                amm_authority_info,
+                amm,
+                false,
+            )?;
+            Ok(Calculator::calc_total_without_take_pnl(
+                pc_amount,
+                coin_amount,
+                &open_orders,
+                amm,
+                &market_state,
+                market_event_queue_info,
+                amm_open_orders_info,
+            )?)
+        }
+        MarketBackend::OpenBookV2 => {
+            let (market, open_orders) = load_openbook_v2_market_order(
+                market_info,
+                amm_open_orders_info,
+                amm_authority_info,
+                amm,
+            )?;
+            Ok(Calculator::calc_total_without_take_pnl_openbook_v2(
+                pc_amount,
+                coin_amount,
+                &open_orders,
+                amm,
+                &market,
+            )?)
+        }
+    }
+}
+
+/// Cancels every amm order on the market the pool is bound to and settles the funds back to
+/// the amm vaults, then returns the pool totals without the pnl to take. The totals are taken
+/// from the open orders loaded before the cancel, so pc_amount and coin_amount are the vault
+/// amounts before the settle. An OpenBook v2 pool passes the market authority in the vault
+/// signer slot and the system program in the event queue slot
+pub fn cancel_all_and_settle_pool_orders<'a>(
+    amm: &AmmInfo,
+    pc_amount: u64,
+    coin_amount: u64,
+    market_program_info: &AccountInfo<'a>,
+    market_info: &AccountInfo<'a>,
+    market_bids_info: &AccountInfo<'a>,
+    market_asks_info: &AccountInfo<'a>,
+    market_event_q_info: &AccountInfo<'a>,
+    market_coin_vault_info: &AccountInfo<'a>,
+    market_pc_vault_info: &AccountInfo<'a>,
+    market_vault_signer: &AccountInfo<'a>,
+    amm_open_orders_info: &AccountInfo<'a>,
+    amm_authority_info: &AccountInfo<'a>,
+    amm_coin_vault_info: &AccountInfo<'a>,
+    amm_pc_vault_info: &AccountInfo<'a>,
+    token_program_info: &AccountInfo<'a>,
+    referrer_pc_wallet: Option<&AccountInfo<'a>>,
+) -> Result<(u64, u64), ProgramError> {
+    if *market_info.key != amm.market {
+        return Err(AmmError::InvalidMarket.into());
+    }
+    if *market_program_info.key != amm.market_program {
+        return Err(AmmError::InvalidMarketProgram.into());
+    }
+    match market_backend(&amm.market_program)? {
+        MarketBackend::OpenBookV1 => {
+            let (market_state, open_orders) = load_serum_market_order(
+                market_info,
+                amm_open_orders_info,
+                amm_authority_info,
+                amm,
+                false,
+            )?;
+            if identity(market_state.coin_mint) != amm.coin_vault_mint.to_aligned_bytes() {
+                return Err(AmmError::InvalidCoinMint.into());
+            }
+            if identity(market_state.pc_mint) != amm.pc_vault_mint.to_aligned_bytes() {
+                return Err(AmmError::InvalidPCMint.into());
+            }
+            let bids_orders = market_state.load_bids_checked(market_bids_info)?;
+            let asks_orders = market_state.load_asks_checked(market_asks_info)?;
+            let (bids, asks) = get_amm_orders(&open_orders, bids_orders, asks_orders)?;
+            cancel_amm_orders_and_settle(
+                market_program_info,
+                market_info,
+                market_bids_info,
+                market_asks_info,
+                amm_open_orders_info,
+                amm_authority_info,
+                market_event_q_info,
+                market_coin_vault_info,
+                market_pc_vault_info,
+                amm_coin_vault_info,
+                amm_pc_vault_info,
+                market_vault_signer,
+                token_program_info,
+                referrer_pc_wallet,
+                &bids,
+                &asks,
+                amm.nonce as u8,
+            )?;
+            Ok(Calculator::calc_total_without_take_pnl(
+                pc_amount,
+                coin_amount,
+                &open_orders,
+                amm,
+                &market_state,
+                market_event_q_info,
+                amm_open_orders_info,
+            )?)
+        }
+        MarketBackend::OpenBookV2 => {
+            let (market, open_orders) = load_openbook_v2_market_order(
+                market_info,
+                amm_open_orders_info,
+                amm_authority_info,
+                amm,
+            )?;
+            if market.base_mint != amm.coin_vault_mint {
+                return Err(AmmError::InvalidCoinMint.into());
+            }
+            if market.quote_mint != amm.pc_vault_mint {
+                return Err(AmmError::InvalidPCMint.into());
+            }
+            if *market_bids_info.key != market.bids || *market_asks_info.key != market.asks {
+                return Err(AmmError::InvalidMarket.into());
+            }
+            if *market_vault_signer.key != market.market_authority
+                || *market_coin_vault_info.key != market.base_vault
+                || *market_pc_vault_info.key != market.quote_vault
+            {
+                return Err(AmmError::InvalidMarket.into());
+            }
+            if !open_orders.orders.is_empty() {
+                Invokers::invoke_openbook_v2_cancel_all_orders(
+                    market_program_info.clone(),
+                    market_info.clone(),
+                    market_bids_info.clone(),
+                    market_asks_info.clone(),
+                    amm_open_orders_info.clone(),
+                    amm_authority_info.clone(),
+                    AUTHORITY_AMM,
+                    amm.nonce as u8,
+                    None,
+                )?;
+            }
+            Invokers::invoke_openbook_v2_settle_funds(
+                market_program_info.clone(),
+                market_info.clone(),
+                amm_open_orders_info.clone(),
+                amm_authority_info.clone(),
+                market_vault_signer.clone(),
+                market_coin_vault_info.clone(),
+                market_pc_vault_info.clone(),
+                amm_coin_vault_info.clone(),
+                amm_pc_vault_info.clone(),
+                token_program_info.clone(),
+                market_event_q_info.clone(),
+                referrer_pc_wallet,
+                AUTHORITY_AMM,
+                amm.nonce as u8,
+            )?;
+            Ok(Calculator::calc_total_without_take_pnl_openbook_v2(
+                pc_amount,
+                coin_amount,
+                &open_orders,
+                amm,
+                &market,
+            )?)
+        }
+    }
+}
+
 pub fn get_amm_orders(
     open_orders: &OpenOrders,
     bids: Ref<Slab>,
@@ -123,7 +356,12 @@ pub fn check_account_readonly(account_info: &AccountInfo) -> ProgramResult {
     Ok(())
 }
 
-/// Calculate take PnL for target orders
+/// Takes the protocol pnl out of the pool totals. The pool grew from
+/// (calc_pnl_x, calc_pnl_y) to (x1, y1), the part of the growth above the last k at the
+/// current price is fee income, and fees.pnl_numerator / pnl_denominator of it is the
+/// protocol pnl. The pnl is added to need_take_pnl_* until it is claimed, total_pnl_*
+/// counts all of it. Returns the pnl in normalized decimals, the caller sets
+/// calc_pnl_x/y to (x1, y1) minus it.
 pub fn calc_take_pnl(
     target: &TargetOrders,
     amm: &mut AmmInfo,
@@ -132,37 +370,135 @@ pub fn calc_take_pnl(
     x1: U256,
     y1: U256,
 ) -> Result<(u128, u128), ProgramError> {
-    // Simplified PnL calculation
-    let delta_x = target.calc_pnl_x;
-    let delta_y = target.calc_pnl_y;
-    
-    // Update AMM state data
-    amm.state_data.total_pnl_pc = amm.state_data.total_pnl_pc.saturating_add(delta_x as u64);
-    amm.state_data.total_pnl_coin = amm.state_data.total_pnl_coin.saturating_add(delta_y as u64);
-    
-    // Update totals
-    *total_pc_without_take_pnl = total_pc_without_take_pnl.saturating_sub(delta_x as u64);
-    *total_coin_without_take_pnl = total_coin_without_take_pnl.saturating_sub(delta_y as u64);
-    
-    Ok((delta_x, delta_y))
+    let (last_x, last_y) = (target.calc_pnl_x, target.calc_pnl_y);
+    // nothing recorded yet, or an empty pool
+    if last_x == 0 || last_y == 0 || x1.is_zero() || y1.is_zero() {
+        return Ok((0, 0));
+    }
+    if x1 * y1 < U256::from(last_x) * U256::from(last_y) {
+        msg!(
+            "calc_take_pnl error x:{}, y:{}, calc_pnl_x:{}, calc_pnl_y:{}",
+            x1,
+            y1,
+            last_x,
+            last_y
+        );
+        return Err(AmmError::CalcPnlError.into());
+    }
+    // (x2, y2) holds the last k at the current price
+    let x2 = Calculator::calc_x_power(last_x.into(), last_y.into(), x1, y1).integer_sqrt();
+    let y2 = x2 * y1 / x1;
+    let pnl_share = |diff: U256| -> U256 {
+        diff * U256::from(amm.fees.pnl_numerator) / U256::from(amm.fees.pnl_denominator)
+    };
+    let delta_x = pnl_share(x1.saturating_sub(x2));
+    let delta_y = pnl_share(y1.saturating_sub(y2));
+    let pc_pnl_amount = Calculator::restore_decimal(
+        U128::from(delta_x.as_u128()),
+        amm.pc_decimals,
+        amm.sys_decimal_value,
+    )
+    .as_u64();
+    let coin_pnl_amount = Calculator::restore_decimal(
+        U128::from(delta_y.as_u128()),
+        amm.coin_decimals,
+        amm.sys_decimal_value,
+    )
+    .as_u64();
+    // the pnl is taken on both sides at once, so the price does not move
+    if pc_pnl_amount == 0
+        || coin_pnl_amount == 0
+        || pc_pnl_amount > *total_pc_without_take_pnl
+        || coin_pnl_amount > *total_coin_without_take_pnl
+    {
+        return Ok((0, 0));
+    }
+    amm.state_data.need_take_pnl_pc = amm
+        .state_data
+        .need_take_pnl_pc
+        .checked_add(pc_pnl_amount)
+        .ok_or(AmmError::CheckedAddOverflow)?;
+    amm.state_data.need_take_pnl_coin = amm
+        .state_data
+        .need_take_pnl_coin
+        .checked_add(coin_pnl_amount)
+        .ok_or(AmmError::CheckedAddOverflow)?;
+    amm.state_data.total_pnl_pc = amm.state_data.total_pnl_pc.saturating_add(pc_pnl_amount);
+    amm.state_data.total_pnl_coin = amm.state_data.total_pnl_coin.saturating_add(coin_pnl_amount);
+    *total_pc_without_take_pnl -= pc_pnl_amount;
+    *total_coin_without_take_pnl -= coin_pnl_amount;
+
+    Ok((delta_x.as_u128(), delta_y.as_u128()))
+}
+
+/// Raises the calc_take_pnl baseline by pnl that is left in the vaults for the lp, so the
+/// next calc_take_pnl does not take it as pool growth again. Nothing is raised before the
+/// first baseline is recorded
+pub fn raise_calc_pnl(target: &mut TargetOrders, amm: &AmmInfo, coin_amount: u64, pc_amount: u64) {
+    if target.calc_pnl_x == 0 || target.calc_pnl_y == 0 {
+        return;
+    }
+    let x = Calculator::normalize_decimal_v2(pc_amount, amm.pc_decimals, amm.sys_decimal_value);
+    let y = Calculator::normalize_decimal_v2(coin_amount, amm.coin_decimals, amm.sys_decimal_value);
+    target.calc_pnl_x = target.calc_pnl_x.saturating_add(x.as_u128());
+    target.calc_pnl_y = target.calc_pnl_y.saturating_add(y.as_u128());
 }
 
-/// Update target orders PnL
-pub fn update_target_orders_pnl(
+/// Compounds the unclaimed pnl into the pool when the pool has auto_compound on and logs
+/// it, the pnl baseline of the target orders is raised by it. Returns whether the pool
+/// auto-compounds, pools before AMM_INFO_VERSION_V2 never do
+pub fn compound_pnl(
+    program_id: &Pubkey,
+    amm_info: &AccountInfo,
     target_orders: &mut TargetOrders,
-    x1: U128,
-    y1: U128,
-    pc_amount: u64,
-    coin_amount: u64,
-    delta_x: u128,
-    delta_y: u128,
-    amm: &AmmInfo,
-) {
-    // Update target orders with new PnL calculations
-    target_orders.calc_pnl_x = delta_x;
-    target_orders.calc_pnl_y = delta_y;
-    target_orders.target_x = x1.as_u128();
-    target_orders.target_y = y1.as_u128();
+) -> Result<bool, ProgramError> {
+    if AmmInfo::load_checked(amm_info, program_id)?.version() < AMM_INFO_VERSION_V2 {
+        return Ok(false);
+    }
+    let (mut amm, mut extension) = AmmInfo::load_mut_checked_with_extension(amm_info, program_id)?;
+    if extension.auto_compound == 0 {
+        return Ok(false);
+    }
+    let (compound_coin, compound_pc) = extension.compound_pnl(&mut amm.state_data);
+    raise_calc_pnl(target_orders, &amm, compound_coin, compound_pc);
+    if compound_coin != 0 || compound_pc != 0 {
+        encode_ray_log(CompoundPnlLog {
+            log_type: LogType::CompoundPnl.into_u8(),
+            compound_coin,
+            compound_pc,
+            compounded_coin: extension.pnl_compounded_coin,
+            compounded_pc: extension.pnl_compounded_pc,
+        });
+    }
+    Ok(true)
+}
+
+/// Adds the lp share of a swap fee, the fee less the protocol pnl share, to the fee growth
+/// per lp token of the lp_amount the pool had before the trade. Pools before
+/// AMM_INFO_VERSION_V2 do not track the fee growth
+pub fn accrue_fee_growth(
+    program_id: &Pubkey,
+    amm_info: &AccountInfo,
+    coin_fee: u64,
+    pc_fee: u64,
+    lp_amount: u64,
+) -> ProgramResult {
+    if AmmInfo::load_checked(amm_info, program_id)?.version() < AMM_INFO_VERSION_V2 {
+        return Ok(());
+    }
+    let (amm, mut extension) = AmmInfo::load_mut_checked_with_extension(amm_info, program_id)?;
+    let (pnl_numerator, pnl_denominator) = (amm.fees.pnl_numerator, amm.fees.pnl_denominator);
+    let lp_share = |fee: u64| -> u64 {
+        let pnl = U128::from(fee)
+            .checked_mul(pnl_numerator.into())
+            .unwrap()
+            .checked_div(pnl_denominator.into())
+            .unwrap()
+            .as_u64();
+        fee - pnl
+    };
+    extension.accrue_fee_growth(lp_share(coin_fee), lp_share(pc_fee), lp_amount);
+    Ok(())
 }
 
 /// Validates slippage for withdrawal
@@ -181,8 +517,31 @@ pub fn validate_withdraw_slippage(
     Ok(())
 }
 
+/// Validates slippage for single sided withdrawal
+pub fn validate_single_sided_withdraw_slippage(
+    withdraw: &WithdrawSingleSidedInstruction,
+    amount_out: u64,
+) -> Result<(), AmmError> {
+    if withdraw.amount == 0 || amount_out == 0 {
+        return Err(AmmError::InvalidInput);
+    }
+    if amount_out < withdraw.min_amount_out {
+        return Err(AmmError::ExceededSlippage);
+    }
+    Ok(())
+}
+
 pub fn identity<T>(x: T) -> T { x }
 
+/// Moves the lamports of an account owned by the amm program to destination and wipes its data
+pub fn close_program_account(account: &AccountInfo, destination: &AccountInfo) -> ProgramResult {
+    let lamports = account.lamports();
+    **destination.lamports.borrow_mut() = destination.lamports().checked_add(lamports).unwrap();
+    **account.lamports.borrow_mut() = 0;
+    account.try_borrow_mut_data()?.fill(0);
+    Ok(())
+}
+
 /// Gets the associated address and bump seed for a given market and seed
 pub fn get_associated_address_and_bump_seed(
     info_id: &Pubkey,
@@ -198,6 +557,81 @@ pub fn get_associated_address_and_bump_seed(
     Pubkey::find_program_address(seeds, program_id)
 }
 
+/// Gets the key the pool accounts are derived from, the market for pools with one,
+/// otherwise the hash of the mint pair and fee tier. The mints are hashed in sorted order,
+/// so a pair has one pool per fee tier whichever side is the coin
+pub fn get_pool_key(
+    market: &Pubkey,
+    coin_mint: &Pubkey,
+    pc_mint: &Pubkey,
+    fee_tier: u32,
+) -> Pubkey {
+    if *market != Pubkey::default() {
+        return *market;
+    }
+    let (mint_a, mint_b) = if coin_mint <= pc_mint {
+        (coin_mint, pc_mint)
+    } else {
+        (pc_mint, coin_mint)
+    };
+    Pubkey::new_from_array(
+        hashv(&[mint_a.as_ref(), mint_b.as_ref(), &fee_tier.to_le_bytes()]).to_bytes(),
+    )
+}
+
+/// Checks that the signer holds the role on the pool, see AmmRole::holder. The amm config
+/// must be the program's config account
+pub fn check_amm_role(
+    program_id: &Pubkey,
+    role: AmmRole,
+    signer_info: &AccountInfo,
+    amm_info: &AccountInfo,
+    amm_config_info: &AccountInfo,
+) -> ProgramResult {
+    let amm_owner = get_amm_owner_id()?;
+    let (amm_config_address, _) =
+        get_associated_address_and_bump_seed(&amm_owner, &amm_owner, AMM_CONFIG_SEED, program_id);
+    if *amm_config_info.key != amm_config_address {
+        return Err(AmmError::InvalidConfigAccount.into());
+    }
+    let amm_config = AmmConfig::load_checked(amm_config_info, program_id)?;
+    let pool_roles = AmmInfo::load_roles(amm_info, program_id)?;
+    let amm = AmmInfo::load_checked(amm_info, program_id)?;
+    if *signer_info.key != role.holder(&amm.amm_owner, &pool_roles, &amm_config) {
+        return Err(role.error().into());
+    }
+    Ok(())
+}
+
+/// Checks the signer may override role on a pool. The pool admin only overrides the
+/// PoolAdmin role, the other roles are overridden by their holder in the amm config or by
+/// the config authority, so a pool admin can not grant itself FeeAdmin or PauseGuardian
+pub fn check_role_override(
+    program_id: &Pubkey,
+    role: AmmRole,
+    signer_info: &AccountInfo,
+    amm_info: &AccountInfo,
+    amm_config_info: &AccountInfo,
+) -> ProgramResult {
+    if role == AmmRole::PoolAdmin {
+        return check_amm_role(program_id, role, signer_info, amm_info, amm_config_info);
+    }
+    let amm_owner = get_amm_owner_id()?;
+    let (amm_config_address, _) =
+        get_associated_address_and_bump_seed(&amm_owner, &amm_owner, AMM_CONFIG_SEED, program_id);
+    if *amm_config_info.key != amm_config_address {
+        return Err(AmmError::InvalidConfigAccount.into());
+    }
+    let amm_config = AmmConfig::load_checked(amm_config_info, program_id)?;
+    let config_holder = amm_config.role(role);
+    if *signer_info.key != amm_config.pnl_owner
+        && (config_holder == Pubkey::default() || *signer_info.key != config_holder)
+    {
+        return Err(role.error().into());
+    }
+    Ok(())
+}
+
 /// Validates withdraw permissions and basic account checks
 pub fn validate_withdraw_permissions(
     amm: &AmmInfo,
@@ -352,4 +786,95 @@ pub fn calculate_withdrawal_amounts(
         .ok_or(AmmError::CalculationExRateFailure)?;
     
     Ok((coin_amount, pc_amount))
-} 
\ No newline at end of file
+} 
+
+/// Calculates the pro rata share of both sides of a single sided withdrawal and swaps the
+/// unwanted side back into the pool left after the withdrawal.
+/// Returns (coin_amount, pc_amount, swap_fee, swap_amount_out)
+pub fn calculate_single_sided_withdrawal(
+    withdraw_amount: u64,
+    amm_lp_amount: u64,
+    total_coin_without_take_pnl: u64,
+    total_pc_without_take_pnl: u64,
+    swap_fee_numerator: u64,
+    swap_fee_denominator: u64,
+    swap_direction: SwapDirection,
+) -> Result<(u64, u64, u64, u64), AmmError> {
+    let (coin_amount, pc_amount) = calculate_withdrawal_amounts(
+        withdraw_amount,
+        amm_lp_amount,
+        total_coin_without_take_pnl,
+        total_pc_without_take_pnl,
+    )?;
+    let pc_left = total_pc_without_take_pnl.checked_sub(pc_amount).unwrap();
+    let coin_left = total_coin_without_take_pnl.checked_sub(coin_amount).unwrap();
+    let swap_amount_in = match swap_direction {
+        SwapDirection::Coin2PC => coin_amount,
+        SwapDirection::PC2Coin => pc_amount,
+    };
+    let swap_fee = U128::from(swap_amount_in)
+        .checked_mul(swap_fee_numerator.into())
+        .unwrap()
+        .checked_ceil_div(swap_fee_denominator.into())
+        .unwrap()
+        .0;
+    let swap_in_after_deduct_fee = U128::from(swap_amount_in).checked_sub(swap_fee).unwrap();
+    let swap_amount_out = Calculator::swap_token_amount_base_in(
+        swap_in_after_deduct_fee,
+        pc_left.into(),
+        coin_left.into(),
+        swap_direction,
+    )
+    .as_u64();
+    Ok((coin_amount, pc_amount, swap_fee.as_u64(), swap_amount_out))
+}
+
+/// Counts the coin amount of a swap against the allowlist cap of the wallet while the
+/// allowlist phase is active
+pub fn check_allowlist_swap(
+    program_id: &Pubkey,
+    amm: &AmmInfo,
+    amm_key: &Pubkey,
+    allowlist_entry_info: Option<&AccountInfo>,
+    wallet: &Pubkey,
+    coin_amount: u64,
+    now: u64,
+) -> ProgramResult {
+    if !amm.allowlist_active(now) {
+        return Ok(());
+    }
+    let allowlist_entry_info = allowlist_entry_info.ok_or(AmmError::NotAllowlisted)?;
+    let mut allowlist_entry =
+        AllowlistEntry::load_mut_checked(allowlist_entry_info, program_id, amm_key, wallet)?;
+    allowlist_entry.consume(coin_amount)?;
+    Ok(())
+}
+
+/// Checks a swap against the circuit breaker limits of the pool and switches the pool
+/// to WithdrawOnly once the slot volume limit was hit in enough consecutive slots
+pub fn check_circuit_breaker(
+    amm: &mut AmmInfo,
+    swap_in_after_deduct_fee: U128,
+    swap_amount_out: u64,
+    total_pc_without_take_pnl: u64,
+    total_coin_without_take_pnl: u64,
+    swap_direction: SwapDirection,
+    slot: u64,
+) -> ProgramResult {
+    let price_impact = Calculator::calc_price_impact(
+        swap_in_after_deduct_fee,
+        total_pc_without_take_pnl.into(),
+        total_coin_without_take_pnl.into(),
+        swap_direction,
+    );
+    let reserve_out = match swap_direction {
+        SwapDirection::Coin2PC => total_pc_without_take_pnl,
+        SwapDirection::PC2Coin => total_coin_without_take_pnl,
+    };
+    if amm.check_circuit_breaker(price_impact, swap_amount_out, reserve_out, slot)? {
+        amm.status = AmmStatus::WithdrawOnly.into_u64();
+        let circuit_breaker_trips = amm.circuit_breaker_trips;
+        msg!("circuit breaker: {} trips, switch to WithdrawOnly", circuit_breaker_trips);
+    }
+    Ok(())
+}
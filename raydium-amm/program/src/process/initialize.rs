@@ -1,54 +1,135 @@
 //! Handles the initialize2 instruction logic for Raydium AMM
 use solana_program::{
     account_info::{next_account_info, AccountInfo},
+    clock::Clock,
     entrypoint::ProgramResult,
+    program::{invoke, invoke_signed},
+    program_error::ProgramError,
+    program_pack::Pack,
     pubkey::Pubkey,
+    rent::Rent,
+    sysvar::Sysvar,
     msg,
 };
 use crate::{
     error::AmmError,
     instruction::InitializeInstruction2,
-    state::{AmmInfo, TargetOrders, TargetOrder, AmmStatus, MAX_ORDER_LIMIT},
+    invokers::Invokers,
+    log::{encode_ray_log, InitLog, LogType},
+    state::{
+        AmmConfig, AmmInfo, TargetOrders, TargetOrder, AmmStatus, AMM_INFO_VERSION_CURRENT,
+        MAX_ORDER_LIMIT, TEN_THOUSAND,
+    },
 };
+use crate::process::helpers::{
+    authority_id, get_associated_address_and_bump_seed, get_pool_key, identity, unpack_mint,
+};
+use crate::process::constants::{
+    get_amm_owner_id, get_create_pool_fee_address_id, AMM_ASSOCIATED_SEED, AMM_CONFIG_SEED,
+    AUTHORITY_AMM, COIN_VAULT_ASSOCIATED_SEED, LP_MINT_ASSOCIATED_SEED,
+    OPEN_ORDER_ASSOCIATED_SEED, PC_VAULT_ASSOCIATED_SEED, TARGET_ASSOCIATED_SEED,
+};
+use serum_dex::state::{MarketState, ToAlignedBytes};
+use solana_system_interface::instruction as system_instruction;
+use std::mem::size_of;
 
+/// [token_program_info, ata_program_info, system_program_info, rent_info, amm_info, amm_authority_info, amm_open_orders_info, amm_lp_mint_info, coin_mint_info, pc_mint_info, amm_coin_vault_info, amm_pc_vault_info, amm_target_orders_info, amm_config_info, create_fee_destination_info, market_program_info, market_info, user_wallet_info, user_token_coin_info, user_token_pc_info, user_token_lp_info, (user_token_fee_info)] = 21 or 22 accounts
+const INITIALIZE2_ACCOUNT_LEN: usize = 21;
 
-use crate::process::helpers::authority_id;
-use crate::process::constants::AUTHORITY_AMM;
+/// Checks a pool account is the derived address of the pool key and has not been created yet
+pub fn check_pool_account(
+    program_id: &Pubkey,
+    account: &AccountInfo,
+    pool_key: &Pubkey,
+    associated_seed: &[u8],
+) -> Result<u8, ProgramError> {
+    let (address, bump_seed) =
+        get_associated_address_and_bump_seed(program_id, pool_key, associated_seed, program_id);
+    if *account.key != address {
+        msg!("expected {}, found {}", address, account.key);
+        return Err(AmmError::InvalidProgramAddress.into());
+    }
+    if !account.data_is_empty() || *account.owner != solana_program::system_program::id() {
+        return Err(AmmError::RepeatCreateAmm.into());
+    }
+    Ok(bump_seed)
+}
+
+/// Creates a pool account at its derived address, signed with its seeds
+pub fn create_pool_account<'a>(
+    payer: &AccountInfo<'a>,
+    account: &AccountInfo<'a>,
+    system_program: &AccountInfo<'a>,
+    space: usize,
+    owner: &Pubkey,
+    signer_seeds: &[&[u8]],
+) -> ProgramResult {
+    let lamports = Rent::get()?.minimum_balance(space);
+    if account.lamports() == 0 {
+        return invoke_signed(
+            &system_instruction::create_account(
+                payer.key,
+                account.key,
+                lamports,
+                space as u64,
+                owner,
+            ),
+            &[payer.clone(), account.clone(), system_program.clone()],
+            &[signer_seeds],
+        );
+    }
+    // anyone can fund a derived address before the pool exists, which create_account refuses
+    let top_up = lamports.saturating_sub(account.lamports());
+    if top_up != 0 {
+        invoke(
+            &system_instruction::transfer(payer.key, account.key, top_up),
+            &[payer.clone(), account.clone(), system_program.clone()],
+        )?;
+    }
+    invoke_signed(
+        &system_instruction::allocate(account.key, space as u64),
+        &[account.clone(), system_program.clone()],
+        &[signer_seeds],
+    )?;
+    invoke_signed(
+        &system_instruction::assign(account.key, owner),
+        &[account.clone(), system_program.clone()],
+        &[signer_seeds],
+    )
+}
 
 pub fn process_initialize2(
     program_id: &Pubkey,
     accounts: &[AccountInfo],
     init: InitializeInstruction2,
 ) -> ProgramResult {
+    if accounts.len() != INITIALIZE2_ACCOUNT_LEN && accounts.len() != INITIALIZE2_ACCOUNT_LEN + 1
+    {
+        return Err(AmmError::WrongAccountsNumber.into());
+    }
     let account_info_iter = &mut accounts.iter();
+    let token_program_info = next_account_info(account_info_iter)?;
+    let ata_program_info = next_account_info(account_info_iter)?;
+    let system_program_info = next_account_info(account_info_iter)?;
+    let rent_info = next_account_info(account_info_iter)?;
     let amm_info = next_account_info(account_info_iter)?;
     let amm_authority_info = next_account_info(account_info_iter)?;
     let amm_open_orders_info = next_account_info(account_info_iter)?;
-    let amm_target_orders_info = next_account_info(account_info_iter)?;
+    let amm_lp_mint_info = next_account_info(account_info_iter)?;
+    let coin_mint_info = next_account_info(account_info_iter)?;
+    let pc_mint_info = next_account_info(account_info_iter)?;
     let amm_coin_vault_info = next_account_info(account_info_iter)?;
     let amm_pc_vault_info = next_account_info(account_info_iter)?;
-    let amm_lp_mint_info = next_account_info(account_info_iter)?;
-    let pool_withdraw_queue_info = next_account_info(account_info_iter)?;
-    let lp_withdraw_queue_info = next_account_info(account_info_iter)?;
+    let amm_target_orders_info = next_account_info(account_info_iter)?;
+    let amm_config_info = next_account_info(account_info_iter)?;
+    let create_fee_destination_info = next_account_info(account_info_iter)?;
     let market_program_info = next_account_info(account_info_iter)?;
     let market_info = next_account_info(account_info_iter)?;
-    let market_bids_info = next_account_info(account_info_iter)?;
-    let market_asks_info = next_account_info(account_info_iter)?;
-    let market_event_queue_info = next_account_info(account_info_iter)?;
-    let market_coin_vault_info = next_account_info(account_info_iter)?;
-    let market_pc_vault_info = next_account_info(account_info_iter)?;
-    let market_vault_signer = next_account_info(account_info_iter)?;
-    let token_program_info = next_account_info(account_info_iter)?;
-    let system_program_info = next_account_info(account_info_iter)?;
-    let rent_info = next_account_info(account_info_iter)?;
     let user_wallet_info = next_account_info(account_info_iter)?;
     let user_token_coin_info = next_account_info(account_info_iter)?;
     let user_token_pc_info = next_account_info(account_info_iter)?;
     let user_token_lp_info = next_account_info(account_info_iter)?;
-    let coin_mint_info = next_account_info(account_info_iter)?;
-    let pc_mint_info = next_account_info(account_info_iter)?;
-    let srm_token_info = next_account_info(account_info_iter)?;
-    let referrer_pc_info = next_account_info(account_info_iter)?;
+    let user_token_fee_info = next_account_info(account_info_iter).ok();
 
     if !user_wallet_info.is_signer {
         return Err(AmmError::InvalidSignAccount.into());
@@ -59,14 +140,19 @@ pub fn process_initialize2(
         return Err(AmmError::InvalidSplTokenProgram.into());
     }
 
+    // Check associated token program
+    if *ata_program_info.key != spl_associated_token_account::id() {
+        return Err(AmmError::InvalidProgramAddress.into());
+    }
+
     // Check system program
     if *system_program_info.key != solana_program::system_program::id() {
-        return Err(AmmError::InvalidMarketProgram.into());
+        return Err(AmmError::InvalidSystemProgram.into());
     }
 
     // Check rent sysvar
     if *rent_info.key != solana_program::sysvar::rent::id() {
-        return Err(AmmError::InvalidMarketProgram.into());
+        return Err(AmmError::InvalidSysProgramAddress.into());
     }
 
     // Generate authority
@@ -75,21 +161,99 @@ pub fn process_initialize2(
         return Err(AmmError::InvalidProgramAddress.into());
     }
 
+    if *coin_mint_info.key == *pc_mint_info.key {
+        return Err(AmmError::InvalidCoinMint.into());
+    }
+    let coin_mint = unpack_mint(coin_mint_info, token_program_info.key)?;
+    let pc_mint = unpack_mint(pc_mint_info, token_program_info.key)?;
+
+    // The create pool fee is set by the config of the amm owner
+    let amm_owner = get_amm_owner_id()?;
+    let (amm_config_address, _) =
+        get_associated_address_and_bump_seed(&amm_owner, &amm_owner, AMM_CONFIG_SEED, program_id);
+    if *amm_config_info.key != amm_config_address {
+        return Err(AmmError::InvalidConfigAccount.into());
+    }
+    let amm_config = AmmConfig::load_checked(amm_config_info, program_id)?;
+    let create_pool_fee = amm_config.create_pool_fee;
+    let create_pool_fee_mint = amm_config.create_pool_fee_mint;
+    drop(amm_config);
+
+    // A pool with a market trades against it, a pool without one is swap only
+    let with_market = *market_info.key != Pubkey::default();
+    let (coin_lot_size, pc_lot_size) = if with_market {
+        let market_state =
+            MarketState::load_checked(market_info, market_program_info.key, false)?;
+        if identity(market_state.coin_mint) != coin_mint_info.key.to_aligned_bytes() {
+            return Err(AmmError::InvalidCoinMint.into());
+        }
+        if identity(market_state.pc_mint) != pc_mint_info.key.to_aligned_bytes() {
+            return Err(AmmError::InvalidPCMint.into());
+        }
+        (market_state.coin_lot_size, market_state.pc_lot_size)
+    } else {
+        (0, 0)
+    };
+
+    // Every pool account is derived from the pool key, so anyone can find the pool
+    // from its market or from its mint pair and fee tier
+    let pool_key = get_pool_key(
+        market_info.key,
+        coin_mint_info.key,
+        pc_mint_info.key,
+        init.fee_tier,
+    );
+    let amm_bump = check_pool_account(program_id, amm_info, &pool_key, AMM_ASSOCIATED_SEED)?;
+    let open_orders_bump = check_pool_account(
+        program_id,
+        amm_open_orders_info,
+        &pool_key,
+        OPEN_ORDER_ASSOCIATED_SEED,
+    )?;
+    let lp_mint_bump =
+        check_pool_account(program_id, amm_lp_mint_info, &pool_key, LP_MINT_ASSOCIATED_SEED)?;
+    let coin_vault_bump = check_pool_account(
+        program_id,
+        amm_coin_vault_info,
+        &pool_key,
+        COIN_VAULT_ASSOCIATED_SEED,
+    )?;
+    let pc_vault_bump =
+        check_pool_account(program_id, amm_pc_vault_info, &pool_key, PC_VAULT_ASSOCIATED_SEED)?;
+    let target_orders_bump = check_pool_account(
+        program_id,
+        amm_target_orders_info,
+        &pool_key,
+        TARGET_ASSOCIATED_SEED,
+    )?;
+    if *user_token_lp_info.key
+        != spl_associated_token_account::get_associated_token_address(
+            user_wallet_info.key,
+            amm_lp_mint_info.key,
+        )
+    {
+        return Err(AmmError::InvalidUserToken.into());
+    }
+
     // Initialize AMM info with default values and the provided fields
     let mut amm = AmmInfo {
-        status: AmmStatus::Initialized.into_u64(),
+        status: if with_market {
+            AmmStatus::Initialized.into_u64()
+        } else {
+            AmmStatus::SwapOnly.into_u64()
+        },
         nonce: init.nonce as u64,
         order_num: 0,
         depth: 0,
-        coin_decimals: 0,
-        pc_decimals: 0,
+        coin_decimals: coin_mint.decimals as u64,
+        pc_decimals: pc_mint.decimals as u64,
         state: 0,
         reset_flag: 0,
         min_size: 0,
         vol_max_cut_ratio: 0,
         amount_wave: 0,
-        coin_lot_size: 0,
-        pc_lot_size: 0,
+        coin_lot_size,
+        pc_lot_size,
         min_price_multiplier: 0,
         max_price_multiplier: 0,
         sys_decimal_value: 0,
@@ -104,23 +268,214 @@ pub fn process_initialize2(
         market: *market_info.key,
         market_program: *market_program_info.key,
         target_orders: *amm_target_orders_info.key,
-        padding1: [0; 8],
+        dynamic_fee_min_numerator: 0,
+        dynamic_fee_max_numerator: 0,
+        pc_price_cumulative: 0,
+        coin_price_cumulative: 0,
+        allowlist_end_time: 0,
+        launch_fee_numerator: init.launch_fee_numerator,
+        launch_fee_decay_seconds: init.launch_fee_decay_seconds,
+        max_price_impact_bps: 0,
+        max_output_bps: 0,
+        max_slot_volume_bps: 0,
+        circuit_breaker_max_trips: 0,
+        circuit_breaker_trips: 0,
         amm_owner: *user_wallet_info.key,
         lp_amount: 0,
         client_order_id: 0,
         recent_epoch: 0,
-        padding2: 0,
+        circuit_breaker_slot: 0,
+        slot_volume_bps: 0,
+        version: AMM_INFO_VERSION_CURRENT,
     };
 
-    // Initialize state data
-    amm.state_data.initialize(init.open_time)?;
+    if with_market {
+        // The order book parameters follow the market lot sizes, initialize also sets
+        // the state data and fees and resets the fields restored below
+        amm.initialize(
+            init.nonce,
+            init.open_time,
+            coin_mint.decimals,
+            pc_mint.decimals,
+            coin_lot_size,
+            pc_lot_size,
+        )?;
+        amm.status = AmmStatus::Initialized.into_u64();
+        amm.launch_fee_numerator = init.launch_fee_numerator;
+        amm.launch_fee_decay_seconds = init.launch_fee_decay_seconds;
+        amm.version = AMM_INFO_VERSION_CURRENT;
+    } else {
+        // Initialize state data
+        amm.state_data.initialize(init.open_time)?;
 
-    // Initialize fees
-    amm.fees.initialize()?;
+        // Initialize fees
+        amm.fees.initialize()?;
+    }
 
-    // Save AMM info by writing to account data
-    let mut amm_data = amm_info.try_borrow_mut_data()?;
-    amm_data.copy_from_slice(&bytemuck::bytes_of(&amm));
+    // A fee tier replaces the default swap and trade fee
+    if init.fee_tier != 0 {
+        if init.fee_tier as u64 >= TEN_THOUSAND {
+            return Err(AmmError::InvalidFee.into());
+        }
+        amm.fees.swap_fee_numerator = init.fee_tier as u64;
+        amm.fees.trade_fee_numerator = init.fee_tier as u64;
+    }
+
+    // The launch fee must start above the swap fee and decay over at least one second
+    if init.launch_fee_numerator != 0
+        && (init.launch_fee_numerator as u64 <= amm.fees.swap_fee_numerator
+            || init.launch_fee_numerator as u64 >= amm.fees.swap_fee_denominator
+            || init.launch_fee_decay_seconds == 0)
+    {
+        return Err(AmmError::InvalidFee.into());
+    }
+
+    // Charge the create pool fee in lamports, or in the configured token
+    let create_pool_fee_address = get_create_pool_fee_address_id()?;
+    if create_pool_fee_mint == Pubkey::default() {
+        if *create_fee_destination_info.key != create_pool_fee_address {
+            return Err(AmmError::InvalidCreatePoolFeeAccount.into());
+        }
+        if create_pool_fee != 0 {
+            invoke(
+                &system_instruction::transfer(
+                    user_wallet_info.key,
+                    create_fee_destination_info.key,
+                    create_pool_fee,
+                ),
+                &[
+                    user_wallet_info.clone(),
+                    create_fee_destination_info.clone(),
+                    system_program_info.clone(),
+                ],
+            )?;
+        }
+    } else {
+        if *create_fee_destination_info.key
+            != spl_associated_token_account::get_associated_token_address(
+                &create_pool_fee_address,
+                &create_pool_fee_mint,
+            )
+        {
+            return Err(AmmError::InvalidCreatePoolFeeAccount.into());
+        }
+        if create_pool_fee != 0 {
+            let user_token_fee_info =
+                user_token_fee_info.ok_or(AmmError::WrongAccountsNumber)?;
+            invoke(
+                &spl_token::instruction::transfer(
+                    token_program_info.key,
+                    user_token_fee_info.key,
+                    create_fee_destination_info.key,
+                    user_wallet_info.key,
+                    &[],
+                    create_pool_fee,
+                )?,
+                &[
+                    user_token_fee_info.clone(),
+                    create_fee_destination_info.clone(),
+                    user_wallet_info.clone(),
+                ],
+            )?;
+        }
+    }
+
+    // Create the lp mint, decimals follow the coin mint
+    create_pool_account(
+        user_wallet_info,
+        amm_lp_mint_info,
+        system_program_info,
+        spl_token::state::Mint::LEN,
+        token_program_info.key,
+        &[program_id.as_ref(), pool_key.as_ref(), LP_MINT_ASSOCIATED_SEED, &[lp_mint_bump]],
+    )?;
+    invoke(
+        &spl_token::instruction::initialize_mint2(
+            token_program_info.key,
+            amm_lp_mint_info.key,
+            amm_authority_info.key,
+            None,
+            coin_mint.decimals,
+        )?,
+        &[amm_lp_mint_info.clone()],
+    )?;
+
+    // Create the vaults, owned by $authority
+    for (vault_info, mint_info, seed, bump_seed) in [
+        (amm_coin_vault_info, coin_mint_info, COIN_VAULT_ASSOCIATED_SEED, coin_vault_bump),
+        (amm_pc_vault_info, pc_mint_info, PC_VAULT_ASSOCIATED_SEED, pc_vault_bump),
+    ] {
+        create_pool_account(
+            user_wallet_info,
+            vault_info,
+            system_program_info,
+            spl_token::state::Account::LEN,
+            token_program_info.key,
+            &[program_id.as_ref(), pool_key.as_ref(), seed, &[bump_seed]],
+        )?;
+        invoke(
+            &spl_token::instruction::initialize_account3(
+                token_program_info.key,
+                vault_info.key,
+                mint_info.key,
+                amm_authority_info.key,
+            )?,
+            &[vault_info.clone(), mint_info.clone()],
+        )?;
+    }
+
+    // Create the open orders on the market, owned by the market program
+    if with_market {
+        create_pool_account(
+            user_wallet_info,
+            amm_open_orders_info,
+            system_program_info,
+            size_of::<serum_dex::state::OpenOrders>() + 12,
+            market_program_info.key,
+            &[
+                program_id.as_ref(),
+                pool_key.as_ref(),
+                OPEN_ORDER_ASSOCIATED_SEED,
+                &[open_orders_bump],
+            ],
+        )?;
+        Invokers::invoke_dex_init_open_orders(
+            market_program_info.clone(),
+            amm_open_orders_info.clone(),
+            amm_authority_info.clone(),
+            market_info.clone(),
+            rent_info.clone(),
+            AUTHORITY_AMM,
+            init.nonce,
+        )?;
+    }
+
+    // Create the target orders and the AMM account
+    create_pool_account(
+        user_wallet_info,
+        amm_target_orders_info,
+        system_program_info,
+        size_of::<TargetOrders>(),
+        program_id,
+        &[
+            program_id.as_ref(),
+            pool_key.as_ref(),
+            TARGET_ASSOCIATED_SEED,
+            &[target_orders_bump],
+        ],
+    )?;
+    create_pool_account(
+        user_wallet_info,
+        amm_info,
+        system_program_info,
+        AmmInfo::data_len(AMM_INFO_VERSION_CURRENT).unwrap(),
+        program_id,
+        &[program_id.as_ref(), pool_key.as_ref(), AMM_ASSOCIATED_SEED, &[amm_bump]],
+    )?;
+
+    // Save AMM info by writing to account data, the extension and role overrides start zeroed
+    amm_info.try_borrow_mut_data()?[..size_of::<AmmInfo>()]
+        .copy_from_slice(&bytemuck::bytes_of(&amm));
 
     // Initialize target orders manually since Default is only available in test mode
     let mut target_orders = TargetOrders {
@@ -147,19 +502,32 @@ pub fn process_initialize2(
         place_orders_cur: 0,
         valid_buy_order_num: 0,
         valid_sell_order_num: 0,
-        padding3: [0; 10],
+        admin_cancel_client_id: 0,
+        padding3: [0; 9],
         free_slot_bits: std::u128::MAX,
     };
     target_orders.check_init(0, 0, amm_info.key)?;
-    
+
     let mut target_orders_data = amm_target_orders_info.try_borrow_mut_data()?;
     target_orders_data.copy_from_slice(&bytemuck::bytes_of(&target_orders));
+    drop(target_orders_data);
+
+    // Create the user lp ATA
+    Invokers::create_ata_spl_token(
+        user_token_lp_info.clone(),
+        user_wallet_info.clone(),
+        user_wallet_info.clone(),
+        amm_lp_mint_info.clone(),
+        token_program_info.clone(),
+        ata_program_info.clone(),
+        system_program_info.clone(),
+    )?;
 
     // Calculate initial LP amount (geometric mean)
     let product = (init.init_coin_amount as u128)
         .checked_mul(init.init_pc_amount as u128)
         .unwrap();
-    
+
     // Simple integer square root implementation
     let mut initial_lp_amount = 0u64;
     if product > 0 {
@@ -193,8 +561,8 @@ pub fn process_initialize2(
     )?;
 
     amm.lp_amount = initial_lp_amount;
-    let mut amm_data = amm_info.try_borrow_mut_data()?;
-    amm_data.copy_from_slice(&bytemuck::bytes_of(&amm));
+    amm_info.try_borrow_mut_data()?[..size_of::<AmmInfo>()]
+        .copy_from_slice(&bytemuck::bytes_of(&amm));
 
     // Transfer initial tokens from user to AMM vaults
     let transfer_coin_ix = spl_token::instruction::transfer(
@@ -234,12 +602,19 @@ pub fn process_initialize2(
         ],
     )?;
 
-    // Initialize market accounts if needed
-    // Note: In a real implementation, you'd also initialize the Serum/OpenBook market
-    // For now, we'll just validate the accounts are present
-
+    encode_ray_log(InitLog {
+        log_type: LogType::Init.into_u8(),
+        time: Clock::get()?.unix_timestamp as u64,
+        pc_decimals: pc_mint.decimals,
+        coin_decimals: coin_mint.decimals,
+        pc_lot_size,
+        coin_lot_size,
+        pc_amount: init.init_pc_amount,
+        coin_amount: init.init_coin_amount,
+        market: *market_info.key,
+        create_pool_fee,
+        create_pool_fee_mint,
+    });
     msg!("AMM initialized successfully with {} LP tokens", initial_lp_amount);
-    msg!("Initial coin amount: {}", init.init_coin_amount);
-    msg!("Initial pc amount: {}", init.init_pc_amount);
     Ok(())
-} 
\ No newline at end of file
+}
@@ -0,0 +1,651 @@
+//! Handles the lp lock instruction logic for Raydium AMM
+use crate::{
+    error::AmmError,
+    instruction::{LockLpInstruction, LockLpPermanentInstruction, UnlockLpInstruction},
+    invokers::Invokers,
+    math::{Calculator, U128},
+    process::constants::{
+        AUTHORITY_AMM, LP_LOCK_ASSOCIATED_SEED, LP_PERMANENT_LOCK_ASSOCIATED_SEED,
+    },
+    process::helpers::{
+        authority_id, calc_take_pnl, close_program_account, get_associated_address_and_bump_seed,
+        calc_pool_total_without_take_pnl, unpack_token_account,
+    },
+    state::{AmmInfo, AmmInfoExtension, AmmStatus, Loadable, LpLock, TargetOrders},
+};
+use crate::check_assert_eq;
+use crate::log::log_keys_mismatch;
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    clock::Clock,
+    entrypoint::ProgramResult,
+    msg,
+    program::invoke_signed,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    rent::Rent,
+    sysvar::Sysvar,
+};
+use solana_system_interface::instruction as system_instruction;
+use std::mem::size_of;
+
+/// [token_program_info, ata_program_info, system_program_info, amm_info, amm_authority_info, amm_open_orders_info, amm_coin_vault_info, amm_pc_vault_info, amm_lp_mint_info, market_info, market_event_queue_info, lp_lock_info, lock_lp_token_info, user_lp_info, user_owner_info] = 15 accounts
+const LOCK_LP_ACCOUNT_LEN: usize = 15;
+/// [token_program_info, amm_info, amm_authority_info, amm_open_orders_info, amm_coin_vault_info, amm_pc_vault_info, amm_lp_mint_info, market_info, market_event_queue_info, lp_lock_info, lock_lp_token_info, user_lp_info, user_owner_info] = 13 accounts
+const CLAIM_LP_LOCK_FEE_ACCOUNT_LEN: usize = 13;
+/// [token_program_info, amm_info, amm_authority_info, amm_lp_mint_info, lp_lock_info, lock_lp_token_info, user_lp_info, user_owner_info] = 8 accounts
+const UNLOCK_LP_ACCOUNT_LEN: usize = 8;
+/// [token_program_info, amm_info, amm_authority_info, amm_open_orders_info, amm_target_orders_info, amm_coin_vault_info, amm_pc_vault_info, amm_lp_mint_info, amm_coin_mint_info, amm_pc_mint_info, market_info, market_event_queue_info, lp_lock_info, lock_lp_token_info, user_coin_info, user_pc_info, user_owner_info] = 17 accounts
+const CLAIM_PERMANENT_LOCK_FEE_ACCOUNT_LEN: usize = 17;
+
+/// Pool totals without pnl, including the funds on the orderbook
+pub fn load_pool_totals_without_take_pnl<'a>(
+    amm: &AmmInfo,
+    amm_authority_info: &AccountInfo<'a>,
+    amm_open_orders_info: &AccountInfo<'a>,
+    amm_coin_vault_info: &AccountInfo<'a>,
+    amm_pc_vault_info: &AccountInfo<'a>,
+    market_info: &AccountInfo<'a>,
+    market_event_queue_info: &AccountInfo<'a>,
+) -> Result<(u64, u64), ProgramError> {
+    if *amm_coin_vault_info.key != amm.coin_vault {
+        return Err(AmmError::InvalidCoinVault.into());
+    }
+    if *amm_pc_vault_info.key != amm.pc_vault {
+        return Err(AmmError::InvalidPCVault.into());
+    }
+    let amm_coin_vault = unpack_token_account(&amm_coin_vault_info, &spl_token::id())?;
+    let amm_pc_vault = unpack_token_account(&amm_pc_vault_info, &spl_token::id())?;
+    let (total_pc_without_take_pnl, total_coin_without_take_pnl) =
+        if AmmStatus::from_u64(amm.status).orderbook_permission() {
+            if *market_info.key != amm.market {
+                return Err(AmmError::InvalidMarket.into());
+            }
+            calc_pool_total_without_take_pnl(
+                amm,
+                amm_pc_vault.amount,
+                amm_coin_vault.amount,
+                market_info,
+                market_event_queue_info,
+                amm_open_orders_info,
+                amm_authority_info,
+            )?
+        } else {
+            Calculator::calc_total_without_take_pnl_no_orderbook(
+                amm_pc_vault.amount,
+                amm_coin_vault.amount,
+                amm,
+            )?
+        };
+    Ok((total_pc_without_take_pnl, total_coin_without_take_pnl))
+}
+
+/// Liquidity backing one lp token at the current pool totals without pnl
+pub fn calc_pool_liquidity_per_lp<'a>(
+    amm: &AmmInfo,
+    amm_authority_info: &AccountInfo<'a>,
+    amm_open_orders_info: &AccountInfo<'a>,
+    amm_coin_vault_info: &AccountInfo<'a>,
+    amm_pc_vault_info: &AccountInfo<'a>,
+    market_info: &AccountInfo<'a>,
+    market_event_queue_info: &AccountInfo<'a>,
+) -> Result<u128, ProgramError> {
+    let (total_pc_without_take_pnl, total_coin_without_take_pnl) =
+        load_pool_totals_without_take_pnl(
+            amm,
+            amm_authority_info,
+            amm_open_orders_info,
+            amm_coin_vault_info,
+            amm_pc_vault_info,
+            market_info,
+            market_event_queue_info,
+        )?;
+    Ok(Calculator::calc_liquidity_per_lp_x64(
+        total_pc_without_take_pnl,
+        total_coin_without_take_pnl,
+        amm.lp_amount,
+    ))
+}
+
+fn check_lock_lp_token_account(
+    amm_authority_info: &AccountInfo,
+    amm_lp_mint_info: &AccountInfo,
+    lock_lp_token_info: &AccountInfo,
+) -> ProgramResult {
+    let lock_lp_token = spl_associated_token_account::get_associated_token_address(
+        amm_authority_info.key,
+        amm_lp_mint_info.key,
+    );
+    if *lock_lp_token_info.key != lock_lp_token {
+        return Err(AmmError::InvalidLpLockAccount.into());
+    }
+    Ok(())
+}
+
+pub fn process_lock_lp(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    lock: LockLpInstruction,
+) -> ProgramResult {
+    let now = Clock::get()?.unix_timestamp as u64;
+    if lock.unlock_end_time < lock.unlock_start_time || lock.unlock_end_time <= now {
+        return Err(AmmError::InvalidUnlockSchedule.into());
+    }
+    create_lp_lock(
+        program_id,
+        accounts,
+        lock.amount,
+        lock.unlock_start_time,
+        lock.unlock_end_time,
+        false,
+    )
+}
+
+pub fn process_lock_lp_permanent(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    lock: LockLpPermanentInstruction,
+) -> ProgramResult {
+    // never unlocked
+    create_lp_lock(program_id, accounts, lock.amount, u64::MAX, u64::MAX, true)
+}
+
+fn create_lp_lock(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    amount: u64,
+    unlock_start_time: u64,
+    unlock_end_time: u64,
+    permanent: bool,
+) -> ProgramResult {
+    if accounts.len() != LOCK_LP_ACCOUNT_LEN {
+        return Err(AmmError::WrongAccountsNumber.into());
+    }
+    let account_info_iter = &mut accounts.iter();
+    let token_program_info = next_account_info(account_info_iter)?;
+    let ata_program_info = next_account_info(account_info_iter)?;
+    let system_program_info = next_account_info(account_info_iter)?;
+    let amm_info = next_account_info(account_info_iter)?;
+    let amm_authority_info = next_account_info(account_info_iter)?;
+    let amm_open_orders_info = next_account_info(account_info_iter)?;
+    let amm_coin_vault_info = next_account_info(account_info_iter)?;
+    let amm_pc_vault_info = next_account_info(account_info_iter)?;
+    let amm_lp_mint_info = next_account_info(account_info_iter)?;
+    let market_info = next_account_info(account_info_iter)?;
+    let market_event_queue_info = next_account_info(account_info_iter)?;
+    let lp_lock_info = next_account_info(account_info_iter)?;
+    let lock_lp_token_info = next_account_info(account_info_iter)?;
+    let user_lp_info = next_account_info(account_info_iter)?;
+    let user_owner_info = next_account_info(account_info_iter)?;
+
+    if !user_owner_info.is_signer {
+        return Err(AmmError::InvalidSignAccount.into());
+    }
+    if *token_program_info.key != spl_token::id() {
+        return Err(AmmError::InvalidSplTokenProgram.into());
+    }
+    if *system_program_info.key != solana_program::system_program::id() {
+        return Err(AmmError::InvalidSystemProgram.into());
+    }
+    if amount == 0 {
+        return Err(AmmError::InvalidInput.into());
+    }
+
+    let amm = AmmInfo::load_checked(&amm_info, program_id)?;
+    if *amm_authority_info.key != authority_id(program_id, AUTHORITY_AMM, amm.nonce as u8)? {
+        return Err(AmmError::InvalidProgramAddress.into());
+    }
+    if *amm_lp_mint_info.key != amm.lp_mint {
+        return Err(AmmError::InvalidPoolMint.into());
+    }
+    check_lock_lp_token_account(amm_authority_info, amm_lp_mint_info, lock_lp_token_info)?;
+    let user_lp = unpack_token_account(&user_lp_info, token_program_info.key)?;
+    if user_lp.mint != amm.lp_mint {
+        return Err(AmmError::InvalidTokenLP.into());
+    }
+    if amount > user_lp.amount {
+        return Err(AmmError::InsufficientFunds.into());
+    }
+
+    let lp_lock_seed = if permanent {
+        LP_PERMANENT_LOCK_ASSOCIATED_SEED
+    } else {
+        LP_LOCK_ASSOCIATED_SEED
+    };
+    let (lp_lock_address, bump_seed) = get_associated_address_and_bump_seed(
+        amm_info.key,
+        user_owner_info.key,
+        lp_lock_seed,
+        program_id,
+    );
+    if *lp_lock_info.key != lp_lock_address || !lp_lock_info.data_is_empty() {
+        return Err(AmmError::InvalidLpLockAccount.into());
+    }
+    let liquidity_per_lp_x64 = calc_pool_liquidity_per_lp(
+        &amm,
+        amm_authority_info,
+        amm_open_orders_info,
+        amm_coin_vault_info,
+        amm_pc_vault_info,
+        market_info,
+        market_event_queue_info,
+    )?;
+    // permanent locks claim the fee growth per lp, pools before AMM_INFO_VERSION_V2 do not track it
+    let extension = if permanent {
+        AmmInfo::load_extension(amm_info, program_id)?
+    } else {
+        AmmInfoExtension::default()
+    };
+
+    let space = size_of::<LpLock>();
+    let lamports = Rent::get()?.minimum_balance(space);
+    invoke_signed(
+        &system_instruction::create_account(
+            user_owner_info.key,
+            lp_lock_info.key,
+            lamports,
+            space as u64,
+            program_id,
+        ),
+        &[
+            user_owner_info.clone(),
+            lp_lock_info.clone(),
+            system_program_info.clone(),
+        ],
+        &[&[
+            amm_info.key.as_ref(),
+            user_owner_info.key.as_ref(),
+            lp_lock_seed,
+            &[bump_seed],
+        ]],
+    )?;
+    if lock_lp_token_info.data_is_empty() {
+        Invokers::create_ata_spl_token(
+            lock_lp_token_info.clone(),
+            user_owner_info.clone(),
+            amm_authority_info.clone(),
+            amm_lp_mint_info.clone(),
+            token_program_info.clone(),
+            ata_program_info.clone(),
+            system_program_info.clone(),
+        )?;
+    }
+    Invokers::token_transfer(
+        program_id,
+        token_program_info.clone(),
+        user_lp_info.clone(),
+        lock_lp_token_info.clone(),
+        user_owner_info.clone(),
+        amount,
+        amm_lp_mint_info.clone(),
+        &[],
+    )?;
+
+    let mut lp_lock = LpLock::load_mut(&lp_lock_info)?;
+    lp_lock.amm_id = *amm_info.key;
+    lp_lock.owner = *user_owner_info.key;
+    lp_lock.locked_amount = amount;
+    lp_lock.unlock_start_time = unlock_start_time;
+    lp_lock.unlock_end_time = unlock_end_time;
+    lp_lock.liquidity_per_lp_x64 = liquidity_per_lp_x64;
+    lp_lock.permanent = permanent as u64;
+    lp_lock.snapshot_fee_growth(&extension);
+    msg!(
+        "lock lp amount:{}, unlock_start_time:{}, unlock_end_time:{}, permanent:{}",
+        amount,
+        unlock_start_time,
+        unlock_end_time,
+        permanent
+    );
+    Ok(())
+}
+
+pub fn process_claim_lp_lock_fee(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    if accounts.len() != CLAIM_LP_LOCK_FEE_ACCOUNT_LEN {
+        return Err(AmmError::WrongAccountsNumber.into());
+    }
+    let account_info_iter = &mut accounts.iter();
+    let token_program_info = next_account_info(account_info_iter)?;
+    let amm_info = next_account_info(account_info_iter)?;
+    let amm_authority_info = next_account_info(account_info_iter)?;
+    let amm_open_orders_info = next_account_info(account_info_iter)?;
+    let amm_coin_vault_info = next_account_info(account_info_iter)?;
+    let amm_pc_vault_info = next_account_info(account_info_iter)?;
+    let amm_lp_mint_info = next_account_info(account_info_iter)?;
+    let market_info = next_account_info(account_info_iter)?;
+    let market_event_queue_info = next_account_info(account_info_iter)?;
+    let lp_lock_info = next_account_info(account_info_iter)?;
+    let lock_lp_token_info = next_account_info(account_info_iter)?;
+    let user_lp_info = next_account_info(account_info_iter)?;
+    let user_owner_info = next_account_info(account_info_iter)?;
+
+    if !user_owner_info.is_signer {
+        return Err(AmmError::InvalidSignAccount.into());
+    }
+    if *token_program_info.key != spl_token::id() {
+        return Err(AmmError::InvalidSplTokenProgram.into());
+    }
+    let amm = AmmInfo::load_checked(&amm_info, program_id)?;
+    if *amm_authority_info.key != authority_id(program_id, AUTHORITY_AMM, amm.nonce as u8)? {
+        return Err(AmmError::InvalidProgramAddress.into());
+    }
+    if *amm_lp_mint_info.key != amm.lp_mint {
+        return Err(AmmError::InvalidPoolMint.into());
+    }
+    check_lock_lp_token_account(amm_authority_info, amm_lp_mint_info, lock_lp_token_info)?;
+    let mut lp_lock = LpLock::load_mut_checked(&lp_lock_info, program_id, amm_info.key)?;
+    if lp_lock.owner != *user_owner_info.key {
+        return Err(AmmError::InvalidOwner.into());
+    }
+    // permanent locks claim their fees with ClaimPermanentLockFee
+    if lp_lock.is_permanent() {
+        return Err(AmmError::InvalidLpLockAccount.into());
+    }
+
+    let liquidity_per_lp_x64 = calc_pool_liquidity_per_lp(
+        &amm,
+        amm_authority_info,
+        amm_open_orders_info,
+        amm_coin_vault_info,
+        amm_pc_vault_info,
+        market_info,
+        market_event_queue_info,
+    )?;
+    let fee_amount = Calculator::calc_lock_fee_lp(
+        lp_lock.locked_amount,
+        lp_lock.liquidity_per_lp_x64,
+        liquidity_per_lp_x64,
+    );
+    if fee_amount == 0 {
+        msg!("no fee to claim");
+        return Ok(());
+    }
+    Invokers::token_transfer_with_authority(
+        program_id,
+        token_program_info.clone(),
+        lock_lp_token_info.clone(),
+        user_lp_info.clone(),
+        amm_authority_info.clone(),
+        AUTHORITY_AMM,
+        amm.nonce as u8,
+        fee_amount,
+        amm_lp_mint_info.clone(),
+        &[],
+    )?;
+    lp_lock.locked_amount = lp_lock.locked_amount.checked_sub(fee_amount).unwrap();
+    lp_lock.claimed_fee_amount = lp_lock.claimed_fee_amount.checked_add(fee_amount).unwrap();
+    lp_lock.liquidity_per_lp_x64 = liquidity_per_lp_x64;
+    msg!("claim lp lock fee amount:{}", fee_amount);
+    Ok(())
+}
+
+pub fn process_unlock_lp(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    unlock: UnlockLpInstruction,
+) -> ProgramResult {
+    if accounts.len() != UNLOCK_LP_ACCOUNT_LEN {
+        return Err(AmmError::WrongAccountsNumber.into());
+    }
+    let account_info_iter = &mut accounts.iter();
+    let token_program_info = next_account_info(account_info_iter)?;
+    let amm_info = next_account_info(account_info_iter)?;
+    let amm_authority_info = next_account_info(account_info_iter)?;
+    let amm_lp_mint_info = next_account_info(account_info_iter)?;
+    let lp_lock_info = next_account_info(account_info_iter)?;
+    let lock_lp_token_info = next_account_info(account_info_iter)?;
+    let user_lp_info = next_account_info(account_info_iter)?;
+    let user_owner_info = next_account_info(account_info_iter)?;
+
+    if !user_owner_info.is_signer {
+        return Err(AmmError::InvalidSignAccount.into());
+    }
+    if *token_program_info.key != spl_token::id() {
+        return Err(AmmError::InvalidSplTokenProgram.into());
+    }
+    let amm = AmmInfo::load_checked(&amm_info, program_id)?;
+    if *amm_authority_info.key != authority_id(program_id, AUTHORITY_AMM, amm.nonce as u8)? {
+        return Err(AmmError::InvalidProgramAddress.into());
+    }
+    if *amm_lp_mint_info.key != amm.lp_mint {
+        return Err(AmmError::InvalidPoolMint.into());
+    }
+    check_lock_lp_token_account(amm_authority_info, amm_lp_mint_info, lock_lp_token_info)?;
+    let mut lp_lock = LpLock::load_mut_checked(&lp_lock_info, program_id, amm_info.key)?;
+    if lp_lock.owner != *user_owner_info.key {
+        return Err(AmmError::InvalidOwner.into());
+    }
+    if unlock.amount == 0 {
+        return Err(AmmError::InvalidInput.into());
+    }
+    let withdrawable_amount = lp_lock.withdrawable_amount(Clock::get()?.unix_timestamp as u64);
+    if unlock.amount > withdrawable_amount {
+        msg!(
+            "unlock amount:{}, withdrawable amount:{}",
+            unlock.amount,
+            withdrawable_amount
+        );
+        return Err(AmmError::LpStillLocked.into());
+    }
+
+    Invokers::token_transfer_with_authority(
+        program_id,
+        token_program_info.clone(),
+        lock_lp_token_info.clone(),
+        user_lp_info.clone(),
+        amm_authority_info.clone(),
+        AUTHORITY_AMM,
+        amm.nonce as u8,
+        unlock.amount,
+        amm_lp_mint_info.clone(),
+        &[],
+    )?;
+    lp_lock.locked_amount = lp_lock.locked_amount.checked_sub(unlock.amount).unwrap();
+    lp_lock.withdrawn_amount = lp_lock.withdrawn_amount.checked_add(unlock.amount).unwrap();
+    // an empty lock is closed, its lamports go back to the owner
+    if lp_lock.locked_amount == 0 {
+        drop(lp_lock);
+        close_program_account(lp_lock_info, user_owner_info)?;
+    }
+    Ok(())
+}
+
+pub fn process_claim_permanent_lock_fee(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    if accounts.len() != CLAIM_PERMANENT_LOCK_FEE_ACCOUNT_LEN {
+        return Err(AmmError::WrongAccountsNumber.into());
+    }
+    let account_info_iter = &mut accounts.iter();
+    let token_program_info = next_account_info(account_info_iter)?;
+    let amm_info = next_account_info(account_info_iter)?;
+    let amm_authority_info = next_account_info(account_info_iter)?;
+    let amm_open_orders_info = next_account_info(account_info_iter)?;
+    let amm_target_orders_info = next_account_info(account_info_iter)?;
+    let amm_coin_vault_info = next_account_info(account_info_iter)?;
+    let amm_pc_vault_info = next_account_info(account_info_iter)?;
+    let amm_lp_mint_info = next_account_info(account_info_iter)?;
+    let amm_coin_mint_info = next_account_info(account_info_iter)?;
+    let amm_pc_mint_info = next_account_info(account_info_iter)?;
+    let market_info = next_account_info(account_info_iter)?;
+    let market_event_queue_info = next_account_info(account_info_iter)?;
+    let lp_lock_info = next_account_info(account_info_iter)?;
+    let lock_lp_token_info = next_account_info(account_info_iter)?;
+    let user_coin_info = next_account_info(account_info_iter)?;
+    let user_pc_info = next_account_info(account_info_iter)?;
+    let user_owner_info = next_account_info(account_info_iter)?;
+
+    if !user_owner_info.is_signer {
+        return Err(AmmError::InvalidSignAccount.into());
+    }
+    if *token_program_info.key != spl_token::id() {
+        return Err(AmmError::InvalidSplTokenProgram.into());
+    }
+    let (mut amm, extension) = AmmInfo::load_mut_checked_with_extension(&amm_info, program_id)?;
+    let mut target_orders =
+        TargetOrders::load_mut_checked(&amm_target_orders_info, program_id, amm_info.key)?;
+    if *amm_authority_info.key != authority_id(program_id, AUTHORITY_AMM, amm.nonce as u8)? {
+        return Err(AmmError::InvalidProgramAddress.into());
+    }
+    check_assert_eq!(
+        *amm_target_orders_info.key,
+        amm.target_orders,
+        "target_orders",
+        AmmError::InvalidTargetOrders
+    );
+    check_assert_eq!(
+        *amm_lp_mint_info.key,
+        amm.lp_mint,
+        "lp_mint",
+        AmmError::InvalidPoolMint
+    );
+    check_assert_eq!(
+        *amm_coin_mint_info.key,
+        amm.coin_vault_mint,
+        "coin_mint",
+        AmmError::InvalidCoinMint
+    );
+    check_assert_eq!(
+        *amm_pc_mint_info.key,
+        amm.pc_vault_mint,
+        "pc_mint",
+        AmmError::InvalidPCMint
+    );
+    if *user_coin_info.key == amm.coin_vault || *user_pc_info.key == amm.pc_vault {
+        return Err(AmmError::InvalidUserToken.into());
+    }
+    check_lock_lp_token_account(amm_authority_info, amm_lp_mint_info, lock_lp_token_info)?;
+    let mut lp_lock = LpLock::load_mut_checked(&lp_lock_info, program_id, amm_info.key)?;
+    if lp_lock.owner != *user_owner_info.key {
+        return Err(AmmError::InvalidOwner.into());
+    }
+    if !lp_lock.is_permanent() {
+        return Err(AmmError::InvalidLpLockAccount.into());
+    }
+
+    let (mut total_pc_without_take_pnl, mut total_coin_without_take_pnl) =
+        load_pool_totals_without_take_pnl(
+            &amm,
+            amm_authority_info,
+            amm_open_orders_info,
+            amm_coin_vault_info,
+            amm_pc_vault_info,
+            market_info,
+            market_event_queue_info,
+        )?;
+    let x1 = Calculator::normalize_decimal_v2(
+        total_pc_without_take_pnl,
+        amm.pc_decimals,
+        amm.sys_decimal_value,
+    );
+    let y1 = Calculator::normalize_decimal_v2(
+        total_coin_without_take_pnl,
+        amm.coin_decimals,
+        amm.sys_decimal_value,
+    );
+    let (delta_x, delta_y) = calc_take_pnl(
+        &target_orders,
+        &mut amm,
+        &mut total_pc_without_take_pnl,
+        &mut total_coin_without_take_pnl,
+        x1.as_u128().into(),
+        y1.as_u128().into(),
+    )?;
+
+    // lp share of the swap fees accrued to the locked lp since the last claim
+    let (coin_fee, pc_fee) = lp_lock.accrued_fees(&extension);
+    if coin_fee == 0 && pc_fee == 0 {
+        msg!("no fee to claim");
+        return Ok(());
+    }
+    lp_lock.snapshot_fee_growth(&extension);
+    // the lock gives up the lp worth the fees, so the locked liquidity stays the same
+    let burn_amount = Calculator::calc_lock_fee_burn_lp(
+        coin_fee,
+        pc_fee,
+        total_coin_without_take_pnl,
+        total_pc_without_take_pnl,
+        amm.lp_amount,
+    );
+    let amm_coin_vault = unpack_token_account(&amm_coin_vault_info, token_program_info.key)?;
+    let amm_pc_vault = unpack_token_account(&amm_pc_vault_info, token_program_info.key)?;
+    if burn_amount >= lp_lock.locked_amount
+        || coin_fee > amm_coin_vault.amount
+        || pc_fee > amm_pc_vault.amount
+    {
+        return Err(AmmError::InsufficientFunds.into());
+    }
+
+    if coin_fee != 0 {
+        Invokers::token_transfer_with_authority(
+            program_id,
+            token_program_info.clone(),
+            amm_coin_vault_info.clone(),
+            user_coin_info.clone(),
+            amm_authority_info.clone(),
+            AUTHORITY_AMM,
+            amm.nonce as u8,
+            coin_fee,
+            amm_coin_mint_info.clone(),
+            &[],
+        )?;
+    }
+    if pc_fee != 0 {
+        Invokers::token_transfer_with_authority(
+            program_id,
+            token_program_info.clone(),
+            amm_pc_vault_info.clone(),
+            user_pc_info.clone(),
+            amm_authority_info.clone(),
+            AUTHORITY_AMM,
+            amm.nonce as u8,
+            pc_fee,
+            amm_pc_mint_info.clone(),
+            &[],
+        )?;
+    }
+    if burn_amount != 0 {
+        Invokers::token_burn_with_authority(
+            token_program_info.clone(),
+            lock_lp_token_info.clone(),
+            amm_lp_mint_info.clone(),
+            amm_authority_info.clone(),
+            AUTHORITY_AMM,
+            amm.nonce as u8,
+            burn_amount,
+        )?;
+    }
+    amm.lp_amount = amm.lp_amount.checked_sub(burn_amount).unwrap();
+    lp_lock.locked_amount = lp_lock.locked_amount.checked_sub(burn_amount).unwrap();
+    lp_lock.claimed_fee_amount = lp_lock.claimed_fee_amount.checked_add(burn_amount).unwrap();
+
+    target_orders.calc_pnl_x = x1
+        .checked_sub(Calculator::normalize_decimal_v2(
+            pc_fee,
+            amm.pc_decimals,
+            amm.sys_decimal_value,
+        ))
+        .unwrap()
+        .checked_sub(U128::from(delta_x))
+        .unwrap()
+        .as_u128();
+    target_orders.calc_pnl_y = y1
+        .checked_sub(Calculator::normalize_decimal_v2(
+            coin_fee,
+            amm.coin_decimals,
+            amm.sys_decimal_value,
+        ))
+        .unwrap()
+        .checked_sub(U128::from(delta_y))
+        .unwrap()
+        .as_u128();
+    msg!(
+        "claim permanent lock fee coin:{}, pc:{}, burn lp:{}",
+        coin_fee,
+        pc_fee,
+        burn_amount
+    );
+    amm.recent_epoch = Clock::get()?.epoch;
+    Ok(())
+}
@@ -8,19 +8,46 @@ use solana_program::{
 use crate::{
     error::AmmError,
     invokers::Invokers,
-    state::AmmInfo,
+    state::{
+        AmmInfo, AmmResetFlag, AmmRole, AmmState, MarketBackend, TargetOrders, MAX_ORDER_LIMIT,
+    },
 };
-use crate::process::constants::AUTHORITY_AMM;
-use crate::process::helpers::{authority_id, load_serum_market_order};
+use crate::check_assert_eq;
+use crate::log::log_keys_mismatch;
+use crate::process::constants::{get_openbook_program_id, AUTHORITY_AMM, OPEN_ORDER_ASSOCIATED_SEED};
+use crate::openbook_v2::{MarketV2, OpenOrdersIndexerV2};
+use crate::process::helpers::{
+    authority_id, cancel_amm_orders_and_settle, check_amm_role, get_amm_orders, identity,
+    load_serum_market_order, market_backend,
+};
+use crate::process::initialize::{check_pool_account, create_pool_account};
+use serum_dex::state::{MarketState, ToAlignedBytes};
+use std::mem::size_of;
+
+/// [token_program_info, system_program_info, rent_info, amm_info, amm_authority_info, amm_open_orders_info, amm_coin_vault_info, amm_pc_vault_info, amm_target_orders_info, market_program_info, market_info, market_bids_info, market_asks_info, market_event_queue_info, market_coin_vault_info, market_pc_vault_info, market_vault_signer, new_amm_open_orders_info, new_market_program_info, new_market_info, admin_info, amm_config_info] = 22 accounts
+/// + the open orders indexer of the amm authority when the new market is an OpenBook v2 market
+const MIGRATE_TO_OPENBOOK_ACCOUNT_LEN: usize = 22;
 
+/// Rebinds the pool to a new OpenBook v1 or v2 market: cancels and settles every amm order
+/// on the old market, closes the old open orders, opens the new ones and takes the new lot
+/// sizes. This is how a pool is bound to an OpenBook v2 market, Initialize2 only takes v1
 pub fn process_migrate_to_openbook(
     program_id: &Pubkey,
     accounts: &[AccountInfo],
 ) -> ProgramResult {
+    if accounts.len() < MIGRATE_TO_OPENBOOK_ACCOUNT_LEN {
+        return Err(AmmError::WrongAccountsNumber.into());
+    }
     let account_info_iter = &mut accounts.iter();
+    let token_program_info = next_account_info(account_info_iter)?;
+    let system_program_info = next_account_info(account_info_iter)?;
+    let rent_info = next_account_info(account_info_iter)?;
     let amm_info = next_account_info(account_info_iter)?;
     let amm_authority_info = next_account_info(account_info_iter)?;
     let amm_open_orders_info = next_account_info(account_info_iter)?;
+    let amm_coin_vault_info = next_account_info(account_info_iter)?;
+    let amm_pc_vault_info = next_account_info(account_info_iter)?;
+    let amm_target_orders_info = next_account_info(account_info_iter)?;
     let market_program_info = next_account_info(account_info_iter)?;
     let market_info = next_account_info(account_info_iter)?;
     let market_bids_info = next_account_info(account_info_iter)?;
@@ -29,13 +56,34 @@ pub fn process_migrate_to_openbook(
     let market_coin_vault_info = next_account_info(account_info_iter)?;
     let market_pc_vault_info = next_account_info(account_info_iter)?;
     let market_vault_signer = next_account_info(account_info_iter)?;
-    let token_program_info = next_account_info(account_info_iter)?;
-    let user_wallet_info = next_account_info(account_info_iter)?;
+    let new_amm_open_orders_info = next_account_info(account_info_iter)?;
+    let new_market_program_info = next_account_info(account_info_iter)?;
+    let new_market_info = next_account_info(account_info_iter)?;
+    let admin_info = next_account_info(account_info_iter)?;
+    let amm_config_info = next_account_info(account_info_iter)?;
+    let new_open_orders_indexer_info = next_account_info(account_info_iter).ok();
 
-    if !user_wallet_info.is_signer {
+    if !admin_info.is_signer {
         return Err(AmmError::InvalidSignAccount.into());
     }
 
+    // Check token program and sysvars
+    if *token_program_info.key != spl_token::id() {
+        return Err(AmmError::InvalidSplTokenProgram.into());
+    }
+    if *system_program_info.key != solana_program::system_program::id()
+        || *rent_info.key != solana_program::sysvar::rent::id()
+    {
+        return Err(AmmError::InvalidSysProgramAddress.into());
+    }
+
+    check_amm_role(
+        program_id,
+        AmmRole::PoolAdmin,
+        admin_info,
+        amm_info,
+        amm_config_info,
+    )?;
     let mut amm = AmmInfo::load_mut_checked(&amm_info, program_id)?;
 
     // Check authority
@@ -44,128 +92,220 @@ pub fn process_migrate_to_openbook(
         return Err(AmmError::InvalidProgramAddress.into());
     }
 
-    // Check token program
-    if *token_program_info.key != spl_token::id() {
-        return Err(AmmError::InvalidSplTokenProgram.into());
+    // Check amm and old market accounts
+    check_assert_eq!(
+        *amm_coin_vault_info.key,
+        amm.coin_vault,
+        "coin_vault",
+        AmmError::InvalidCoinVault
+    );
+    check_assert_eq!(
+        *amm_pc_vault_info.key,
+        amm.pc_vault,
+        "pc_vault",
+        AmmError::InvalidPCVault
+    );
+    check_assert_eq!(
+        *market_program_info.key,
+        amm.market_program,
+        "market_program",
+        AmmError::InvalidMarketProgram
+    );
+    check_assert_eq!(*market_info.key, amm.market, "market", AmmError::InvalidMarket);
+    if *amm_target_orders_info.key != amm.target_orders {
+        return Err(AmmError::InvalidTargetOrders.into());
     }
-
-    // Check if market program is OpenBook
-    if *market_program_info.key != solana_program::pubkey!("9xQeWvG816bUx9EPjHmaT23yvVM2ZWbrrpZb9PusVFin") {
+    // Only pools on an OpenBook v1 market can be migrated
+    if market_backend(&amm.market_program)? != MarketBackend::OpenBookV1 {
         return Err(AmmError::InvalidMarketProgram.into());
     }
 
-    // Check market accounts
-    if *market_info.key != amm.market {
+    // The new market must be an OpenBook v1 or v2 market of the pool mints
+    let new_backend = market_backend(new_market_program_info.key)?;
+    if new_backend == MarketBackend::OpenBookV1 {
+        check_assert_eq!(
+            *new_market_program_info.key,
+            get_openbook_program_id()?,
+            "new_market_program",
+            AmmError::InvalidMarketProgram
+        );
+    }
+    if *new_market_info.key == amm.market {
         return Err(AmmError::InvalidMarket.into());
     }
-    if *amm_open_orders_info.key != amm.open_orders {
-        return Err(AmmError::InvalidOpenOrders.into());
+    let (new_coin_lot_size, new_pc_lot_size) = match new_backend {
+        MarketBackend::OpenBookV1 => {
+            let new_market_state =
+                MarketState::load_checked(new_market_info, new_market_program_info.key, false)?;
+            if identity(new_market_state.coin_mint) != amm.coin_vault_mint.to_aligned_bytes() {
+                return Err(AmmError::InvalidCoinMint.into());
+            }
+            if identity(new_market_state.pc_mint) != amm.pc_vault_mint.to_aligned_bytes() {
+                return Err(AmmError::InvalidPCMint.into());
+            }
+            (new_market_state.coin_lot_size, new_market_state.pc_lot_size)
+        }
+        MarketBackend::OpenBookV2 => {
+            let new_market = MarketV2::load_checked(new_market_info, new_market_program_info.key)?;
+            if new_market.base_mint != amm.coin_vault_mint {
+                return Err(AmmError::InvalidCoinMint.into());
+            }
+            if new_market.quote_mint != amm.pc_vault_mint {
+                return Err(AmmError::InvalidPCMint.into());
+            }
+            (new_market.base_lot_size, new_market.quote_lot_size)
+        }
+    };
+    // v1 open orders are derived from the new market like Initialize2 does, v2 open orders are
+    // numbered by the open orders indexer of the amm authority
+    let new_open_orders_bump = match new_backend {
+        MarketBackend::OpenBookV1 => check_pool_account(
+            program_id,
+            new_amm_open_orders_info,
+            new_market_info.key,
+            OPEN_ORDER_ASSOCIATED_SEED,
+        )?,
+        MarketBackend::OpenBookV2 => {
+            let indexer_info =
+                new_open_orders_indexer_info.ok_or(AmmError::WrongAccountsNumber)?;
+            let indexer = OpenOrdersIndexerV2::load_or_default(
+                indexer_info,
+                new_market_program_info.key,
+                &authority,
+            )?;
+            check_assert_eq!(
+                *new_amm_open_orders_info.key,
+                indexer.next_open_orders_account(new_market_program_info.key, &authority)?,
+                "new_open_orders",
+                AmmError::InvalidOpenOrders
+            );
+            // the v2 program derives and creates the account itself
+            0
+        }
+    };
+
+    // Cancel every amm order on the old market and settle the funds back to the vaults
+    {
+        let (market_state, open_orders) = load_serum_market_order(
+            market_info,
+            amm_open_orders_info,
+            amm_authority_info,
+            &amm,
+            false,
+        )?;
+        let bids_orders = market_state.load_bids_checked(&market_bids_info)?;
+        let asks_orders = market_state.load_asks_checked(&market_asks_info)?;
+        let (bids, asks) = get_amm_orders(&open_orders, bids_orders, asks_orders)?;
+        cancel_amm_orders_and_settle(
+            market_program_info,
+            market_info,
+            market_bids_info,
+            market_asks_info,
+            amm_open_orders_info,
+            amm_authority_info,
+            market_event_queue_info,
+            market_coin_vault_info,
+            market_pc_vault_info,
+            amm_coin_vault_info,
+            amm_pc_vault_info,
+            market_vault_signer,
+            token_program_info,
+            None,
+            &bids,
+            &asks,
+            amm.nonce as u8,
+        )?;
     }
 
-    // Load market state to verify it's a valid OpenBook market
-    let (market_state, open_orders) = load_serum_market_order(
-        market_info,
-        amm_open_orders_info,
-        amm_authority_info,
-        &amm,
-        false,
+    // Close the old open orders, fills still waiting in the event queue keep it open
+    // until the market is cranked
+    Invokers::invoke_dex_close_open_orders(
+        market_program_info.clone(),
+        amm_open_orders_info.clone(),
+        amm_authority_info.clone(),
+        admin_info.clone(),
+        market_info.clone(),
+        AUTHORITY_AMM,
+        amm.nonce as u8,
     )?;
 
-    // Cancel all existing orders
-    let bids_orders = market_state.load_bids_checked(&market_bids_info)?;
-    let asks_orders = market_state.load_asks_checked(&market_asks_info)?;
-    let (bids, asks) = crate::process::helpers::get_amm_orders(&open_orders, bids_orders, asks_orders)?;
-
-    // Cancel all bids
-    if !bids.is_empty() {
-        let mut amm_order_ids_vec = Vec::new();
-        let mut order_ids = [0u64; 8];
-        let mut count = 0;
-        
-        for order in bids.into_iter() {
-            order_ids[count] = order.client_order_id();
-            count += 1;
-            if count == 8 {
-                amm_order_ids_vec.push(order_ids);
-                order_ids = [0u64; 8];
-                count = 0;
-            }
-        }
-        if count != 0 {
-            amm_order_ids_vec.push(order_ids);
-        }
-        
-        for ids in amm_order_ids_vec.iter() {
-            Invokers::invoke_dex_cancel_orders_by_client_order_ids(
-                market_program_info.clone(),
-                market_info.clone(),
-                market_bids_info.clone(),
-                market_asks_info.clone(),
-                amm_open_orders_info.clone(),
+    // Open the orders on the new market, paid by the admin
+    match new_backend {
+        MarketBackend::OpenBookV1 => {
+            create_pool_account(
+                admin_info,
+                new_amm_open_orders_info,
+                system_program_info,
+                size_of::<serum_dex::state::OpenOrders>() + 12,
+                new_market_program_info.key,
+                &[
+                    program_id.as_ref(),
+                    new_market_info.key.as_ref(),
+                    OPEN_ORDER_ASSOCIATED_SEED,
+                    &[new_open_orders_bump],
+                ],
+            )?;
+            Invokers::invoke_dex_init_open_orders(
+                new_market_program_info.clone(),
+                new_amm_open_orders_info.clone(),
                 amm_authority_info.clone(),
-                market_event_queue_info.clone(),
+                new_market_info.clone(),
+                rent_info.clone(),
                 AUTHORITY_AMM,
                 amm.nonce as u8,
-                *ids,
             )?;
         }
-    }
-
-    // Cancel all asks
-    if !asks.is_empty() {
-        let mut amm_order_ids_vec = Vec::new();
-        let mut order_ids = [0u64; 8];
-        let mut count = 0;
-        
-        for order in asks.into_iter() {
-            order_ids[count] = order.client_order_id();
-            count += 1;
-            if count == 8 {
-                amm_order_ids_vec.push(order_ids);
-                order_ids = [0u64; 8];
-                count = 0;
+        MarketBackend::OpenBookV2 => {
+            let indexer_info =
+                new_open_orders_indexer_info.ok_or(AmmError::WrongAccountsNumber)?;
+            if indexer_info.data_is_empty() {
+                Invokers::invoke_openbook_v2_create_open_orders_indexer(
+                    new_market_program_info.clone(),
+                    admin_info.clone(),
+                    amm_authority_info.clone(),
+                    indexer_info.clone(),
+                    system_program_info.clone(),
+                    AUTHORITY_AMM,
+                    amm.nonce as u8,
+                )?;
             }
-        }
-        if count != 0 {
-            amm_order_ids_vec.push(order_ids);
-        }
-        
-        for ids in amm_order_ids_vec.iter() {
-            Invokers::invoke_dex_cancel_orders_by_client_order_ids(
-                market_program_info.clone(),
-                market_info.clone(),
-                market_bids_info.clone(),
-                market_asks_info.clone(),
-                amm_open_orders_info.clone(),
+            Invokers::invoke_openbook_v2_create_open_orders_account(
+                new_market_program_info.clone(),
+                admin_info.clone(),
                 amm_authority_info.clone(),
-                market_event_queue_info.clone(),
+                indexer_info.clone(),
+                new_amm_open_orders_info.clone(),
+                new_market_info.clone(),
+                system_program_info.clone(),
                 AUTHORITY_AMM,
                 amm.nonce as u8,
-                *ids,
             )?;
         }
     }
 
-    // Settle funds
-    Invokers::invoke_dex_settle_funds(
-        market_program_info.clone(),
-        market_info.clone(),
-        amm_open_orders_info.clone(),
-        amm_authority_info.clone(),
-        market_coin_vault_info.clone(),
-        market_pc_vault_info.clone(),
-        market_coin_vault_info.clone(),
-        market_pc_vault_info.clone(),
-        market_vault_signer.clone(),
-        token_program_info.clone(),
-        None,
-        AUTHORITY_AMM,
-        amm.nonce as u8,
-    )?;
+    // Rebind the pool, the ladder is planned again against the new market
+    amm.open_orders = *new_amm_open_orders_info.key;
+    amm.market = *new_market_info.key;
+    amm.market_program = *new_market_program_info.key;
+    amm.set_lot_sizes(new_coin_lot_size, new_pc_lot_size)?;
+    amm.state = AmmState::IdleState.into_u64();
+    amm.reset_flag = AmmResetFlag::ResetNo.into_u64();
 
-    // Update market program to OpenBook
-    amm.market_program = solana_program::pubkey!("9xQeWvG816bUx9EPjHmaT23yvVM2ZWbrrpZb9PusVFin");
+    let mut target_orders =
+        TargetOrders::load_mut_checked(&amm_target_orders_info, program_id, amm_info.key)?;
+    target_orders.replace_buy_client_id = [0u64; MAX_ORDER_LIMIT];
+    target_orders.replace_sell_client_id = [0u64; MAX_ORDER_LIMIT];
+    target_orders.valid_buy_order_num = 0;
+    target_orders.valid_sell_order_num = 0;
+    target_orders.plan_orders_cur = 0;
+    target_orders.place_orders_cur = 0;
 
-    msg!("Successfully migrated to OpenBook");
+    let (market, coin_lot_size, pc_lot_size) = (amm.market, amm.coin_lot_size, amm.pc_lot_size);
+    msg!(
+        "migrated to market:{}, coin_lot_size:{}, pc_lot_size:{}",
+        market,
+        coin_lot_size,
+        pc_lot_size
+    );
     Ok(())
-} 
\ No newline at end of file
+}
@@ -0,0 +1,66 @@
+//! Handles the migrate_pool_account instruction logic for Raydium AMM
+use crate::{
+    error::AmmError,
+    state::{AmmInfo, AMM_INFO_VERSION_CURRENT},
+};
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    msg,
+    program::invoke,
+    pubkey::Pubkey,
+    rent::Rent,
+    sysvar::Sysvar,
+};
+use solana_system_interface::instruction as system_instruction;
+use std::mem::size_of;
+
+/// [amm_info, payer_info, system_program_info] = 3 accounts
+const MIGRATE_POOL_ACCOUNT_LEN: usize = 3;
+
+pub fn process_migrate_pool_account(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    if accounts.len() != MIGRATE_POOL_ACCOUNT_LEN {
+        return Err(AmmError::WrongAccountsNumber.into());
+    }
+    let account_info_iter = &mut accounts.iter();
+    let amm_info = next_account_info(account_info_iter)?;
+    let payer_info = next_account_info(account_info_iter)?;
+    let system_program_info = next_account_info(account_info_iter)?;
+
+    if !payer_info.is_signer {
+        return Err(AmmError::InvalidSignAccount.into());
+    }
+    if *system_program_info.key != solana_program::system_program::id() {
+        return Err(AmmError::InvalidSystemProgram.into());
+    }
+    let version = AmmInfo::load_checked(&amm_info, program_id)?.version();
+    if version >= AMM_INFO_VERSION_CURRENT {
+        msg!("amm account is already version:{}", version);
+        return Ok(());
+    }
+
+    let data_len = AmmInfo::data_len(AMM_INFO_VERSION_CURRENT).unwrap();
+    let lamports = Rent::get()?
+        .minimum_balance(data_len)
+        .saturating_sub(amm_info.lamports());
+    if lamports != 0 {
+        invoke(
+            &system_instruction::transfer(payer_info.key, amm_info.key, lamports),
+            &[
+                payer_info.clone(),
+                amm_info.clone(),
+                system_program_info.clone(),
+            ],
+        )?;
+    }
+    amm_info.resize(data_len)?;
+
+    // every version so far only appends to the layout, the appended part starts zeroed
+    let old_data_len = AmmInfo::data_len(version).unwrap();
+    let mut data = amm_info.try_borrow_mut_data()?;
+    data[old_data_len..].fill(0);
+    bytemuck::from_bytes_mut::<AmmInfo>(&mut data[..size_of::<AmmInfo>()]).version =
+        AMM_INFO_VERSION_CURRENT;
+    msg!("migrate amm account version:{} to {}", version, AMM_INFO_VERSION_CURRENT);
+    Ok(())
+}
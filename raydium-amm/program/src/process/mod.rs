@@ -1,12 +1,19 @@
 pub mod admin;
+pub mod allowlist;
 pub mod args;
+pub mod close_pool;
 pub mod config;
 pub mod constants;
 pub mod deposit;
+pub mod deposit_single_sided;
+pub mod flash_loan;
 pub mod helpers;
 pub mod initialize;
+pub mod lp_lock;
 pub mod migrate;
+pub mod migrate_pool_account;
 pub mod monitor;
+pub mod oracle;
 pub mod set_params;
 pub mod simulate;
 pub mod swap;
@@ -14,6 +21,7 @@ pub mod token2022;
 pub mod whitelist;
 pub mod withdraw;
 pub mod withdraw_pnl;
+pub mod withdraw_single_sided;
 pub mod withdraw_srm;
 
 // Re-export main entrypoints for easier access
@@ -32,3 +40,18 @@ pub use admin::process_admin_cancel_orders;
 pub use config::process_create_config;
 pub use config::process_update_config;
 pub use whitelist::process_update_hook_whitelist; 
+pub use oracle::process_initialize_observation;
+pub use oracle::process_record_observation;
+pub use oracle::process_get_twap;
+pub use flash_loan::process_flash_loan;
+pub use deposit_single_sided::process_deposit_single_sided;
+pub use withdraw_single_sided::process_withdraw_single_sided;
+pub use lp_lock::process_lock_lp;
+pub use lp_lock::process_claim_lp_lock_fee;
+pub use lp_lock::process_unlock_lp;
+pub use lp_lock::process_lock_lp_permanent;
+pub use lp_lock::process_claim_permanent_lock_fee;
+pub use allowlist::process_initialize_allowlist;
+pub use allowlist::process_register_allowlist_wallet;
+pub use close_pool::process_close_pool;
+pub use migrate_pool_account::process_migrate_pool_account;
@@ -1,25 +1,175 @@
 //! Handles the monitor_step instruction logic for Raydium AMM
 use solana_program::{
     account_info::{next_account_info, AccountInfo},
+    clock::Clock,
     entrypoint::ProgramResult,
+    program_error::ProgramError,
     pubkey::Pubkey,
     msg,
+    sysvar::Sysvar,
 };
 use crate::{
     error::AmmError,
     instruction::MonitorStepInstruction,
-    state::{AmmInfo, TargetOrders},
+    invokers::Invokers,
+    state::{
+        AmmInfo, AmmResetFlag, AmmState, AmmStatus, LadderStrategy, MarketBackend, TargetOrder,
+        TargetOrders, AMM_INFO_VERSION_V2, MAX_ORDER_LIMIT, TEN_THOUSAND,
+    },
     math::{Calculator, U256, U128},
 };
-use crate::process::constants::AUTHORITY_AMM;
-use crate::process::helpers::{authority_id, load_serum_market_order, get_amm_orders, unpack_token_account};
+use crate::check_assert_eq;
+use crate::log::log_keys_mismatch;
+use crate::process::constants::{get_referrer_pc_wallet_id, AUTHORITY_AMM};
+use crate::openbook_v2::OpenOrderV2;
+use crate::process::helpers::{
+    authority_id, calc_take_pnl, compound_pnl, load_serum_market_order, get_amm_orders,
+    unpack_token_account, load_openbook_v2_market_order, market_backend,
+};
+use serum_dex::critbit::LeafNode;
+use serum_dex::matching::{OrderType, Side};
+use std::num::NonZeroU64;
+
+/// [token_program_info, rent_info, clock_info, amm_info, amm_authority_info, amm_open_orders_info, amm_target_orders_info, amm_coin_vault_info, amm_pc_vault_info, market_program_info, market_info, market_coin_vault_info, market_pc_vault_info, market_vault_signer, market_request_queue_info, market_event_queue_info, market_bids_info, market_asks_info] = 18 accounts
+const MONITOR_STEP_ACCOUNT_LEN: usize = 18;
+
+/// Number of ladder levels planned and placed on each side of the book
+pub fn ladder_levels(amm: &AmmInfo) -> usize {
+    std::cmp::min(amm.order_num, MAX_ORDER_LIMIT as u64) as usize
+}
+
+/// Plans up to plan_order_limit levels of the strategy's ladder around the price of
+/// (plan_x_buy, plan_y_buy), starting at target.plan_orders_cur.
+/// Level i sits depth% * w[i + 1] / w[levels] away from the price, at least min_separate,
+/// and holds the volume the pool would trade between level i - 1 and level i.
+/// Returns true once every level is planned.
+pub fn plan_orders(
+    amm: &AmmInfo,
+    target: &mut TargetOrders,
+    strategy: LadderStrategy,
+    plan_order_limit: u16,
+) -> bool {
+    let levels = ladder_levels(amm);
+    let (x, y) = (target.plan_x_buy, target.plan_y_buy);
+    if levels == 0 || x == 0 || y == 0 {
+        target.buy_orders = [TargetOrder::default(); 50];
+        target.sell_orders = [TargetOrder::default(); 50];
+        target.plan_orders_cur = levels as u64;
+        return true;
+    }
+    let price = Calculator::to_u64(
+        U128::from(x)
+            .checked_mul(amm.sys_decimal_value.into())
+            .unwrap()
+            .checked_div(y.into())
+            .unwrap()
+            .as_u128(),
+    )
+    .unwrap_or(u64::MAX);
+    let weights = strategy.weights(levels);
+    let min_separate_bps = amm
+        .fees
+        .min_separate_numerator
+        .checked_mul(TEN_THOUSAND)
+        .unwrap()
+        .checked_div(amm.fees.min_separate_denominator)
+        .unwrap();
+    let level_price = |i: usize| -> (u64, u64) {
+        let offset_bps = std::cmp::min(
+            std::cmp::max(
+                min_separate_bps,
+                amm.depth.saturating_mul(100).saturating_mul(weights[i + 1]) / weights[levels],
+            ),
+            TEN_THOUSAND - 1,
+        );
+        let buy_price = (price as u128) * ((TEN_THOUSAND - offset_bps) as u128)
+            / TEN_THOUSAND as u128;
+        let sell_price = (price as u128) * ((TEN_THOUSAND + offset_bps) as u128)
+            / TEN_THOUSAND as u128;
+        (
+            buy_price as u64,
+            Calculator::to_u64(sell_price).unwrap_or(u64::MAX),
+        )
+    };
+    // a single level never takes more than vol_max_cut_ratio of the pool
+    let (buy_cap, sell_cap) = if amm.vol_max_cut_ratio == 0 {
+        (u64::MAX, u64::MAX)
+    } else {
+        (
+            Calculator::get_max_buy_size_at_price(price, x, 0, amm)
+                .saturating_mul(amm.vol_max_cut_ratio)
+                / TEN_THOUSAND,
+            Calculator::to_u64(y).unwrap_or(u64::MAX).saturating_mul(amm.vol_max_cut_ratio)
+                / TEN_THOUSAND,
+        )
+    };
+
+    let start = target.plan_orders_cur as usize;
+    let end = std::cmp::min(levels, start + plan_order_limit as usize);
+    for i in start..end {
+        let (buy_price, sell_price) = level_price(i);
+        let (buy_before, sell_before) = if i == 0 {
+            (0, 0)
+        } else {
+            let (prev_buy_price, prev_sell_price) = level_price(i - 1);
+            (
+                Calculator::get_max_buy_size_at_price(prev_buy_price, x, y, amm),
+                Calculator::get_max_sell_size_at_price(prev_sell_price, x, y, amm),
+            )
+        };
+        let buy_vol = if buy_price == 0 {
+            0
+        } else {
+            Calculator::get_max_buy_size_at_price(buy_price, x, y, amm).saturating_sub(buy_before)
+        };
+        let sell_vol = Calculator::get_max_sell_size_at_price(sell_price, x, y, amm)
+            .saturating_sub(sell_before);
+        target.buy_orders[i] = TargetOrder {
+            price: buy_price,
+            vol: std::cmp::min(buy_vol, buy_cap),
+        };
+        target.sell_orders[i] = TargetOrder {
+            price: sell_price,
+            vol: std::cmp::min(sell_vol, sell_cap),
+        };
+    }
+    target.plan_orders_cur = end as u64;
+    end == levels
+}
+
+/// Whether the pool price moved by min_separate or more since the ladder was planned
+pub fn need_replan(amm: &AmmInfo, target: &TargetOrders, x: u128, y: u128) -> bool {
+    if target.valid_buy_order_num == 0 && target.valid_sell_order_num == 0 {
+        return true;
+    }
+    let (plan_x, plan_y) = (target.plan_x_buy, target.plan_y_buy);
+    if plan_x == 0 || plan_y == 0 || x == 0 || y == 0 {
+        return x != 0 && y != 0;
+    }
+    // |x / y - plan_x / plan_y| >= min_separate * plan_x / plan_y
+    let current = U256::from(x) * U256::from(plan_y);
+    let planned = U256::from(plan_x) * U256::from(y);
+    let diff = if current > planned {
+        current - planned
+    } else {
+        planned - current
+    };
+    diff * U256::from(amm.fees.min_separate_denominator)
+        >= planned * U256::from(amm.fees.min_separate_numerator)
+}
 
 pub fn process_monitor_step(
     program_id: &Pubkey,
     accounts: &[AccountInfo],
-    _monitor: MonitorStepInstruction,
+    monitor: MonitorStepInstruction,
 ) -> ProgramResult {
+    if accounts.len() < MONITOR_STEP_ACCOUNT_LEN {
+        return Err(AmmError::WrongAccountsNumber.into());
+    }
     let account_info_iter = &mut accounts.iter();
+    let token_program_info = next_account_info(account_info_iter)?;
+    let rent_info = next_account_info(account_info_iter)?;
+    let clock_info = next_account_info(account_info_iter)?;
     let amm_info = next_account_info(account_info_iter)?;
     let amm_authority_info = next_account_info(account_info_iter)?;
     let amm_open_orders_info = next_account_info(account_info_iter)?;
@@ -28,20 +178,39 @@ pub fn process_monitor_step(
     let amm_pc_vault_info = next_account_info(account_info_iter)?;
     let market_program_info = next_account_info(account_info_iter)?;
     let market_info = next_account_info(account_info_iter)?;
-    let market_bids_info = next_account_info(account_info_iter)?;
-    let market_asks_info = next_account_info(account_info_iter)?;
-    let market_event_queue_info = next_account_info(account_info_iter)?;
     let market_coin_vault_info = next_account_info(account_info_iter)?;
     let market_pc_vault_info = next_account_info(account_info_iter)?;
     let market_vault_signer = next_account_info(account_info_iter)?;
-    let token_program_info = next_account_info(account_info_iter)?;
-    let user_wallet_info = next_account_info(account_info_iter)?;
+    let market_request_queue_info = next_account_info(account_info_iter)?;
+    let market_event_queue_info = next_account_info(account_info_iter)?;
+    let market_bids_info = next_account_info(account_info_iter)?;
+    let market_asks_info = next_account_info(account_info_iter)?;
+    // the amm program id stands in for a missing optional account
+    let srm_token_info = next_account_info(account_info_iter)
+        .ok()
+        .filter(|info| info.key != program_id);
+    let referrer_pc_info = next_account_info(account_info_iter)
+        .ok()
+        .filter(|info| info.key != program_id);
+    let crank_reward_pc_info = next_account_info(account_info_iter).ok();
+
+    // Check token program
+    if *token_program_info.key != spl_token::id() {
+        return Err(AmmError::InvalidSplTokenProgram.into());
+    }
 
-    if !user_wallet_info.is_signer {
-        return Err(AmmError::InvalidSignAccount.into());
+    // Check sysvars
+    if *rent_info.key != solana_program::sysvar::rent::id()
+        || *clock_info.key != solana_program::sysvar::clock::id()
+    {
+        return Err(AmmError::InvalidSysProgramAddress.into());
     }
 
+    let ladder_strategy = AmmInfo::load_ladder_strategy(&amm_info, program_id)?;
     let mut amm = AmmInfo::load_mut_checked(&amm_info, program_id)?;
+    if !AmmStatus::from_u64(amm.status).orderbook_permission() {
+        return Err(AmmError::InvalidStatus.into());
+    }
 
     // Check authority
     let authority = authority_id(program_id, AUTHORITY_AMM, amm.nonce as u8)?;
@@ -49,102 +218,692 @@ pub fn process_monitor_step(
         return Err(AmmError::InvalidProgramAddress.into());
     }
 
-    // Check token program
-    if *token_program_info.key != spl_token::id() {
-        return Err(AmmError::InvalidSplTokenProgram.into());
-    }
-
-    // Check market accounts
-    if *market_info.key != amm.market {
-        return Err(AmmError::InvalidMarket.into());
-    }
-    if *amm_open_orders_info.key != amm.open_orders {
-        return Err(AmmError::InvalidOpenOrders.into());
-    }
+    // Check amm and market accounts
+    check_assert_eq!(
+        *amm_coin_vault_info.key,
+        amm.coin_vault,
+        "coin_vault",
+        AmmError::InvalidCoinVault
+    );
+    check_assert_eq!(
+        *amm_pc_vault_info.key,
+        amm.pc_vault,
+        "pc_vault",
+        AmmError::InvalidPCVault
+    );
+    check_assert_eq!(
+        *market_program_info.key,
+        amm.market_program,
+        "market_program",
+        AmmError::InvalidMarketProgram
+    );
+    check_assert_eq!(*market_info.key, amm.market, "market", AmmError::InvalidMarket);
     if *amm_target_orders_info.key != amm.target_orders {
         return Err(AmmError::InvalidTargetOrders.into());
     }
 
-    // Load market state
-    let (market_state, open_orders) = load_serum_market_order(
-        market_info,
-        amm_open_orders_info,
-        amm_authority_info,
-        &amm,
-        false,
-    )?;
-
-    let bids_orders = market_state.load_bids_checked(&market_bids_info)?;
-    let asks_orders = market_state.load_asks_checked(&market_asks_info)?;
-    let (bids, asks) = get_amm_orders(&open_orders, bids_orders, asks_orders)?;
+    // The referrer receives part of the settled fees, it must belong to the referrer wallet
+    let referrer_pc_wallet = match referrer_pc_info {
+        Some(referrer_pc_info) => {
+            let referrer_pc_token =
+                unpack_token_account(referrer_pc_info, token_program_info.key)?;
+            check_assert_eq!(
+                referrer_pc_token.owner,
+                get_referrer_pc_wallet_id()?,
+                "referrer_pc_owner",
+                AmmError::InvalidOwner
+            );
+            Some(referrer_pc_info)
+        }
+        None => None,
+    };
 
     // Load target orders
-    let mut target_orders = TargetOrders::load_mut_checked(&amm_target_orders_info, program_id, amm_info.key)?;
+    let mut target_orders =
+        TargetOrders::load_mut_checked(&amm_target_orders_info, program_id, amm_info.key)?;
 
     // Get vault amounts
     let amm_coin_vault = unpack_token_account(&amm_coin_vault_info, token_program_info.key)?;
     let amm_pc_vault = unpack_token_account(&amm_pc_vault_info, token_program_info.key)?;
 
-    // Calculate total amounts
-    let (mut total_pc_without_take_pnl, mut total_coin_without_take_pnl) =
-        Calculator::calc_total_without_take_pnl(
-            amm_pc_vault.amount,
-            amm_coin_vault.amount,
-            &open_orders,
-            &amm,
-            &market_state,
-            &market_event_queue_info,
-            &amm_open_orders_info,
-        )?;
+    // Load market state, the amm orders on the book and the total amounts
+    let backend = market_backend(&amm.market_program)?;
+    let (bids, asks, pc_lot_size, (mut total_pc_without_take_pnl, mut total_coin_without_take_pnl)) =
+        match backend {
+            MarketBackend::OpenBookV1 => {
+                let (market_state, open_orders) = load_serum_market_order(
+                    market_info,
+                    amm_open_orders_info,
+                    amm_authority_info,
+                    &amm,
+                    false,
+                )?;
+                let bids_orders = market_state.load_bids_checked(&market_bids_info)?;
+                let asks_orders = market_state.load_asks_checked(&market_asks_info)?;
+                let (bids, asks) = get_amm_orders(&open_orders, bids_orders, asks_orders)?;
+                let totals = Calculator::calc_total_without_take_pnl(
+                    amm_pc_vault.amount,
+                    amm_coin_vault.amount,
+                    &open_orders,
+                    &amm,
+                    &market_state,
+                    &market_event_queue_info,
+                    &amm_open_orders_info,
+                )?;
+                (
+                    bids.iter().map(BookOrder::from_leaf).collect::<Vec<BookOrder>>(),
+                    asks.iter().map(BookOrder::from_leaf).collect::<Vec<BookOrder>>(),
+                    market_state.pc_lot_size,
+                    totals,
+                )
+            }
+            MarketBackend::OpenBookV2 => {
+                let (market_v2, open_orders) = load_openbook_v2_market_order(
+                    market_info,
+                    amm_open_orders_info,
+                    amm_authority_info,
+                    &amm,
+                )?;
+                let totals = Calculator::calc_total_without_take_pnl_openbook_v2(
+                    amm_pc_vault.amount,
+                    amm_coin_vault.amount,
+                    &open_orders,
+                    &amm,
+                    &market_v2,
+                )?;
+                let side_orders = |side: Side| -> Vec<BookOrder> {
+                    open_orders
+                        .orders
+                        .iter()
+                        .filter(|order| order.side == side)
+                        .map(BookOrder::from_open_order_v2)
+                        .collect()
+                };
+                (
+                    side_orders(Side::Bid),
+                    side_orders(Side::Ask),
+                    market_v2.quote_lot_size,
+                    totals,
+                )
+            }
+        };
 
-    // Process monitor step - calculate PnL
-    let total_pc_u256 = U256::from(total_pc_without_take_pnl);
-    let total_coin_u256 = U256::from(total_coin_without_take_pnl);
-    
-    let (delta_x, delta_y) = crate::process::helpers::calc_take_pnl(
-        &target_orders,
-        &mut amm,
-        &mut total_pc_without_take_pnl,
-        &mut total_coin_without_take_pnl,
-        total_pc_u256,
-        total_coin_u256,
-    )?;
+    let market = MarketAccounts {
+        backend,
+        program: market_program_info,
+        market: market_info,
+        coin_vault: market_coin_vault_info,
+        pc_vault: market_pc_vault_info,
+        vault_signer: market_vault_signer,
+        request_queue: market_request_queue_info,
+        event_queue: market_event_queue_info,
+        bids: market_bids_info,
+        asks: market_asks_info,
+        pc_lot_size,
+    };
+    let pool = PoolAccounts {
+        token_program: token_program_info,
+        rent: rent_info,
+        authority: amm_authority_info,
+        open_orders: amm_open_orders_info,
+        coin_vault: amm_coin_vault_info,
+        pc_vault: amm_pc_vault_info,
+        srm_token: srm_token_info,
+        referrer_pc_wallet,
+    };
+
+    // Turn the state machine around, each state consumes its own limit
+    loop {
+        match AmmState::from_u64(amm.state) {
+            AmmState::InvlidState => return Err(AmmError::InvalidStatus.into()),
+            AmmState::IdleState => {
+                if amm.reset_flag == AmmResetFlag::ResetYes.into_u64() {
+                    amm.state = AmmState::CancelAllOrdersState.into_u64();
+                    continue;
+                }
+                // Take the pnl, then replan against the pool without it
+                let x1 = Calculator::normalize_decimal_v2(
+                    total_pc_without_take_pnl,
+                    amm.pc_decimals,
+                    amm.sys_decimal_value,
+                );
+                let y1 = Calculator::normalize_decimal_v2(
+                    total_coin_without_take_pnl,
+                    amm.coin_decimals,
+                    amm.sys_decimal_value,
+                );
+                let (delta_x, delta_y) = calc_take_pnl(
+                    &target_orders,
+                    &mut amm,
+                    &mut total_pc_without_take_pnl,
+                    &mut total_coin_without_take_pnl,
+                    x1.as_u128().into(),
+                    y1.as_u128().into(),
+                )?;
+                target_orders.calc_pnl_x = x1
+                    .checked_sub(U128::from(delta_x))
+                    .unwrap()
+                    .as_u128();
+                target_orders.calc_pnl_y = y1
+                    .checked_sub(U128::from(delta_y))
+                    .unwrap()
+                    .as_u128();
 
-    // Update target orders PnL
-    crate::process::helpers::update_target_orders_pnl(
-        &mut target_orders,
-        U128::from(total_pc_without_take_pnl),
-        U128::from(total_coin_without_take_pnl),
-        total_pc_without_take_pnl,
-        total_coin_without_take_pnl,
-        delta_x,
-        delta_y,
-        &amm,
+                let x = Calculator::normalize_decimal_v2(
+                    total_pc_without_take_pnl,
+                    amm.pc_decimals,
+                    amm.sys_decimal_value,
+                )
+                .as_u128();
+                let y = Calculator::normalize_decimal_v2(
+                    total_coin_without_take_pnl,
+                    amm.coin_decimals,
+                    amm.sys_decimal_value,
+                )
+                .as_u128();
+                if !need_replan(&amm, &target_orders, x, y) {
+                    break;
+                }
+                target_orders.plan_x_buy = x;
+                target_orders.plan_y_buy = y;
+                target_orders.plan_x_sell = x;
+                target_orders.plan_y_sell = y;
+                target_orders.plan_orders_cur = 0;
+                amm.state = AmmState::PlanOrdersState.into_u64();
+            }
+            AmmState::PlanOrdersState => {
+                if !plan_orders(
+                    &amm,
+                    &mut target_orders,
+                    ladder_strategy,
+                    monitor.plan_order_limit,
+                ) {
+                    break;
+                }
+                target_orders.place_orders_cur = 0;
+                amm.state = AmmState::PlaceOrdersState.into_u64();
+            }
+            AmmState::PlaceOrdersState => {
+                if !place_orders(
+                    &mut amm,
+                    &mut target_orders,
+                    &market,
+                    &pool,
+                    &bids,
+                    &asks,
+                    monitor.place_order_limit,
+                )? {
+                    break;
+                }
+                amm.state = AmmState::PurgeOrderState.into_u64();
+            }
+            AmmState::CancelOrderState | AmmState::PurgeOrderState => {
+                if purge_orders(
+                    &amm,
+                    &target_orders,
+                    &market,
+                    &pool,
+                    &bids,
+                    &asks,
+                    monitor.cancel_order_limit,
+                )? {
+                    amm.state = AmmState::IdleState.into_u64();
+                }
+                break;
+            }
+            AmmState::CancelAllOrdersState => {
+                if !bids.is_empty() || !asks.is_empty() {
+                    let client_order_ids = bids
+                        .iter()
+                        .chain(asks.iter())
+                        .map(|order| order.client_order_id)
+                        .collect::<Vec<u64>>();
+                    cancel_orders_and_settle(&amm, &market, &pool, &client_order_ids, true)?;
+                }
+                target_orders.replace_buy_client_id = [0u64; MAX_ORDER_LIMIT];
+                target_orders.replace_sell_client_id = [0u64; MAX_ORDER_LIMIT];
+                target_orders.valid_buy_order_num = 0;
+                target_orders.valid_sell_order_num = 0;
+                amm.reset_flag = AmmResetFlag::ResetNo.into_u64();
+                amm.state = AmmState::IdleState.into_u64();
+                break;
+            }
+        }
+    }
+
+    let (state, plan_orders_cur, place_orders_cur) = (
+        amm.state,
+        target_orders.plan_orders_cur,
+        target_orders.place_orders_cur,
+    );
+    msg!(
+        "monitor step state:{}, planned:{}, placed:{}",
+        state,
+        plan_orders_cur,
+        place_orders_cur
     );
 
-    // Cancel orders if needed
-    if !bids.is_empty() || !asks.is_empty() {
-        crate::process::helpers::cancel_amm_orders_and_settle(
-            market_program_info,
-            market_info,
-            market_bids_info,
-            market_asks_info,
-            amm_open_orders_info,
-            amm_authority_info,
-            market_event_queue_info,
-            market_coin_vault_info,
-            market_pc_vault_info,
-            amm_coin_vault_info,
-            amm_pc_vault_info,
-            market_vault_signer,
-            token_program_info,
-            None,
-            &bids,
-            &asks,
-            amm.nonce as u8,
+    // the crank reward and the compounded pnl are booked in the AmmInfoExtension
+    drop(amm);
+    if let Some(crank_reward_pc_info) = crank_reward_pc_info {
+        let clock = Clock::from_account_info(clock_info)?;
+        pay_crank_reward(
+            program_id,
+            amm_info,
+            pool.token_program,
+            pool.authority,
+            pool.pc_vault,
+            crank_reward_pc_info,
+            clock.epoch,
+            clock.slot,
         )?;
     }
+    // the crank reward is paid first, the rest of the pnl goes to the lp
+    compound_pnl(program_id, amm_info, &mut target_orders)?;
+    Ok(())
+}
 
-    msg!("Monitor step completed successfully");
+/// Pays the crank reward from the unclaimed pc pnl to the caller's pc token account, the
+/// AmmInfoExtension rate limits it per slot interval and caps it per epoch.
+/// Pools before AMM_INFO_VERSION_V2 pay no reward
+pub fn pay_crank_reward<'a>(
+    program_id: &Pubkey,
+    amm_info: &AccountInfo<'a>,
+    token_program_info: &AccountInfo<'a>,
+    amm_authority_info: &AccountInfo<'a>,
+    amm_pc_vault_info: &AccountInfo<'a>,
+    crank_reward_pc_info: &AccountInfo<'a>,
+    epoch: u64,
+    slot: u64,
+) -> ProgramResult {
+    if AmmInfo::load_checked(amm_info, program_id)?.version() < AMM_INFO_VERSION_V2 {
+        return Ok(());
+    }
+    let (mut amm, mut extension) = AmmInfo::load_mut_checked_with_extension(amm_info, program_id)?;
+    let crank_reward_pc = unpack_token_account(crank_reward_pc_info, token_program_info.key)?;
+    check_assert_eq!(
+        crank_reward_pc.mint,
+        amm.pc_vault_mint,
+        "crank_reward_pc_mint",
+        AmmError::InvalidPCMint
+    );
+    let reward = extension.take_crank_reward(epoch, slot, amm.state_data.need_take_pnl_pc);
+    if reward == 0 {
+        return Ok(());
+    }
+    amm.state_data.need_take_pnl_pc -= reward;
+    extension.pnl_claimed_pc = extension.pnl_claimed_pc.saturating_add(reward);
+    Invokers::token_transfer_with_authority(
+        program_id,
+        token_program_info.clone(),
+        amm_pc_vault_info.clone(),
+        crank_reward_pc_info.clone(),
+        amm_authority_info.clone(),
+        AUTHORITY_AMM,
+        amm.nonce as u8,
+        reward,
+        amm_pc_vault_info.clone(),
+        &[],
+    )?;
+    msg!("crank reward:{}", reward);
     Ok(())
-} 
\ No newline at end of file
+}
+
+/// An amm order on the book, whichever market backend it rests on
+struct BookOrder {
+    client_order_id: u64,
+    price: u64,
+    /// OpenBook v2 open orders only record the price of an order, not what is left of it
+    quantity: Option<u64>,
+}
+
+impl BookOrder {
+    fn from_leaf(order: &LeafNode) -> Self {
+        Self {
+            client_order_id: order.client_order_id(),
+            price: order.price().get(),
+            quantity: Some(order.quantity()),
+        }
+    }
+
+    fn from_open_order_v2(order: &OpenOrderV2) -> Self {
+        Self {
+            client_order_id: order.client_order_id,
+            price: std::cmp::max(order.price_lots, 0) as u64,
+            quantity: None,
+        }
+    }
+}
+
+/// Market side accounts of the monitor step.
+/// On an OpenBook v2 market the event queue is the event heap, the coin and pc vaults are the
+/// market base and quote vaults, the vault signer is the market authority and the request
+/// queue, which v2 has none of, is the system program
+struct MarketAccounts<'a, 'b> {
+    backend: MarketBackend,
+    program: &'b AccountInfo<'a>,
+    market: &'b AccountInfo<'a>,
+    coin_vault: &'b AccountInfo<'a>,
+    pc_vault: &'b AccountInfo<'a>,
+    vault_signer: &'b AccountInfo<'a>,
+    request_queue: &'b AccountInfo<'a>,
+    event_queue: &'b AccountInfo<'a>,
+    bids: &'b AccountInfo<'a>,
+    asks: &'b AccountInfo<'a>,
+    pc_lot_size: u64,
+}
+
+/// Pool side accounts of the monitor step
+struct PoolAccounts<'a, 'b> {
+    token_program: &'b AccountInfo<'a>,
+    rent: &'b AccountInfo<'a>,
+    authority: &'b AccountInfo<'a>,
+    open_orders: &'b AccountInfo<'a>,
+    coin_vault: &'b AccountInfo<'a>,
+    pc_vault: &'b AccountInfo<'a>,
+    srm_token: Option<&'b AccountInfo<'a>>,
+    referrer_pc_wallet: Option<&'b AccountInfo<'a>>,
+}
+
+/// Places the planned ladder from target.place_orders_cur, at most place_order_limit orders.
+/// An order already on the book at the planned price and within amount_wave of the planned
+/// volume is kept, any other is replaced by client order id, or placed when it is gone.
+/// Returns true once every level is placed.
+fn place_orders<'a, 'b>(
+    amm: &mut AmmInfo,
+    target: &mut TargetOrders,
+    market: &MarketAccounts<'a, 'b>,
+    pool: &PoolAccounts<'a, 'b>,
+    bids: &[BookOrder],
+    asks: &[BookOrder],
+    place_order_limit: u16,
+) -> Result<bool, ProgramError> {
+    let levels = ladder_levels(amm);
+    let mut placed = 0u16;
+    while (target.place_orders_cur as usize) < levels {
+        let i = target.place_orders_cur as usize;
+        for side in [Side::Bid, Side::Ask] {
+            let (planned, client_order_id, book) = match side {
+                Side::Bid => (target.buy_orders[i], target.replace_buy_client_id[i], bids),
+                Side::Ask => (target.sell_orders[i], target.replace_sell_client_id[i], asks),
+            };
+            let (price, vol) = (planned.price, planned.vol);
+            let limit_price = Calculator::convert_price_out(price, amm.pc_lot_size);
+            let max_coin_qty = if vol < amm.min_size {
+                0
+            } else {
+                Calculator::convert_vol_out(
+                    vol,
+                    amm.coin_decimals,
+                    amm.coin_lot_size,
+                    amm.sys_decimal_value,
+                )
+            };
+            let on_book = book
+                .iter()
+                .find(|order| client_order_id != 0 && order.client_order_id == client_order_id);
+            if limit_price == 0 || max_coin_qty == 0 {
+                // too small to place, an order left at this level is purged
+                set_client_order_id(target, side, i, 0);
+                continue;
+            }
+            if let Some(order) = on_book {
+                // an order whose quantity is unknown is kept as long as its price holds
+                let within_wave = order.quantity.map_or(true, |quantity| {
+                    Calculator::convert_in_vol(
+                        quantity.abs_diff(max_coin_qty),
+                        amm.coin_decimals,
+                        amm.coin_lot_size,
+                        amm.sys_decimal_value,
+                    ) <= amm.amount_wave
+                });
+                if order.price == limit_price && within_wave {
+                    continue;
+                }
+            }
+            if placed >= place_order_limit {
+                return Ok(false);
+            }
+            let max_native_pc_qty = match side {
+                Side::Bid => Calculator::to_u64(
+                    (limit_price as u128)
+                        .saturating_mul(max_coin_qty as u128)
+                        .saturating_mul(market.pc_lot_size as u128)
+                        .saturating_mul(
+                            (amm.fees.trade_fee_denominator + amm.fees.trade_fee_numerator)
+                                as u128,
+                        )
+                        / amm.fees.trade_fee_denominator as u128,
+                )
+                .unwrap_or(u64::MAX),
+                Side::Ask => u64::MAX,
+            };
+            let (payer, market_vault) = match side {
+                Side::Bid => (pool.pc_vault, market.pc_vault),
+                Side::Ask => (pool.coin_vault, market.coin_vault),
+            };
+            let new_client_order_id = match market.backend {
+                MarketBackend::OpenBookV1 => {
+                    // both dex instructions take the same accounts and order
+                    let (new_client_order_id, invoke) = if on_book.is_some() {
+                        (
+                            client_order_id,
+                            Invokers::invoke_dex_replace_order_by_client_id,
+                        )
+                    } else {
+                        (amm.incr_client_order_id(), Invokers::invoke_dex_new_order_v3)
+                    };
+                    invoke(
+                        market.program.clone(),
+                        market.market.clone(),
+                        pool.open_orders.clone(),
+                        market.request_queue.clone(),
+                        market.event_queue.clone(),
+                        market.bids.clone(),
+                        market.asks.clone(),
+                        payer.clone(),
+                        pool.authority.clone(),
+                        market.coin_vault.clone(),
+                        market.pc_vault.clone(),
+                        pool.token_program.clone(),
+                        pool.rent.clone(),
+                        pool.srm_token,
+                        AUTHORITY_AMM,
+                        amm.nonce as u8,
+                        side,
+                        NonZeroU64::new(limit_price).unwrap(),
+                        NonZeroU64::new(max_coin_qty).unwrap(),
+                        NonZeroU64::new(max_native_pc_qty).unwrap(),
+                        OrderType::PostOnly,
+                        new_client_order_id,
+                        u16::MAX,
+                    )?;
+                    new_client_order_id
+                }
+                MarketBackend::OpenBookV2 => {
+                    // v2 has no replace, the old order is cancelled and a new one placed
+                    if on_book.is_some() {
+                        Invokers::invoke_openbook_v2_cancel_order_by_client_order_id(
+                            market.program.clone(),
+                            market.market.clone(),
+                            market.bids.clone(),
+                            market.asks.clone(),
+                            pool.open_orders.clone(),
+                            pool.authority.clone(),
+                            AUTHORITY_AMM,
+                            amm.nonce as u8,
+                            client_order_id,
+                        )?;
+                    }
+                    let new_client_order_id = amm.incr_client_order_id();
+                    Invokers::invoke_openbook_v2_place_order(
+                        market.program.clone(),
+                        pool.open_orders.clone(),
+                        pool.authority.clone(),
+                        payer.clone(),
+                        market.market.clone(),
+                        market.bids.clone(),
+                        market.asks.clone(),
+                        market.event_queue.clone(),
+                        market_vault.clone(),
+                        pool.token_program.clone(),
+                        AUTHORITY_AMM,
+                        amm.nonce as u8,
+                        side,
+                        limit_price,
+                        max_coin_qty,
+                        max_native_pc_qty / market.pc_lot_size,
+                        new_client_order_id,
+                    )?;
+                    new_client_order_id
+                }
+            };
+            set_client_order_id(target, side, i, new_client_order_id);
+            placed += 1;
+        }
+        target.place_orders_cur += 1;
+    }
+    let (replace_buy_client_id, replace_sell_client_id) =
+        (target.replace_buy_client_id, target.replace_sell_client_id);
+    target.valid_buy_order_num = replace_buy_client_id[..levels]
+        .iter()
+        .filter(|id| **id != 0)
+        .count() as u64;
+    target.valid_sell_order_num = replace_sell_client_id[..levels]
+        .iter()
+        .filter(|id| **id != 0)
+        .count() as u64;
+    target.placed_x = target.plan_x_buy;
+    target.placed_y = target.plan_y_buy;
+    Ok(true)
+}
+
+fn set_client_order_id(target: &mut TargetOrders, side: Side, i: usize, client_order_id: u64) {
+    match side {
+        Side::Bid => target.replace_buy_client_id[i] = client_order_id,
+        Side::Ask => target.replace_sell_client_id[i] = client_order_id,
+    }
+}
+
+/// Cancels the amm orders that are not part of the placed ladder, at most cancel_order_limit
+/// of them, then settles the funds back to the amm vaults.
+/// Returns true once no stray order is left.
+fn purge_orders<'a, 'b>(
+    amm: &AmmInfo,
+    target: &TargetOrders,
+    market: &MarketAccounts<'a, 'b>,
+    pool: &PoolAccounts<'a, 'b>,
+    bids: &[BookOrder],
+    asks: &[BookOrder],
+    cancel_order_limit: u16,
+) -> Result<bool, ProgramError> {
+    let levels = ladder_levels(amm);
+    let (replace_buy_client_id, replace_sell_client_id) =
+        (target.replace_buy_client_id, target.replace_sell_client_id);
+    let placed_ids = replace_buy_client_id[..levels]
+        .iter()
+        .chain(replace_sell_client_id[..levels].iter())
+        .filter(|id| **id != 0)
+        .copied()
+        .collect::<Vec<u64>>();
+    let stray_ids = bids
+        .iter()
+        .chain(asks.iter())
+        .map(|order| order.client_order_id)
+        .filter(|id| !placed_ids.contains(id))
+        .collect::<Vec<u64>>();
+    let cancel_num = std::cmp::min(stray_ids.len(), cancel_order_limit as usize);
+    cancel_orders_and_settle(amm, market, pool, &stray_ids[..cancel_num], false)?;
+    Ok(cancel_num == stray_ids.len())
+}
+
+/// Cancels the given amm orders by client order id, or every amm order when all is set,
+/// then settles the funds back to the amm vaults
+fn cancel_orders_and_settle<'a, 'b>(
+    amm: &AmmInfo,
+    market: &MarketAccounts<'a, 'b>,
+    pool: &PoolAccounts<'a, 'b>,
+    client_order_ids: &[u64],
+    all: bool,
+) -> Result<(), ProgramError> {
+    match market.backend {
+        MarketBackend::OpenBookV1 => {
+            for chunk in client_order_ids.chunks(8) {
+                let mut ids = [0u64; 8];
+                ids[..chunk.len()].copy_from_slice(chunk);
+                Invokers::invoke_dex_cancel_orders_by_client_order_ids(
+                    market.program.clone(),
+                    market.market.clone(),
+                    market.bids.clone(),
+                    market.asks.clone(),
+                    pool.open_orders.clone(),
+                    pool.authority.clone(),
+                    market.event_queue.clone(),
+                    AUTHORITY_AMM,
+                    amm.nonce as u8,
+                    ids,
+                )?;
+            }
+            Invokers::invoke_dex_settle_funds(
+                market.program.clone(),
+                market.market.clone(),
+                pool.open_orders.clone(),
+                pool.authority.clone(),
+                market.coin_vault.clone(),
+                market.pc_vault.clone(),
+                pool.coin_vault.clone(),
+                pool.pc_vault.clone(),
+                market.vault_signer.clone(),
+                pool.token_program.clone(),
+                pool.referrer_pc_wallet,
+                AUTHORITY_AMM,
+                amm.nonce as u8,
+            )
+        }
+        MarketBackend::OpenBookV2 => {
+            if all {
+                Invokers::invoke_openbook_v2_cancel_all_orders(
+                    market.program.clone(),
+                    market.market.clone(),
+                    market.bids.clone(),
+                    market.asks.clone(),
+                    pool.open_orders.clone(),
+                    pool.authority.clone(),
+                    AUTHORITY_AMM,
+                    amm.nonce as u8,
+                    None,
+                )?;
+            } else {
+                for client_order_id in client_order_ids {
+                    Invokers::invoke_openbook_v2_cancel_order_by_client_order_id(
+                        market.program.clone(),
+                        market.market.clone(),
+                        market.bids.clone(),
+                        market.asks.clone(),
+                        pool.open_orders.clone(),
+                        pool.authority.clone(),
+                        AUTHORITY_AMM,
+                        amm.nonce as u8,
+                        *client_order_id,
+                    )?;
+                }
+            }
+            Invokers::invoke_openbook_v2_settle_funds(
+                market.program.clone(),
+                market.market.clone(),
+                pool.open_orders.clone(),
+                pool.authority.clone(),
+                market.vault_signer.clone(),
+                market.coin_vault.clone(),
+                market.pc_vault.clone(),
+                pool.coin_vault.clone(),
+                pool.pc_vault.clone(),
+                pool.token_program.clone(),
+                market.request_queue.clone(),
+                pool.referrer_pc_wallet,
+                AUTHORITY_AMM,
+                amm.nonce as u8,
+            )
+        }
+    }
+}
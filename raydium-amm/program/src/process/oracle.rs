@@ -0,0 +1,158 @@
+//! Handles the price oracle instruction logic for Raydium AMM
+use crate::{
+    error::AmmError,
+    instruction::GetTwapInstruction,
+    math::Calculator,
+    process::constants::OBSERVATION_ASSOCIATED_SEED,
+    process::helpers::get_associated_address_and_bump_seed,
+    process::initialize::create_pool_account,
+    state::{AmmInfo, Loadable, Observation, ObservationState, TwapResult},
+};
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    msg,
+    program::set_return_data,
+    pubkey::Pubkey,
+};
+use std::mem::size_of;
+
+/// [amm_info, observation_info, payer_info, system_program_info] = 4 accounts
+const INITIALIZE_OBSERVATION_ACCOUNT_LEN: usize = 4;
+/// [amm_info, observation_info] = 2 accounts
+const OBSERVATION_ACCOUNT_LEN: usize = 2;
+
+pub fn process_initialize_observation(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    if accounts.len() != INITIALIZE_OBSERVATION_ACCOUNT_LEN {
+        return Err(AmmError::WrongAccountsNumber.into());
+    }
+    let account_info_iter = &mut accounts.iter();
+    let amm_info = next_account_info(account_info_iter)?;
+    let observation_info = next_account_info(account_info_iter)?;
+    let payer_info = next_account_info(account_info_iter)?;
+    let system_program_info = next_account_info(account_info_iter)?;
+
+    if !payer_info.is_signer {
+        return Err(AmmError::InvalidSignAccount.into());
+    }
+    if *system_program_info.key != solana_program::system_program::id() {
+        return Err(AmmError::InvalidSystemProgram.into());
+    }
+    // make sure the amm is valid
+    let _amm = AmmInfo::load_checked(&amm_info, program_id)?;
+
+    let (observation_address, bump_seed) = get_associated_address_and_bump_seed(
+        amm_info.key,
+        amm_info.key,
+        OBSERVATION_ASSOCIATED_SEED,
+        program_id,
+    );
+    if *observation_info.key != observation_address {
+        return Err(AmmError::InvalidObservationAccount.into());
+    }
+    if !observation_info.data_is_empty() {
+        return Err(AmmError::InvalidObservationAccount.into());
+    }
+
+    // also takes an observation address someone sent lamports to beforehand
+    create_pool_account(
+        payer_info,
+        observation_info,
+        system_program_info,
+        size_of::<ObservationState>(),
+        program_id,
+        &[
+            amm_info.key.as_ref(),
+            amm_info.key.as_ref(),
+            OBSERVATION_ASSOCIATED_SEED,
+            &[bump_seed],
+        ],
+    )?;
+
+    let mut observation_state = ObservationState::load_mut(&observation_info)?;
+    observation_state.amm_id = *amm_info.key;
+    observation_state.observation_index = 0;
+    Ok(())
+}
+
+pub fn process_record_observation(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    if accounts.len() != OBSERVATION_ACCOUNT_LEN {
+        return Err(AmmError::WrongAccountsNumber.into());
+    }
+    let account_info_iter = &mut accounts.iter();
+    let amm_info = next_account_info(account_info_iter)?;
+    let observation_info = next_account_info(account_info_iter)?;
+
+    let amm = AmmInfo::load_checked(&amm_info, program_id)?;
+    let mut observation_state =
+        ObservationState::load_mut_checked(&observation_info, program_id, amm_info.key)?;
+
+    let observation = Observation {
+        timestamp: amm.state_data.price_last_update_time,
+        pc_price_cumulative: amm.pc_price_cumulative,
+        coin_price_cumulative: amm.coin_price_cumulative,
+    };
+    if !observation_state.push(observation) {
+        msg!("no price update since the latest observation");
+    }
+    Ok(())
+}
+
+pub fn process_get_twap(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    get_twap: GetTwapInstruction,
+) -> ProgramResult {
+    if accounts.len() != OBSERVATION_ACCOUNT_LEN {
+        return Err(AmmError::WrongAccountsNumber.into());
+    }
+    let account_info_iter = &mut accounts.iter();
+    let amm_info = next_account_info(account_info_iter)?;
+    let observation_info = next_account_info(account_info_iter)?;
+
+    let amm = AmmInfo::load_checked(&amm_info, program_id)?;
+    let observation_state =
+        ObservationState::load_checked(&observation_info, program_id, amm_info.key)?;
+
+    let end_time = amm.state_data.price_last_update_time;
+    let start = end_time
+        .checked_sub(get_twap.window)
+        .and_then(|start_time| observation_state.observation_before(start_time))
+        .ok_or(AmmError::InsufficientObservations)?;
+    let start_time = start.timestamp;
+    let elapsed = end_time - start_time;
+    let pc_price_x64 = Calculator::calc_twap_x64(
+        start.pc_price_cumulative,
+        amm.pc_price_cumulative,
+        elapsed,
+    )
+    .ok_or(AmmError::InsufficientObservations)?;
+    let coin_price_x64 = Calculator::calc_twap_x64(
+        start.coin_price_cumulative,
+        amm.coin_price_cumulative,
+        elapsed,
+    )
+    .ok_or(AmmError::InsufficientObservations)?;
+
+    let result = TwapResult {
+        start_time,
+        end_time,
+        pc_price_x64,
+        coin_price_x64,
+    };
+    msg!(
+        "twap start_time:{}, end_time:{}, pc_price_x64:{}, coin_price_x64:{}",
+        start_time,
+        end_time,
+        pc_price_x64,
+        coin_price_x64
+    );
+    set_return_data(bytemuck::bytes_of(&result));
+    Ok(())
+}
@@ -8,10 +8,10 @@ use solana_program::{
 use crate::{
     error::AmmError,
     instruction::SetParamsInstruction,
-    state::{AmmInfo, AmmParams},
+    state::{AmmInfo, AmmParams, AmmRole, LadderStrategy, TEN_THOUSAND},
 };
 use crate::process::constants::AUTHORITY_AMM;
-use crate::process::helpers::authority_id;
+use crate::process::helpers::{authority_id, check_amm_role, check_role_override};
 
 pub fn process_set_params(
     program_id: &Pubkey,
@@ -22,11 +22,34 @@ pub fn process_set_params(
     let amm_info = next_account_info(account_info_iter)?;
     let amm_authority_info = next_account_info(account_info_iter)?;
     let user_wallet_info = next_account_info(account_info_iter)?;
+    let amm_config_info = next_account_info(account_info_iter)?;
 
     if !user_wallet_info.is_signer {
         return Err(AmmError::InvalidSignAccount.into());
     }
 
+    // Each param needs its own role, see AmmParams::role. Role overrides are checked
+    // against the role they override, see check_role_override
+    let param = AmmParams::from_u64(setparams.param.into());
+    match (param, setparams.value) {
+        (AmmParams::PoolRole, Some(value)) => {
+            check_role_override(
+                program_id,
+                AmmRole::from_u64(value)?,
+                user_wallet_info,
+                amm_info,
+                amm_config_info,
+            )?;
+        }
+        _ => check_amm_role(
+            program_id,
+            param.role(),
+            user_wallet_info,
+            amm_info,
+            amm_config_info,
+        )?,
+    }
+
     let mut amm = AmmInfo::load_mut_checked(&amm_info, program_id)?;
 
     // Check authority
@@ -35,13 +58,8 @@ pub fn process_set_params(
         return Err(AmmError::InvalidProgramAddress.into());
     }
 
-    // Check if user is owner
-    if *user_wallet_info.key != amm.amm_owner {
-        return Err(AmmError::InvalidOwner.into());
-    }
-
     // Update parameters based on param field
-    match AmmParams::from_u64(setparams.param.into()) {
+    match param {
         AmmParams::Status => {
             if let Some(value) = setparams.value {
                 amm.status = value;
@@ -123,6 +141,86 @@ pub fn process_set_params(
         AmmParams::UpdateOpenOrder => {
             // Implementation for update open order
         }
+        AmmParams::DynamicFee => {
+            if let Some(dynamic_fee) = setparams.dynamic_fee {
+                // max_fee_numerator == 0 switches back to the static swap fee
+                if dynamic_fee.max_fee_numerator != 0
+                    && (dynamic_fee.min_fee_numerator > dynamic_fee.max_fee_numerator
+                        || dynamic_fee.max_fee_numerator >= amm.fees.swap_fee_denominator)
+                {
+                    return Err(AmmError::InvalidFee.into());
+                }
+                amm.dynamic_fee_min_numerator = u32::try_from(dynamic_fee.min_fee_numerator)
+                    .map_err(|_| AmmError::InvalidFee)?;
+                amm.dynamic_fee_max_numerator = u32::try_from(dynamic_fee.max_fee_numerator)
+                    .map_err(|_| AmmError::InvalidFee)?;
+                amm.state_data.volatility_ema = 0;
+            }
+        }
+        AmmParams::CircuitBreaker => {
+            if let Some(circuit_breaker) = setparams.circuit_breaker {
+                // all limits 0 switches the circuit breaker off
+                if circuit_breaker.max_price_impact_bps > TEN_THOUSAND
+                    || circuit_breaker.max_output_bps > TEN_THOUSAND
+                    || circuit_breaker.max_slot_volume_bps > TEN_THOUSAND
+                    || circuit_breaker.max_trips > u8::MAX as u64
+                {
+                    return Err(AmmError::InvalidInput.into());
+                }
+                amm.max_price_impact_bps = circuit_breaker.max_price_impact_bps as u16;
+                amm.max_output_bps = circuit_breaker.max_output_bps as u16;
+                amm.max_slot_volume_bps = circuit_breaker.max_slot_volume_bps as u16;
+                amm.circuit_breaker_max_trips = circuit_breaker.max_trips as u8;
+                amm.circuit_breaker_trips = 0;
+                amm.slot_volume_bps = 0;
+            }
+        }
+        AmmParams::LadderStrategy => {
+            if let Some(value) = setparams.value {
+                if !LadderStrategy::valid(value) {
+                    return Err(AmmError::InvalidInput.into());
+                }
+                // the strategy lives in the AmmInfoExtension, takes effect on the next replan
+                drop(amm);
+                let (_amm, mut extension) =
+                    AmmInfo::load_mut_checked_with_extension(&amm_info, program_id)?;
+                extension.ladder_strategy = value;
+            }
+        }
+        AmmParams::CrankReward => {
+            if let Some(crank_reward) = setparams.crank_reward {
+                // reward_pc 0 switches the crank reward off
+                if crank_reward.reward_pc > crank_reward.epoch_cap {
+                    return Err(AmmError::InvalidInput.into());
+                }
+                drop(amm);
+                let (_amm, mut extension) =
+                    AmmInfo::load_mut_checked_with_extension(&amm_info, program_id)?;
+                extension.crank_reward_pc = crank_reward.reward_pc;
+                extension.crank_reward_epoch_cap = crank_reward.epoch_cap;
+                extension.crank_reward_slot_interval = crank_reward.slot_interval;
+            }
+        }
+        AmmParams::AutoCompound => {
+            if let Some(value) = setparams.value {
+                if value > 1 {
+                    return Err(AmmError::InvalidInput.into());
+                }
+                drop(amm);
+                let (_amm, mut extension) =
+                    AmmInfo::load_mut_checked_with_extension(&amm_info, program_id)?;
+                extension.auto_compound = value;
+            }
+        }
+        AmmParams::PoolRole => {
+            if let (Some(value), Some(holder)) = (setparams.value, setparams.new_pubkey) {
+                let role = AmmRole::from_u64(value)?;
+                // Pubkey::default() drops the override, the amm config role applies again
+                drop(amm);
+                let mut pool_roles = AmmInfo::load_roles_mut(&amm_info, program_id)?;
+                pool_roles.set(role, holder);
+            }
+        }
     }
 
     msg!("Parameters updated successfully");
@@ -1,19 +1,30 @@
 //! Handles the simulate instruction logic for Raydium AMM
 use solana_program::{
     account_info::{next_account_info, AccountInfo},
+    clock::Clock,
     entrypoint::ProgramResult,
     program_error::ProgramError,
     pubkey::Pubkey,
     msg,
+    sysvar::Sysvar,
 };
 use crate::{
     error::AmmError,
-    instruction::{SimulateInstruction, SwapInstructionBaseIn, SwapInstructionBaseOut},
-    state::{AmmInfo, AmmStatus, SimulateParams},
+    instruction::{
+        FeeGrowthSnapshot, SimulateInstruction, SwapInstructionBaseIn, SwapInstructionBaseOut,
+    },
+    state::{
+        AmmInfo, AmmStatus, LadderLevel, LpLock, SimulateParams, TargetOrders, MAX_ORDER_LIMIT,
+    },
     math::{Calculator, SwapDirection, U128, CheckedCeilDiv},
 };
 use crate::process::constants::AUTHORITY_AMM;
-use crate::process::helpers::{authority_id, load_serum_market_order, unpack_token_account, unpack_mint, identity};
+use crate::process::helpers::{
+    authority_id, calc_pool_total_without_take_pnl, unpack_token_account, unpack_mint, identity,
+};
+use crate::process::lp_lock::calc_pool_liquidity_per_lp;
+use crate::process::monitor::{ladder_levels, plan_orders};
+use bytemuck::Zeroable;
 
 pub fn process_simulate_info(
     program_id: &Pubkey,
@@ -45,6 +56,23 @@ pub fn process_simulate_info(
             msg!("Run crank data: {:?}", crank_data);
             Ok(())
         }
+        SimulateParams::LpLockInfo => {
+            let lp_lock_data = simulate_lp_lock_info(program_id, accounts)?;
+            msg!("Lp lock data: {:?}", lp_lock_data);
+            Ok(())
+        }
+        SimulateParams::LadderInfo => {
+            let ladder_data = simulate_ladder_info(program_id, accounts)?;
+            msg!("Ladder data: {}", ladder_data.to_json());
+            Ok(())
+        }
+        SimulateParams::FeeGrowthInfo => {
+            if let Some(snapshot) = simulate.fee_growth_value {
+                let fee_growth_data = simulate_fee_growth_info(program_id, accounts, snapshot)?;
+                msg!("Fee growth data: {:?}", fee_growth_data);
+            }
+            Ok(())
+        }
     }
 }
 
@@ -137,30 +165,47 @@ fn simulate_swap_base_in(
         return Err(AmmError::InvalidUserToken.into());
     };
 
-    // Calculate swap amount
+    let (total_pc_without_take_pnl, total_coin_without_take_pnl) =
+        calc_pool_total_without_take_pnl(
+            &amm,
+            amm_pc_vault.amount,
+            amm_coin_vault.amount,
+            market_info,
+            market_event_queue_info,
+            amm_open_orders_info,
+            amm_authority_info,
+        )?;
+
+    // Same fee as process_swap_base_in, including the dynamic fee
     let swap_fee = U128::from(swap.amount_in)
-        .checked_mul(amm.fees.swap_fee_numerator.into())
+        .checked_mul(amm.swap_fee_numerator(Clock::get()?.unix_timestamp as u64).into())
         .unwrap()
         .checked_ceil_div(amm.fees.swap_fee_denominator.into())
         .unwrap()
         .0;
 
     let swap_in_after_deduct_fee = U128::from(swap.amount_in).checked_sub(swap_fee).unwrap();
-    
-    // Calculate output amount (simplified calculation)
-    let swap_amount_out = if swap_direction == SwapDirection::Coin2PC {
-        amm_pc_vault.amount.saturating_sub(1) // Simplified calculation
-    } else {
-        amm_coin_vault.amount.saturating_sub(1) // Simplified calculation
-    };
+    let swap_amount_out = Calculator::swap_token_amount_base_in(
+        swap_in_after_deduct_fee,
+        total_pc_without_take_pnl.into(),
+        total_coin_without_take_pnl.into(),
+        swap_direction,
+    )
+    .as_u64();
+    let price_impact = Calculator::calc_price_impact(
+        swap_in_after_deduct_fee,
+        total_pc_without_take_pnl.into(),
+        total_coin_without_take_pnl.into(),
+        swap_direction,
+    );
 
     let pool_data = simulate_pool_info(program_id, accounts)?;
 
     let swap_data = crate::state::GetSwapBaseInData {
         pool_data,
         amount_in: swap.amount_in,
-        minimum_amount_out: swap.minimum_amount_out,
-        price_impact: 0, // Simplified
+        minimum_amount_out: swap_amount_out,
+        price_impact,
     };
 
     Ok(swap_data)
@@ -209,12 +254,44 @@ fn simulate_swap_base_out(
         return Err(AmmError::InvalidUserToken.into());
     };
 
-    // Calculate input amount (simplified calculation)
-    let max_amount_in = if swap_direction == SwapDirection::Coin2PC {
-        amm_coin_vault.amount.saturating_add(1) // Simplified calculation
-    } else {
-        amm_pc_vault.amount.saturating_add(1) // Simplified calculation
-    };
+    let (total_pc_without_take_pnl, total_coin_without_take_pnl) =
+        calc_pool_total_without_take_pnl(
+            &amm,
+            amm_pc_vault.amount,
+            amm_coin_vault.amount,
+            market_info,
+            market_event_queue_info,
+            amm_open_orders_info,
+            amm_authority_info,
+        )?;
+
+    // Same fee as process_swap_base_out, including the dynamic and launch fee
+    let swap_in_before_add_fee = Calculator::swap_token_amount_base_out(
+        swap.amount_out.into(),
+        total_pc_without_take_pnl.into(),
+        total_coin_without_take_pnl.into(),
+        swap_direction,
+    );
+    let swap_fee_numerator = amm.swap_fee_numerator(Clock::get()?.unix_timestamp as u64);
+    let max_amount_in = swap_in_before_add_fee
+        .checked_mul(amm.fees.swap_fee_denominator.into())
+        .unwrap()
+        .checked_ceil_div(
+            (amm.fees
+                .swap_fee_denominator
+                .checked_sub(swap_fee_numerator)
+                .unwrap())
+            .into(),
+        )
+        .unwrap()
+        .0
+        .as_u64();
+    let price_impact = Calculator::calc_price_impact(
+        swap_in_before_add_fee,
+        total_pc_without_take_pnl.into(),
+        total_coin_without_take_pnl.into(),
+        swap_direction,
+    );
 
     let pool_data = simulate_pool_info(program_id, accounts)?;
 
@@ -222,7 +299,7 @@ fn simulate_swap_base_out(
         pool_data,
         max_amount_in,
         amount_out: swap.amount_out,
-        price_impact: 0, // Simplified
+        price_impact,
     };
 
     Ok(swap_data)
@@ -260,4 +337,201 @@ fn simulate_run_crank(
     };
 
     Ok(crank_data)
-} 
\ No newline at end of file
+} 
+
+fn simulate_lp_lock_info(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+) -> Result<crate::state::GetLpLockData, ProgramError> {
+    let account_info_iter = &mut accounts.iter();
+    let amm_info = next_account_info(account_info_iter)?;
+    let amm_authority_info = next_account_info(account_info_iter)?;
+    let amm_open_orders_info = next_account_info(account_info_iter)?;
+    let amm_coin_vault_info = next_account_info(account_info_iter)?;
+    let amm_pc_vault_info = next_account_info(account_info_iter)?;
+    let _amm_lp_mint_info = next_account_info(account_info_iter)?;
+    let market_info = next_account_info(account_info_iter)?;
+    let market_event_queue_info = next_account_info(account_info_iter)?;
+    let lp_lock_info = next_account_info(account_info_iter)?;
+
+    let amm = AmmInfo::load_checked(&amm_info, program_id)?;
+
+    // Check authority
+    let authority = authority_id(program_id, AUTHORITY_AMM, amm.nonce as u8)?;
+    if *amm_authority_info.key != authority {
+        return Err(AmmError::InvalidProgramAddress.into());
+    }
+
+    let lp_lock = LpLock::load_checked(&lp_lock_info, program_id, amm_info.key)?;
+    let liquidity_per_lp_x64 = calc_pool_liquidity_per_lp(
+        &amm,
+        amm_authority_info,
+        amm_open_orders_info,
+        amm_coin_vault_info,
+        amm_pc_vault_info,
+        market_info,
+        market_event_queue_info,
+    )?;
+    let now = Clock::get()?.unix_timestamp as u64;
+    // swap fees claimable by a permanent lock
+    let (claimable_fee_coin, claimable_fee_pc) = if lp_lock.is_permanent() {
+        lp_lock.accrued_fees(&AmmInfo::load_extension(amm_info, program_id)?)
+    } else {
+        (0, 0)
+    };
+
+    let lp_lock_data = crate::state::GetLpLockData {
+        lp_lock: lp_lock_info.key.to_string(),
+        owner: identity(lp_lock.owner).to_string(),
+        locked_amount: lp_lock.locked_amount,
+        withdrawn_amount: lp_lock.withdrawn_amount,
+        withdrawable_amount: lp_lock.withdrawable_amount(now),
+        claimed_fee_amount: lp_lock.claimed_fee_amount,
+        claimable_fee_amount: if lp_lock.is_permanent() {
+            0
+        } else {
+            Calculator::calc_lock_fee_lp(
+                lp_lock.locked_amount,
+                lp_lock.liquidity_per_lp_x64,
+                liquidity_per_lp_x64,
+            )
+        },
+        unlock_start_time: lp_lock.unlock_start_time,
+        unlock_end_time: lp_lock.unlock_end_time,
+        permanent: lp_lock.is_permanent(),
+        claimable_fee_coin,
+        claimable_fee_pc,
+    };
+
+    Ok(lp_lock_data)
+}
+
+/// Plans the whole ladder the monitor step would place at the current pool price,
+/// without touching the target orders account
+fn simulate_ladder_info(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+) -> Result<crate::state::GetLadderData, ProgramError> {
+    let account_info_iter = &mut accounts.iter();
+    let amm_info = next_account_info(account_info_iter)?;
+    let amm_authority_info = next_account_info(account_info_iter)?;
+    let amm_open_orders_info = next_account_info(account_info_iter)?;
+    let amm_target_orders_info = next_account_info(account_info_iter)?;
+    let amm_coin_vault_info = next_account_info(account_info_iter)?;
+    let amm_pc_vault_info = next_account_info(account_info_iter)?;
+    let market_info = next_account_info(account_info_iter)?;
+    let market_event_queue_info = next_account_info(account_info_iter)?;
+
+    let ladder_strategy = AmmInfo::load_ladder_strategy(&amm_info, program_id)?;
+    let amm = AmmInfo::load_checked(&amm_info, program_id)?;
+
+    // Check authority
+    let authority = authority_id(program_id, AUTHORITY_AMM, amm.nonce as u8)?;
+    if *amm_authority_info.key != authority {
+        return Err(AmmError::InvalidProgramAddress.into());
+    }
+    if *amm_target_orders_info.key != amm.target_orders {
+        return Err(AmmError::InvalidTargetOrders.into());
+    }
+    if !AmmStatus::from_u64(amm.status).orderbook_permission() {
+        return Err(AmmError::InvalidStatus.into());
+    }
+
+    let amm_coin_vault = unpack_token_account(&amm_coin_vault_info, &spl_token::id())?;
+    let amm_pc_vault = unpack_token_account(&amm_pc_vault_info, &spl_token::id())?;
+    let (total_pc_without_take_pnl, total_coin_without_take_pnl) =
+        calc_pool_total_without_take_pnl(
+            &amm,
+            amm_pc_vault.amount,
+            amm_coin_vault.amount,
+            market_info,
+            market_event_queue_info,
+            amm_open_orders_info,
+            amm_authority_info,
+        )?;
+    let x = Calculator::normalize_decimal_v2(
+        total_pc_without_take_pnl,
+        amm.pc_decimals,
+        amm.sys_decimal_value,
+    )
+    .as_u128();
+    let y = Calculator::normalize_decimal_v2(
+        total_coin_without_take_pnl,
+        amm.coin_decimals,
+        amm.sys_decimal_value,
+    )
+    .as_u128();
+
+    // plan into a scratch copy, the simulated target orders account stays untouched
+    let mut ladder: Box<TargetOrders> = Box::new(Zeroable::zeroed());
+    ladder.plan_x_buy = x;
+    ladder.plan_y_buy = y;
+    plan_orders(&amm, &mut ladder, ladder_strategy, MAX_ORDER_LIMIT as u16);
+
+    let levels = ladder_levels(&amm);
+    let price = if y == 0 {
+        0
+    } else {
+        Calculator::to_u64(
+            U128::from(x)
+                .checked_mul(amm.sys_decimal_value.into())
+                .unwrap()
+                .checked_div(y.into())
+                .unwrap()
+                .as_u128(),
+        )
+        .unwrap_or(u64::MAX)
+    };
+    let ladder_data = crate::state::GetLadderData {
+        strategy: ladder_strategy.into_u64(),
+        price,
+        buy_orders: ladder.buy_orders[..levels]
+            .iter()
+            .map(|order| LadderLevel {
+                price: order.price,
+                vol: order.vol,
+            })
+            .collect(),
+        sell_orders: ladder.sell_orders[..levels]
+            .iter()
+            .map(|order| LadderLevel {
+                price: order.price,
+                vol: order.vol,
+            })
+            .collect(),
+    };
+
+    Ok(ladder_data)
+}
+
+/// Swap fees accrued to an lp balance since its fee growth snapshot
+fn simulate_fee_growth_info(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    snapshot: FeeGrowthSnapshot,
+) -> Result<crate::state::GetFeeGrowthData, ProgramError> {
+    let account_info_iter = &mut accounts.iter();
+    let amm_info = next_account_info(account_info_iter)?;
+
+    let extension = AmmInfo::load_extension(&amm_info, program_id)?;
+    let (fee_growth_coin_per_lp, fee_growth_pc_per_lp) =
+        (extension.fee_growth_coin_per_lp, extension.fee_growth_pc_per_lp);
+
+    let fee_growth_data = crate::state::GetFeeGrowthData {
+        fee_growth_coin_per_lp,
+        fee_growth_pc_per_lp,
+        lp_amount: snapshot.lp_amount,
+        accrued_fee_coin: Calculator::calc_fee_growth_accrued(
+            snapshot.lp_amount,
+            snapshot.fee_growth_coin_per_lp,
+            fee_growth_coin_per_lp,
+        ),
+        accrued_fee_pc: Calculator::calc_fee_growth_accrued(
+            snapshot.lp_amount,
+            snapshot.fee_growth_pc_per_lp,
+            fee_growth_pc_per_lp,
+        ),
+    };
+
+    Ok(fee_growth_data)
+}
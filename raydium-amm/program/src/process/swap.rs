@@ -12,19 +12,25 @@ use crate::{
     instruction::{SwapInstructionBaseIn, SwapInstructionBaseOut},
     invokers::Invokers,
     math::{Calculator, SwapDirection, U128, CheckedCeilDiv},
-    state::{AmmInfo, AmmStatus},
+    openbook_v2::OpenOrdersV2,
+    state::{AmmInfo, AmmStatus, MarketBackend},
 };
 use crate::process::constants::AUTHORITY_AMM;
-use crate::process::helpers::{identity, authority_id, unpack_token_account, load_serum_market_order, get_amm_orders};
+use crate::process::helpers::{
+    identity, authority_id, unpack_token_account, load_serum_market_order, get_amm_orders,
+    accrue_fee_growth, check_allowlist_swap, check_circuit_breaker, load_openbook_v2_market_order,
+    market_backend,
+};
 use crate::process::args::{SwapBaseInLog, SwapBaseOutLog, LogType};
-use crate::log::{log_keys_mismatch, encode_ray_log};
+use crate::log::{log_keys_mismatch, encode_ray_log, encode_return_data, AmmReturnData};
 use crate::check_assert_eq;
 use serum_dex::critbit::LeafNode;
+use serum_dex::matching::Side;
 
 /// The number of accounts expected for a swap instruction.
 /// This is based on the order of next_account_info calls in the function:
 /// [token_program_info, amm_info, amm_authority_info, amm_open_orders_info, amm_coin_vault_info, amm_pc_vault_info, amm_coin_mint_info, amm_pc_mint_info, market_program_info, market_info, market_bids_info, market_asks_info, market_event_queue_info, market_coin_vault_info, market_pc_vault_info, market_vault_signer, user_source_info, user_destination_info, user_source_owner]
-/// = 19 accounts (base) + optional amm_target_orders_info
+/// = 19 accounts (base) + optional amm_target_orders_info + optional allowlist_entry_info
 const ACCOUNT_LEN: usize = 19;
 
 pub fn process_swap_base_in(
@@ -33,7 +39,10 @@ pub fn process_swap_base_in(
     swap: SwapInstructionBaseIn,
 ) -> ProgramResult {
     let input_account_len = accounts.len();
-    if input_account_len != ACCOUNT_LEN && input_account_len != ACCOUNT_LEN + 1 {
+    if input_account_len != ACCOUNT_LEN
+        && input_account_len != ACCOUNT_LEN + 1
+        && input_account_len != ACCOUNT_LEN + 2
+    {
         return Err(AmmError::WrongAccountsNumber.into());
     }
     let account_info_iter = &mut accounts.iter();
@@ -42,7 +51,7 @@ pub fn process_swap_base_in(
     let amm_info = next_account_info(account_info_iter)?;
     let amm_authority_info = next_account_info(account_info_iter)?;
     let amm_open_orders_info = next_account_info(account_info_iter)?;
-    if input_account_len == ACCOUNT_LEN + 1 {
+    if input_account_len >= ACCOUNT_LEN + 1 {
         let _amm_target_orders_info = next_account_info(account_info_iter)?;
     }
     let amm_coin_vault_info = next_account_info(account_info_iter)?;
@@ -70,6 +79,7 @@ pub fn process_swap_base_in(
     let user_source_info = next_account_info(account_info_iter)?;
     let user_destination_info = next_account_info(account_info_iter)?;
     let user_source_owner = next_account_info(account_info_iter)?;
+    let allowlist_entry_info = next_account_info(account_info_iter).ok();
     if !user_source_owner.is_signer {
         return Err(AmmError::InvalidSignAccount.into());
     }
@@ -140,6 +150,7 @@ pub fn process_swap_base_in(
     let total_coin_without_take_pnl;
     let mut bids: Vec<LeafNode> = Vec::new();
     let mut asks: Vec<LeafNode> = Vec::new();
+    let mut open_orders_v2: Option<Box<OpenOrdersV2>> = None;
     if enable_orderbook {
         check_assert_eq!(
             *amm_open_orders_info.key,
@@ -159,26 +170,47 @@ pub fn process_swap_base_in(
             "market",
             AmmError::InvalidMarket
         );
-        let (market_state, open_orders) = load_serum_market_order(
-            market_info,
-            amm_open_orders_info,
-            amm_authority_info,
-            &amm,
-            false,
-        )?;
-        let bids_orders = market_state.load_bids_checked(&market_bids_info)?;
-        let asks_orders = market_state.load_asks_checked(&market_asks_info)?;
-        (bids, asks) = get_amm_orders(&open_orders, bids_orders, asks_orders)?;
-        (total_pc_without_take_pnl, total_coin_without_take_pnl) =
-            Calculator::calc_total_without_take_pnl(
-                amm_pc_vault.amount,
-                amm_coin_vault.amount,
-                &open_orders,
-                &amm,
-                &market_state,
-                &market_event_queue_info,
-                &amm_open_orders_info,
-            )?;
+        match market_backend(&amm.market_program)? {
+            MarketBackend::OpenBookV1 => {
+                let (market_state, open_orders) = load_serum_market_order(
+                    market_info,
+                    amm_open_orders_info,
+                    amm_authority_info,
+                    &amm,
+                    false,
+                )?;
+                let bids_orders = market_state.load_bids_checked(&market_bids_info)?;
+                let asks_orders = market_state.load_asks_checked(&market_asks_info)?;
+                (bids, asks) = get_amm_orders(&open_orders, bids_orders, asks_orders)?;
+                (total_pc_without_take_pnl, total_coin_without_take_pnl) =
+                    Calculator::calc_total_without_take_pnl(
+                        amm_pc_vault.amount,
+                        amm_coin_vault.amount,
+                        &open_orders,
+                        &amm,
+                        &market_state,
+                        &market_event_queue_info,
+                        &amm_open_orders_info,
+                    )?;
+            }
+            MarketBackend::OpenBookV2 => {
+                let (market, open_orders) = load_openbook_v2_market_order(
+                    market_info,
+                    amm_open_orders_info,
+                    amm_authority_info,
+                    &amm,
+                )?;
+                (total_pc_without_take_pnl, total_coin_without_take_pnl) =
+                    Calculator::calc_total_without_take_pnl_openbook_v2(
+                        amm_pc_vault.amount,
+                        amm_coin_vault.amount,
+                        &open_orders,
+                        &amm,
+                        &market,
+                    )?;
+                open_orders_v2 = Some(open_orders);
+            }
+        }
     } else {
         (total_pc_without_take_pnl, total_coin_without_take_pnl) =
             Calculator::calc_total_without_take_pnl_no_orderbook(
@@ -187,6 +219,11 @@ pub fn process_swap_base_in(
                 &amm,
             )?;
     }
+    amm.update_price_cumulative(
+        total_pc_without_take_pnl,
+        total_coin_without_take_pnl,
+        Clock::get()?.unix_timestamp as u64,
+    );
 
     let swap_direction;
     if user_source.mint == amm_coin_vault.mint && user_destination.mint == amm_pc_vault.mint {
@@ -212,7 +249,7 @@ pub fn process_swap_base_in(
         return Err(AmmError::InsufficientFunds.into());
     }
     let swap_fee = U128::from(swap.amount_in)
-        .checked_mul(amm.fees.swap_fee_numerator.into())
+        .checked_mul(amm.swap_fee_numerator(Clock::get()?.unix_timestamp as u64).into())
         .unwrap()
         .checked_ceil_div(amm.fees.swap_fee_denominator.into())
         .unwrap()
@@ -241,6 +278,27 @@ pub fn process_swap_base_in(
     if swap_amount_out == 0 || swap.amount_in == 0 {
         return Err(AmmError::InvalidInput.into());
     }
+    check_allowlist_swap(
+        program_id,
+        &amm,
+        amm_info.key,
+        allowlist_entry_info,
+        user_source_owner.key,
+        match swap_direction {
+            SwapDirection::Coin2PC => swap.amount_in,
+            SwapDirection::PC2Coin => swap_amount_out,
+        },
+        Clock::get()?.unix_timestamp as u64,
+    )?;
+    check_circuit_breaker(
+        &mut amm,
+        swap_in_after_deduct_fee,
+        swap_amount_out,
+        total_pc_without_take_pnl,
+        total_coin_without_take_pnl,
+        swap_direction,
+        Clock::get()?.slot,
+    )?;
 
     match swap_direction {
         SwapDirection::Coin2PC => {
@@ -248,7 +306,23 @@ pub fn process_swap_base_in(
                 return Err(AmmError::InsufficientFunds.into());
             }
 
-            if enable_orderbook {
+            if let Some(open_orders_v2) = open_orders_v2.as_ref() {
+                // OpenBook v2 funds are settled by the monitor step, the swap is paid from
+                // the amm vault and only takes the crossing side off the book
+                if open_orders_v2.orders.iter().any(|order| order.side == Side::Bid) {
+                    Invokers::invoke_openbook_v2_cancel_all_orders(
+                        market_program_info.clone(),
+                        market_info.clone(),
+                        market_bids_info.clone(),
+                        market_asks_info.clone(),
+                        amm_open_orders_info.clone(),
+                        amm_authority_info.clone(),
+                        AUTHORITY_AMM,
+                        amm.nonce as u8,
+                        Some(Side::Bid),
+                    )?;
+                }
+            } else if enable_orderbook {
                 // coin -> pc, need cancel buy order
                 if !bids.is_empty() {
                     let mut amm_order_ids_vec = Vec::new();
@@ -349,7 +423,23 @@ pub fn process_swap_base_in(
                 return Err(AmmError::InsufficientFunds.into());
             }
 
-            if enable_orderbook {
+            if let Some(open_orders_v2) = open_orders_v2.as_ref() {
+                // OpenBook v2 funds are settled by the monitor step, the swap is paid from
+                // the amm vault and only takes the crossing side off the book
+                if open_orders_v2.orders.iter().any(|order| order.side == Side::Ask) {
+                    Invokers::invoke_openbook_v2_cancel_all_orders(
+                        market_program_info.clone(),
+                        market_info.clone(),
+                        market_bids_info.clone(),
+                        market_asks_info.clone(),
+                        amm_open_orders_info.clone(),
+                        amm_authority_info.clone(),
+                        AUTHORITY_AMM,
+                        amm.nonce as u8,
+                        Some(Side::Ask),
+                    )?;
+                }
+            } else if enable_orderbook {
                 // pc -> coin, need cancel sell order
                 if !asks.is_empty() {
                     let mut amm_order_ids_vec = Vec::new();
@@ -445,7 +535,43 @@ pub fn process_swap_base_in(
                 .unwrap();
         }
     };
+    let (pc_after, coin_after) = match swap_direction {
+        SwapDirection::Coin2PC => (
+            total_pc_without_take_pnl.checked_sub(swap_amount_out).unwrap(),
+            total_coin_without_take_pnl.checked_add(swap.amount_in).unwrap(),
+        ),
+        SwapDirection::PC2Coin => (
+            total_pc_without_take_pnl.checked_add(swap.amount_in).unwrap(),
+            total_coin_without_take_pnl.checked_sub(swap_amount_out).unwrap(),
+        ),
+    };
+    amm.update_volatility_ema(
+        total_pc_without_take_pnl,
+        total_coin_without_take_pnl,
+        pc_after,
+        coin_after,
+    );
+    let (coin_in, pc_in, coin_out, pc_out, coin_fee, pc_fee) = match swap_direction {
+        SwapDirection::Coin2PC => (swap.amount_in, 0, 0, swap_amount_out, swap_fee.as_u64(), 0),
+        SwapDirection::PC2Coin => (0, swap.amount_in, swap_amount_out, 0, 0, swap_fee.as_u64()),
+    };
+    encode_return_data(AmmReturnData {
+        log_type: crate::log::LogType::SwapBaseIn.into_u8(),
+        coin_in,
+        pc_in,
+        coin_out,
+        pc_out,
+        coin_fee,
+        pc_fee,
+        pool_coin: coin_after,
+        pool_pc: pc_after,
+        pool_lp: amm.lp_amount,
+        ..Default::default()
+    });
     amm.recent_epoch = Clock::get()?.epoch;
+    let lp_amount = amm.lp_amount;
+    drop(amm);
+    accrue_fee_growth(program_id, amm_info, coin_fee, pc_fee, lp_amount)?;
 
     Ok(())
 }
@@ -457,7 +583,10 @@ pub fn process_swap_base_out(
 ) -> ProgramResult {
     const SWAP_ACCOUNT_NUM: usize = 19;
     let input_account_len = accounts.len();
-    if input_account_len != SWAP_ACCOUNT_NUM && input_account_len != SWAP_ACCOUNT_NUM + 1 {
+    if input_account_len != SWAP_ACCOUNT_NUM
+        && input_account_len != SWAP_ACCOUNT_NUM + 1
+        && input_account_len != SWAP_ACCOUNT_NUM + 2
+    {
         return Err(AmmError::WrongAccountsNumber.into());
     }
     let account_info_iter = &mut accounts.iter();
@@ -466,7 +595,7 @@ pub fn process_swap_base_out(
     let amm_info = next_account_info(account_info_iter)?;
     let amm_authority_info = next_account_info(account_info_iter)?;
     let amm_open_orders_info = next_account_info(account_info_iter)?;
-    if input_account_len == SWAP_ACCOUNT_NUM + 1 {
+    if input_account_len >= SWAP_ACCOUNT_NUM + 1 {
         let _amm_target_orders_info = next_account_info(account_info_iter)?;
     }
     let amm_coin_vault_info = next_account_info(account_info_iter)?;
@@ -495,6 +624,7 @@ pub fn process_swap_base_out(
     let user_source_info = next_account_info(account_info_iter)?;
     let user_destination_info = next_account_info(account_info_iter)?;
     let user_source_owner = next_account_info(account_info_iter)?;
+    let allowlist_entry_info = next_account_info(account_info_iter).ok();
     if !user_source_owner.is_signer {
         return Err(AmmError::InvalidSignAccount.into());
     }
@@ -568,6 +698,7 @@ pub fn process_swap_base_out(
     let total_coin_without_take_pnl;
     let mut bids: Vec<LeafNode> = Vec::new();
     let mut asks: Vec<LeafNode> = Vec::new();
+    let mut open_orders_v2: Option<Box<OpenOrdersV2>> = None;
     if enable_orderbook {
         check_assert_eq!(
             *amm_open_orders_info.key,
@@ -587,26 +718,47 @@ pub fn process_swap_base_out(
             "market",
             AmmError::InvalidMarket
         );
-        let (market_state, open_orders) = load_serum_market_order(
-            market_info,
-            amm_open_orders_info,
-            amm_authority_info,
-            &amm,
-            false,
-        )?;
-        let bids_orders = market_state.load_bids_checked(&market_bids_info)?;
-        let asks_orders = market_state.load_asks_checked(&market_asks_info)?;
-        (bids, asks) = get_amm_orders(&open_orders, bids_orders, asks_orders)?;
-        (total_pc_without_take_pnl, total_coin_without_take_pnl) =
-            Calculator::calc_total_without_take_pnl(
-                amm_pc_vault.amount,
-                amm_coin_vault.amount,
-                &open_orders,
-                &amm,
-                &market_state,
-                &market_event_queue_info,
-                &amm_open_orders_info,
-            )?;
+        match market_backend(&amm.market_program)? {
+            MarketBackend::OpenBookV1 => {
+                let (market_state, open_orders) = load_serum_market_order(
+                    market_info,
+                    amm_open_orders_info,
+                    amm_authority_info,
+                    &amm,
+                    false,
+                )?;
+                let bids_orders = market_state.load_bids_checked(&market_bids_info)?;
+                let asks_orders = market_state.load_asks_checked(&market_asks_info)?;
+                (bids, asks) = get_amm_orders(&open_orders, bids_orders, asks_orders)?;
+                (total_pc_without_take_pnl, total_coin_without_take_pnl) =
+                    Calculator::calc_total_without_take_pnl(
+                        amm_pc_vault.amount,
+                        amm_coin_vault.amount,
+                        &open_orders,
+                        &amm,
+                        &market_state,
+                        &market_event_queue_info,
+                        &amm_open_orders_info,
+                    )?;
+            }
+            MarketBackend::OpenBookV2 => {
+                let (market, open_orders) = load_openbook_v2_market_order(
+                    market_info,
+                    amm_open_orders_info,
+                    amm_authority_info,
+                    &amm,
+                )?;
+                (total_pc_without_take_pnl, total_coin_without_take_pnl) =
+                    Calculator::calc_total_without_take_pnl_openbook_v2(
+                        amm_pc_vault.amount,
+                        amm_coin_vault.amount,
+                        &open_orders,
+                        &amm,
+                        &market,
+                    )?;
+                open_orders_v2 = Some(open_orders);
+            }
+        }
     } else {
         (total_pc_without_take_pnl, total_coin_without_take_pnl) =
             Calculator::calc_total_without_take_pnl_no_orderbook(
@@ -615,6 +767,11 @@ pub fn process_swap_base_out(
                 &amm,
             )?;
     }
+    amm.update_price_cumulative(
+        total_pc_without_take_pnl,
+        total_coin_without_take_pnl,
+        Clock::get()?.unix_timestamp as u64,
+    );
 
     let swap_direction;
     if user_source.mint == amm_coin_vault.mint && user_destination.mint == amm_pc_vault.mint {
@@ -635,13 +792,14 @@ pub fn process_swap_base_out(
     );
     // swap_in_after_add_fee * (1 - 0.0025) = swap_in_before_add_fee
     // swap_in_after_add_fee = swap_in_before_add_fee / (1 - 0.0025)
+    let swap_fee_numerator = amm.swap_fee_numerator(Clock::get()?.unix_timestamp as u64);
     let swap_in_after_add_fee = swap_in_before_add_fee
         .checked_mul(amm.fees.swap_fee_denominator.into())
         .unwrap()
         .checked_ceil_div(
             (amm.fees
                 .swap_fee_denominator
-                .checked_sub(amm.fees.swap_fee_numerator)
+                .checked_sub(swap_fee_numerator)
                 .unwrap())
             .into(),
         )
@@ -670,6 +828,27 @@ pub fn process_swap_base_out(
     if swap_in_after_add_fee == 0 || swap.amount_out == 0 {
         return Err(AmmError::InvalidInput.into());
     }
+    check_allowlist_swap(
+        program_id,
+        &amm,
+        amm_info.key,
+        allowlist_entry_info,
+        user_source_owner.key,
+        match swap_direction {
+            SwapDirection::Coin2PC => swap_in_after_add_fee,
+            SwapDirection::PC2Coin => swap.amount_out,
+        },
+        Clock::get()?.unix_timestamp as u64,
+    )?;
+    check_circuit_breaker(
+        &mut amm,
+        swap_in_before_add_fee,
+        swap.amount_out,
+        total_pc_without_take_pnl,
+        total_coin_without_take_pnl,
+        swap_direction,
+        Clock::get()?.slot,
+    )?;
 
     match swap_direction {
         SwapDirection::Coin2PC => {
@@ -677,7 +856,23 @@ pub fn process_swap_base_out(
                 return Err(AmmError::InsufficientFunds.into());
             }
 
-            if enable_orderbook {
+            if let Some(open_orders_v2) = open_orders_v2.as_ref() {
+                // OpenBook v2 funds are settled by the monitor step, the swap is paid from
+                // the amm vault and only takes the crossing side off the book
+                if open_orders_v2.orders.iter().any(|order| order.side == Side::Bid) {
+                    Invokers::invoke_openbook_v2_cancel_all_orders(
+                        market_program_info.clone(),
+                        market_info.clone(),
+                        market_bids_info.clone(),
+                        market_asks_info.clone(),
+                        amm_open_orders_info.clone(),
+                        amm_authority_info.clone(),
+                        AUTHORITY_AMM,
+                        amm.nonce as u8,
+                        Some(Side::Bid),
+                    )?;
+                }
+            } else if enable_orderbook {
                 // coin -> pc, need cancel buy order
                 if !bids.is_empty() {
                     let mut amm_order_ids_vec = Vec::new();
@@ -777,7 +972,23 @@ pub fn process_swap_base_out(
                 return Err(AmmError::InsufficientFunds.into());
             }
 
-            if enable_orderbook {
+            if let Some(open_orders_v2) = open_orders_v2.as_ref() {
+                // OpenBook v2 funds are settled by the monitor step, the swap is paid from
+                // the amm vault and only takes the crossing side off the book
+                if open_orders_v2.orders.iter().any(|order| order.side == Side::Ask) {
+                    Invokers::invoke_openbook_v2_cancel_all_orders(
+                        market_program_info.clone(),
+                        market_info.clone(),
+                        market_bids_info.clone(),
+                        market_asks_info.clone(),
+                        amm_open_orders_info.clone(),
+                        amm_authority_info.clone(),
+                        AUTHORITY_AMM,
+                        amm.nonce as u8,
+                        Some(Side::Ask),
+                    )?;
+                }
+            } else if enable_orderbook {
                 // pc -> coin, need cancel sell order
                 if !asks.is_empty() {
                     let mut amm_order_ids_vec = Vec::new();
@@ -873,7 +1084,43 @@ pub fn process_swap_base_out(
                 .unwrap();
         }
     };
+    let (pc_after, coin_after) = match swap_direction {
+        SwapDirection::Coin2PC => (
+            total_pc_without_take_pnl.checked_sub(swap.amount_out).unwrap(),
+            total_coin_without_take_pnl.checked_add(swap_in_after_add_fee).unwrap(),
+        ),
+        SwapDirection::PC2Coin => (
+            total_pc_without_take_pnl.checked_add(swap_in_after_add_fee).unwrap(),
+            total_coin_without_take_pnl.checked_sub(swap.amount_out).unwrap(),
+        ),
+    };
+    amm.update_volatility_ema(
+        total_pc_without_take_pnl,
+        total_coin_without_take_pnl,
+        pc_after,
+        coin_after,
+    );
+    let (coin_in, pc_in, coin_out, pc_out, coin_fee, pc_fee) = match swap_direction {
+        SwapDirection::Coin2PC => (swap_in_after_add_fee, 0, 0, swap.amount_out, swap_fee, 0),
+        SwapDirection::PC2Coin => (0, swap_in_after_add_fee, swap.amount_out, 0, 0, swap_fee),
+    };
+    encode_return_data(AmmReturnData {
+        log_type: crate::log::LogType::SwapBaseOut.into_u8(),
+        coin_in,
+        pc_in,
+        coin_out,
+        pc_out,
+        coin_fee,
+        pc_fee,
+        pool_coin: coin_after,
+        pool_pc: pc_after,
+        pool_lp: amm.lp_amount,
+        ..Default::default()
+    });
     amm.recent_epoch = Clock::get()?.epoch;
+    let lp_amount = amm.lp_amount;
+    drop(amm);
+    accrue_fee_growth(program_id, amm_info, coin_fee, pc_fee, lp_amount)?;
 
     Ok(())
 } 
\ No newline at end of file
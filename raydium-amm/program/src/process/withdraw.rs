@@ -15,11 +15,13 @@ use crate::{
 };
 use crate::process::constants::AUTHORITY_AMM;
 use crate::process::config;
-use crate::process::helpers::{identity, authority_id, unpack_token_account, unpack_mint, load_serum_market_order, calc_take_pnl, get_amm_orders};
+use crate::process::helpers::{
+    authority_id, calc_take_pnl, cancel_all_and_settle_pool_orders, unpack_mint,
+    unpack_token_account,
+};
 use crate::process::args::LogType;
 use crate::log::WithdrawLog;
-use serum_dex::state::ToAlignedBytes;
-use crate::log::{log_keys_mismatch, encode_ray_log};
+use crate::log::{log_keys_mismatch, encode_ray_log, encode_return_data, AmmReturnData};
 use crate::check_assert_eq;
 
 /// The number of accounts expected for a withdraw instruction.
@@ -166,112 +168,38 @@ pub fn process_withdraw(
     }
 
     let (mut total_pc_without_take_pnl, mut total_coin_without_take_pnl) = if enable_orderbook {
-        // Validate market accounts
-        check_assert_eq!(
-            *market_info.key,
-            amm.market,
-            "market",
-            AmmError::InvalidMarket
-        );
-        check_assert_eq!(
-            *market_program_info.key,
-            amm.market_program,
-            "market_program",
-            AmmError::InvalidMarketProgram
-        );
         check_assert_eq!(
             *amm_open_orders_info.key,
             amm.open_orders,
             "open_orders",
             AmmError::InvalidOpenOrders
         );
-
-        // Load market state and orders
-        let (market_state, open_orders) = load_serum_market_order(
-            market_info,
-            amm_open_orders_info,
-            amm_authority_info,
-            &amm,
-            false,
-        )?;
-
-        let bids_orders = market_state.load_bids_checked(&market_bids_info)?;
-        let asks_orders = market_state.load_asks_checked(&market_asks_info)?;
-        let (bids, asks) = get_amm_orders(&open_orders, bids_orders, asks_orders)?;
-
-        // Cancel all orders and settle funds
-        let mut amm_order_ids_vec = Vec::new();
-        let mut order_ids = [0u64; 8];
-        let mut count = 0;
-        for i in 0..std::cmp::max(bids.len(), asks.len()) {
-            if i < bids.len() {
-                order_ids[count] = bids[i].client_order_id();
-                count += 1;
-            }
-            if i < asks.len() {
-                order_ids[count] = asks[i].client_order_id();
-                count += 1;
-            }
-            if count == 8 {
-                amm_order_ids_vec.push(order_ids);
-                order_ids = [0u64; 8];
-                count = 0;
-            }
-        }
-        if count != 0 {
-            amm_order_ids_vec.push(order_ids);
-        }
-
-        for ids in amm_order_ids_vec.iter() {
-            Invokers::invoke_dex_cancel_orders_by_client_order_ids(
-                market_program_info.clone(),
-                market_info.clone(),
-                market_bids_info.clone(),
-                market_asks_info.clone(),
-                amm_open_orders_info.clone(),
-                amm_authority_info.clone(),
-                market_event_q_info.clone(),
-                AUTHORITY_AMM,
-                amm.nonce as u8,
-                *ids,
-            )?;
-        }
-
-        Invokers::invoke_dex_settle_funds(
-            market_program_info.clone(),
-            market_info.clone(),
-            amm_open_orders_info.clone(),
-            amm_authority_info.clone(),
-            market_coin_vault_info.clone(),
-            market_pc_vault_info.clone(),
-            amm_coin_vault_info.clone(),
-            amm_pc_vault_info.clone(),
-            market_vault_signer.clone(),
-            token_program_info.clone(),
-            referrer_pc_wallet.clone(),
-            AUTHORITY_AMM,
-            amm.nonce as u8,
-        )?;
-
-        if identity(market_state.coin_mint) != amm_coin_vault.mint.to_aligned_bytes()
-            || identity(market_state.coin_mint) != user_dest_coin.mint.to_aligned_bytes()
-        {
+        if user_dest_coin.mint != amm_coin_vault.mint {
             return Err(AmmError::InvalidCoinMint.into());
         }
-        if identity(market_state.pc_mint) != amm_pc_vault.mint.to_aligned_bytes()
-            || identity(market_state.pc_mint) != user_dest_pc.mint.to_aligned_bytes()
-        {
+        if user_dest_pc.mint != amm_pc_vault.mint {
             return Err(AmmError::InvalidPCMint.into());
         }
 
-        Calculator::calc_total_without_take_pnl(
+        // Cancel all orders and settle funds, on whichever market backend the pool is bound to
+        cancel_all_and_settle_pool_orders(
+            &amm,
             amm_pc_vault.amount,
             amm_coin_vault.amount,
-            &open_orders,
-            &amm,
-            &market_state,
-            &market_event_q_info,
-            &amm_open_orders_info,
+            market_program_info,
+            market_info,
+            market_bids_info,
+            market_asks_info,
+            market_event_q_info,
+            market_coin_vault_info,
+            market_pc_vault_info,
+            market_vault_signer,
+            amm_open_orders_info,
+            amm_authority_info,
+            amm_coin_vault_info,
+            amm_pc_vault_info,
+            token_program_info,
+            referrer_pc_wallet,
         )?
     } else {
         Calculator::calc_total_without_take_pnl_no_orderbook(
@@ -305,6 +233,11 @@ pub fn process_withdraw(
             y1.as_u128().into(),
         )?;
     }
+    amm.update_price_cumulative(
+        total_pc_without_take_pnl,
+        total_coin_without_take_pnl,
+        Clock::get()?.unix_timestamp as u64,
+    );
 
     // Calculate withdrawal amounts
     let invariant = InvariantPool {
@@ -409,6 +342,16 @@ pub fn process_withdraw(
         .unwrap()
         .as_u128();
 
+    encode_return_data(AmmReturnData {
+        log_type: crate::log::LogType::Withdraw.into_u8(),
+        coin_out: coin_amount,
+        pc_out: pc_amount,
+        lp_burn: withdraw.amount,
+        pool_coin: total_coin_without_take_pnl.checked_sub(coin_amount).unwrap(),
+        pool_pc: total_pc_without_take_pnl.checked_sub(pc_amount).unwrap(),
+        pool_lp: amm.lp_amount,
+        ..Default::default()
+    });
     amm.recent_epoch = Clock::get()?.epoch;
     Ok(())
 } 
\ No newline at end of file
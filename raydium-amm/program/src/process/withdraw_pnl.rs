@@ -8,124 +8,323 @@ use solana_program::{
 use crate::{
     error::AmmError,
     invokers::Invokers,
-    state::AmmInfo,
+    log::{encode_ray_log, LogType, WithdrawPnlLog},
+    state::{AmmConfig, AmmInfo, AmmStatus, MarketBackend, TargetOrders, AMM_INFO_VERSION_V2},
 };
-use crate::process::constants::AUTHORITY_AMM;
-use crate::process::helpers::{authority_id, unpack_token_account};
+use crate::check_assert_eq;
+use crate::log::log_keys_mismatch;
+use crate::process::constants::{
+    get_amm_owner_id, get_referrer_pc_wallet_id, AMM_CONFIG_SEED, AUTHORITY_AMM,
+};
+use crate::process::helpers::{
+    authority_id, compound_pnl, get_associated_address_and_bump_seed,
+    load_openbook_v2_market_order, load_serum_market_order, market_backend, raise_calc_pnl,
+    unpack_token_account,
+};
+
+/// [token_program_info, amm_info, amm_config_info, amm_authority_info, amm_open_orders_info, amm_coin_vault_info, amm_pc_vault_info, treasury_coin_info, treasury_pc_info, pnl_owner_info, amm_target_orders_info, market_program_info, market_info, market_event_queue_info, market_coin_vault_info, market_pc_vault_info, market_vault_signer, buyback_coin_info, buyback_pc_info] = 19 accounts
+const WITHDRAW_PNL_ACCOUNT_LEN: usize = 19;
 
+/// Pays the unclaimed protocol pnl out by the AmmConfig split: the treasury and buyback
+/// shares are transferred, the lp rebate stays in the vaults for the lp holders
 pub fn process_withdrawpnl(
     program_id: &Pubkey,
     accounts: &[AccountInfo],
 ) -> ProgramResult {
+    if accounts.len() < WITHDRAW_PNL_ACCOUNT_LEN {
+        return Err(AmmError::WrongAccountsNumber.into());
+    }
     let account_info_iter = &mut accounts.iter();
+    let token_program_info = next_account_info(account_info_iter)?;
     let amm_info = next_account_info(account_info_iter)?;
+    let amm_config_info = next_account_info(account_info_iter)?;
     let amm_authority_info = next_account_info(account_info_iter)?;
+    let amm_open_orders_info = next_account_info(account_info_iter)?;
     let amm_coin_vault_info = next_account_info(account_info_iter)?;
     let amm_pc_vault_info = next_account_info(account_info_iter)?;
-    let user_dest_coin_info = next_account_info(account_info_iter)?;
-    let user_dest_pc_info = next_account_info(account_info_iter)?;
-    let token_program_info = next_account_info(account_info_iter)?;
-    let user_wallet_info = next_account_info(account_info_iter)?;
+    let treasury_coin_info = next_account_info(account_info_iter)?;
+    let treasury_pc_info = next_account_info(account_info_iter)?;
+    let pnl_owner_info = next_account_info(account_info_iter)?;
+    let amm_target_orders_info = next_account_info(account_info_iter)?;
+    let market_program_info = next_account_info(account_info_iter)?;
+    let market_info = next_account_info(account_info_iter)?;
+    let market_event_queue_info = next_account_info(account_info_iter)?;
+    let market_coin_vault_info = next_account_info(account_info_iter)?;
+    let market_pc_vault_info = next_account_info(account_info_iter)?;
+    let market_vault_signer = next_account_info(account_info_iter)?;
+    let buyback_coin_info = next_account_info(account_info_iter)?;
+    let buyback_pc_info = next_account_info(account_info_iter)?;
+    let referrer_pc_info = next_account_info(account_info_iter).ok();
 
-    if !user_wallet_info.is_signer {
+    if !pnl_owner_info.is_signer {
         return Err(AmmError::InvalidSignAccount.into());
     }
 
-    let mut amm = AmmInfo::load_mut_checked(&amm_info, program_id)?;
-
-    // Check authority
-    let authority = authority_id(program_id, AUTHORITY_AMM, amm.nonce as u8)?;
-    if *amm_authority_info.key != authority {
-        return Err(AmmError::InvalidProgramAddress.into());
-    }
-
     // Check token program
     if *token_program_info.key != spl_token::id() {
         return Err(AmmError::InvalidSplTokenProgram.into());
     }
 
-    // Check vaults
-    if *amm_coin_vault_info.key != amm.coin_vault {
-        return Err(AmmError::InvalidCoinVault.into());
+    // Only the pnl owner of the amm config withdraws the pnl
+    let amm_owner = get_amm_owner_id()?;
+    let (amm_config_address, _) =
+        get_associated_address_and_bump_seed(&amm_owner, &amm_owner, AMM_CONFIG_SEED, program_id);
+    if *amm_config_info.key != amm_config_address {
+        return Err(AmmError::InvalidConfigAccount.into());
     }
-    if *amm_pc_vault_info.key != amm.pc_vault {
-        return Err(AmmError::InvalidPCVault.into());
+    let amm_config = AmmConfig::load_checked(amm_config_info, program_id)?;
+    if *pnl_owner_info.key != amm_config.pnl_owner {
+        return Err(AmmError::InvalidOwner.into());
     }
 
-    // Unpack token accounts
-    let amm_coin_vault = unpack_token_account(&amm_coin_vault_info, token_program_info.key)?;
-    let amm_pc_vault = unpack_token_account(&amm_pc_vault_info, token_program_info.key)?;
-    let user_dest_coin = unpack_token_account(&user_dest_coin_info, token_program_info.key)?;
-    let user_dest_pc = unpack_token_account(&user_dest_pc_info, token_program_info.key)?;
+    let mut amm = AmmInfo::load_mut_checked(&amm_info, program_id)?;
 
-    // Check user destination accounts
-    if user_dest_coin.owner != *user_wallet_info.key {
-        return Err(AmmError::InvalidOwner.into());
-    }
-    if user_dest_pc.owner != *user_wallet_info.key {
-        return Err(AmmError::InvalidOwner.into());
+    // Check authority
+    let authority = authority_id(program_id, AUTHORITY_AMM, amm.nonce as u8)?;
+    if *amm_authority_info.key != authority {
+        return Err(AmmError::InvalidProgramAddress.into());
     }
 
-    // Check mints
-    if user_dest_coin.mint != amm_coin_vault.mint {
-        return Err(AmmError::InvalidCoinMint.into());
+    // Check amm and market accounts
+    check_assert_eq!(
+        *amm_coin_vault_info.key,
+        amm.coin_vault,
+        "coin_vault",
+        AmmError::InvalidCoinVault
+    );
+    check_assert_eq!(
+        *amm_pc_vault_info.key,
+        amm.pc_vault,
+        "pc_vault",
+        AmmError::InvalidPCVault
+    );
+    check_assert_eq!(
+        *market_program_info.key,
+        amm.market_program,
+        "market_program",
+        AmmError::InvalidMarketProgram
+    );
+    check_assert_eq!(*market_info.key, amm.market, "market", AmmError::InvalidMarket);
+    if *amm_open_orders_info.key != amm.open_orders {
+        return Err(AmmError::InvalidOpenOrders.into());
     }
-    if user_dest_pc.mint != amm_pc_vault.mint {
-        return Err(AmmError::InvalidPCMint.into());
+    if *amm_target_orders_info.key != amm.target_orders {
+        return Err(AmmError::InvalidTargetOrders.into());
     }
 
-    // Calculate PnL amounts
-    let coin_pnl = if amm.state_data.total_pnl_coin > 0 {
-        amm.state_data.total_pnl_coin
-    } else {
-        0
-    };
-
-    let pc_pnl = if amm.state_data.total_pnl_pc > 0 {
-        amm.state_data.total_pnl_pc
-    } else {
-        0
-    };
+    // Pools with auto_compound on leave the whole pnl in the vaults for the lp
+    drop(amm);
+    let mut target_orders =
+        TargetOrders::load_mut_checked(&amm_target_orders_info, program_id, amm_info.key)?;
+    if compound_pnl(program_id, amm_info, &mut target_orders)? {
+        return Ok(());
+    }
+    let mut amm = AmmInfo::load_mut_checked(&amm_info, program_id)?;
 
-    if coin_pnl == 0 && pc_pnl == 0 {
+    // Split the unclaimed pnl
+    if amm.state_data.need_take_pnl_coin == 0 && amm.state_data.need_take_pnl_pc == 0 {
         return Err(AmmError::InsufficientFunds.into());
     }
+    let (treasury_coin, buyback_coin, lp_rebate_coin) =
+        amm_config.split_pnl(amm.state_data.need_take_pnl_coin);
+    let (treasury_pc, buyback_pc, lp_rebate_pc) =
+        amm_config.split_pnl(amm.state_data.need_take_pnl_pc);
 
-    // Transfer PnL tokens
-    if coin_pnl > 0 {
-        Invokers::token_transfer_with_authority(
-            program_id,
-            token_program_info.clone(),
-            amm_coin_vault_info.clone(),
-            user_dest_coin_info.clone(),
-            amm_authority_info.clone(),
-            AUTHORITY_AMM,
-            amm.nonce as u8,
-            coin_pnl,
-            amm_coin_vault_info.clone(),
-            &[],
-        )?;
+    // Check the recipients
+    let treasury_coin_token = unpack_token_account(treasury_coin_info, token_program_info.key)?;
+    let treasury_pc_token = unpack_token_account(treasury_pc_info, token_program_info.key)?;
+    check_assert_eq!(
+        treasury_coin_token.owner,
+        amm_config.treasury(),
+        "treasury_coin_owner",
+        AmmError::InvalidOwner
+    );
+    check_assert_eq!(
+        treasury_pc_token.owner,
+        amm_config.treasury(),
+        "treasury_pc_owner",
+        AmmError::InvalidOwner
+    );
+    check_assert_eq!(
+        treasury_coin_token.mint,
+        amm.coin_vault_mint,
+        "treasury_coin_mint",
+        AmmError::InvalidCoinMint
+    );
+    check_assert_eq!(
+        treasury_pc_token.mint,
+        amm.pc_vault_mint,
+        "treasury_pc_mint",
+        AmmError::InvalidPCMint
+    );
+    // The buyback accounts are placeholders when the config has no buyback share
+    if buyback_coin != 0 || buyback_pc != 0 {
+        let buyback_coin_token = unpack_token_account(buyback_coin_info, token_program_info.key)?;
+        let buyback_pc_token = unpack_token_account(buyback_pc_info, token_program_info.key)?;
+        check_assert_eq!(
+            buyback_coin_token.owner,
+            amm_config.pnl_buyback,
+            "buyback_coin_owner",
+            AmmError::InvalidOwner
+        );
+        check_assert_eq!(
+            buyback_pc_token.owner,
+            amm_config.pnl_buyback,
+            "buyback_pc_owner",
+            AmmError::InvalidOwner
+        );
+        check_assert_eq!(
+            buyback_coin_token.mint,
+            amm.coin_vault_mint,
+            "buyback_coin_mint",
+            AmmError::InvalidCoinMint
+        );
+        check_assert_eq!(
+            buyback_pc_token.mint,
+            amm.pc_vault_mint,
+            "buyback_pc_mint",
+            AmmError::InvalidPCMint
+        );
     }
+    drop(amm_config);
 
-    if pc_pnl > 0 {
+    // Settle the free funds of the open orders so the vaults can pay the pnl
+    let enable_orderbook = AmmStatus::from_u64(amm.status).orderbook_permission();
+    if enable_orderbook {
+        let referrer_pc_wallet = match referrer_pc_info {
+            Some(referrer_pc_info) => {
+                let referrer_pc_token =
+                    unpack_token_account(referrer_pc_info, token_program_info.key)?;
+                check_assert_eq!(
+                    referrer_pc_token.owner,
+                    get_referrer_pc_wallet_id()?,
+                    "referrer_pc_owner",
+                    AmmError::InvalidOwner
+                );
+                Some(referrer_pc_info)
+            }
+            None => None,
+        };
+        // checks the open orders belong to the market and the amm authority
+        match market_backend(&amm.market_program)? {
+            MarketBackend::OpenBookV1 => {
+                load_serum_market_order(
+                    market_info,
+                    amm_open_orders_info,
+                    amm_authority_info,
+                    &amm,
+                    false,
+                )?;
+                Invokers::invoke_dex_settle_funds(
+                    market_program_info.clone(),
+                    market_info.clone(),
+                    amm_open_orders_info.clone(),
+                    amm_authority_info.clone(),
+                    market_coin_vault_info.clone(),
+                    market_pc_vault_info.clone(),
+                    amm_coin_vault_info.clone(),
+                    amm_pc_vault_info.clone(),
+                    market_vault_signer.clone(),
+                    token_program_info.clone(),
+                    referrer_pc_wallet,
+                    AUTHORITY_AMM,
+                    amm.nonce as u8,
+                )?;
+            }
+            // the market authority comes in the vault signer slot, the system program in the
+            // event queue slot
+            MarketBackend::OpenBookV2 => {
+                load_openbook_v2_market_order(
+                    market_info,
+                    amm_open_orders_info,
+                    amm_authority_info,
+                    &amm,
+                )?;
+                Invokers::invoke_openbook_v2_settle_funds(
+                    market_program_info.clone(),
+                    market_info.clone(),
+                    amm_open_orders_info.clone(),
+                    amm_authority_info.clone(),
+                    market_vault_signer.clone(),
+                    market_coin_vault_info.clone(),
+                    market_pc_vault_info.clone(),
+                    amm_coin_vault_info.clone(),
+                    amm_pc_vault_info.clone(),
+                    token_program_info.clone(),
+                    market_event_queue_info.clone(),
+                    referrer_pc_wallet,
+                    AUTHORITY_AMM,
+                    amm.nonce as u8,
+                )?;
+            }
+        }
+    }
+
+    for (amount, vault_info, destination_info) in [
+        (treasury_coin, amm_coin_vault_info, treasury_coin_info),
+        (treasury_pc, amm_pc_vault_info, treasury_pc_info),
+        (buyback_coin, amm_coin_vault_info, buyback_coin_info),
+        (buyback_pc, amm_pc_vault_info, buyback_pc_info),
+    ] {
+        if amount == 0 {
+            continue;
+        }
         Invokers::token_transfer_with_authority(
             program_id,
             token_program_info.clone(),
-            amm_pc_vault_info.clone(),
-            user_dest_pc_info.clone(),
+            vault_info.clone(),
+            destination_info.clone(),
             amm_authority_info.clone(),
             AUTHORITY_AMM,
             amm.nonce as u8,
-            pc_pnl,
-            amm_pc_vault_info.clone(),
+            amount,
+            vault_info.clone(),
             &[],
         )?;
     }
 
-    // Reset PnL amounts
-    amm.state_data.total_pnl_coin = 0;
-    amm.state_data.total_pnl_pc = 0;
+    // The whole unclaimed pnl is settled, the lp rebate is left to the lp holders
+    amm.state_data.need_take_pnl_coin = 0;
+    amm.state_data.need_take_pnl_pc = 0;
+    raise_calc_pnl(&mut target_orders, &amm, lp_rebate_coin, lp_rebate_pc);
+    drop(target_orders);
+
+    let (mut claimed_coin, mut claimed_pc) = (0u64, 0u64);
+    if amm.version() >= AMM_INFO_VERSION_V2 {
+        drop(amm);
+        let (_amm, mut extension) =
+            AmmInfo::load_mut_checked_with_extension(amm_info, program_id)?;
+        extension.pnl_claimed_coin = extension
+            .pnl_claimed_coin
+            .saturating_add(treasury_coin + buyback_coin);
+        extension.pnl_claimed_pc = extension
+            .pnl_claimed_pc
+            .saturating_add(treasury_pc + buyback_pc);
+        extension.pnl_rebated_coin = extension.pnl_rebated_coin.saturating_add(lp_rebate_coin);
+        extension.pnl_rebated_pc = extension.pnl_rebated_pc.saturating_add(lp_rebate_pc);
+        claimed_coin = extension.pnl_claimed_coin;
+        claimed_pc = extension.pnl_claimed_pc;
+    }
 
-    msg!("PnL withdrawn successfully: {} coin, {} pc", coin_pnl, pc_pnl);
+    encode_ray_log(WithdrawPnlLog {
+        log_type: LogType::WithdrawPnl.into_u8(),
+        treasury_coin,
+        treasury_pc,
+        buyback_coin,
+        buyback_pc,
+        lp_rebate_coin,
+        lp_rebate_pc,
+        claimed_coin,
+        claimed_pc,
+    });
+    msg!(
+        "pnl withdrawn, treasury:{}/{}, buyback:{}/{}, lp rebate:{}/{}",
+        treasury_coin,
+        treasury_pc,
+        buyback_coin,
+        buyback_pc,
+        lp_rebate_coin,
+        lp_rebate_pc
+    );
     Ok(())
-} 
\ No newline at end of file
+}
@@ -0,0 +1,370 @@
+//! Handles the single sided withdraw (zap-out) instruction logic for Raydium AMM
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    clock::Clock,
+    entrypoint::ProgramResult,
+    pubkey::Pubkey,
+    sysvar::Sysvar,
+};
+use crate::{
+    error::AmmError,
+    instruction::WithdrawSingleSidedInstruction,
+    invokers::Invokers,
+    math::{Calculator, SwapDirection, U128},
+    state::{AmmInfo, AmmStatus, TargetOrders},
+};
+use crate::process::constants::AUTHORITY_AMM;
+use crate::process::helpers::{
+    accrue_fee_growth, calc_take_pnl, calculate_single_sided_withdrawal,
+    cancel_all_and_settle_pool_orders, check_circuit_breaker, unpack_mint, unpack_token_account,
+    validate_lp_withdrawal, validate_single_sided_withdraw_slippage,
+    validate_withdraw_permissions,
+};
+use crate::log::{
+    encode_ray_log, encode_return_data, log_keys_mismatch, AmmReturnData, LogType,
+    WithdrawSingleSidedLog,
+};
+use crate::check_assert_eq;
+
+/// The number of accounts expected for a single sided withdraw instruction.
+/// This is based on the order of next_account_info calls in the function:
+/// [token_program_info, amm_info, amm_authority_info, amm_open_orders_info, amm_target_orders_info, amm_lp_mint_info, amm_coin_vault_info, amm_pc_vault_info, dest_mint_info, market_program_info, market_info, market_coin_vault_info, market_pc_vault_info, market_vault_signer, user_source_lp_info, user_dest_info, source_lp_owner_info, market_event_q_info, market_bids_info, market_asks_info]
+/// = 20 accounts
+const ACCOUNT_LEN: usize = 20;
+
+pub fn process_withdraw_single_sided(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    withdraw: WithdrawSingleSidedInstruction,
+) -> ProgramResult {
+    if accounts.len() != ACCOUNT_LEN {
+        return Err(AmmError::WrongAccountsNumber.into());
+    }
+    let account_info_iter = &mut accounts.iter();
+    let token_program_info = next_account_info(account_info_iter)?;
+    let amm_info = next_account_info(account_info_iter)?;
+    let amm_authority_info = next_account_info(account_info_iter)?;
+    let amm_open_orders_info = next_account_info(account_info_iter)?;
+    let amm_target_orders_info = next_account_info(account_info_iter)?;
+    let amm_lp_mint_info = next_account_info(account_info_iter)?;
+    let amm_coin_vault_info = next_account_info(account_info_iter)?;
+    let amm_pc_vault_info = next_account_info(account_info_iter)?;
+    let dest_mint_info = next_account_info(account_info_iter)?;
+
+    let market_program_info = next_account_info(account_info_iter)?;
+    let market_info = next_account_info(account_info_iter)?;
+    let market_coin_vault_info = next_account_info(account_info_iter)?;
+    let market_pc_vault_info = next_account_info(account_info_iter)?;
+    let market_vault_signer = next_account_info(account_info_iter)?;
+
+    let user_source_lp_info = next_account_info(account_info_iter)?;
+    let user_dest_info = next_account_info(account_info_iter)?;
+    let source_lp_owner_info = next_account_info(account_info_iter)?;
+
+    let market_event_q_info = next_account_info(account_info_iter)?;
+    let market_bids_info = next_account_info(account_info_iter)?;
+    let market_asks_info = next_account_info(account_info_iter)?;
+
+    let mut amm = AmmInfo::load_mut_checked(&amm_info, program_id)?;
+    let mut target_orders =
+        TargetOrders::load_mut_checked(&amm_target_orders_info, program_id, amm_info.key)?;
+
+    validate_withdraw_permissions(&amm, amm_authority_info, source_lp_owner_info, program_id)?;
+    // the other side is swapped back into the pool
+    if !AmmStatus::from_u64(amm.status).swap_permission()
+        || (amm.status == AmmStatus::WaitingTrade.into_u64()
+            && (Clock::get()?.unix_timestamp as u64) < amm.state_data.pool_open_time)
+    {
+        return Err(AmmError::InvalidStatus.into());
+    }
+    // the internal swap has no allowlist cap
+    if amm.allowlist_active(Clock::get()?.unix_timestamp as u64) {
+        return Err(AmmError::NotAllowlisted.into());
+    }
+    let enable_orderbook = AmmStatus::from_u64(amm.status).orderbook_permission();
+    let spl_token_program_id = token_program_info.key;
+    check_assert_eq!(
+        *token_program_info.key,
+        spl_token::id(),
+        "spl_token_program",
+        AmmError::InvalidSplTokenProgram
+    );
+    check_assert_eq!(
+        *amm_coin_vault_info.key,
+        amm.coin_vault,
+        "coin_vault",
+        AmmError::InvalidCoinVault
+    );
+    check_assert_eq!(
+        *amm_pc_vault_info.key,
+        amm.pc_vault,
+        "pc_vault",
+        AmmError::InvalidPCVault
+    );
+    if *user_dest_info.key == amm.coin_vault || *user_dest_info.key == amm.pc_vault {
+        return Err(AmmError::InvalidUserToken.into());
+    }
+    check_assert_eq!(
+        *amm_target_orders_info.key,
+        amm.target_orders,
+        "target_orders",
+        AmmError::InvalidTargetOrders
+    );
+    check_assert_eq!(
+        *amm_lp_mint_info.key,
+        amm.lp_mint,
+        "lp_mint",
+        AmmError::InvalidPoolMint
+    );
+
+    let amm_coin_vault = unpack_token_account(&amm_coin_vault_info, spl_token_program_id)?;
+    let amm_pc_vault = unpack_token_account(&amm_pc_vault_info, spl_token_program_id)?;
+    let user_dest = unpack_token_account(&user_dest_info, spl_token_program_id)?;
+    // the user receives the output of swapping the other side back into the pool
+    let swap_direction = if user_dest.mint == amm_pc_vault.mint {
+        SwapDirection::Coin2PC
+    } else if user_dest.mint == amm_coin_vault.mint {
+        SwapDirection::PC2Coin
+    } else {
+        return Err(AmmError::InvalidUserToken.into());
+    };
+    if *dest_mint_info.key != user_dest.mint {
+        return Err(AmmError::InvalidUserToken.into());
+    }
+
+    let lp_mint = unpack_mint(&amm_lp_mint_info, spl_token_program_id)?;
+    let user_source_lp = unpack_token_account(&user_source_lp_info, spl_token_program_id)?;
+    if user_source_lp.mint != *amm_lp_mint_info.key {
+        return Err(AmmError::InvalidTokenLP.into());
+    }
+    validate_lp_withdrawal(
+        withdraw.amount,
+        user_source_lp.amount,
+        lp_mint.supply,
+        amm.lp_amount,
+    )?;
+
+    let (mut total_pc_without_take_pnl, mut total_coin_without_take_pnl) = if enable_orderbook {
+        check_assert_eq!(
+            *amm_open_orders_info.key,
+            amm.open_orders,
+            "open_orders",
+            AmmError::InvalidOpenOrders
+        );
+        cancel_all_and_settle_pool_orders(
+            &amm,
+            amm_pc_vault.amount,
+            amm_coin_vault.amount,
+            market_program_info,
+            market_info,
+            market_bids_info,
+            market_asks_info,
+            market_event_q_info,
+            market_coin_vault_info,
+            market_pc_vault_info,
+            market_vault_signer,
+            amm_open_orders_info,
+            amm_authority_info,
+            amm_coin_vault_info,
+            amm_pc_vault_info,
+            token_program_info,
+            Some(amm_pc_vault_info),
+        )?
+    } else {
+        Calculator::calc_total_without_take_pnl_no_orderbook(
+            amm_pc_vault.amount,
+            amm_coin_vault.amount,
+            &amm,
+        )?
+    };
+
+    let x1 = Calculator::normalize_decimal_v2(
+        total_pc_without_take_pnl,
+        amm.pc_decimals,
+        amm.sys_decimal_value,
+    );
+    let y1 = Calculator::normalize_decimal_v2(
+        total_coin_without_take_pnl,
+        amm.coin_decimals,
+        amm.sys_decimal_value,
+    );
+    let (delta_x, delta_y) = calc_take_pnl(
+        &target_orders,
+        &mut amm,
+        &mut total_pc_without_take_pnl,
+        &mut total_coin_without_take_pnl,
+        x1.as_u128().into(),
+        y1.as_u128().into(),
+    )?;
+    amm.update_price_cumulative(
+        total_pc_without_take_pnl,
+        total_coin_without_take_pnl,
+        Clock::get()?.unix_timestamp as u64,
+    );
+
+    // pro rata share of both sides, the unwanted side is swapped back into the pool
+    let (coin_amount, pc_amount, swap_fee, swap_amount_out) = calculate_single_sided_withdrawal(
+        withdraw.amount,
+        amm.lp_amount,
+        total_coin_without_take_pnl,
+        total_pc_without_take_pnl,
+        amm.swap_fee_numerator(Clock::get()?.unix_timestamp as u64),
+        amm.fees.swap_fee_denominator,
+        swap_direction,
+    )?;
+    let pc_left = total_pc_without_take_pnl.checked_sub(pc_amount).unwrap();
+    let coin_left = total_coin_without_take_pnl.checked_sub(coin_amount).unwrap();
+    let swap_amount_in = match swap_direction {
+        SwapDirection::Coin2PC => coin_amount,
+        SwapDirection::PC2Coin => pc_amount,
+    };
+    let swap_in_after_deduct_fee = U128::from(swap_amount_in.checked_sub(swap_fee).unwrap());
+    let (amount_out, coin_out, pc_out, coin_fee, pc_fee) = match swap_direction {
+        SwapDirection::Coin2PC => {
+            let amount_out = pc_amount.checked_add(swap_amount_out).unwrap();
+            (amount_out, 0, amount_out, swap_fee, 0)
+        }
+        SwapDirection::PC2Coin => {
+            let amount_out = coin_amount.checked_add(swap_amount_out).unwrap();
+            (amount_out, amount_out, 0, 0, swap_fee)
+        }
+    };
+
+    encode_ray_log(WithdrawSingleSidedLog {
+        log_type: LogType::WithdrawSingleSided.into_u8(),
+        withdraw_lp: withdraw.amount,
+        minimum_out: withdraw.min_amount_out,
+        direction: swap_direction as u64,
+        user_lp: user_source_lp.amount,
+        pool_coin: total_coin_without_take_pnl,
+        pool_pc: total_pc_without_take_pnl,
+        pool_lp: amm.lp_amount,
+        out_coin: coin_amount,
+        out_pc: pc_amount,
+        swap_out: swap_amount_out,
+    });
+    validate_single_sided_withdraw_slippage(&withdraw, amount_out)?;
+    check_circuit_breaker(
+        &mut amm,
+        swap_in_after_deduct_fee,
+        swap_amount_out,
+        pc_left,
+        coin_left,
+        swap_direction,
+        Clock::get()?.slot,
+    )?;
+
+    let (amm_vault_info, dest_vault_total) = match swap_direction {
+        SwapDirection::Coin2PC => (amm_pc_vault_info, total_pc_without_take_pnl),
+        SwapDirection::PC2Coin => (amm_coin_vault_info, total_coin_without_take_pnl),
+    };
+    // orders were settled above, so the vault holds the settled funds
+    let amm_vault = unpack_token_account(&amm_vault_info, spl_token_program_id)?;
+    if amount_out >= dest_vault_total || amount_out >= amm_vault.amount {
+        return Err(AmmError::TakePnlError.into());
+    }
+    Invokers::token_transfer_with_authority(
+        program_id,
+        token_program_info.clone(),
+        amm_vault_info.clone(),
+        user_dest_info.clone(),
+        amm_authority_info.clone(),
+        AUTHORITY_AMM,
+        amm.nonce as u8,
+        amount_out,
+        dest_mint_info.clone(),
+        &[],
+    )?;
+    Invokers::token_burn(
+        token_program_info.clone(),
+        user_source_lp_info.clone(),
+        amm_lp_mint_info.clone(),
+        source_lp_owner_info.clone(),
+        withdraw.amount,
+    )?;
+    // the fee of the internal swap goes to the lp held before this withdraw
+    let lp_amount_before = amm.lp_amount;
+    amm.lp_amount = amm.lp_amount.checked_sub(withdraw.amount).unwrap();
+
+    // the internal swap is accounted like a regular one
+    match swap_direction {
+        SwapDirection::Coin2PC => {
+            amm.state_data.swap_coin_in_amount = amm
+                .state_data
+                .swap_coin_in_amount
+                .checked_add(swap_amount_in.into())
+                .unwrap();
+            amm.state_data.swap_pc_out_amount = amm
+                .state_data
+                .swap_pc_out_amount
+                .checked_add(swap_amount_out.into())
+                .unwrap();
+            amm.state_data.swap_acc_coin_fee = amm
+                .state_data
+                .swap_acc_coin_fee
+                .checked_add(coin_fee)
+                .unwrap();
+        }
+        SwapDirection::PC2Coin => {
+            amm.state_data.swap_pc_in_amount = amm
+                .state_data
+                .swap_pc_in_amount
+                .checked_add(swap_amount_in.into())
+                .unwrap();
+            amm.state_data.swap_coin_out_amount = amm
+                .state_data
+                .swap_coin_out_amount
+                .checked_add(swap_amount_out.into())
+                .unwrap();
+            amm.state_data.swap_acc_pc_fee = amm
+                .state_data
+                .swap_acc_pc_fee
+                .checked_add(pc_fee)
+                .unwrap();
+        }
+    };
+    let pc_after = total_pc_without_take_pnl.checked_sub(pc_out).unwrap();
+    let coin_after = total_coin_without_take_pnl.checked_sub(coin_out).unwrap();
+    amm.update_volatility_ema(
+        total_pc_without_take_pnl,
+        total_coin_without_take_pnl,
+        pc_after,
+        coin_after,
+    );
+    target_orders.calc_pnl_x = x1
+        .checked_sub(Calculator::normalize_decimal_v2(
+            pc_out,
+            amm.pc_decimals,
+            amm.sys_decimal_value,
+        ))
+        .unwrap()
+        .checked_sub(U128::from(delta_x))
+        .unwrap()
+        .as_u128();
+    target_orders.calc_pnl_y = y1
+        .checked_sub(Calculator::normalize_decimal_v2(
+            coin_out,
+            amm.coin_decimals,
+            amm.sys_decimal_value,
+        ))
+        .unwrap()
+        .checked_sub(U128::from(delta_y))
+        .unwrap()
+        .as_u128();
+    encode_return_data(AmmReturnData {
+        log_type: LogType::WithdrawSingleSided.into_u8(),
+        coin_out,
+        pc_out,
+        coin_fee,
+        pc_fee,
+        lp_burn: withdraw.amount,
+        pool_coin: coin_after,
+        pool_pc: pc_after,
+        pool_lp: amm.lp_amount,
+        ..Default::default()
+    });
+    amm.recent_epoch = Clock::get()?.epoch;
+    drop(amm);
+    accrue_fee_growth(program_id, amm_info, coin_fee, pc_fee, lp_amount_before)?;
+    Ok(())
+}
@@ -9,16 +9,22 @@ use crate::{
     error::AmmError,
     instruction::WithdrawSrmInstruction,
     invokers::Invokers,
-    state::AmmInfo,
+    state::{AmmInfo, AmmRole},
 };
 use crate::process::constants::AUTHORITY_AMM;
-use crate::process::helpers::{authority_id, unpack_token_account};
+use crate::process::helpers::{authority_id, check_amm_role, unpack_token_account};
+
+/// [amm_info, amm_authority_info, amm_srm_vault_info, user_dest_srm_info, token_program_info, user_wallet_info, amm_config_info] = 7 accounts
+const WITHDRAW_SRM_ACCOUNT_LEN: usize = 7;
 
 pub fn process_withdraw_srm(
     program_id: &Pubkey,
     accounts: &[AccountInfo],
     withdrawsrm: WithdrawSrmInstruction,
 ) -> ProgramResult {
+    if accounts.len() != WITHDRAW_SRM_ACCOUNT_LEN {
+        return Err(AmmError::WrongAccountsNumber.into());
+    }
     let account_info_iter = &mut accounts.iter();
     let amm_info = next_account_info(account_info_iter)?;
     let amm_authority_info = next_account_info(account_info_iter)?;
@@ -26,12 +32,20 @@ pub fn process_withdraw_srm(
     let user_dest_srm_info = next_account_info(account_info_iter)?;
     let token_program_info = next_account_info(account_info_iter)?;
     let user_wallet_info = next_account_info(account_info_iter)?;
+    let amm_config_info = next_account_info(account_info_iter)?;
 
     if !user_wallet_info.is_signer {
         return Err(AmmError::InvalidSignAccount.into());
     }
+    check_amm_role(
+        program_id,
+        AmmRole::FeeAdmin,
+        user_wallet_info,
+        amm_info,
+        amm_config_info,
+    )?;
 
-    let mut amm = AmmInfo::load_mut_checked(&amm_info, program_id)?;
+    let amm = AmmInfo::load_checked(&amm_info, program_id)?;
 
     // Check authority
     let authority = authority_id(program_id, AUTHORITY_AMM, amm.nonce as u8)?;
@@ -48,6 +62,14 @@ pub fn process_withdraw_srm(
     let amm_srm_vault = unpack_token_account(&amm_srm_vault_info, token_program_info.key)?;
     let user_dest_srm = unpack_token_account(&user_dest_srm_info, token_program_info.key)?;
 
+    // The (M)SRM account belongs to the amm authority and is never one of the pool vaults
+    if *amm_srm_vault_info.key == amm.coin_vault
+        || *amm_srm_vault_info.key == amm.pc_vault
+        || amm_srm_vault.owner != authority
+    {
+        return Err(AmmError::InvalidSrmToken.into());
+    }
+
     // Check user destination account
     if user_dest_srm.owner != *user_wallet_info.key {
         return Err(AmmError::InvalidOwner.into());
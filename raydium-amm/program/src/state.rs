@@ -4,6 +4,7 @@ use crate::{error::AmmError, math::Calculator};
 use serum_dex::state::ToAlignedBytes;
 use solana_program::{
     account_info::AccountInfo,
+    hash::hashv,
     program_error::ProgramError,
     program_pack::{IsInitialized, Pack, Sealed},
     pubkey::Pubkey,
@@ -22,6 +23,20 @@ use std::{
 
 pub const TEN_THOUSAND: u64 = 10000;
 pub const MAX_ORDER_LIMIT: usize = 10;
+/// volatility_ema denominator, 1_000_000 => 100% price move
+pub const VOLATILITY_PRECISION: u64 = 1_000_000;
+/// volatility_ema at which the dynamic swap fee reaches its upper bound, 20_000 => 2%
+pub const DYNAMIC_FEE_VOLATILITY_CAP: u64 = 20_000;
+/// weight of the latest price move in volatility_ema, TEN_THOUSAND as denominator
+pub const VOLATILITY_EMA_ALPHA: u64 = 2_000;
+/// AmmInfo only, pools created before the version tag read 0 and are treated as this version
+pub const AMM_INFO_VERSION_V1: u16 = 1;
+/// AmmInfo followed by AmmInfoExtension
+pub const AMM_INFO_VERSION_V2: u16 = 2;
+/// AmmInfo followed by AmmInfoExtension and AmmRoles
+pub const AMM_INFO_VERSION_V3: u16 = 3;
+/// version of new pools and the target of MigratePoolAccount
+pub const AMM_INFO_VERSION_CURRENT: u16 = AMM_INFO_VERSION_V3;
 
 #[cfg(not(test))]
 pub fn get_recent_epoch() -> Result<u64, ProgramError> {
@@ -109,7 +124,9 @@ pub struct TargetOrders {
     pub valid_buy_order_num: u64,
     pub valid_sell_order_num: u64,
 
-    pub padding3: [u64; 10],
+    /// highest client order id AdminCancelOrders cancelled so far, 0 once every order is gone
+    pub admin_cancel_client_id: u64,
+    pub padding3: [u64; 9],
 
     pub free_slot_bits: u128,
 }
@@ -143,7 +160,8 @@ impl Default for TargetOrders {
             place_orders_cur: 0,
             valid_buy_order_num: 0,
             valid_sell_order_num: 0,
-            padding3: [0; 10],
+            admin_cancel_client_id: 0,
+            padding3: [0; 9],
             free_slot_bits: std::u128::MAX,
         }
     }
@@ -388,6 +406,12 @@ pub enum AmmParams {
     ClearOpenTime = 15u64,
     Seperate = 16u64,
     UpdateOpenOrder = 17u64,
+    DynamicFee = 18u64,
+    CircuitBreaker = 19u64,
+    LadderStrategy = 20u64,
+    CrankReward = 21u64,
+    AutoCompound = 22u64,
+    PoolRole = 23u64,
 }
 impl AmmParams {
     pub fn from_u64(state: u64) -> Self {
@@ -410,6 +434,12 @@ impl AmmParams {
             15u64 => AmmParams::ClearOpenTime,
             16u64 => AmmParams::Seperate,
             17u64 => AmmParams::UpdateOpenOrder,
+            18u64 => AmmParams::DynamicFee,
+            19u64 => AmmParams::CircuitBreaker,
+            20u64 => AmmParams::LadderStrategy,
+            21u64 => AmmParams::CrankReward,
+            22u64 => AmmParams::AutoCompound,
+            23u64 => AmmParams::PoolRole,
             _ => unreachable!(),
         }
     }
@@ -434,8 +464,93 @@ impl AmmParams {
             AmmParams::ClearOpenTime => 15u64,
             AmmParams::Seperate => 16u64,
             AmmParams::UpdateOpenOrder => 17u64,
+            AmmParams::DynamicFee => 18u64,
+            AmmParams::CircuitBreaker => 19u64,
+            AmmParams::LadderStrategy => 20u64,
+            AmmParams::CrankReward => 21u64,
+            AmmParams::AutoCompound => 22u64,
+            AmmParams::PoolRole => 23u64,
         }
     }
+
+    /// role SetParams checks for the param
+    pub fn role(&self) -> AmmRole {
+        match self {
+            AmmParams::Status | AmmParams::CircuitBreaker => AmmRole::PauseGuardian,
+            AmmParams::Fees
+            | AmmParams::DynamicFee
+            | AmmParams::CrankReward
+            | AmmParams::AutoCompound => AmmRole::FeeAdmin,
+            _ => AmmRole::PoolAdmin,
+        }
+    }
+}
+
+/// Admin roles, assigned program wide in the AmmConfig and overridden per pool in AmmRoles
+#[cfg_attr(feature = "client", derive(Debug))]
+#[derive(Copy, Clone, Eq, PartialEq)]
+#[repr(u64)]
+pub enum AmmRole {
+    /// order book params, open time, amm owner, PoolAdmin overrides, market migration and close
+    PoolAdmin = 0u64,
+    /// swap fees, dynamic fee, crank reward, auto compound and (M)SRM withdraw
+    FeeAdmin = 1u64,
+    /// pool status and circuit breaker
+    PauseGuardian = 2u64,
+    /// admin cancel of the pool orders
+    CrankOperator = 3u64,
+    /// launch allowlist
+    WhitelistAdmin = 4u64,
+}
+impl AmmRole {
+    /// The role comes from instruction data, an unknown one is rejected as InvalidInput
+    pub fn from_u64(flag: u64) -> Result<Self, AmmError> {
+        match flag {
+            0u64 => Ok(AmmRole::PoolAdmin),
+            1u64 => Ok(AmmRole::FeeAdmin),
+            2u64 => Ok(AmmRole::PauseGuardian),
+            3u64 => Ok(AmmRole::CrankOperator),
+            4u64 => Ok(AmmRole::WhitelistAdmin),
+            _ => Err(AmmError::InvalidInput),
+        }
+    }
+
+    pub fn into_u64(&self) -> u64 {
+        match self {
+            AmmRole::PoolAdmin => 0u64,
+            AmmRole::FeeAdmin => 1u64,
+            AmmRole::PauseGuardian => 2u64,
+            AmmRole::CrankOperator => 3u64,
+            AmmRole::WhitelistAdmin => 4u64,
+        }
+    }
+
+
+    /// error returned to a signer that does not hold the role
+    pub fn error(&self) -> AmmError {
+        match self {
+            AmmRole::PoolAdmin => AmmError::InvalidPoolAdmin,
+            AmmRole::FeeAdmin => AmmError::InvalidFeeAdmin,
+            AmmRole::PauseGuardian => AmmError::InvalidPauseGuardian,
+            AmmRole::CrankOperator => AmmError::InvalidCrankOperator,
+            AmmRole::WhitelistAdmin => AmmError::InvalidWhitelistAdmin,
+        }
+    }
+
+    /// key holding the role on a pool: the pool override, else the role of the amm config,
+    /// else the amm owner
+    pub fn holder(
+        &self,
+        amm_owner: &Pubkey,
+        pool_roles: &AmmRoles,
+        amm_config: &AmmConfig,
+    ) -> Pubkey {
+        [pool_roles.get(*self), amm_config.role(*self)]
+            .iter()
+            .copied()
+            .find(|holder| *holder != Pubkey::default())
+            .unwrap_or(*amm_owner)
+    }
 }
 
 #[cfg_attr(feature = "client", derive(Debug))]
@@ -462,6 +577,84 @@ impl AmmResetFlag {
     }
 }
 
+/// Orderbook program a pool's market lives on, picked from AmmInfo.market_program
+#[cfg_attr(feature = "client", derive(Debug))]
+#[derive(Copy, Clone, Eq, PartialEq)]
+#[repr(u64)]
+pub enum MarketBackend {
+    /// serum dex v3 and OpenBook v1, driven through serum_dex
+    OpenBookV1 = 0u64,
+    /// OpenBook v2, see crate::openbook_v2
+    OpenBookV2 = 1u64,
+}
+impl MarketBackend {
+    pub fn from_u64(flag: u64) -> Self {
+        match flag {
+            0u64 => MarketBackend::OpenBookV1,
+            1u64 => MarketBackend::OpenBookV2,
+            _ => unreachable!(),
+        }
+    }
+
+    pub fn into_u64(&self) -> u64 {
+        match self {
+            MarketBackend::OpenBookV1 => 0u64,
+            MarketBackend::OpenBookV2 => 1u64,
+        }
+    }
+}
+
+/// How the orderbook ladder spreads its levels between the pool price and depth% away from it
+#[cfg_attr(feature = "client", derive(Debug))]
+#[derive(Copy, Clone, Eq, PartialEq)]
+#[repr(u64)]
+pub enum LadderStrategy {
+    /// level i sits fib[i + 1] / fib[levels] of the depth away
+    Fibonacci = 0u64,
+    /// levels are evenly spaced
+    Linear = 1u64,
+    /// level i sits (2^(i + 1) - 1) / (2^levels - 1) of the depth away
+    Exponential = 2u64,
+    /// level i sits (i + 1)^3 / levels^3 of the depth away, most of the volume close to the price
+    ConcentratedNearMid = 3u64,
+}
+impl LadderStrategy {
+    pub fn from_u64(flag: u64) -> Self {
+        match flag {
+            0u64 => LadderStrategy::Fibonacci,
+            1u64 => LadderStrategy::Linear,
+            2u64 => LadderStrategy::Exponential,
+            3u64 => LadderStrategy::ConcentratedNearMid,
+            _ => unreachable!(),
+        }
+    }
+
+    pub fn into_u64(&self) -> u64 {
+        match self {
+            LadderStrategy::Fibonacci => 0u64,
+            LadderStrategy::Linear => 1u64,
+            LadderStrategy::Exponential => 2u64,
+            LadderStrategy::ConcentratedNearMid => 3u64,
+        }
+    }
+
+    pub fn valid(flag: u64) -> bool {
+        flag <= LadderStrategy::ConcentratedNearMid.into_u64()
+    }
+
+    /// weights w[0..=levels] with w[0] = 0, level i sits w[i + 1] / w[levels] of the depth away
+    pub fn weights(&self, levels: usize) -> Vec<u64> {
+        match self {
+            LadderStrategy::Fibonacci => Calculator::fibonacci(levels as u64 + 1),
+            LadderStrategy::Linear => (0..=levels as u64).collect(),
+            LadderStrategy::Exponential => (0..=levels as u32).map(|k| (1u64 << k) - 1).collect(),
+            LadderStrategy::ConcentratedNearMid => {
+                (0..=levels as u64).map(|k| k * k * k).collect()
+            }
+        }
+    }
+}
+
 fn validate_fraction(numerator: u64, denominator: u64) -> Result<(), AmmError> {
     if numerator >= denominator || denominator == 0 {
         Err(AmmError::InvalidFee)
@@ -587,14 +780,16 @@ pub struct StateData {
     pub need_take_pnl_coin: u64,
     /// delay to take pnl pc
     pub need_take_pnl_pc: u64,
-    /// total pnl pc
+    /// total pnl pc, less the pnl compounded into the pool
     pub total_pnl_pc: u64,
-    /// total pnl coin
+    /// total pnl coin, less the pnl compounded into the pool
     pub total_pnl_coin: u64,
     /// ido pool open time
     pub pool_open_time: u64,
-    /// padding for future updates
-    pub padding: [u64; 2],
+    /// ema of the price move caused by each swap, VOLATILITY_PRECISION as denominator
+    pub volatility_ema: u64,
+    /// unix timestamp of the last pc_price_cumulative/coin_price_cumulative update
+    pub price_last_update_time: u64,
     /// switch from orderbookonly to init
     pub orderbook_to_init_time: u64,
 
@@ -620,7 +815,8 @@ impl StateData {
         self.total_pnl_pc = 0u64;
         self.total_pnl_coin = 0u64;
         self.pool_open_time = open_time;
-        self.padding = Zeroable::zeroed();
+        self.volatility_ema = 0u64;
+        self.price_last_update_time = 0u64;
         self.orderbook_to_init_time = 0u64;
         self.swap_coin_in_amount = 0u128;
         self.swap_pc_out_amount = 0u128;
@@ -695,8 +891,36 @@ pub struct AmmInfo {
     pub market_program: Pubkey,
     /// target_orders key
     pub target_orders: Pubkey,
-    /// padding
-    pub padding1: [u64; 8],
+    /// dynamic swap fee lower bound, fees.swap_fee_denominator as denominator
+    pub dynamic_fee_min_numerator: u32,
+    /// dynamic swap fee upper bound, fees.swap_fee_denominator as denominator.
+    /// 0 means the dynamic fee is disabled and fees.swap_fee_numerator is charged
+    pub dynamic_fee_max_numerator: u32,
+    /// wrapping sum of the Q64.64 coin price in pc times the seconds it was held
+    pub pc_price_cumulative: u128,
+    /// wrapping sum of the Q64.64 pc price in coin times the seconds it was held
+    pub coin_price_cumulative: u128,
+    /// end of the allowlist phase that starts at state_data.pool_open_time,
+    /// only wallets registered in the allowlist account can swap before it. 0 means no allowlist
+    pub allowlist_end_time: u64,
+    /// swap fee numerator charged at state_data.pool_open_time, decays linearly to the
+    /// configured swap fee over launch_fee_decay_seconds. 0 means no launch fee
+    pub launch_fee_numerator: u32,
+    /// number of seconds after state_data.pool_open_time the launch fee decays over
+    pub launch_fee_decay_seconds: u32,
+    /// circuit breaker: max price impact of a swap, TEN_THOUSAND as denominator. 0 means no limit
+    pub max_price_impact_bps: u16,
+    /// circuit breaker: max output of a swap as a fraction of the output reserve,
+    /// TEN_THOUSAND as denominator. 0 means no limit
+    pub max_output_bps: u16,
+    /// circuit breaker: max output volume per slot as a fraction of the output reserve,
+    /// TEN_THOUSAND as denominator. 0 means no limit
+    pub max_slot_volume_bps: u16,
+    /// number of consecutive slots hitting max_slot_volume_bps that switch the pool
+    /// to WithdrawOnly. 0 means never
+    pub circuit_breaker_max_trips: u8,
+    /// consecutive slots that hit max_slot_volume_bps so far
+    pub circuit_breaker_trips: u8,
     /// amm owner key
     pub amm_owner: Pubkey,
     /// pool lp amount
@@ -705,14 +929,159 @@ pub struct AmmInfo {
     pub client_order_id: u64,
     /// recent epoch
     pub recent_epoch: u64,
-    /// padding
-    pub padding2: u64,
+    /// low 32 bits of the slot slot_volume_bps is accumulated for
+    pub circuit_breaker_slot: u32,
+    /// output volume swapped in circuit_breaker_slot, TEN_THOUSAND as denominator
+    pub slot_volume_bps: u16,
+    /// layout version of the account, see AmmInfo::version
+    pub version: u16,
 }
 impl_loadable!(AmmInfo);
 
+/// AmmInfo fields added from version 2 on, stored right after AmmInfo in the same account
+#[cfg_attr(feature = "client", derive(Debug))]
+#[repr(C, packed)]
+#[derive(Clone, Copy, Default, PartialEq)]
+pub struct AmmInfoExtension {
+    /// orderbook ladder strategy, see LadderStrategy
+    pub ladder_strategy: u64,
+    /// pc paid from the pnl to the monitor step caller per rewarded crank, 0 means no reward
+    pub crank_reward_pc: u64,
+    /// max pc paid as crank reward per epoch
+    pub crank_reward_epoch_cap: u64,
+    /// min slots between two rewarded cranks
+    pub crank_reward_slot_interval: u64,
+    /// epoch crank_reward_epoch_paid is accumulated for
+    pub crank_reward_epoch: u64,
+    /// pc paid as crank reward in crank_reward_epoch
+    pub crank_reward_epoch_paid: u64,
+    /// slot of the last rewarded crank
+    pub crank_reward_last_slot: u64,
+    /// coin pnl paid out by WithdrawPnl and the crank reward
+    pub pnl_claimed_coin: u64,
+    /// pc pnl paid out by WithdrawPnl and the crank reward
+    pub pnl_claimed_pc: u64,
+    /// coin pnl WithdrawPnl left in the vault for the lp
+    pub pnl_rebated_coin: u64,
+    /// pc pnl WithdrawPnl left in the vault for the lp
+    pub pnl_rebated_pc: u64,
+    /// 1 leaves the whole pnl in the vaults for the lp instead of paying it out
+    pub auto_compound: u64,
+    /// coin pnl compounded into the pool
+    pub pnl_compounded_coin: u64,
+    /// pc pnl compounded into the pool
+    pub pnl_compounded_pc: u64,
+    /// Q64.64 lp share of the coin swap fees per lp token, wraps
+    pub fee_growth_coin_per_lp: u128,
+    /// Q64.64 lp share of the pc swap fees per lp token, wraps
+    pub fee_growth_pc_per_lp: u128,
+    /// padding
+    pub padding: [u64; 14],
+}
+impl_loadable!(AmmInfoExtension);
+
+impl AmmInfoExtension {
+    /// Books the crank reward for a monitor step in (epoch, slot) and returns the pc to pay,
+    /// 0 while rate limited, once the epoch cap is reached or without pnl to pay it from
+    pub fn take_crank_reward(&mut self, epoch: u64, slot: u64, available_pnl_pc: u64) -> u64 {
+        if self.crank_reward_pc == 0 {
+            return 0;
+        }
+        if self.crank_reward_last_slot != 0
+            && slot < self.crank_reward_last_slot.saturating_add(self.crank_reward_slot_interval)
+        {
+            return 0;
+        }
+        if epoch != self.crank_reward_epoch {
+            self.crank_reward_epoch = epoch;
+            self.crank_reward_epoch_paid = 0;
+        }
+        let reward = std::cmp::min(
+            std::cmp::min(self.crank_reward_pc, available_pnl_pc),
+            self.crank_reward_epoch_cap.saturating_sub(self.crank_reward_epoch_paid),
+        );
+        if reward == 0 {
+            return 0;
+        }
+        self.crank_reward_epoch_paid += reward;
+        self.crank_reward_last_slot = slot;
+        reward
+    }
+
+    /// Adds the lp share of a swap fee to the fee growth per lp token
+    pub fn accrue_fee_growth(&mut self, coin_fee: u64, pc_fee: u64, lp_amount: u64) {
+        self.fee_growth_coin_per_lp = self
+            .fee_growth_coin_per_lp
+            .wrapping_add(Calculator::calc_fee_growth_x64(coin_fee, lp_amount));
+        self.fee_growth_pc_per_lp = self
+            .fee_growth_pc_per_lp
+            .wrapping_add(Calculator::calc_fee_growth_x64(pc_fee, lp_amount));
+    }
+
+    /// Compounds the unclaimed pnl into the pool when auto_compound is on: need_take_pnl_*
+    /// drops to 0 and total_pnl_* no longer counts it, so the pnl stays in the vaults for
+    /// the lp. Returns the (coin, pc) compounded
+    pub fn compound_pnl(&mut self, state_data: &mut StateData) -> (u64, u64) {
+        if self.auto_compound == 0 {
+            return (0, 0);
+        }
+        let (coin, pc) = (state_data.need_take_pnl_coin, state_data.need_take_pnl_pc);
+        state_data.need_take_pnl_coin = 0;
+        state_data.need_take_pnl_pc = 0;
+        state_data.total_pnl_coin = state_data.total_pnl_coin.saturating_sub(coin);
+        state_data.total_pnl_pc = state_data.total_pnl_pc.saturating_sub(pc);
+        self.pnl_compounded_coin = self.pnl_compounded_coin.saturating_add(coin);
+        self.pnl_compounded_pc = self.pnl_compounded_pc.saturating_add(pc);
+        (coin, pc)
+    }
+}
+
+/// Per pool role overrides, stored after the AmmInfoExtension from version 3 on.
+/// Pubkey::default() falls back to the role of the AmmConfig
+#[cfg_attr(feature = "client", derive(Debug))]
+#[repr(C, packed)]
+#[derive(Clone, Copy, Default, PartialEq)]
+pub struct AmmRoles {
+    /// see AmmRole::PoolAdmin
+    pub pool_admin: Pubkey,
+    /// see AmmRole::FeeAdmin
+    pub fee_admin: Pubkey,
+    /// see AmmRole::PauseGuardian
+    pub pause_guardian: Pubkey,
+    /// see AmmRole::CrankOperator
+    pub crank_operator: Pubkey,
+    /// see AmmRole::WhitelistAdmin
+    pub whitelist_admin: Pubkey,
+    /// padding
+    pub padding: [u64; 12],
+}
+impl_loadable!(AmmRoles);
+
+impl AmmRoles {
+    pub fn get(&self, role: AmmRole) -> Pubkey {
+        match role {
+            AmmRole::PoolAdmin => self.pool_admin,
+            AmmRole::FeeAdmin => self.fee_admin,
+            AmmRole::PauseGuardian => self.pause_guardian,
+            AmmRole::CrankOperator => self.crank_operator,
+            AmmRole::WhitelistAdmin => self.whitelist_admin,
+        }
+    }
+
+    pub fn set(&mut self, role: AmmRole, holder: Pubkey) {
+        match role {
+            AmmRole::PoolAdmin => self.pool_admin = holder,
+            AmmRole::FeeAdmin => self.fee_admin = holder,
+            AmmRole::PauseGuardian => self.pause_guardian = holder,
+            AmmRole::CrankOperator => self.crank_operator = holder,
+            AmmRole::WhitelistAdmin => self.whitelist_admin = holder,
+        }
+    }
+}
+
 impl AmmInfo {
     /// Helper function to get the more efficient packed size of the struct
-    /// load_mut_checked
+    /// load_mut_checked, accepts every supported version and maps the AmmInfo part
     #[inline]
     pub fn load_mut_checked<'a>(
         account: &'a AccountInfo,
@@ -721,17 +1090,22 @@ impl AmmInfo {
         if account.owner != program_id {
             return Err(AmmError::InvalidAmmAccountOwner.into());
         }
-        if account.data_len() != size_of::<Self>() {
+        if account.data_len() < size_of::<Self>() {
+            return Err(AmmError::ExpectedAccount.into());
+        }
+        let data = RefMut::map(account.try_borrow_mut_data()?, |data| {
+            from_bytes_mut::<Self>(&mut data[..size_of::<Self>()])
+        });
+        if Self::data_len(data.version()) != Some(account.data_len()) {
             return Err(AmmError::ExpectedAccount.into());
         }
-        let data = Self::load_mut(account)?;
         if data.status == AmmStatus::Uninitialized as u64 {
             return Err(AmmError::InvalidStatus.into());
         }
         Ok(data)
     }
 
-    /// load_checked
+    /// load_checked, accepts every supported version and maps the AmmInfo part
     #[inline]
     pub fn load_checked<'a>(
         account: &'a AccountInfo,
@@ -740,16 +1114,133 @@ impl AmmInfo {
         if account.owner != program_id {
             return Err(AmmError::InvalidAmmAccountOwner.into());
         }
-        if account.data_len() != size_of::<Self>() {
+        if account.data_len() < size_of::<Self>() {
+            return Err(AmmError::ExpectedAccount.into());
+        }
+        let data = Ref::map(account.try_borrow_data()?, |data| {
+            from_bytes::<Self>(&data[..size_of::<Self>()])
+        });
+        if Self::data_len(data.version()) != Some(account.data_len()) {
             return Err(AmmError::ExpectedAccount.into());
         }
-        let data = Self::load(account)?;
         if data.status == AmmStatus::Uninitialized as u64 {
             return Err(AmmError::InvalidStatus.into());
         }
         Ok(data)
     }
 
+    /// load_mut_checked together with the AmmInfoExtension, the pool must be migrated to
+    /// AMM_INFO_VERSION_V2 at least
+    pub fn load_mut_checked_with_extension<'a>(
+        account: &'a AccountInfo,
+        program_id: &Pubkey,
+    ) -> Result<(RefMut<'a, Self>, RefMut<'a, AmmInfoExtension>), ProgramError> {
+        let version = Self::load_checked(account, program_id)?.version();
+        if version < AMM_INFO_VERSION_V2 {
+            return Err(AmmError::OutdatedAmmAccount.into());
+        }
+        Ok(RefMut::map_split(
+            account.try_borrow_mut_data()?,
+            |data| {
+                let (amm, extension) = data.split_at_mut(size_of::<Self>());
+                (
+                    from_bytes_mut::<Self>(amm),
+                    from_bytes_mut::<AmmInfoExtension>(
+                        &mut extension[..size_of::<AmmInfoExtension>()],
+                    ),
+                )
+            },
+        ))
+    }
+
+    /// orderbook ladder strategy of the pool, pools before AMM_INFO_VERSION_V2 always
+    /// use the fibonacci ladder
+    pub fn load_ladder_strategy(
+        account: &AccountInfo,
+        program_id: &Pubkey,
+    ) -> Result<LadderStrategy, ProgramError> {
+        if Self::load_checked(account, program_id)?.version() < AMM_INFO_VERSION_V2 {
+            return Ok(LadderStrategy::Fibonacci);
+        }
+        let data = account.try_borrow_data()?;
+        let extension = from_bytes::<AmmInfoExtension>(
+            &data[size_of::<Self>()..size_of::<Self>() + size_of::<AmmInfoExtension>()],
+        );
+        Ok(LadderStrategy::from_u64(extension.ladder_strategy))
+    }
+
+    /// copy of the AmmInfoExtension, the pool must be migrated to AMM_INFO_VERSION_V2 at least
+    pub fn load_extension(
+        account: &AccountInfo,
+        program_id: &Pubkey,
+    ) -> Result<AmmInfoExtension, ProgramError> {
+        if Self::load_checked(account, program_id)?.version() < AMM_INFO_VERSION_V2 {
+            return Err(AmmError::OutdatedAmmAccount.into());
+        }
+        let data = account.try_borrow_data()?;
+        let extension = from_bytes::<AmmInfoExtension>(
+            &data[size_of::<Self>()..size_of::<Self>() + size_of::<AmmInfoExtension>()],
+        );
+        Ok(*extension)
+    }
+
+    /// copy of the pool role overrides, pools before AMM_INFO_VERSION_V3 override none
+    pub fn load_roles(
+        account: &AccountInfo,
+        program_id: &Pubkey,
+    ) -> Result<AmmRoles, ProgramError> {
+        if Self::load_checked(account, program_id)?.version() < AMM_INFO_VERSION_V3 {
+            return Ok(AmmRoles::default());
+        }
+        let offset = size_of::<Self>() + size_of::<AmmInfoExtension>();
+        let data = account.try_borrow_data()?;
+        Ok(*from_bytes::<AmmRoles>(
+            &data[offset..offset + size_of::<AmmRoles>()],
+        ))
+    }
+
+    /// load_mut of the pool role overrides, the pool must be migrated to AMM_INFO_VERSION_V3
+    /// at least
+    pub fn load_roles_mut<'a>(
+        account: &'a AccountInfo,
+        program_id: &Pubkey,
+    ) -> Result<RefMut<'a, AmmRoles>, ProgramError> {
+        if Self::load_checked(account, program_id)?.version() < AMM_INFO_VERSION_V3 {
+            return Err(AmmError::OutdatedAmmAccount.into());
+        }
+        let offset = size_of::<Self>() + size_of::<AmmInfoExtension>();
+        Ok(RefMut::map(account.try_borrow_mut_data()?, |data| {
+            from_bytes_mut::<AmmRoles>(&mut data[offset..offset + size_of::<AmmRoles>()])
+        }))
+    }
+
+    /// layout version, pools created before the version tag read 0 and are version 1
+    pub fn version(&self) -> u16 {
+        if self.version == 0 {
+            AMM_INFO_VERSION_V1
+        } else {
+            self.version
+        }
+    }
+
+    /// account data length of a layout version, None if the version is not supported
+    pub fn data_len(version: u16) -> Option<usize> {
+        match version {
+            AMM_INFO_VERSION_V1 => Some(size_of::<Self>()),
+            AMM_INFO_VERSION_V2 => Some(size_of::<Self>() + size_of::<AmmInfoExtension>()),
+            AMM_INFO_VERSION_V3 => {
+                Some(size_of::<Self>() + size_of::<AmmInfoExtension>() + size_of::<AmmRoles>())
+            }
+            _ => None,
+        }
+    }
+
+    /// layout version of an account data length, None if no supported version has it
+    pub fn version_of_data_len(data_len: usize) -> Option<u16> {
+        (AMM_INFO_VERSION_V1..=AMM_INFO_VERSION_CURRENT)
+            .find(|version| Self::data_len(*version) == Some(data_len))
+    }
+
     pub fn initialize(
         &mut self,
         nonce: u8,
@@ -805,52 +1296,550 @@ impl AmmInfo {
             )
             .unwrap();
         }
-        let min_size = (coin_lot_size as u128)
-            .checked_mul(self.sys_decimal_value as u128)
-            .unwrap()
-            .checked_div(
-                (10u128)
-                    .checked_pow(coin_decimals.try_into().unwrap())
-                    .unwrap(),
-            )
-            .unwrap();
-        if min_size < u64::max_value().into() {
-            self.min_size = Calculator::to_u64(min_size)?;
-        } else {
-            // must check not zero in process_monitor_step
-            self.min_size = 0;
+        self.vol_max_cut_ratio = 500; // TEN_THOUSAND as denominator
+        self.amount_wave = self
+            .sys_decimal_value
+            .checked_mul(5)
+            .unwrap()
+            .checked_div(1000)
+            .unwrap();
+        self.set_lot_sizes(coin_lot_size, pc_lot_size)?;
+        self.min_price_multiplier = 1;
+        self.max_price_multiplier = 1000000000;
+        self.client_order_id = 0;
+        self.dynamic_fee_min_numerator = 0;
+        self.dynamic_fee_max_numerator = 0;
+        self.pc_price_cumulative = 0;
+        self.coin_price_cumulative = 0;
+        self.allowlist_end_time = 0;
+        self.launch_fee_numerator = 0;
+        self.launch_fee_decay_seconds = 0;
+        self.max_price_impact_bps = 0;
+        self.max_output_bps = 0;
+        self.max_slot_volume_bps = 0;
+        self.circuit_breaker_max_trips = 0;
+        self.circuit_breaker_trips = 0;
+        self.recent_epoch = get_recent_epoch().unwrap();
+        self.circuit_breaker_slot = 0;
+        self.slot_volume_bps = 0;
+        self.version = AMM_INFO_VERSION_V1;
+
+        Ok(())
+    }
+
+    /// Sets the lot sizes of the market and the min_size following them,
+    /// sys_decimal_value must be set already
+    pub fn set_lot_sizes(&mut self, coin_lot_size: u64, pc_lot_size: u64) -> Result<(), AmmError> {
+        let min_size = (coin_lot_size as u128)
+            .checked_mul(self.sys_decimal_value as u128)
+            .unwrap()
+            .checked_div(
+                (10u128)
+                    .checked_pow(self.coin_decimals.try_into().unwrap())
+                    .unwrap(),
+            )
+            .unwrap();
+        if min_size < u64::max_value().into() {
+            self.min_size = Calculator::to_u64(min_size)?;
+        } else {
+            // must check not zero in process_monitor_step
+            self.min_size = 0;
+        }
+        self.coin_lot_size = coin_lot_size;
+        self.pc_lot_size = Calculator::convert_in_pc_lot_size(
+            self.pc_decimals as u8,
+            self.coin_decimals as u8,
+            pc_lot_size,
+            coin_lot_size,
+            self.sys_decimal_value,
+        );
+        Ok(())
+    }
+
+    pub fn dynamic_fee_enabled(&self) -> bool {
+        self.dynamic_fee_max_numerator != 0
+    }
+
+    /// a pool can be closed once Disabled, with every lp token burnt and the pnl withdrawn.
+    /// What the vaults still hold then is rounding left over by the withdrawals
+    pub fn check_closable(&self, lp_supply: u64) -> Result<(), AmmError> {
+        if self.status != AmmStatus::Disabled.into_u64() {
+            return Err(AmmError::InvalidStatus);
+        }
+        if lp_supply != 0
+            || self.lp_amount != 0
+            || self.state_data.need_take_pnl_coin != 0
+            || self.state_data.need_take_pnl_pc != 0
+        {
+            return Err(AmmError::PoolNotDrained);
+        }
+        Ok(())
+    }
+
+    /// only allowlisted wallets can swap until allowlist_end_time
+    pub fn allowlist_active(&self, now: u64) -> bool {
+        self.allowlist_end_time != 0 && now < self.allowlist_end_time
+    }
+
+    /// swap fee numerator charged by the next swap, fees.swap_fee_denominator as denominator
+    pub fn swap_fee_numerator(&self, now: u64) -> u64 {
+        let fee_numerator = if !self.dynamic_fee_enabled() {
+            self.fees.swap_fee_numerator
+        } else {
+            Calculator::calc_dynamic_fee_numerator(
+                self.dynamic_fee_min_numerator as u64,
+                self.dynamic_fee_max_numerator as u64,
+                self.state_data.volatility_ema,
+            )
+        };
+        if self.launch_fee_numerator == 0 {
+            return fee_numerator;
+        }
+        std::cmp::max(
+            fee_numerator,
+            Calculator::calc_launch_fee_numerator(
+                self.launch_fee_numerator as u64,
+                fee_numerator,
+                now.saturating_sub(self.state_data.pool_open_time),
+                self.launch_fee_decay_seconds as u64,
+            ),
+        )
+    }
+
+    /// check a swap against the circuit breaker limits, returns true once the pool
+    /// should be switched to WithdrawOnly.
+    /// A rejected swap reverts every account write, so the slot volume is a soft cap:
+    /// the swap that crosses it still goes through and counts the slot as a trip
+    pub fn check_circuit_breaker(
+        &mut self,
+        price_impact: u64,
+        amount_out: u64,
+        reserve_out: u64,
+        slot: u64,
+    ) -> Result<bool, AmmError> {
+        if self.max_price_impact_bps != 0 && price_impact > self.max_price_impact_bps as u64 {
+            return Err(AmmError::CircuitBreakerTripped);
+        }
+        let output_bps = Calculator::calc_output_bps(amount_out, reserve_out);
+        if self.max_output_bps != 0 && output_bps > self.max_output_bps as u64 {
+            return Err(AmmError::CircuitBreakerTripped);
+        }
+        if self.max_slot_volume_bps == 0 {
+            return Ok(false);
+        }
+        let max_slot_volume_bps = self.max_slot_volume_bps;
+        let slot = slot as u32;
+        if slot != self.circuit_breaker_slot {
+            // trips only add up over consecutive slots that all hit the limit
+            if self.slot_volume_bps < max_slot_volume_bps
+                || slot != self.circuit_breaker_slot.wrapping_add(1)
+            {
+                self.circuit_breaker_trips = 0;
+            }
+            self.circuit_breaker_slot = slot;
+            self.slot_volume_bps = 0;
+        }
+        if self.slot_volume_bps >= max_slot_volume_bps {
+            return Err(AmmError::CircuitBreakerTripped);
+        }
+        self.slot_volume_bps = self
+            .slot_volume_bps
+            .saturating_add(u16::try_from(output_bps).unwrap_or(u16::MAX));
+        if self.slot_volume_bps < max_slot_volume_bps {
+            return Ok(false);
+        }
+        self.circuit_breaker_trips = self.circuit_breaker_trips.saturating_add(1);
+        Ok(self.circuit_breaker_max_trips != 0
+            && self.circuit_breaker_trips >= self.circuit_breaker_max_trips)
+    }
+
+    /// fold the reserve change of a swap into state_data.volatility_ema,
+    /// only tracked while the dynamic fee is enabled
+    pub fn update_volatility_ema(
+        &mut self,
+        pc_before: u64,
+        coin_before: u64,
+        pc_after: u64,
+        coin_after: u64,
+    ) {
+        if !self.dynamic_fee_enabled() {
+            return;
+        }
+        self.state_data.volatility_ema = Calculator::calc_volatility_ema(
+            self.state_data.volatility_ema,
+            pc_before,
+            coin_before,
+            pc_after,
+            coin_after,
+        );
+    }
+
+    /// accumulate the price held since state_data.price_last_update_time,
+    /// must be called with the pool totals before they are changed
+    pub fn update_price_cumulative(&mut self, total_pc: u64, total_coin: u64, now: u64) {
+        let last_update_time = self.state_data.price_last_update_time;
+        if last_update_time != 0 && now > last_update_time {
+            let elapsed = u128::from(now - last_update_time);
+            self.pc_price_cumulative = self
+                .pc_price_cumulative
+                .wrapping_add(Calculator::calc_price_x64(total_pc, total_coin).wrapping_mul(elapsed));
+            self.coin_price_cumulative = self
+                .coin_price_cumulative
+                .wrapping_add(Calculator::calc_price_x64(total_coin, total_pc).wrapping_mul(elapsed));
+        }
+        if now > last_update_time {
+            self.state_data.price_last_update_time = now;
+        }
+    }
+
+    pub fn incr_client_order_id(&mut self) -> u64 {
+        self.client_order_id = self.client_order_id.wrapping_add(1);
+        if self.client_order_id == 0 {
+            self.client_order_id += 1;
+        }
+        self.client_order_id
+    }
+}
+
+pub const OBSERVATION_NUM: usize = 64;
+
+/// Snapshot of the amm price cumulatives
+#[repr(C, packed)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Observation {
+    /// state_data.price_last_update_time of the snapshot
+    pub timestamp: u64,
+    /// pc_price_cumulative of the snapshot
+    pub pc_price_cumulative: u128,
+    /// coin_price_cumulative of the snapshot
+    pub coin_price_cumulative: u128,
+}
+
+/// Ring buffer of price cumulative snapshots of one amm
+#[repr(C, packed)]
+#[derive(Clone, Copy)]
+pub struct ObservationState {
+    /// amm key
+    pub amm_id: Pubkey,
+    /// index of the latest observation
+    pub observation_index: u64,
+    /// padding
+    pub padding: [u64; 4],
+    /// observations, zero timestamp means unused
+    pub observations: [Observation; OBSERVATION_NUM],
+}
+impl_loadable!(ObservationState);
+
+impl ObservationState {
+    /// load_mut_checked
+    #[inline]
+    pub fn load_mut_checked<'a>(
+        account: &'a AccountInfo,
+        program_id: &Pubkey,
+        amm_id: &Pubkey,
+    ) -> Result<RefMut<'a, Self>, ProgramError> {
+        if account.owner != program_id {
+            return Err(AmmError::InvalidOwner.into());
+        }
+        if account.data_len() != size_of::<Self>() {
+            return Err(AmmError::ExpectedAccount.into());
+        }
+        let data = Self::load_mut(account)?;
+        if data.amm_id != *amm_id {
+            return Err(AmmError::InvalidObservationAccount.into());
+        }
+        Ok(data)
+    }
+
+    /// load_checked
+    #[inline]
+    pub fn load_checked<'a>(
+        account: &'a AccountInfo,
+        program_id: &Pubkey,
+        amm_id: &Pubkey,
+    ) -> Result<Ref<'a, Self>, ProgramError> {
+        if account.owner != program_id {
+            return Err(AmmError::InvalidOwner.into());
+        }
+        if account.data_len() != size_of::<Self>() {
+            return Err(AmmError::ExpectedAccount.into());
+        }
+        let data = Self::load(account)?;
+        if data.amm_id != *amm_id {
+            return Err(AmmError::InvalidObservationAccount.into());
+        }
+        Ok(data)
+    }
+
+    pub fn latest(&self) -> Observation {
+        self.observations[self.observation_index as usize % OBSERVATION_NUM]
+    }
+
+    /// append an observation, returns false if it is not newer than the latest one
+    pub fn push(&mut self, observation: Observation) -> bool {
+        let latest_timestamp = self.latest().timestamp;
+        if observation.timestamp <= latest_timestamp {
+            return false;
+        }
+        if latest_timestamp != 0 {
+            self.observation_index = (self.observation_index + 1) % OBSERVATION_NUM as u64;
+        }
+        self.observations[self.observation_index as usize] = observation;
+        true
+    }
+
+    /// the newest observation taken at or before the timestamp
+    pub fn observation_before(&self, timestamp: u64) -> Option<Observation> {
+        let mut index = self.observation_index as usize % OBSERVATION_NUM;
+        for _ in 0..OBSERVATION_NUM {
+            let observation = self.observations[index];
+            if observation.timestamp == 0 {
+                return None;
+            }
+            if observation.timestamp <= timestamp {
+                return Some(observation);
+            }
+            index = (index + OBSERVATION_NUM - 1) % OBSERVATION_NUM;
+        }
+        None
+    }
+}
+
+/// Result of GetTwap, written as return data
+#[repr(C, packed)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct TwapResult {
+    /// timestamp of the observation the window starts at
+    pub start_time: u64,
+    /// state_data.price_last_update_time
+    pub end_time: u64,
+    /// average Q64.64 coin price in pc
+    pub pc_price_x64: u128,
+    /// average Q64.64 pc price in coin
+    pub coin_price_x64: u128,
+}
+impl_loadable!(TwapResult);
+
+/// Merkle allowlist of the launch phase of one amm
+#[repr(C, packed)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Allowlist {
+    /// amm key
+    pub amm_id: Pubkey,
+    /// root of the merkle tree of Allowlist::leaf(wallet, cap)
+    pub merkle_root: [u8; 32],
+    /// padding
+    pub padding: [u64; 8],
+}
+impl_loadable!(Allowlist);
+
+impl Allowlist {
+    /// load_checked
+    #[inline]
+    pub fn load_checked<'a>(
+        account: &'a AccountInfo,
+        program_id: &Pubkey,
+        amm_id: &Pubkey,
+    ) -> Result<Ref<'a, Self>, ProgramError> {
+        if account.owner != program_id {
+            return Err(AmmError::InvalidOwner.into());
+        }
+        if account.data_len() != size_of::<Self>() {
+            return Err(AmmError::ExpectedAccount.into());
+        }
+        let data = Self::load(account)?;
+        if data.amm_id != *amm_id {
+            return Err(AmmError::InvalidAllowlistAccount.into());
+        }
+        Ok(data)
+    }
+
+    pub fn leaf(wallet: &Pubkey, cap: u64) -> [u8; 32] {
+        hashv(&[wallet.as_ref(), &cap.to_le_bytes()]).to_bytes()
+    }
+
+    /// verify a proof of sorted pair hashes from the leaf up to the root
+    pub fn verify(&self, wallet: &Pubkey, cap: u64, proof: &[[u8; 32]]) -> bool {
+        let mut node = Self::leaf(wallet, cap);
+        for sibling in proof.iter() {
+            node = if node <= *sibling {
+                hashv(&[&node, sibling]).to_bytes()
+            } else {
+                hashv(&[sibling, &node]).to_bytes()
+            };
+        }
+        node == self.merkle_root
+    }
+}
+
+/// Launch phase swap cap of one allowlisted wallet
+#[repr(C, packed)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct AllowlistEntry {
+    /// amm key
+    pub amm_id: Pubkey,
+    /// allowlisted wallet
+    pub wallet: Pubkey,
+    /// coin amount the wallet can swap during the allowlist phase
+    pub cap: u64,
+    /// coin amount the wallet swapped during the allowlist phase
+    pub used: u64,
+    /// padding
+    pub padding: [u64; 4],
+}
+impl_loadable!(AllowlistEntry);
+
+impl AllowlistEntry {
+    /// load_mut_checked
+    #[inline]
+    pub fn load_mut_checked<'a>(
+        account: &'a AccountInfo,
+        program_id: &Pubkey,
+        amm_id: &Pubkey,
+        wallet: &Pubkey,
+    ) -> Result<RefMut<'a, Self>, ProgramError> {
+        if account.owner != program_id {
+            return Err(AmmError::InvalidOwner.into());
+        }
+        if account.data_len() != size_of::<Self>() {
+            return Err(AmmError::ExpectedAccount.into());
+        }
+        let data = Self::load_mut(account)?;
+        if data.amm_id != *amm_id || data.wallet != *wallet {
+            return Err(AmmError::NotAllowlisted.into());
+        }
+        Ok(data)
+    }
+
+    /// count a swap of coin_amount against the cap
+    pub fn consume(&mut self, coin_amount: u64) -> Result<(), AmmError> {
+        let used = self
+            .used
+            .checked_add(coin_amount)
+            .ok_or(AmmError::AllowlistCapExceeded)?;
+        if used > self.cap {
+            return Err(AmmError::AllowlistCapExceeded);
+        }
+        self.used = used;
+        Ok(())
+    }
+}
+
+/// Lp tokens of one owner escrowed in the amm authority lp account
+#[repr(C, packed)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct LpLock {
+    /// amm key
+    pub amm_id: Pubkey,
+    /// owner allowed to claim fees and withdraw unlocked lp
+    pub owner: Pubkey,
+    /// lp currently held by the lock
+    pub locked_amount: u64,
+    /// lp already withdrawn after unlock
+    pub withdrawn_amount: u64,
+    /// lp claimed as fees
+    pub claimed_fee_amount: u64,
+    /// nothing is unlocked before this time
+    pub unlock_start_time: u64,
+    /// everything is unlocked at this time, linear vesting in between.
+    /// equal to unlock_start_time for a plain timelock
+    pub unlock_end_time: u64,
+    /// Calculator::calc_liquidity_per_lp_x64 at the last fee claim
+    pub liquidity_per_lp_x64: u128,
+    /// 1 for a lock that is never unlocked
+    pub permanent: u64,
+    /// AmmInfoExtension::fee_growth_coin_per_lp at the last fee claim of a permanent lock
+    pub fee_growth_coin_per_lp_x64: u128,
+    /// AmmInfoExtension::fee_growth_pc_per_lp at the last fee claim of a permanent lock
+    pub fee_growth_pc_per_lp_x64: u128,
+    /// padding
+    pub padding: [u64; 3],
+}
+impl_loadable!(LpLock);
+
+impl LpLock {
+    /// load_mut_checked
+    #[inline]
+    pub fn load_mut_checked<'a>(
+        account: &'a AccountInfo,
+        program_id: &Pubkey,
+        amm_id: &Pubkey,
+    ) -> Result<RefMut<'a, Self>, ProgramError> {
+        if account.owner != program_id {
+            return Err(AmmError::InvalidOwner.into());
+        }
+        if account.data_len() != size_of::<Self>() {
+            return Err(AmmError::ExpectedAccount.into());
         }
-        self.vol_max_cut_ratio = 500; // TEN_THOUSAND as denominator
-        self.amount_wave = self
-            .sys_decimal_value
-            .checked_mul(5)
-            .unwrap()
-            .checked_div(1000)
-            .unwrap();
-        self.coin_lot_size = coin_lot_size;
-        self.pc_lot_size = Calculator::convert_in_pc_lot_size(
-            pc_decimals,
-            coin_decimals,
-            pc_lot_size,
-            coin_lot_size,
-            self.sys_decimal_value,
-        );
-        self.min_price_multiplier = 1;
-        self.max_price_multiplier = 1000000000;
-        self.client_order_id = 0;
-        self.padding1 = Zeroable::zeroed();
-        self.recent_epoch = get_recent_epoch().unwrap();
-        self.padding2 = Zeroable::zeroed();
+        let data = Self::load_mut(account)?;
+        if data.amm_id != *amm_id {
+            return Err(AmmError::InvalidLpLockAccount.into());
+        }
+        Ok(data)
+    }
 
-        Ok(())
+    /// load_checked
+    #[inline]
+    pub fn load_checked<'a>(
+        account: &'a AccountInfo,
+        program_id: &Pubkey,
+        amm_id: &Pubkey,
+    ) -> Result<Ref<'a, Self>, ProgramError> {
+        if account.owner != program_id {
+            return Err(AmmError::InvalidOwner.into());
+        }
+        if account.data_len() != size_of::<Self>() {
+            return Err(AmmError::ExpectedAccount.into());
+        }
+        let data = Self::load(account)?;
+        if data.amm_id != *amm_id {
+            return Err(AmmError::InvalidLpLockAccount.into());
+        }
+        Ok(data)
     }
 
-    pub fn incr_client_order_id(&mut self) -> u64 {
-        self.client_order_id = self.client_order_id.wrapping_add(1);
-        if self.client_order_id == 0 {
-            self.client_order_id += 1;
+    pub fn is_permanent(&self) -> bool {
+        self.permanent != 0
+    }
+
+    /// (coin, pc) lp share of the swap fees accrued to the locked lp since the last claim
+    pub fn accrued_fees(&self, extension: &AmmInfoExtension) -> (u64, u64) {
+        (
+            Calculator::calc_fee_growth_accrued(
+                self.locked_amount,
+                self.fee_growth_coin_per_lp_x64,
+                extension.fee_growth_coin_per_lp,
+            ),
+            Calculator::calc_fee_growth_accrued(
+                self.locked_amount,
+                self.fee_growth_pc_per_lp_x64,
+                extension.fee_growth_pc_per_lp,
+            ),
+        )
+    }
+
+    /// Moves the fee growth snapshot to the current fee growth of the pool
+    pub fn snapshot_fee_growth(&mut self, extension: &AmmInfoExtension) {
+        self.fee_growth_coin_per_lp_x64 = extension.fee_growth_coin_per_lp;
+        self.fee_growth_pc_per_lp_x64 = extension.fee_growth_pc_per_lp;
+    }
+
+    /// lp the owner can withdraw at the timestamp
+    pub fn withdrawable_amount(&self, now: u64) -> u64 {
+        if self.is_permanent() || now < self.unlock_start_time {
+            return 0;
         }
-        self.client_order_id
+        let total = self.locked_amount.checked_add(self.withdrawn_amount).unwrap();
+        let unlocked = if now >= self.unlock_end_time {
+            total
+        } else {
+            (u128::from(total) * u128::from(now - self.unlock_start_time)
+                / u128::from(self.unlock_end_time - self.unlock_start_time)) as u64
+        };
+        std::cmp::min(
+            unlocked.saturating_sub(self.withdrawn_amount),
+            self.locked_amount,
+        )
     }
 }
 
@@ -862,10 +1851,32 @@ pub struct AmmConfig {
     pub pnl_owner: Pubkey,
     /// admin amm order owner
     pub cancel_owner: Pubkey,
+    /// mint of the create pool fee, Pubkey::default() charges lamports
+    pub create_pool_fee_mint: Pubkey,
+    /// wallet receiving the treasury share of the pnl, Pubkey::default() is the pnl_owner
+    pub pnl_treasury: Pubkey,
+    /// wallet receiving the buyback share of the pnl
+    pub pnl_buyback: Pubkey,
+    /// pnl share of the treasury, TEN_THOUSAND as denominator
+    pub pnl_treasury_bps: u64,
+    /// pnl share of the buyback, TEN_THOUSAND as denominator
+    pub pnl_buyback_bps: u64,
+    /// pnl share left in the vaults for the lp, TEN_THOUSAND as denominator
+    pub pnl_lp_rebate_bps: u64,
     /// pending
-    pub pending_1: [u64; 28],
+    pub pending_1: [u64; 13],
+    /// program wide AmmRole::PoolAdmin, Pubkey::default() falls back to the amm owner
+    pub pool_admin: Pubkey,
+    /// program wide AmmRole::FeeAdmin, Pubkey::default() falls back to the amm owner
+    pub fee_admin: Pubkey,
+    /// program wide AmmRole::PauseGuardian, Pubkey::default() falls back to the amm owner
+    pub pause_guardian: Pubkey,
+    /// program wide AmmRole::CrankOperator, Pubkey::default() falls back to the amm owner
+    pub crank_operator: Pubkey,
+    /// program wide AmmRole::WhitelistAdmin, Pubkey::default() falls back to the amm owner
+    pub whitelist_admin: Pubkey,
     /// pending
-    pub pending_2: [u64; 31],
+    pub pending_2: [u64; 11],
     /// init amm pool fee amount
     pub create_pool_fee: u64,
 }
@@ -904,6 +1915,64 @@ impl AmmConfig {
         let data = Self::load(account)?;
         Ok(data)
     }
+
+    /// Splits a pnl amount into the (treasury, buyback, lp rebate) shares, the rounding
+    /// goes to the treasury. A config without a split pays everything to the treasury
+    pub fn split_pnl(&self, amount: u64) -> (u64, u64, u64) {
+        if self.pnl_treasury_bps == 0 && self.pnl_buyback_bps == 0 && self.pnl_lp_rebate_bps == 0
+        {
+            return (amount, 0, 0);
+        }
+        let share = |bps: u64| ((amount as u128) * (bps as u128) / TEN_THOUSAND as u128) as u64;
+        let (buyback, lp_rebate) = (share(self.pnl_buyback_bps), share(self.pnl_lp_rebate_bps));
+        (amount - buyback - lp_rebate, buyback, lp_rebate)
+    }
+
+    /// wallet the treasury share is paid to
+    pub fn treasury(&self) -> Pubkey {
+        if self.pnl_treasury == Pubkey::default() {
+            self.pnl_owner
+        } else {
+            self.pnl_treasury
+        }
+    }
+
+    pub fn role(&self, role: AmmRole) -> Pubkey {
+        match role {
+            AmmRole::PoolAdmin => self.pool_admin,
+            AmmRole::FeeAdmin => self.fee_admin,
+            AmmRole::PauseGuardian => self.pause_guardian,
+            AmmRole::CrankOperator => self.crank_operator,
+            AmmRole::WhitelistAdmin => self.whitelist_admin,
+        }
+    }
+
+    pub fn set_role(&mut self, role: AmmRole, holder: Pubkey) {
+        match role {
+            AmmRole::PoolAdmin => self.pool_admin = holder,
+            AmmRole::FeeAdmin => self.fee_admin = holder,
+            AmmRole::PauseGuardian => self.pause_guardian = holder,
+            AmmRole::CrankOperator => self.crank_operator = holder,
+            AmmRole::WhitelistAdmin => self.whitelist_admin = holder,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct RoleAssignment {
+    pub role: u64,
+    pub holder: Pubkey,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct PnlSplit {
+    pub treasury: Pubkey,
+    pub buyback: Pubkey,
+    pub treasury_bps: u64,
+    pub buyback_bps: u64,
+    pub lp_rebate_bps: u64,
 }
 
 #[repr(C)]
@@ -913,6 +1982,30 @@ pub struct LastOrderDistance {
     pub last_order_denominator: u64,
 }
 
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct DynamicFee {
+    pub min_fee_numerator: u64,
+    pub max_fee_numerator: u64,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct CircuitBreaker {
+    pub max_price_impact_bps: u64,
+    pub max_output_bps: u64,
+    pub max_slot_volume_bps: u64,
+    pub max_trips: u64,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct CrankReward {
+    pub reward_pc: u64,
+    pub epoch_cap: u64,
+    pub slot_interval: u64,
+}
+
 /// For simulateTransaction to get instruction data
 #[cfg_attr(feature = "client", derive(Debug))]
 #[derive(Copy, Clone)]
@@ -922,6 +2015,9 @@ pub enum SimulateParams {
     SwapBaseInInfo = 1u64,
     SwapBaseOutInfo = 2u64,
     RunCrankInfo = 3u64,
+    LpLockInfo = 4u64,
+    LadderInfo = 5u64,
+    FeeGrowthInfo = 6u64,
 }
 impl SimulateParams {
     pub fn from_u64(flag: u64) -> Self {
@@ -930,6 +2026,9 @@ impl SimulateParams {
             1u64 => SimulateParams::SwapBaseInInfo,
             2u64 => SimulateParams::SwapBaseOutInfo,
             3u64 => SimulateParams::RunCrankInfo,
+            4u64 => SimulateParams::LpLockInfo,
+            5u64 => SimulateParams::LadderInfo,
+            6u64 => SimulateParams::FeeGrowthInfo,
             _ => unreachable!(),
         }
     }
@@ -940,6 +2039,9 @@ impl SimulateParams {
             SimulateParams::SwapBaseInInfo => 1u64,
             SimulateParams::SwapBaseOutInfo => 2u64,
             SimulateParams::RunCrankInfo => 3u64,
+            SimulateParams::LpLockInfo => 4u64,
+            SimulateParams::LadderInfo => 5u64,
+            SimulateParams::FeeGrowthInfo => 6u64,
         }
     }
 }
@@ -1015,6 +2117,75 @@ impl GetSwapBaseOutData {
     }
 }
 
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct GetLpLockData {
+    pub lp_lock: String,
+    pub owner: String,
+    pub locked_amount: u64,
+    pub withdrawn_amount: u64,
+    pub withdrawable_amount: u64,
+    pub claimed_fee_amount: u64,
+    // fee lp claimable at the current pool liquidity
+    pub claimable_fee_amount: u64,
+    pub unlock_start_time: u64,
+    pub unlock_end_time: u64,
+    pub permanent: bool,
+    // swap fees claimable by a permanent lock
+    pub claimable_fee_coin: u64,
+    pub claimable_fee_pc: u64,
+}
+impl GetLpLockData {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+    pub fn from_json(data: &str) -> Self {
+        serde_json::from_str(data).unwrap()
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct LadderLevel {
+    pub price: u64,
+    pub vol: u64,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct GetLadderData {
+    pub strategy: u64,
+    // pool price the ladder is planned around, sys_decimal_value as denominator
+    pub price: u64,
+    // same units as the TargetOrders entries, closest to the price first
+    pub buy_orders: Vec<LadderLevel>,
+    pub sell_orders: Vec<LadderLevel>,
+}
+impl GetLadderData {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+    pub fn from_json(data: &str) -> Self {
+        serde_json::from_str(data).unwrap()
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct GetFeeGrowthData {
+    // Q64.64 fee growth per lp token now
+    pub fee_growth_coin_per_lp: u128,
+    pub fee_growth_pc_per_lp: u128,
+    // fees accrued to the lp balance since the snapshot
+    pub lp_amount: u64,
+    pub accrued_fee_coin: u64,
+    pub accrued_fee_pc: u64,
+}
+impl GetFeeGrowthData {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+    pub fn from_json(data: &str) -> Self {
+        serde_json::from_str(data).unwrap()
+    }
+}
+
 // Maximum number of hooks that can be whitelisted
 // This determines your account size - 
 pub const MAX_HOOKS: usize = 32; // Allows 32 different transfer hook programs
@@ -1210,7 +2381,8 @@ mod test {
         let total_pnl_pc: u64 = 0x12f3456789abcde0;
         let total_pnl_coin: u64 = 0x1f23456789abcde0;
         let pool_open_time: u64 = 0x123456789abcedf0;
-        let padding: [u64; 2] = [0x123456789abecdf0, 0x123456789aebcdf0];
+        let volatility_ema: u64 = 0x123456789abecdf0;
+        let price_last_update_time: u64 = 0x123456789aebcdf0;
         let orderbook_to_init_time: u64 = 0x123456789eabcdf0;
         let swap_coin_in_amount: u128 = 0x11002233445566778899aabbccddeeff;
         let swap_pc_out_amount: u128 = 0x11220033445566778899aabbccddeeff;
@@ -1229,19 +2401,25 @@ mod test {
         let market_program = Pubkey::new_unique();
         let target_orders = Pubkey::new_unique();
 
-        let mut padding1: [u64; 8] = [0u64; 8];
-        let mut padding1_data = [0u8; 8 * 8];
-        let mut offset = 0;
-        for i in 0..8 {
-            padding1[i] = u64::MAX - i as u64;
-            padding1_data[offset..offset + 8].copy_from_slice(&padding1[i].to_le_bytes());
-            offset += 8;
-        }
+        let dynamic_fee_min_numerator: u32 = 0x1234abcd;
+        let dynamic_fee_max_numerator: u32 = 0x5678ef01;
+        let pc_price_cumulative: u128 = 0x11223344556677008899aabbccddeeff;
+        let coin_price_cumulative: u128 = 0x11223344556677880099aabbccddeeff;
+        let allowlist_end_time: u64 = 0x1234567e89abcdf0;
+        let launch_fee_numerator: u32 = 0x2345bcde;
+        let launch_fee_decay_seconds: u32 = 0x6789f012;
+        let max_price_impact_bps: u16 = 0x12ab;
+        let max_output_bps: u16 = 0x34cd;
+        let max_slot_volume_bps: u16 = 0x56ef;
+        let circuit_breaker_max_trips: u8 = 0x78;
+        let circuit_breaker_trips: u8 = 0x9a;
         let amm_owner = Pubkey::new_unique();
         let lp_amount: u64 = 0x123456e789abcdf0;
         let client_order_id: u64 = 0x12345e6789abcdf0;
         let recent_epoch: u64 = 0x1234e56789abcdf0;
-        let padding2: u64 = 0x123e456789abcdf0;
+        let circuit_breaker_slot: u32 = 0x1234abde;
+        let slot_volume_bps: u16 = 0x5678;
+        let version: u16 = 0xcdf0;
 
         // serialize original data
         let mut pool_data = [0u8; 752];
@@ -1306,9 +2484,9 @@ mod test {
         offset += 8;
         pool_data[offset..offset + 8].copy_from_slice(&pool_open_time.to_le_bytes());
         offset += 8;
-        pool_data[offset..offset + 8].copy_from_slice(&padding[0].to_le_bytes());
+        pool_data[offset..offset + 8].copy_from_slice(&volatility_ema.to_le_bytes());
         offset += 8;
-        pool_data[offset..offset + 8].copy_from_slice(&padding[1].to_le_bytes());
+        pool_data[offset..offset + 8].copy_from_slice(&price_last_update_time.to_le_bytes());
         offset += 8;
         pool_data[offset..offset + 8].copy_from_slice(&orderbook_to_init_time.to_le_bytes());
         offset += 8;
@@ -1343,8 +2521,30 @@ mod test {
         offset += 32;
         pool_data[offset..offset + 32].copy_from_slice(&target_orders.to_bytes());
         offset += 32;
-        pool_data[offset..offset + 8 * 8].copy_from_slice(&padding1_data);
-        offset += 8 * 8;
+        pool_data[offset..offset + 4].copy_from_slice(&dynamic_fee_min_numerator.to_le_bytes());
+        offset += 4;
+        pool_data[offset..offset + 4].copy_from_slice(&dynamic_fee_max_numerator.to_le_bytes());
+        offset += 4;
+        pool_data[offset..offset + 16].copy_from_slice(&pc_price_cumulative.to_le_bytes());
+        offset += 16;
+        pool_data[offset..offset + 16].copy_from_slice(&coin_price_cumulative.to_le_bytes());
+        offset += 16;
+        pool_data[offset..offset + 8].copy_from_slice(&allowlist_end_time.to_le_bytes());
+        offset += 8;
+        pool_data[offset..offset + 4].copy_from_slice(&launch_fee_numerator.to_le_bytes());
+        offset += 4;
+        pool_data[offset..offset + 4].copy_from_slice(&launch_fee_decay_seconds.to_le_bytes());
+        offset += 4;
+        pool_data[offset..offset + 2].copy_from_slice(&max_price_impact_bps.to_le_bytes());
+        offset += 2;
+        pool_data[offset..offset + 2].copy_from_slice(&max_output_bps.to_le_bytes());
+        offset += 2;
+        pool_data[offset..offset + 2].copy_from_slice(&max_slot_volume_bps.to_le_bytes());
+        offset += 2;
+        pool_data[offset] = circuit_breaker_max_trips;
+        offset += 1;
+        pool_data[offset] = circuit_breaker_trips;
+        offset += 1;
         pool_data[offset..offset + 32].copy_from_slice(&amm_owner.to_bytes());
         offset += 32;
         pool_data[offset..offset + 8].copy_from_slice(&lp_amount.to_le_bytes());
@@ -1353,8 +2553,12 @@ mod test {
         offset += 8;
         pool_data[offset..offset + 8].copy_from_slice(&recent_epoch.to_le_bytes());
         offset += 8;
-        pool_data[offset..offset + 8].copy_from_slice(&padding2.to_le_bytes());
-        offset += 8;
+        pool_data[offset..offset + 4].copy_from_slice(&circuit_breaker_slot.to_le_bytes());
+        offset += 4;
+        pool_data[offset..offset + 2].copy_from_slice(&slot_volume_bps.to_le_bytes());
+        offset += 2;
+        pool_data[offset..offset + 2].copy_from_slice(&version.to_le_bytes());
+        offset += 2;
 
         // len check
         assert_eq!(offset, pool_data.len());
@@ -1422,10 +2626,10 @@ mod test {
         assert_eq!(total_pnl_coin, unpack_total_pnl_coin);
         let unpack_pool_open_time = unpack_data.state_data.pool_open_time;
         assert_eq!(pool_open_time, unpack_pool_open_time);
-        for i in 0..2 {
-            let unpack_padding = unpack_data.state_data.padding[i];
-            assert_eq!(padding[i], unpack_padding);
-        }
+        let unpack_volatility_ema = unpack_data.state_data.volatility_ema;
+        assert_eq!(volatility_ema, unpack_volatility_ema);
+        let unpack_price_last_update_time = unpack_data.state_data.price_last_update_time;
+        assert_eq!(price_last_update_time, unpack_price_last_update_time);
         let unpack_orderbook_to_init_time = unpack_data.state_data.orderbook_to_init_time;
         assert_eq!(orderbook_to_init_time, unpack_orderbook_to_init_time);
         let unpack_swap_coin_in_amount = unpack_data.state_data.swap_coin_in_amount;
@@ -1458,10 +2662,30 @@ mod test {
         assert_eq!(market_program, unpack_market_program);
         let unpack_target_orders = unpack_data.target_orders;
         assert_eq!(target_orders, unpack_target_orders);
-        for i in 0..8 {
-            let unpack_padding1 = unpack_data.padding1[i];
-            assert_eq!(padding1[i], unpack_padding1);
-        }
+        let unpack_dynamic_fee_min_numerator = unpack_data.dynamic_fee_min_numerator;
+        assert_eq!(dynamic_fee_min_numerator, unpack_dynamic_fee_min_numerator);
+        let unpack_dynamic_fee_max_numerator = unpack_data.dynamic_fee_max_numerator;
+        assert_eq!(dynamic_fee_max_numerator, unpack_dynamic_fee_max_numerator);
+        let unpack_pc_price_cumulative = unpack_data.pc_price_cumulative;
+        assert_eq!(pc_price_cumulative, unpack_pc_price_cumulative);
+        let unpack_coin_price_cumulative = unpack_data.coin_price_cumulative;
+        assert_eq!(coin_price_cumulative, unpack_coin_price_cumulative);
+        let unpack_allowlist_end_time = unpack_data.allowlist_end_time;
+        assert_eq!(allowlist_end_time, unpack_allowlist_end_time);
+        let unpack_launch_fee_numerator = unpack_data.launch_fee_numerator;
+        assert_eq!(launch_fee_numerator, unpack_launch_fee_numerator);
+        let unpack_launch_fee_decay_seconds = unpack_data.launch_fee_decay_seconds;
+        assert_eq!(launch_fee_decay_seconds, unpack_launch_fee_decay_seconds);
+        let unpack_max_price_impact_bps = unpack_data.max_price_impact_bps;
+        assert_eq!(max_price_impact_bps, unpack_max_price_impact_bps);
+        let unpack_max_output_bps = unpack_data.max_output_bps;
+        assert_eq!(max_output_bps, unpack_max_output_bps);
+        let unpack_max_slot_volume_bps = unpack_data.max_slot_volume_bps;
+        assert_eq!(max_slot_volume_bps, unpack_max_slot_volume_bps);
+        let unpack_circuit_breaker_max_trips = unpack_data.circuit_breaker_max_trips;
+        assert_eq!(circuit_breaker_max_trips, unpack_circuit_breaker_max_trips);
+        let unpack_circuit_breaker_trips = unpack_data.circuit_breaker_trips;
+        assert_eq!(circuit_breaker_trips, unpack_circuit_breaker_trips);
         let unpack_amm_owner = unpack_data.amm_owner;
         assert_eq!(amm_owner, unpack_amm_owner);
         let unpack_lp_amount = unpack_data.lp_amount;
@@ -1470,8 +2694,12 @@ mod test {
         assert_eq!(client_order_id, unpack_client_order_id);
         let unpack_recent_epoch = unpack_data.recent_epoch;
         assert_eq!(recent_epoch, unpack_recent_epoch);
-        let unpack_padding2 = unpack_data.padding2;
-        assert_eq!(padding2, unpack_padding2);
+        let unpack_circuit_breaker_slot = unpack_data.circuit_breaker_slot;
+        assert_eq!(circuit_breaker_slot, unpack_circuit_breaker_slot);
+        let unpack_slot_volume_bps = unpack_data.slot_volume_bps;
+        assert_eq!(slot_volume_bps, unpack_slot_volume_bps);
+        let unpack_version = unpack_data.version;
+        assert_eq!(version, unpack_version);
     }
 
     #[test]
@@ -1535,8 +2763,9 @@ mod test {
         let place_orders_cur: u64 = 0x1234567809abcedf;
         let valid_buy_order_num: u64 = 0x1234567089abcedf;
         let valid_sell_order_num: u64 = 0x1234560789abcedf;
-        let mut padding3 = [0u64; 10];
-        for i in 0..10 {
+        let admin_cancel_client_id: u64 = 0x1234506789abcedf;
+        let mut padding3 = [0u64; 9];
+        for i in 0..9 {
             padding3[i] = 1 << (i + 8 + 6 + MAX_ORDER_LIMIT + MAX_ORDER_LIMIT);
         }
         let free_slot_bits: u128 = 0x112233445566778899aabb00ccddeeff;
@@ -1603,7 +2832,10 @@ mod test {
         offset += 8;
         target_orders_data[offset..offset + 8].copy_from_slice(&valid_sell_order_num.to_le_bytes());
         offset += 8;
-        for i in 0..10 {
+        target_orders_data[offset..offset + 8]
+            .copy_from_slice(&admin_cancel_client_id.to_le_bytes());
+        offset += 8;
+        for i in 0..9 {
             target_orders_data[offset..offset + 8].copy_from_slice(&padding3[i].to_le_bytes());
             offset += 8;
         }
@@ -1691,11 +2923,610 @@ mod test {
         assert_eq!(valid_buy_order_num, unpack_valid_buy_order_num);
         let unpack_valid_sell_order_num = unpack_data.valid_sell_order_num;
         assert_eq!(valid_sell_order_num, unpack_valid_sell_order_num);
+        let unpack_admin_cancel_client_id = unpack_data.admin_cancel_client_id;
+        assert_eq!(admin_cancel_client_id, unpack_admin_cancel_client_id);
         let unpack_padding3 = unpack_data.padding3;
-        for i in 0..10 {
+        for i in 0..9 {
             assert_eq!(padding3[i], unpack_padding3[i]);
         }
         let unpack_free_slot_bits = unpack_data.free_slot_bits;
         assert_eq!(free_slot_bits, unpack_free_slot_bits);
     }
+
+    #[test]
+    fn test_observation_ring_buffer() {
+        let mut observation_state: ObservationState = Zeroable::zeroed();
+        assert_eq!(observation_state.observation_before(u64::MAX), None);
+        for i in 1..=(OBSERVATION_NUM as u64 + 10) {
+            assert!(observation_state.push(Observation {
+                timestamp: i * 10,
+                pc_price_cumulative: i as u128,
+                coin_price_cumulative: i as u128,
+            }));
+        }
+        // stale observation is ignored
+        assert!(!observation_state.push(Observation {
+            timestamp: 10,
+            ..Default::default()
+        }));
+        let latest = observation_state.latest();
+        let latest_timestamp = latest.timestamp;
+        assert_eq!(latest_timestamp, (OBSERVATION_NUM as u64 + 10) * 10);
+
+        let observation = observation_state.observation_before(705).unwrap();
+        let timestamp = observation.timestamp;
+        assert_eq!(timestamp, 700);
+        // overwritten observations are no longer available
+        assert_eq!(observation_state.observation_before(100), None);
+    }
+
+    #[test]
+    fn test_update_price_cumulative() {
+        let mut amm = AmmInfo::default();
+        amm.update_price_cumulative(2_000, 1_000, 100);
+        let pc_price_cumulative = amm.pc_price_cumulative;
+        assert_eq!(pc_price_cumulative, 0);
+
+        amm.update_price_cumulative(2_000, 1_000, 110);
+        let pc_price_cumulative = amm.pc_price_cumulative;
+        let coin_price_cumulative = amm.coin_price_cumulative;
+        assert_eq!(pc_price_cumulative, (2u128 << 64) * 10);
+        assert_eq!(coin_price_cumulative, (1u128 << 63) * 10);
+        assert_eq!(
+            Calculator::calc_twap_x64(0, pc_price_cumulative, 10),
+            Some(2u128 << 64)
+        );
+        let price_last_update_time = amm.state_data.price_last_update_time;
+        assert_eq!(price_last_update_time, 110);
+    }
+
+    #[test]
+    fn test_lp_lock_vesting() {
+        let mut lp_lock = LpLock {
+            locked_amount: 1_000,
+            unlock_start_time: 100,
+            unlock_end_time: 200,
+            ..Default::default()
+        };
+        assert_eq!(lp_lock.withdrawable_amount(99), 0);
+        assert_eq!(lp_lock.withdrawable_amount(150), 500);
+        lp_lock.locked_amount -= 500;
+        lp_lock.withdrawn_amount += 500;
+        assert_eq!(lp_lock.withdrawable_amount(150), 0);
+        assert_eq!(lp_lock.withdrawable_amount(175), 250);
+        assert_eq!(lp_lock.withdrawable_amount(300), 500);
+
+        // plain timelock
+        lp_lock.unlock_end_time = 100;
+        assert_eq!(lp_lock.withdrawable_amount(99), 0);
+        assert_eq!(lp_lock.withdrawable_amount(100), 500);
+
+        // liquidity per lp doubles, the lock keeps the liquidity it started with
+        let last = Calculator::calc_liquidity_per_lp_x64(1_000_000, 1_000_000, 1_000_000);
+        assert_eq!(last, 1u128 << 64);
+        let now = Calculator::calc_liquidity_per_lp_x64(2_000_000, 2_000_000, 1_000_000);
+        assert_eq!(Calculator::calc_lock_fee_lp(1_000, last, now), 500);
+        // rounding keeps the lock whole
+        assert_eq!(Calculator::calc_lock_fee_lp(1_001, last, now), 500);
+        assert_eq!(Calculator::calc_lock_fee_lp(1_000, now, last), 0);
+    }
+
+    #[test]
+    fn test_permanent_lp_lock_fee() {
+        let lp_lock = LpLock {
+            locked_amount: 250,
+            unlock_start_time: u64::MAX,
+            unlock_end_time: u64::MAX,
+            permanent: 1,
+            ..Default::default()
+        };
+        assert_eq!(lp_lock.withdrawable_amount(u64::MAX), 0);
+
+        // a quarter of the lp earns a quarter of the fees
+        let mut extension = AmmInfoExtension::default();
+        extension.accrue_fee_growth(1_000, 2_000, 1_000);
+        assert_eq!(lp_lock.accrued_fees(&extension), (250, 500));
+        // 10 coin and 20 pc are worth 1% of a 1_000 coin / 2_000 pc pool
+        assert_eq!(
+            Calculator::calc_lock_fee_burn_lp(10, 20, 1_000, 2_000, 1_000),
+            10
+        );
+        assert_eq!(
+            Calculator::calc_lock_fee_burn_lp(10, 21, 1_000, 2_000, 1_000),
+            11
+        );
+    }
+
+    #[test]
+    fn test_permanent_lp_locks_created_at_different_times() {
+        let mut extension = AmmInfoExtension::default();
+        let mut first_lock = LpLock {
+            locked_amount: 250,
+            permanent: 1,
+            ..Default::default()
+        };
+        first_lock.snapshot_fee_growth(&extension);
+        // 1 coin and 2 pc per lp before the second lock
+        extension.accrue_fee_growth(1_000, 2_000, 1_000);
+        let mut second_lock = LpLock {
+            locked_amount: 250,
+            permanent: 1,
+            ..Default::default()
+        };
+        second_lock.snapshot_fee_growth(&extension);
+        assert_eq!(second_lock.accrued_fees(&extension), (0, 0));
+        // 0.5 coin and 0.5 pc per lp after it
+        extension.accrue_fee_growth(500, 500, 1_000);
+
+        // the second lock only earns the fees accrued since it was created
+        assert_eq!(first_lock.accrued_fees(&extension), (375, 625));
+        assert_eq!(second_lock.accrued_fees(&extension), (125, 125));
+        // a claim moves the snapshot, the same fees are not claimed twice
+        first_lock.snapshot_fee_growth(&extension);
+        assert_eq!(first_lock.accrued_fees(&extension), (0, 0));
+        assert_eq!(second_lock.accrued_fees(&extension), (125, 125));
+    }
+
+    #[test]
+    fn test_allowlist_merkle_proof() {
+        let wallets = [Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique()];
+        let caps = [100u64, 200, 300];
+        let leaves: Vec<[u8; 32]> = wallets
+            .iter()
+            .zip(caps.iter())
+            .map(|(wallet, cap)| Allowlist::leaf(wallet, *cap))
+            .collect();
+        let hash_pair = |a: [u8; 32], b: [u8; 32]| {
+            if a <= b {
+                hashv(&[&a, &b]).to_bytes()
+            } else {
+                hashv(&[&b, &a]).to_bytes()
+            }
+        };
+        let node01 = hash_pair(leaves[0], leaves[1]);
+        let allowlist = Allowlist {
+            merkle_root: hash_pair(node01, leaves[2]),
+            ..Default::default()
+        };
+        assert!(allowlist.verify(&wallets[0], 100, &[leaves[1], leaves[2]]));
+        assert!(allowlist.verify(&wallets[1], 200, &[leaves[0], leaves[2]]));
+        assert!(allowlist.verify(&wallets[2], 300, &[node01]));
+        // wrong cap or wallet
+        assert!(!allowlist.verify(&wallets[0], 101, &[leaves[1], leaves[2]]));
+        assert!(!allowlist.verify(&Pubkey::new_unique(), 100, &[leaves[1], leaves[2]]));
+
+        let mut allowlist_entry = AllowlistEntry {
+            cap: 100,
+            ..Default::default()
+        };
+        assert!(allowlist_entry.consume(60).is_ok());
+        assert_eq!(
+            allowlist_entry.consume(41),
+            Err(AmmError::AllowlistCapExceeded)
+        );
+        assert!(allowlist_entry.consume(40).is_ok());
+        let used = allowlist_entry.used;
+        assert_eq!(used, 100);
+
+        let mut amm = AmmInfo::default();
+        assert!(!amm.allowlist_active(0));
+        amm.allowlist_end_time = 100;
+        assert!(amm.allowlist_active(99));
+        assert!(!amm.allowlist_active(100));
+    }
+
+    #[test]
+    fn test_check_closable() {
+        let mut amm = AmmInfo::default();
+        amm.status = AmmStatus::SwapOnly.into_u64();
+        assert_eq!(amm.check_closable(0), Err(AmmError::InvalidStatus));
+        amm.status = AmmStatus::Disabled.into_u64();
+        assert_eq!(amm.check_closable(0), Ok(()));
+        // any lp left, even dust, still has a claim on the vaults
+        assert_eq!(amm.check_closable(1), Err(AmmError::PoolNotDrained));
+        amm.lp_amount = 1;
+        assert_eq!(amm.check_closable(0), Err(AmmError::PoolNotDrained));
+        amm.lp_amount = 0;
+        // the pnl must be withdrawn first
+        amm.state_data.need_take_pnl_pc = 1;
+        assert_eq!(amm.check_closable(0), Err(AmmError::PoolNotDrained));
+        amm.state_data.need_take_pnl_pc = 0;
+        amm.state_data.need_take_pnl_coin = 1;
+        assert_eq!(amm.check_closable(0), Err(AmmError::PoolNotDrained));
+    }
+
+    #[test]
+    fn test_launch_fee_decay() {
+        assert_eq!(Calculator::calc_launch_fee_numerator(5000, 25, 0, 100), 5000);
+        assert_eq!(Calculator::calc_launch_fee_numerator(5000, 25, 50, 100), 2513);
+        assert_eq!(Calculator::calc_launch_fee_numerator(5000, 25, 99, 100), 75);
+        assert_eq!(Calculator::calc_launch_fee_numerator(5000, 25, 100, 100), 25);
+        assert_eq!(Calculator::calc_launch_fee_numerator(5000, 25, 1000, 100), 25);
+
+        let mut amm = AmmInfo::default();
+        amm.fees.swap_fee_numerator = 25;
+        amm.fees.swap_fee_denominator = 10000;
+        amm.state_data.pool_open_time = 1000;
+        assert_eq!(amm.swap_fee_numerator(1000), 25);
+        amm.launch_fee_numerator = 5000;
+        amm.launch_fee_decay_seconds = 100;
+        // before open and right at open the full launch fee is charged
+        assert_eq!(amm.swap_fee_numerator(900), 5000);
+        assert_eq!(amm.swap_fee_numerator(1000), 5000);
+        // half way through the decay
+        assert_eq!(amm.swap_fee_numerator(1050), 2513);
+        assert_eq!(amm.swap_fee_numerator(1100), 25);
+    }
+
+    #[test]
+    fn test_circuit_breaker() {
+        assert_eq!(Calculator::calc_output_bps(1, 10000), 1);
+        assert_eq!(Calculator::calc_output_bps(1, 20000), 1);
+        assert_eq!(Calculator::calc_output_bps(500, 10000), 500);
+        assert_eq!(Calculator::calc_output_bps(1, 0), TEN_THOUSAND);
+
+        let mut amm = AmmInfo::default();
+        assert_eq!(amm.check_circuit_breaker(9000, 9000, 10000, 1), Ok(false));
+
+        amm.max_price_impact_bps = 1000;
+        amm.max_output_bps = 500;
+        assert_eq!(
+            amm.check_circuit_breaker(1001, 1, 10000, 1),
+            Err(AmmError::CircuitBreakerTripped)
+        );
+        assert_eq!(
+            amm.check_circuit_breaker(1000, 501, 10000, 1),
+            Err(AmmError::CircuitBreakerTripped)
+        );
+        assert_eq!(amm.check_circuit_breaker(1000, 500, 10000, 1), Ok(false));
+
+        amm.max_output_bps = 0;
+        amm.max_slot_volume_bps = 800;
+        amm.circuit_breaker_max_trips = 2;
+        assert_eq!(amm.check_circuit_breaker(0, 500, 10000, 10), Ok(false));
+        // the swap crossing the slot volume goes through and counts a trip
+        assert_eq!(amm.check_circuit_breaker(0, 500, 10000, 10), Ok(false));
+        let circuit_breaker_trips = amm.circuit_breaker_trips;
+        assert_eq!(circuit_breaker_trips, 1);
+        assert_eq!(
+            amm.check_circuit_breaker(0, 1, 10000, 10),
+            Err(AmmError::CircuitBreakerTripped)
+        );
+        // a gap between slots resets the trips
+        assert_eq!(amm.check_circuit_breaker(0, 800, 10000, 12), Ok(false));
+        let circuit_breaker_trips = amm.circuit_breaker_trips;
+        assert_eq!(circuit_breaker_trips, 1);
+        // the next consecutive slot hitting the limit switches the pool
+        assert_eq!(amm.check_circuit_breaker(0, 800, 10000, 13), Ok(true));
+        let circuit_breaker_trips = amm.circuit_breaker_trips;
+        assert_eq!(circuit_breaker_trips, 2);
+    }
+
+    #[test]
+    fn test_crank_reward() {
+        let mut extension = AmmInfoExtension::default();
+        // no reward configured
+        assert_eq!(extension.take_crank_reward(1, 100, 1_000), 0);
+
+        extension.crank_reward_pc = 10;
+        extension.crank_reward_epoch_cap = 25;
+        extension.crank_reward_slot_interval = 5;
+        assert_eq!(extension.take_crank_reward(1, 100, 1_000), 10);
+        // rate limited until the interval passed
+        assert_eq!(extension.take_crank_reward(1, 104, 1_000), 0);
+        assert_eq!(extension.take_crank_reward(1, 105, 1_000), 10);
+        // the epoch cap only leaves 5
+        assert_eq!(extension.take_crank_reward(1, 110, 1_000), 5);
+        assert_eq!(extension.take_crank_reward(1, 120, 1_000), 0);
+        // a new epoch resets the cap, the reward never exceeds the pnl
+        assert_eq!(extension.take_crank_reward(2, 130, 3), 3);
+        assert_eq!(extension.take_crank_reward(2, 140, 0), 0);
+        let (epoch, paid, last_slot) = (
+            extension.crank_reward_epoch,
+            extension.crank_reward_epoch_paid,
+            extension.crank_reward_last_slot,
+        );
+        assert_eq!((epoch, paid, last_slot), (2, 3, 130));
+    }
+
+    #[test]
+    fn test_fee_growth() {
+        let mut extension = AmmInfoExtension::default();
+        // 30 coin and 10 pc of fees shared by 1_000 lp
+        extension.accrue_fee_growth(30, 0, 1_000);
+        extension.accrue_fee_growth(0, 10, 1_000);
+        let (coin_growth, pc_growth) =
+            (extension.fee_growth_coin_per_lp, extension.fee_growth_pc_per_lp);
+        assert_eq!(Calculator::calc_fee_growth_accrued(100, 0, coin_growth), 2);
+        assert_eq!(Calculator::calc_fee_growth_accrued(1_000, 0, pc_growth), 9);
+        // nothing accrued since the snapshot
+        assert_eq!(Calculator::calc_fee_growth_accrued(100, coin_growth, coin_growth), 0);
+        // no lp, no growth
+        extension.accrue_fee_growth(30, 10, 0);
+        let coin_growth_after = extension.fee_growth_coin_per_lp;
+        assert_eq!(coin_growth_after, coin_growth);
+
+        // the growth wraps, the accrued fees since the snapshot do not
+        extension.fee_growth_coin_per_lp = u128::MAX - (1u128 << 64) + 1;
+        let snapshot = extension.fee_growth_coin_per_lp;
+        extension.accrue_fee_growth(2_000, 0, 1_000);
+        let coin_growth = extension.fee_growth_coin_per_lp;
+        assert!(coin_growth < snapshot);
+        assert_eq!(Calculator::calc_fee_growth_accrued(500, snapshot, coin_growth), 1_000);
+    }
+
+    #[test]
+    fn test_compound_pnl() {
+        let mut extension = AmmInfoExtension::default();
+        let mut state_data = StateData::default();
+        state_data.need_take_pnl_coin = 30;
+        state_data.need_take_pnl_pc = 70;
+        state_data.total_pnl_coin = 100;
+        state_data.total_pnl_pc = 200;
+        // the pnl is kept for WithdrawPnl
+        assert_eq!(extension.compound_pnl(&mut state_data), (0, 0));
+        let need_take_pnl = (state_data.need_take_pnl_coin, state_data.need_take_pnl_pc);
+        assert_eq!(need_take_pnl, (30, 70));
+
+        extension.auto_compound = 1;
+        assert_eq!(extension.compound_pnl(&mut state_data), (30, 70));
+        assert_eq!(extension.compound_pnl(&mut state_data), (0, 0));
+        let need_take_pnl = (state_data.need_take_pnl_coin, state_data.need_take_pnl_pc);
+        let total_pnl = (state_data.total_pnl_coin, state_data.total_pnl_pc);
+        let compounded = (extension.pnl_compounded_coin, extension.pnl_compounded_pc);
+        assert_eq!(need_take_pnl, (0, 0));
+        assert_eq!(total_pnl, (70, 130));
+        assert_eq!(compounded, (30, 70));
+    }
+
+    #[test]
+    fn test_amm_config_split_pnl() {
+        let mut config = AmmConfig::default();
+        // no split configured, the treasury is the pnl owner
+        config.pnl_owner = Pubkey::new_unique();
+        assert_eq!(config.split_pnl(1_001), (1_001, 0, 0));
+        assert_eq!(config.treasury(), config.pnl_owner);
+
+        config.pnl_treasury = Pubkey::new_unique();
+        config.pnl_treasury_bps = 5_000;
+        config.pnl_buyback_bps = 3_000;
+        config.pnl_lp_rebate_bps = 2_000;
+        // the rounding goes to the treasury
+        assert_eq!(config.split_pnl(1_001), (501, 300, 200));
+        assert_eq!(config.split_pnl(0), (0, 0, 0));
+        assert_eq!(config.treasury(), config.pnl_treasury);
+    }
+
+    #[test]
+    fn test_amm_role_holder() {
+        assert_eq!(core::mem::size_of::<AmmConfig>(), 544);
+        assert_eq!(core::mem::size_of::<AmmRoles>(), 256);
+
+        let amm_owner = Pubkey::new_unique();
+        let mut pool_roles = AmmRoles::default();
+        let mut config = AmmConfig::default();
+        // nothing assigned, the amm owner holds every role
+        for role in 0..=AmmRole::WhitelistAdmin.into_u64() {
+            let role = AmmRole::from_u64(role).unwrap();
+            assert_eq!(role.holder(&amm_owner, &pool_roles, &config), amm_owner);
+        }
+        assert!(matches!(
+            AmmRole::from_u64(AmmRole::WhitelistAdmin.into_u64() + 1),
+            Err(AmmError::InvalidInput)
+        ));
+
+        // the config role applies to every pool, the pool override wins over it
+        let fee_admin = Pubkey::new_unique();
+        let pool_fee_admin = Pubkey::new_unique();
+        config.set_role(AmmRole::FeeAdmin, fee_admin);
+        assert_eq!(
+            AmmRole::FeeAdmin.holder(&amm_owner, &pool_roles, &config),
+            fee_admin
+        );
+        assert_eq!(
+            AmmRole::PoolAdmin.holder(&amm_owner, &pool_roles, &config),
+            amm_owner
+        );
+        pool_roles.set(AmmRole::FeeAdmin, pool_fee_admin);
+        assert_eq!(
+            AmmRole::FeeAdmin.holder(&amm_owner, &pool_roles, &config),
+            pool_fee_admin
+        );
+        pool_roles.set(AmmRole::FeeAdmin, Pubkey::default());
+        assert_eq!(
+            AmmRole::FeeAdmin.holder(&amm_owner, &pool_roles, &config),
+            fee_admin
+        );
+
+        assert!(AmmParams::Status.role() == AmmRole::PauseGuardian);
+        assert!(AmmParams::CircuitBreaker.role() == AmmRole::PauseGuardian);
+        assert!(AmmParams::Fees.role() == AmmRole::FeeAdmin);
+        assert!(AmmParams::AutoCompound.role() == AmmRole::FeeAdmin);
+        assert!(AmmParams::AmmOwner.role() == AmmRole::PoolAdmin);
+        assert!(AmmParams::PoolRole.role() == AmmRole::PoolAdmin);
+    }
+
+    #[test]
+    fn test_amm_info_versions() {
+        assert_eq!(AmmInfo::data_len(AMM_INFO_VERSION_V1), Some(752));
+        assert_eq!(AmmInfo::data_len(AMM_INFO_VERSION_V2), Some(752 + 256));
+        assert_eq!(
+            AmmInfo::data_len(AMM_INFO_VERSION_V3),
+            Some(752 + 256 + 256)
+        );
+        assert_eq!(AmmInfo::data_len(0), None);
+        assert_eq!(AmmInfo::version_of_data_len(752), Some(AMM_INFO_VERSION_V1));
+        assert_eq!(AmmInfo::version_of_data_len(752 + 256), Some(AMM_INFO_VERSION_V2));
+        assert_eq!(AmmInfo::version_of_data_len(752 + 256 + 256), Some(AMM_INFO_VERSION_V3));
+        assert_eq!(AmmInfo::version_of_data_len(753), None);
+
+        // version 2 extension layout
+        let ladder_strategy = LadderStrategy::Exponential.into_u64();
+        let crank_reward = [1u64, 2, 3, 4, 5, 6];
+        let pnl = [7u64, 8, 9, 10, 1, 11, 12];
+        let fee_growth = [u128::MAX - 1, 1u128 << 64];
+        let mut padding = [0u64; 14];
+        let mut extension_data = [0u8; 32 * 8];
+        let mut offset = 0;
+        extension_data[offset..offset + 8].copy_from_slice(&ladder_strategy.to_le_bytes());
+        offset += 8;
+        for value in crank_reward.iter().chain(pnl.iter()) {
+            extension_data[offset..offset + 8].copy_from_slice(&value.to_le_bytes());
+            offset += 8;
+        }
+        for value in fee_growth {
+            extension_data[offset..offset + 16].copy_from_slice(&value.to_le_bytes());
+            offset += 16;
+        }
+        for i in 0..14 {
+            padding[i] = u64::MAX - i as u64;
+            extension_data[offset..offset + 8].copy_from_slice(&padding[i].to_le_bytes());
+            offset += 8;
+        }
+        assert_eq!(offset, core::mem::size_of::<AmmInfoExtension>());
+        let unpack_extension: &AmmInfoExtension = bytemuck::from_bytes(&extension_data);
+        let unpack_ladder_strategy = unpack_extension.ladder_strategy;
+        let unpack_crank_reward = [
+            unpack_extension.crank_reward_pc,
+            unpack_extension.crank_reward_epoch_cap,
+            unpack_extension.crank_reward_slot_interval,
+            unpack_extension.crank_reward_epoch,
+            unpack_extension.crank_reward_epoch_paid,
+            unpack_extension.crank_reward_last_slot,
+        ];
+        let unpack_pnl = [
+            unpack_extension.pnl_claimed_coin,
+            unpack_extension.pnl_claimed_pc,
+            unpack_extension.pnl_rebated_coin,
+            unpack_extension.pnl_rebated_pc,
+            unpack_extension.auto_compound,
+            unpack_extension.pnl_compounded_coin,
+            unpack_extension.pnl_compounded_pc,
+        ];
+        let unpack_fee_growth = [
+            unpack_extension.fee_growth_coin_per_lp,
+            unpack_extension.fee_growth_pc_per_lp,
+        ];
+        let unpack_padding = unpack_extension.padding;
+        assert_eq!(ladder_strategy, unpack_ladder_strategy);
+        assert_eq!(crank_reward, unpack_crank_reward);
+        assert_eq!(pnl, unpack_pnl);
+        assert_eq!(fee_growth, unpack_fee_growth);
+        assert_eq!(padding, unpack_padding);
+
+        let program_id = Pubkey::new_unique();
+        let key = Pubkey::new_unique();
+        let mut amm = AmmInfo::default();
+        amm.status = AmmStatus::SwapOnly.into_u64();
+
+        // pools created before the version tag read 0 and load as version 1
+        let mut v1_data = bytemuck::bytes_of(&amm).to_vec();
+        let mut lamports = 0u64;
+        let v1_account = AccountInfo::new(
+            &key,
+            false,
+            true,
+            &mut lamports,
+            &mut v1_data,
+            &program_id,
+            false,
+            0,
+        );
+        assert_eq!(
+            AmmInfo::load_checked(&v1_account, &program_id).unwrap().version(),
+            AMM_INFO_VERSION_V1
+        );
+        assert_eq!(
+            AmmInfo::load_mut_checked_with_extension(&v1_account, &program_id).err(),
+            Some(AmmError::OutdatedAmmAccount.into())
+        );
+        assert!(
+            AmmInfo::load_ladder_strategy(&v1_account, &program_id).unwrap()
+                == LadderStrategy::Fibonacci
+        );
+
+        amm.version = AMM_INFO_VERSION_V2;
+        let mut v2_data = bytemuck::bytes_of(&amm).to_vec();
+        v2_data.extend_from_slice(&extension_data);
+        let mut lamports = 0u64;
+        let v2_account = AccountInfo::new(
+            &key,
+            false,
+            true,
+            &mut lamports,
+            &mut v2_data,
+            &program_id,
+            false,
+            0,
+        );
+        assert_eq!(
+            AmmInfo::load_mut_checked(&v2_account, &program_id).unwrap().version(),
+            AMM_INFO_VERSION_V2
+        );
+        {
+            let (amm, extension) =
+                AmmInfo::load_mut_checked_with_extension(&v2_account, &program_id).unwrap();
+            let status = amm.status;
+            assert_eq!(status, AmmStatus::SwapOnly.into_u64());
+            let unpack_padding = extension.padding;
+            assert_eq!(padding, unpack_padding);
+        }
+        assert!(
+            AmmInfo::load_ladder_strategy(&v2_account, &program_id).unwrap()
+                == LadderStrategy::Exponential
+        );
+        // version 2 pools override no role
+        assert!(AmmInfo::load_roles(&v2_account, &program_id).unwrap() == AmmRoles::default());
+        assert!(AmmInfo::load_roles_mut(&v2_account, &program_id).is_err());
+
+        amm.version = AMM_INFO_VERSION_V3;
+        let mut pool_roles = AmmRoles::default();
+        pool_roles.set(AmmRole::CrankOperator, Pubkey::new_unique());
+        let mut v3_data = bytemuck::bytes_of(&amm).to_vec();
+        v3_data.extend_from_slice(&extension_data);
+        v3_data.extend_from_slice(bytemuck::bytes_of(&pool_roles));
+        let mut lamports = 0u64;
+        let v3_account = AccountInfo::new(
+            &key,
+            false,
+            true,
+            &mut lamports,
+            &mut v3_data,
+            &program_id,
+            false,
+            0,
+        );
+        assert!(AmmInfo::load_roles(&v3_account, &program_id).unwrap() == pool_roles);
+        AmmInfo::load_roles_mut(&v3_account, &program_id)
+            .unwrap()
+            .set(AmmRole::FeeAdmin, key);
+        let unpack_roles = AmmInfo::load_roles(&v3_account, &program_id).unwrap();
+        assert_eq!(unpack_roles.get(AmmRole::FeeAdmin), key);
+        assert_eq!(
+            unpack_roles.get(AmmRole::CrankOperator),
+            pool_roles.get(AmmRole::CrankOperator)
+        );
+        let unpack_padding = AmmInfo::load_extension(&v3_account, &program_id)
+            .unwrap()
+            .padding;
+        assert_eq!(padding, unpack_padding);
+
+        // the data length has to match the version
+        let mut short_data = bytemuck::bytes_of(&amm).to_vec();
+        let mut lamports = 0u64;
+        let short_account = AccountInfo::new(
+            &key,
+            false,
+            true,
+            &mut lamports,
+            &mut short_data,
+            &program_id,
+            false,
+            0,
+        );
+        assert_eq!(
+            AmmInfo::load_checked(&short_account, &program_id).err(),
+            Some(AmmError::ExpectedAccount.into())
+        );
+    }
 }
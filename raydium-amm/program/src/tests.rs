@@ -3,12 +3,46 @@ mod tests {
     use crate::{
         error::AmmError,
         instruction::{
+            simulate_get_fee_growth_info,
+            ConfigArgs,
             CreateToken2022MintInstruction,
+            FeeGrowthSnapshot,
+            SetParamsInstruction,
+            SimulateInstruction,
             UpdateHookWhitelistInstruction,
+            WithdrawSingleSidedInstruction,
             HookWhitelistAction,
             AmmInstruction,
         },
-        state::{HookWhitelist, find_whitelist_pda},
+        math::{Calculator, SwapDirection, U256},
+        openbook_v2::{
+            open_orders_indexer_address, MarketV2, OpenOrdersIndexerV2, OpenOrdersV2,
+            MARKET_DISCRIMINATOR, MARKET_LEN, OPEN_ORDERS_ACCOUNT_DISCRIMINATOR,
+            OPEN_ORDERS_ACCOUNT_LEN, OPEN_ORDERS_INDEXER_DISCRIMINATOR, OPEN_ORDERS_SEED,
+        },
+        process::admin::admin_cancel_batch,
+        process::close_pool::process_close_pool,
+        process::config::process_update_config,
+        process::constants::{
+            get_amm_owner_id, AMM_ASSOCIATED_SEED, AMM_CONFIG_SEED, AUTHORITY_AMM,
+            LP_MINT_ASSOCIATED_SEED,
+        },
+        process::initialize::check_pool_account,
+        process::helpers::{
+            accrue_fee_growth, calc_take_pnl, calculate_single_sided_withdrawal,
+            get_associated_address_and_bump_seed, get_pool_key, raise_calc_pnl,
+            validate_single_sided_withdraw_slippage,
+        },
+        process::monitor::{need_replan, pay_crank_reward, plan_orders},
+        process::set_params::process_set_params,
+        process::withdraw_pnl::process_withdrawpnl,
+        log::{decode_return_data, AmmReturnData, LogType, RETURN_DATA_VERSION},
+        state::{
+            AmmConfig, AmmInfo, AmmInfoExtension, AmmParams, AmmRole, AmmRoles, AmmStatus,
+            HookWhitelist, LadderStrategy, RoleAssignment, AMM_INFO_VERSION_V2,
+            AMM_INFO_VERSION_V3,
+            SimulateParams, TargetOrders, find_whitelist_pda,
+        },
     };
     use solana_program::{
         account_info::{next_account_info, AccountInfo},
@@ -51,6 +85,1202 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_update_config_create_pool_fee_mint_instruction() {
+        let config_args = ConfigArgs {
+            param: 3,
+            owner: None,
+            create_pool_fee: None,
+            create_pool_fee_mint: Some(Pubkey::new_unique()),
+            pnl_split: None,
+            role: None,
+        };
+        let serialized = AmmInstruction::UpdateConfigAccount(config_args).pack().unwrap();
+        let deserialized = AmmInstruction::unpack(&serialized).unwrap();
+        assert_eq!(deserialized, AmmInstruction::UpdateConfigAccount(config_args));
+
+        // the mint is required for param 3
+        let config_args = ConfigArgs {
+            create_pool_fee_mint: None,
+            ..config_args
+        };
+        assert!(AmmInstruction::UpdateConfigAccount(config_args).pack().is_err());
+    }
+
+    #[test]
+    fn test_update_config_cancel_owner_instruction() {
+        let config_args = ConfigArgs {
+            param: 6,
+            owner: Some(Pubkey::new_unique()),
+            create_pool_fee: None,
+            create_pool_fee_mint: None,
+            pnl_split: None,
+            role: None,
+        };
+        let serialized = AmmInstruction::UpdateConfigAccount(config_args).pack().unwrap();
+        let deserialized = AmmInstruction::unpack(&serialized).unwrap();
+        assert_eq!(deserialized, AmmInstruction::UpdateConfigAccount(config_args));
+        // the cancel owner is required for param 6
+        let config_args = ConfigArgs {
+            owner: None,
+            ..config_args
+        };
+        assert!(AmmInstruction::UpdateConfigAccount(config_args).pack().is_err());
+    }
+
+    #[test]
+    fn test_update_config_owner_params() {
+        let program_id = Pubkey::new_unique();
+        let pnl_owner = Pubkey::new_unique();
+        let new_owner = Pubkey::new_unique();
+        let config_key = Pubkey::new_unique();
+        let update = |param: u8| {
+            let mut config = AmmConfig::default();
+            config.pnl_owner = pnl_owner;
+            let mut config_data = bytemuck::bytes_of(&config).to_vec();
+            let (mut config_lamports, mut owner_lamports) = (1_000_000u64, 1_000_000u64);
+            let mut owner_data = Vec::new();
+            let accounts = [
+                AccountInfo::new(
+                    &config_key,
+                    false,
+                    true,
+                    &mut config_lamports,
+                    &mut config_data,
+                    &program_id,
+                    false,
+                    0,
+                ),
+                AccountInfo::new(
+                    &pnl_owner,
+                    true,
+                    false,
+                    &mut owner_lamports,
+                    &mut owner_data,
+                    &program_id,
+                    false,
+                    0,
+                ),
+            ];
+            let config_args = ConfigArgs {
+                param,
+                owner: Some(new_owner),
+                create_pool_fee: None,
+                create_pool_fee_mint: None,
+                pnl_split: None,
+                role: None,
+            };
+            process_update_config(&program_id, &accounts, config_args).unwrap();
+            drop(accounts);
+            let config = *bytemuck::from_bytes::<AmmConfig>(&config_data);
+            (config.pnl_owner, config.cancel_owner)
+        };
+        // param 1 keeps setting the pnl owner
+        assert_eq!(update(0), (new_owner, Pubkey::default()));
+        assert_eq!(update(1), (new_owner, Pubkey::default()));
+        assert_eq!(update(6), (pnl_owner, new_owner));
+    }
+
+    #[test]
+    fn test_role_instructions() {
+        let config_args = ConfigArgs {
+            param: 5,
+            owner: None,
+            create_pool_fee: None,
+            create_pool_fee_mint: None,
+            pnl_split: None,
+            role: Some(RoleAssignment {
+                role: AmmRole::PauseGuardian.into_u64(),
+                holder: Pubkey::new_unique(),
+            }),
+        };
+        let serialized = AmmInstruction::UpdateConfigAccount(config_args).pack().unwrap();
+        let deserialized = AmmInstruction::unpack(&serialized).unwrap();
+        assert_eq!(deserialized, AmmInstruction::UpdateConfigAccount(config_args));
+        // the assignment is required for param 5
+        let config_args = ConfigArgs {
+            role: None,
+            ..config_args
+        };
+        assert!(AmmInstruction::UpdateConfigAccount(config_args).pack().is_err());
+
+        let set_params = SetParamsInstruction {
+            param: AmmParams::PoolRole.into_u64() as u8,
+            value: Some(AmmRole::CrankOperator.into_u64()),
+            new_pubkey: Some(Pubkey::new_unique()),
+            fees: None,
+            last_order_distance: None,
+            dynamic_fee: None,
+            circuit_breaker: None,
+            crank_reward: None,
+        };
+        let serialized = AmmInstruction::SetParams(set_params).pack().unwrap();
+        let deserialized = AmmInstruction::unpack(&serialized).unwrap();
+        assert_eq!(deserialized, AmmInstruction::SetParams(set_params));
+        // the holder is required for a pool role
+        assert!(AmmInstruction::unpack(&serialized[..serialized.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn test_simulate_fee_growth_instruction() {
+        let program_id = Pubkey::new_unique();
+        let instruction = simulate_get_fee_growth_info(
+            &program_id,
+            &Pubkey::new_unique(),
+            1_000,
+            u128::MAX,
+            1u128 << 64,
+        )
+        .unwrap();
+        assert_eq!(
+            AmmInstruction::unpack(&instruction.data).unwrap(),
+            AmmInstruction::SimulateInfo(SimulateInstruction {
+                param: SimulateParams::FeeGrowthInfo as u8,
+                swap_base_in_value: None,
+                swap_base_out_value: None,
+                fee_growth_value: Some(FeeGrowthSnapshot {
+                    lp_amount: 1_000,
+                    fee_growth_coin_per_lp: u128::MAX,
+                    fee_growth_pc_per_lp: 1u128 << 64,
+                }),
+            })
+        );
+        // a truncated snapshot is rejected
+        assert!(AmmInstruction::unpack(&instruction.data[..instruction.data.len() - 1]).is_err());
+    }
+
+    // ===== RETURN DATA TESTS =====
+
+    #[test]
+    fn test_decode_return_data() {
+        let data = AmmReturnData {
+            version: RETURN_DATA_VERSION,
+            log_type: LogType::SwapBaseIn.into_u8(),
+            coin_in: 1_000,
+            pc_out: 1_990,
+            coin_fee: 3,
+            pool_coin: 101_000,
+            pool_pc: 198_010,
+            pool_lp: 50_000,
+            ..Default::default()
+        };
+        let mut bytes = bincode::serialize(&data).unwrap();
+        assert_eq!(decode_return_data(&bytes), Some(data.clone()));
+
+        // fields appended by a newer version are ignored
+        bytes[0] = RETURN_DATA_VERSION + 1;
+        bytes.extend_from_slice(&[0u8; 8]);
+        let decoded = decode_return_data(&bytes).unwrap();
+        assert_eq!(decoded.pool_lp, data.pool_lp);
+
+        assert_eq!(decode_return_data(&[]), None);
+        assert_eq!(decode_return_data(&[0u8; 8]), None);
+    }
+
+    // ===== DYNAMIC FEE TESTS =====
+
+    #[test]
+    fn test_calc_dynamic_fee_numerator() {
+        // no volatility charges the min fee
+        assert_eq!(Calculator::calc_dynamic_fee_numerator(25, 100, 0), 25);
+        // the fee scales linearly up to DYNAMIC_FEE_VOLATILITY_CAP
+        assert_eq!(Calculator::calc_dynamic_fee_numerator(25, 100, 10_000), 62);
+        assert_eq!(Calculator::calc_dynamic_fee_numerator(25, 100, 20_000), 100);
+        // and is clamped to the max fee above it
+        assert_eq!(Calculator::calc_dynamic_fee_numerator(25, 100, 20_001), 100);
+        assert_eq!(Calculator::calc_dynamic_fee_numerator(25, 100, u64::MAX), 100);
+        // an inverted range never goes below the min fee
+        assert_eq!(Calculator::calc_dynamic_fee_numerator(100, 25, 20_000), 100);
+    }
+
+    #[test]
+    fn test_calc_volatility_ema() {
+        // a 10% price move, up or down, with an alpha of 20%
+        assert_eq!(Calculator::calc_volatility_ema(0, 1_000, 1_000, 1_100, 1_000), 20_000);
+        assert_eq!(Calculator::calc_volatility_ema(0, 1_000, 1_000, 900, 1_000), 20_000);
+        assert_eq!(Calculator::calc_volatility_ema(0, 1_000, 1_000, 1_000, 1_100), 18_181);
+        // without a move the ema decays by alpha
+        assert_eq!(Calculator::calc_volatility_ema(20_000, 1_000, 1_000, 1_000, 1_000), 16_000);
+        // the price move is clamped to 100%
+        assert_eq!(Calculator::calc_volatility_ema(0, 1_000, 1_000, 5_000, 1_000), 200_000);
+        // an empty pool leaves the ema as it is
+        assert_eq!(Calculator::calc_volatility_ema(20_000, 0, 1_000, 1_000, 1_000), 20_000);
+        assert_eq!(Calculator::calc_volatility_ema(20_000, 1_000, 1_000, 1_000, 0), 20_000);
+    }
+
+    // ===== FLASH LOAN TESTS =====
+
+    #[test]
+    fn test_calc_flash_fee() {
+        // 0.25% of 1_000 is 2.5, rounded up in favour of the pool
+        assert_eq!(Calculator::calc_flash_fee(1_000, 25, 10_000), 3);
+        assert_eq!(Calculator::calc_flash_fee(400, 25, 10_000), 1);
+        // below one unit the fee is rounded to the nearest unit
+        assert_eq!(Calculator::calc_flash_fee(200, 25, 10_000), 1);
+        assert_eq!(Calculator::calc_flash_fee(100, 25, 10_000), 0);
+        assert_eq!(Calculator::calc_flash_fee(0, 25, 10_000), 0);
+    }
+
+    #[test]
+    fn test_flash_loan_repaid() {
+        // the loan and the fee paid back exactly
+        assert!(Calculator::flash_loan_repaid(1_000, 1_000, 1_003, 1_000, 3, 0));
+        assert!(Calculator::flash_loan_repaid(1_000, 1_000, 1_003, 1_001, 3, 1));
+        // short by one on either side
+        assert!(!Calculator::flash_loan_repaid(1_000, 1_000, 1_002, 1_000, 3, 0));
+        assert!(!Calculator::flash_loan_repaid(1_000, 1_000, 1_003, 1_000, 3, 1));
+        // the vaults hold less than the fee
+        assert!(!Calculator::flash_loan_repaid(1_000, 1_000, 2, 1_000, 3, 0));
+        // repaying in the other token is a swap, even when k does not decrease
+        assert!(!Calculator::flash_loan_repaid(1_000, 1_000, 1_103, 910, 3, 0));
+        assert!(!Calculator::flash_loan_repaid(1_000, 1_000, 2_000, 999, 3, 0));
+        // paying back more than owed is fine
+        assert!(Calculator::flash_loan_repaid(1_000, 1_000, 1_010, 1_005, 3, 1));
+    }
+
+    // ===== SINGLE SIDED LIQUIDITY TESTS =====
+
+    #[test]
+    fn test_zap_in_swap_amount_matches_pool_ratio() {
+        let (reserve_in, reserve_out) = (1_000_000_000u64, 4_000_000_000u64);
+        let (fee_numerator, fee_denominator) = (25u64, 10_000u64);
+        let amount_in = 10_000_000u64;
+        let swap_in = Calculator::calc_zap_in_swap_amount(
+            amount_in,
+            reserve_in,
+            fee_numerator,
+            fee_denominator,
+        );
+        // a bit less than half of the input is swapped, the swap itself moves
+        // the pool ratio towards the input token
+        assert!(swap_in < amount_in / 2 && swap_in > amount_in / 2 - amount_in / 100);
+
+        let swap_in_after_fee = swap_in - swap_in * fee_numerator / fee_denominator;
+        let swap_out = Calculator::swap_token_amount_base_in(
+            swap_in_after_fee.into(),
+            reserve_in.into(),
+            reserve_out.into(),
+            SwapDirection::PC2Coin,
+        )
+        .as_u64();
+        // the rest of the input matches the pool ratio after the swap
+        let lhs = (amount_in - swap_in) as u128 * (reserve_out - swap_out) as u128;
+        let rhs = swap_out as u128 * (reserve_in + swap_in) as u128;
+        assert!(lhs.abs_diff(rhs) * 10_000 <= rhs);
+    }
+
+    #[test]
+    fn test_calculate_single_sided_withdrawal() {
+        // 10% of a 1_000_000 coin, 4_000_000 pc pool
+        let (lp_amount, total_coin, total_pc) = (2_000_000u64, 1_000_000u64, 4_000_000u64);
+
+        // the coin share is swapped back into the pool left after the withdraw
+        let (coin_amount, pc_amount, swap_fee, swap_amount_out) = calculate_single_sided_withdrawal(
+            200_000,
+            lp_amount,
+            total_coin,
+            total_pc,
+            25,
+            10_000,
+            SwapDirection::Coin2PC,
+        )
+        .unwrap();
+        assert_eq!((coin_amount, pc_amount), (100_000, 400_000));
+        // the fee is charged on the swapped side and accrued as a coin swap fee
+        assert_eq!(swap_fee, 250);
+        // 3_600_000 * 99_750 / (900_000 + 99_750)
+        assert_eq!(swap_amount_out, 359_189);
+
+        // the pc share is swapped back for coin
+        let (coin_amount, pc_amount, swap_fee, swap_amount_out) = calculate_single_sided_withdrawal(
+            200_000,
+            lp_amount,
+            total_coin,
+            total_pc,
+            25,
+            10_000,
+            SwapDirection::PC2Coin,
+        )
+        .unwrap();
+        assert_eq!((coin_amount, pc_amount), (100_000, 400_000));
+        assert_eq!(swap_fee, 1_000);
+        // 900_000 * 399_000 / (3_600_000 + 399_000)
+        assert_eq!(swap_amount_out, 89_797);
+
+        // the fee is rounded up in favour of the pool
+        let (coin_amount, _, swap_fee, _) = calculate_single_sided_withdrawal(
+            200_002,
+            lp_amount,
+            total_coin,
+            total_pc,
+            25,
+            10_000,
+            SwapDirection::Coin2PC,
+        )
+        .unwrap();
+        assert_eq!((coin_amount, swap_fee), (100_001, 251));
+
+        // 400_000 + 359_189 pc out
+        let withdraw = WithdrawSingleSidedInstruction {
+            amount: 200_000,
+            min_amount_out: 759_189,
+        };
+        assert_eq!(validate_single_sided_withdraw_slippage(&withdraw, 759_189), Ok(()));
+        assert_eq!(
+            validate_single_sided_withdraw_slippage(&withdraw, 759_188),
+            Err(AmmError::ExceededSlippage)
+        );
+        assert_eq!(
+            validate_single_sided_withdraw_slippage(&withdraw, 0),
+            Err(AmmError::InvalidInput)
+        );
+    }
+
+    #[test]
+    fn test_get_pool_key() {
+        let market = Pubkey::new_unique();
+        let (coin_mint, pc_mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+        // pools with a market are keyed by the market whatever the fee tier
+        assert_eq!(get_pool_key(&market, &coin_mint, &pc_mint, 0), market);
+        assert_eq!(get_pool_key(&market, &coin_mint, &pc_mint, 100), market);
+
+        // pools without one are keyed by the mint pair and the fee tier
+        let no_market = Pubkey::default();
+        let pool_key = get_pool_key(&no_market, &coin_mint, &pc_mint, 0);
+        assert_eq!(pool_key, get_pool_key(&no_market, &coin_mint, &pc_mint, 0));
+        assert_ne!(pool_key, get_pool_key(&no_market, &coin_mint, &pc_mint, 100));
+        // whichever side is the coin, so a pair can not get a second pool of the same tier
+        assert_eq!(pool_key, get_pool_key(&no_market, &pc_mint, &coin_mint, 0));
+    }
+
+    #[test]
+    fn test_plan_orders_fibonacci_ladder() {
+        let mut amm = AmmInfo::default();
+        amm.fees.initialize().unwrap();
+        amm.sys_decimal_value = 1_000_000;
+        amm.order_num = 3;
+        amm.depth = 3;
+        let mut target = TargetOrders::default();
+        // 100_000 pc against 50_000 coin, the price is 2
+        target.plan_x_buy = 100_000_000_000;
+        target.plan_y_buy = 50_000_000_000;
+
+        // the plan continues where the previous step stopped
+        assert!(!plan_orders(&amm, &mut target, LadderStrategy::Fibonacci, 2));
+        let plan_orders_cur = target.plan_orders_cur;
+        assert_eq!(plan_orders_cur, 2);
+        assert!(plan_orders(&amm, &mut target, LadderStrategy::Fibonacci, 5));
+
+        // the fibonacci weights 1, 2, 3 put the levels 1%, 2% and 3% away from the price,
+        // with 3 levels the sequence happens to be evenly spaced
+        let (buy_orders, sell_orders) = (target.buy_orders, target.sell_orders);
+        let buy_prices = buy_orders[..3].iter().map(|order| order.price).collect::<Vec<u64>>();
+        let sell_prices = sell_orders[..3].iter().map(|order| order.price).collect::<Vec<u64>>();
+        assert_eq!(buy_prices, vec![1_980_000, 1_960_000, 1_940_000]);
+        assert_eq!(sell_prices, vec![2_020_000, 2_040_000, 2_060_000]);
+        assert!(buy_orders[..3].iter().all(|order| order.vol != 0));
+        assert!(sell_orders[..3].iter().all(|order| order.vol != 0));
+
+        // a price move under min_separate keeps the ladder
+        target.valid_buy_order_num = 3;
+        target.valid_sell_order_num = 3;
+        assert!(!need_replan(&amm, &target, 100_000_000_000, 50_001_000_000));
+        assert!(need_replan(&amm, &target, 100_000_000_000, 50_100_000_000));
+    }
+
+    #[test]
+    fn test_plan_orders_ladder_strategies() {
+        let mut amm = AmmInfo::default();
+        amm.fees.initialize().unwrap();
+        amm.sys_decimal_value = 1_000_000;
+        amm.order_num = 4;
+        amm.depth = 8;
+
+        // 8% range around the price of 2, closest level first
+        let cases = [
+            (
+                LadderStrategy::Fibonacci,
+                [1_968_000, 1_936_000, 1_904_000, 1_840_000],
+            ),
+            (
+                LadderStrategy::Linear,
+                [1_960_000, 1_920_000, 1_880_000, 1_840_000],
+            ),
+            (
+                LadderStrategy::Exponential,
+                [1_989_400, 1_968_000, 1_925_400, 1_840_000],
+            ),
+            (
+                LadderStrategy::ConcentratedNearMid,
+                [1_997_600, 1_980_000, 1_932_600, 1_840_000],
+            ),
+        ];
+        for (strategy, expected_buy_prices) in cases {
+            assert!(strategy == LadderStrategy::from_u64(strategy.into_u64()));
+            let mut target = TargetOrders::default();
+            target.plan_x_buy = 100_000_000_000;
+            target.plan_y_buy = 50_000_000_000;
+            assert!(plan_orders(&amm, &mut target, strategy, 10));
+
+            let (buy_orders, sell_orders) = (target.buy_orders, target.sell_orders);
+            let buy_prices = buy_orders[..4].iter().map(|order| order.price).collect::<Vec<u64>>();
+            assert_eq!(buy_prices, expected_buy_prices.to_vec());
+            // the sell side mirrors the buy side
+            let sell_prices = sell_orders[..4].iter().map(|order| order.price).collect::<Vec<u64>>();
+            let mirrored = expected_buy_prices
+                .iter()
+                .map(|price| 4_000_000 - price)
+                .collect::<Vec<u64>>();
+            assert_eq!(sell_prices, mirrored);
+            assert!(buy_orders[..4].iter().all(|order| order.vol != 0));
+            // every level is outside the previous one
+            assert!(buy_prices.windows(2).all(|pair| pair[0] > pair[1]));
+        }
+        assert!(LadderStrategy::valid(3));
+        assert!(!LadderStrategy::valid(4));
+    }
+
+    #[test]
+    fn test_calc_take_pnl() {
+        let mut amm = AmmInfo::default();
+        amm.fees.initialize().unwrap();
+        amm.sys_decimal_value = 1_000_000;
+        amm.coin_decimals = 6;
+        amm.pc_decimals = 6;
+        let mut target = TargetOrders::default();
+        let (mut total_pc, mut total_coin) = (1_210_000u64, 1_000_000u64);
+        let (x1, y1) = (U256::from(total_pc), U256::from(total_coin));
+
+        // nothing recorded yet
+        assert_eq!(
+            calc_take_pnl(
+                &target,
+                &mut amm,
+                &mut total_pc,
+                &mut total_coin,
+                x1,
+                y1
+            ),
+            Ok((0, 0))
+        );
+
+        // k grew from 1e12 to 1.21e12, 12% of the growth at the current price is taken
+        target.calc_pnl_x = 1_000_000;
+        target.calc_pnl_y = 1_000_000;
+        assert_eq!(
+            calc_take_pnl(
+                &target,
+                &mut amm,
+                &mut total_pc,
+                &mut total_coin,
+                x1,
+                y1
+            ),
+            Ok((13_200, 10_909))
+        );
+        let (need_take_pnl_pc, need_take_pnl_coin) =
+            (amm.state_data.need_take_pnl_pc, amm.state_data.need_take_pnl_coin);
+        assert_eq!((need_take_pnl_pc, need_take_pnl_coin), (13_200, 10_909));
+        let (total_pnl_pc, total_pnl_coin) =
+            (amm.state_data.total_pnl_pc, amm.state_data.total_pnl_coin);
+        assert_eq!((total_pnl_pc, total_pnl_coin), (13_200, 10_909));
+        assert_eq!((total_pc, total_coin), (1_196_800, 989_091));
+
+        // k below the recorded one
+        target.calc_pnl_x = 2_000_000;
+        assert_eq!(
+            calc_take_pnl(
+                &target,
+                &mut amm,
+                &mut total_pc,
+                &mut total_coin,
+                x1,
+                y1
+            ),
+            Err(AmmError::CalcPnlError.into())
+        );
+    }
+
+    #[test]
+    fn test_calc_take_pnl_after_price_move() {
+        let mut amm = AmmInfo::default();
+        amm.fees.initialize().unwrap();
+        amm.sys_decimal_value = 1_000_000;
+        amm.coin_decimals = 6;
+        amm.pc_decimals = 9;
+        let mut target = TargetOrders::default();
+        target.calc_pnl_x = 1_000_000;
+        target.calc_pnl_y = 1_000_000;
+
+        // k grew from 1e12 to 1.21e12 while the price moved from 1 to 4.84:
+        // the last k at the current price is (2_200_000, 454_545)
+        let (mut total_pc, mut total_coin) = (2_420_000_000u64, 500_000u64);
+        let x1 = Calculator::normalize_decimal_v2(total_pc, amm.pc_decimals, amm.sys_decimal_value);
+        let y1 =
+            Calculator::normalize_decimal_v2(total_coin, amm.coin_decimals, amm.sys_decimal_value);
+        assert_eq!((x1.as_u64(), y1.as_u64()), (2_420_000, 500_000));
+        // 12% of (220_000, 45_455)
+        assert_eq!(
+            calc_take_pnl(
+                &target,
+                &mut amm,
+                &mut total_pc,
+                &mut total_coin,
+                x1.as_u128().into(),
+                y1.as_u128().into()
+            ),
+            Ok((26_400, 5_454))
+        );
+        // the pnl is restored to the native decimals of each side
+        let (need_take_pnl_pc, need_take_pnl_coin) =
+            (amm.state_data.need_take_pnl_pc, amm.state_data.need_take_pnl_coin);
+        assert_eq!((need_take_pnl_pc, need_take_pnl_coin), (26_400_000, 5_454));
+        assert_eq!((total_pc, total_coin), (2_393_600_000, 494_546));
+
+        // a k growth too small to give pnl on both sides takes nothing
+        let mut amm = AmmInfo::default();
+        amm.fees.initialize().unwrap();
+        amm.sys_decimal_value = 1_000_000;
+        amm.coin_decimals = 6;
+        amm.pc_decimals = 6;
+        let (mut total_pc, mut total_coin) = (1_000_010u64, 1_000_000u64);
+        assert_eq!(
+            calc_take_pnl(
+                &target,
+                &mut amm,
+                &mut total_pc,
+                &mut total_coin,
+                U256::from(1_000_010u64),
+                U256::from(1_000_000u64)
+            ),
+            Ok((0, 0))
+        );
+        let need_take_pnl_pc = amm.state_data.need_take_pnl_pc;
+        assert_eq!(need_take_pnl_pc, 0);
+        assert_eq!((total_pc, total_coin), (1_000_010, 1_000_000));
+    }
+
+    #[test]
+    fn test_lp_rebate_is_not_taken_as_pnl_again() {
+        let mut amm = AmmInfo::default();
+        amm.fees.initialize().unwrap();
+        amm.sys_decimal_value = 1_000_000;
+        amm.coin_decimals = 6;
+        amm.pc_decimals = 9;
+        let mut target = TargetOrders::default();
+        target.calc_pnl_x = 1_000_000;
+        target.calc_pnl_y = 1_000_000;
+
+        // WithdrawPnl leaves 1_000 coin and 1_000_000 pc (1_000 normalized) of lp rebate
+        raise_calc_pnl(&mut target, &amm, 1_000, 1_000_000);
+        let (calc_pnl_x, calc_pnl_y) = (target.calc_pnl_x, target.calc_pnl_y);
+        assert_eq!((calc_pnl_x, calc_pnl_y), (1_001_000, 1_001_000));
+        let (mut total_pc, mut total_coin) = (1_001_000_000u64, 1_001_000u64);
+        assert_eq!(
+            calc_take_pnl(
+                &target,
+                &mut amm,
+                &mut total_pc,
+                &mut total_coin,
+                U256::from(1_001_000u64),
+                U256::from(1_001_000u64)
+            ),
+            Ok((0, 0))
+        );
+
+        // nothing is raised before the first baseline
+        let mut target = TargetOrders::default();
+        raise_calc_pnl(&mut target, &amm, 1_000, 1_000_000);
+        let (calc_pnl_x, calc_pnl_y) = (target.calc_pnl_x, target.calc_pnl_y);
+        assert_eq!((calc_pnl_x, calc_pnl_y), (0, 0));
+    }
+
+    #[test]
+    fn test_openbook_v2_open_orders_totals() {
+        let mut market_data = vec![0u8; MARKET_LEN];
+        market_data[..8].copy_from_slice(&MARKET_DISCRIMINATOR);
+        market_data[448..456].copy_from_slice(&10i64.to_le_bytes());
+        market_data[456..464].copy_from_slice(&100i64.to_le_bytes());
+        let market = MarketV2::unpack(&market_data).unwrap();
+        assert_eq!((market.quote_lot_size, market.base_lot_size), (10, 100));
+
+        let mut open_orders_data = vec![0u8; OPEN_ORDERS_ACCOUNT_LEN];
+        // an account with the wrong discriminator is rejected
+        assert!(OpenOrdersV2::unpack(&open_orders_data).is_err());
+        open_orders_data[..8].copy_from_slice(&OPEN_ORDERS_ACCOUNT_DISCRIMINATOR);
+        // asks_base_lots, base_free_native, quote_free_native, locked_maker_fees
+        open_orders_data[152..160].copy_from_slice(&3i64.to_le_bytes());
+        open_orders_data[160..168].copy_from_slice(&7u64.to_le_bytes());
+        open_orders_data[168..176].copy_from_slice(&11u64.to_le_bytes());
+        open_orders_data[176..184].copy_from_slice(&2u64.to_le_bytes());
+        // bids_quote_lots
+        open_orders_data[232..240].copy_from_slice(&5i64.to_le_bytes());
+        // every slot is free except the second one, a resting ask
+        for i in 0..24 {
+            open_orders_data[304 + i * 40 + 32] = 1;
+        }
+        let order = 304 + 40;
+        open_orders_data[order + 16..order + 24].copy_from_slice(&42u64.to_le_bytes());
+        open_orders_data[order + 24..order + 32].copy_from_slice(&1_234i64.to_le_bytes());
+        open_orders_data[order + 32] = 0;
+        open_orders_data[order + 33] = 1;
+
+        let open_orders = OpenOrdersV2::unpack(&open_orders_data).unwrap();
+        assert_eq!(open_orders.orders.len(), 1);
+        assert_eq!(open_orders.orders[0].client_order_id, 42);
+        assert_eq!(open_orders.orders[0].price_lots, 1_234);
+        assert!(open_orders.orders[0].side == serum_dex::matching::Side::Ask);
+        // pc: 5 * 10 + 11 + 2, coin: 3 * 100 + 7
+        assert_eq!(open_orders.native_totals(&market).unwrap(), (63, 307));
+    }
+
+    #[test]
+    fn test_openbook_v2_market_mints_and_next_open_orders() {
+        let base_mint = Pubkey::new_unique();
+        let quote_mint = Pubkey::new_unique();
+        let mut market_data = vec![0u8; MARKET_LEN];
+        market_data[..8].copy_from_slice(&MARKET_DISCRIMINATOR);
+        market_data[448..456].copy_from_slice(&10i64.to_le_bytes());
+        market_data[456..464].copy_from_slice(&100i64.to_le_bytes());
+        market_data[576..608].copy_from_slice(base_mint.as_ref());
+        market_data[608..640].copy_from_slice(quote_mint.as_ref());
+        let market = MarketV2::unpack(&market_data).unwrap();
+        assert_eq!((market.base_mint, market.quote_mint), (base_mint, quote_mint));
+
+        let market_program = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+        let indexer_key = open_orders_indexer_address(&market_program, &owner);
+        let mut lamports = 0u64;
+        let mut indexer_data: Vec<u8> = vec![];
+        let system_program = solana_program::system_program::id();
+        let indexer_info = AccountInfo::new(
+            &indexer_key,
+            false,
+            true,
+            &mut lamports,
+            &mut indexer_data,
+            &system_program,
+            false,
+            0,
+        );
+        // an indexer not created yet numbers the first account 1
+        let indexer =
+            OpenOrdersIndexerV2::load_or_default(&indexer_info, &market_program, &owner).unwrap();
+        let (first, _) = Pubkey::find_program_address(
+            &[OPEN_ORDERS_SEED, owner.as_ref(), &1u32.to_le_bytes()],
+            &market_program,
+        );
+        assert_eq!(indexer.next_open_orders_account(&market_program, &owner).unwrap(), first);
+        // any other indexer is rejected
+        assert!(OpenOrdersIndexerV2::load_or_default(
+            &indexer_info,
+            &market_program,
+            &Pubkey::new_unique()
+        )
+        .is_err());
+
+        // discriminator, bump, created_counter, addresses
+        let mut data = vec![0u8; 8 + 1 + 4 + 4];
+        data[..8].copy_from_slice(&OPEN_ORDERS_INDEXER_DISCRIMINATOR);
+        data[9..13].copy_from_slice(&3u32.to_le_bytes());
+        let indexer = OpenOrdersIndexerV2::unpack(&data).unwrap();
+        let (fourth, _) = Pubkey::find_program_address(
+            &[OPEN_ORDERS_SEED, owner.as_ref(), &4u32.to_le_bytes()],
+            &market_program,
+        );
+        assert_eq!(indexer.next_open_orders_account(&market_program, &owner).unwrap(), fourth);
+    }
+
+    #[test]
+    fn test_admin_cancel_batch() {
+        let client_order_ids = [9, 3, 12, 5, 7];
+        // lowest client order id first, at most limit per call
+        assert_eq!(admin_cancel_batch(&client_order_ids, 0, 2), (vec![3, 5], false));
+        // the next call goes on after the last cancelled order
+        assert_eq!(admin_cancel_batch(&client_order_ids, 5, 2), (vec![7, 9], false));
+        assert_eq!(admin_cancel_batch(&client_order_ids, 9, 2), (vec![12], true));
+        assert_eq!(admin_cancel_batch(&[], 0, 2), (vec![], true));
+    }
+
+    #[test]
+    fn test_single_sided_fee_growth_uses_lp_before_the_trade() {
+        let program_id = Pubkey::new_unique();
+        let amm_key = Pubkey::new_unique();
+        let mut amm = AmmInfo::default();
+        amm.version = AMM_INFO_VERSION_V2;
+        amm.status = AmmStatus::SwapOnly.into_u64();
+        amm.fees.pnl_numerator = 0;
+        amm.fees.pnl_denominator = 100;
+        // a deposit minted 500 lp on top of the 500 lp the pool had before
+        amm.lp_amount = 1_000;
+        let mut lamports = 1_000_000u64;
+        let mut amm_data =
+            [bytemuck::bytes_of(&amm), bytemuck::bytes_of(&AmmInfoExtension::default())].concat();
+        let amm_info = AccountInfo::new(
+            &amm_key,
+            false,
+            true,
+            &mut lamports,
+            &mut amm_data,
+            &program_id,
+            false,
+            0,
+        );
+        accrue_fee_growth(&program_id, &amm_info, 500, 250, 500).unwrap();
+        let extension = AmmInfo::load_extension(&amm_info, &program_id).unwrap();
+        let fee_growth = (extension.fee_growth_coin_per_lp, extension.fee_growth_pc_per_lp);
+        assert_eq!(fee_growth, (1u128 << 64, 1u128 << 63));
+    }
+
+    // ===== ACCOUNT FIXTURE =====
+
+    /// Runs process on the accounts of keys, data and owners, only the account at signer
+    /// signs. The processed account data is left in data
+    fn process_with_accounts<F>(
+        keys: &[Pubkey],
+        data: &mut [Vec<u8>],
+        owners: &[Pubkey],
+        signer: Option<usize>,
+        process: F,
+    ) -> ProgramResult
+    where
+        F: FnOnce(&[AccountInfo]) -> ProgramResult,
+    {
+        let mut lamports = vec![1_000_000u64; keys.len()];
+        let accounts = keys
+            .iter()
+            .zip(lamports.iter_mut())
+            .zip(data.iter_mut())
+            .zip(owners.iter())
+            .enumerate()
+            .map(|(i, (((key, lamports), data), owner))| {
+                AccountInfo::new(key, Some(i) == signer, true, lamports, data, owner, false, 0)
+            })
+            .collect::<Vec<AccountInfo>>();
+        process(&accounts)
+    }
+
+    // ===== POOL ROLE TESTS =====
+
+    fn set_pool_role_signed_by(
+        program_id: &Pubkey,
+        amm_data: &mut Vec<u8>,
+        amm_config: &AmmConfig,
+        signer: Pubkey,
+        role: AmmRole,
+        holder: Pubkey,
+    ) -> ProgramResult {
+        let nonce = bytemuck::from_bytes::<AmmInfo>(&amm_data[..std::mem::size_of::<AmmInfo>()])
+            .nonce;
+        let amm_owner = get_amm_owner_id().unwrap();
+        let keys = [
+            Pubkey::new_unique(),
+            Pubkey::create_program_address(&[AUTHORITY_AMM, &[nonce as u8]], program_id).unwrap(),
+            signer,
+            get_associated_address_and_bump_seed(&amm_owner, &amm_owner, AMM_CONFIG_SEED, program_id)
+                .0,
+        ];
+        let mut data = [
+            std::mem::take(amm_data),
+            Vec::new(),
+            Vec::new(),
+            bytemuck::bytes_of(amm_config).to_vec(),
+        ];
+        let set_params = SetParamsInstruction {
+            param: AmmParams::PoolRole.into_u64() as u8,
+            value: Some(role.into_u64()),
+            new_pubkey: Some(holder),
+            fees: None,
+            last_order_distance: None,
+            dynamic_fee: None,
+            circuit_breaker: None,
+            crank_reward: None,
+        };
+        let result =
+            process_with_accounts(&keys, &mut data, &[*program_id; 4], Some(2), |accounts| {
+                process_set_params(program_id, accounts, set_params)
+            });
+        *amm_data = std::mem::take(&mut data[0]);
+        result
+    }
+
+    #[test]
+    fn test_pool_admin_can_not_grant_itself_other_roles() {
+        let program_id = Pubkey::new_unique();
+        let (_, nonce) = Pubkey::find_program_address(&[AUTHORITY_AMM], &program_id);
+        let pool_admin = Pubkey::new_unique();
+        let mut amm = AmmInfo::default();
+        amm.version = AMM_INFO_VERSION_V3;
+        amm.status = AmmStatus::SwapOnly.into_u64();
+        amm.nonce = nonce as u64;
+        amm.amm_owner = pool_admin;
+        let mut amm_data = [
+            bytemuck::bytes_of(&amm),
+            bytemuck::bytes_of(&AmmInfoExtension::default()),
+            bytemuck::bytes_of(&AmmRoles::default()),
+        ]
+        .concat();
+        let mut amm_config = AmmConfig::default();
+        amm_config.pnl_owner = Pubkey::new_unique();
+        amm_config.fee_admin = Pubkey::new_unique();
+        let pool_roles = |amm_data: &Vec<u8>| {
+            *bytemuck::from_bytes::<AmmRoles>(
+                &amm_data[std::mem::size_of::<AmmInfo>() + std::mem::size_of::<AmmInfoExtension>()..],
+            )
+        };
+
+        // the pool admin can not take over the fee admin or an unassigned pause guardian
+        for role in [AmmRole::FeeAdmin, AmmRole::PauseGuardian] {
+            assert_eq!(
+                set_pool_role_signed_by(
+                    &program_id,
+                    &mut amm_data,
+                    &amm_config,
+                    pool_admin,
+                    role,
+                    pool_admin,
+                ),
+                Err(role.error().into())
+            );
+        }
+        assert!(pool_roles(&amm_data) == AmmRoles::default());
+
+        // the config fee admin overrides its own role, the config authority any role
+        let fee_admin = Pubkey::new_unique();
+        let pause_guardian = Pubkey::new_unique();
+        set_pool_role_signed_by(
+            &program_id,
+            &mut amm_data,
+            &amm_config,
+            amm_config.fee_admin,
+            AmmRole::FeeAdmin,
+            fee_admin,
+        )
+        .unwrap();
+        set_pool_role_signed_by(
+            &program_id,
+            &mut amm_data,
+            &amm_config,
+            amm_config.pnl_owner,
+            AmmRole::PauseGuardian,
+            pause_guardian,
+        )
+        .unwrap();
+        // the fee admin can not override another role
+        assert_eq!(
+            set_pool_role_signed_by(
+                &program_id,
+                &mut amm_data,
+                &amm_config,
+                amm_config.fee_admin,
+                AmmRole::CrankOperator,
+                fee_admin,
+            ),
+            Err(AmmError::InvalidCrankOperator.into())
+        );
+        // the pool admin still hands over its own role
+        let new_pool_admin = Pubkey::new_unique();
+        set_pool_role_signed_by(
+            &program_id,
+            &mut amm_data,
+            &amm_config,
+            pool_admin,
+            AmmRole::PoolAdmin,
+            new_pool_admin,
+        )
+        .unwrap();
+        let roles = pool_roles(&amm_data);
+        assert_eq!(roles.get(AmmRole::FeeAdmin), fee_admin);
+        assert_eq!(roles.get(AmmRole::PauseGuardian), pause_guardian);
+        assert_eq!(roles.get(AmmRole::CrankOperator), Pubkey::default());
+        assert_eq!(roles.get(AmmRole::PoolAdmin), new_pool_admin);
+    }
+
+    // ===== CLOSE POOL TESTS =====
+
+    fn close_pool_signed_by(
+        program_id: &Pubkey,
+        amm: &AmmInfo,
+        signer: Pubkey,
+        is_signer: bool,
+    ) -> ProgramResult {
+        let amm_owner = get_amm_owner_id().unwrap();
+        let (amm_config, _) =
+            get_associated_address_and_bump_seed(&amm_owner, &amm_owner, AMM_CONFIG_SEED, program_id);
+        let mut keys = (0..18).map(|_| Pubkey::new_unique()).collect::<Vec<Pubkey>>();
+        keys[0] = spl_token::id();
+        keys[14] = signer;
+        keys[15] = amm_config;
+        let mut data = vec![Vec::new(); 18];
+        data[1] = bytemuck::bytes_of(amm).to_vec();
+        data[15] = bytemuck::bytes_of(&AmmConfig::default()).to_vec();
+        let signer = if is_signer { Some(14) } else { None };
+        process_with_accounts(&keys, &mut data, &[*program_id; 18], signer, |accounts| {
+            process_close_pool(program_id, accounts)
+        })
+    }
+
+    #[test]
+    fn test_close_pool_pool_admin_check() {
+        let program_id = Pubkey::new_unique();
+        let pool_admin = Pubkey::new_unique();
+        let mut amm = AmmInfo::default();
+        amm.status = AmmStatus::Disabled.into_u64();
+        amm.amm_owner = pool_admin;
+
+        assert_eq!(
+            close_pool_signed_by(&program_id, &amm, pool_admin, false),
+            Err(AmmError::InvalidSignAccount.into())
+        );
+        assert_eq!(
+            close_pool_signed_by(&program_id, &amm, Pubkey::new_unique(), true),
+            Err(AmmError::InvalidPoolAdmin.into())
+        );
+        // the pool admin gets past the role check to the pool account checks
+        assert_eq!(
+            close_pool_signed_by(&program_id, &amm, pool_admin, true),
+            Err(AmmError::InvalidProgramAddress.into())
+        );
+    }
+
+    #[test]
+    fn test_closed_pool_key_can_not_be_initialized_again() {
+        let program_id = Pubkey::new_unique();
+        let (coin_mint, pc_mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let pool_key = get_pool_key(&Pubkey::default(), &coin_mint, &pc_mint, 0);
+        let system_program = solana_program::system_program::id();
+        let spl_token_program = spl_token::id();
+
+        // the closed amm account is back to the system program and could be created again
+        let (amm_key, _) = get_associated_address_and_bump_seed(
+            &program_id,
+            &pool_key,
+            AMM_ASSOCIATED_SEED,
+            &program_id,
+        );
+        let (mut amm_lamports, mut amm_data) = (0u64, Vec::new());
+        let amm_info = AccountInfo::new(
+            &amm_key,
+            false,
+            true,
+            &mut amm_lamports,
+            &mut amm_data,
+            &system_program,
+            false,
+            0,
+        );
+        assert!(check_pool_account(&program_id, &amm_info, &pool_key, AMM_ASSOCIATED_SEED).is_ok());
+
+        // but the lp mint stays with the spl token program, so Initialize2 refuses the pool key
+        let (lp_mint_key, _) = get_associated_address_and_bump_seed(
+            &program_id,
+            &pool_key,
+            LP_MINT_ASSOCIATED_SEED,
+            &program_id,
+        );
+        let (mut lp_mint_lamports, mut lp_mint_data) =
+            (1_000_000u64, vec![0u8; spl_token::state::Mint::LEN]);
+        let lp_mint_info = AccountInfo::new(
+            &lp_mint_key,
+            false,
+            true,
+            &mut lp_mint_lamports,
+            &mut lp_mint_data,
+            &spl_token_program,
+            false,
+            0,
+        );
+        assert_eq!(
+            check_pool_account(&program_id, &lp_mint_info, &pool_key, LP_MINT_ASSOCIATED_SEED),
+            Err(AmmError::RepeatCreateAmm.into())
+        );
+        // a new pool of the pair takes another fee tier
+        assert_ne!(get_pool_key(&Pubkey::default(), &coin_mint, &pc_mint, 100), pool_key);
+    }
+
+    // ===== WITHDRAW PNL TESTS =====
+
+    fn withdraw_pnl_with_coin_vault(
+        program_id: &Pubkey,
+        amm_data: &mut Vec<u8>,
+        target_orders: &mut TargetOrders,
+        coin_vault: Pubkey,
+    ) -> ProgramResult {
+        let amm = *bytemuck::from_bytes::<AmmInfo>(&amm_data[..std::mem::size_of::<AmmInfo>()]);
+        let amm_owner = get_amm_owner_id().unwrap();
+        let (amm_config, _) =
+            get_associated_address_and_bump_seed(&amm_owner, &amm_owner, AMM_CONFIG_SEED, program_id);
+        let mut keys = (0..19).map(|_| Pubkey::new_unique()).collect::<Vec<Pubkey>>();
+        keys[0] = spl_token::id();
+        keys[2] = amm_config;
+        keys[3] = Pubkey::create_program_address(&[AUTHORITY_AMM, &[amm.nonce as u8]], program_id)
+            .unwrap();
+        keys[4] = amm.open_orders;
+        keys[5] = coin_vault;
+        keys[6] = amm.pc_vault;
+        // the default AmmConfig has the default pnl owner
+        keys[9] = Pubkey::default();
+        keys[10] = amm.target_orders;
+        keys[11] = amm.market_program;
+        keys[12] = amm.market;
+        let mut data = vec![Vec::new(); 19];
+        data[1] = std::mem::take(amm_data);
+        data[2] = bytemuck::bytes_of(&AmmConfig::default()).to_vec();
+        target_orders.owner = bytemuck::cast(keys[1].to_bytes());
+        data[10] = bytemuck::bytes_of(target_orders).to_vec();
+        let result =
+            process_with_accounts(&keys, &mut data, &[*program_id; 19], Some(9), |accounts| {
+                process_withdrawpnl(program_id, accounts)
+            });
+        *amm_data = std::mem::take(&mut data[1]);
+        *target_orders = *bytemuck::from_bytes::<TargetOrders>(&data[10]);
+        result
+    }
+
+    #[test]
+    fn test_withdraw_pnl_auto_compound_checks_accounts() {
+        let program_id = Pubkey::new_unique();
+        let (_, nonce) = Pubkey::find_program_address(&[AUTHORITY_AMM], &program_id);
+        let mut amm = AmmInfo::default();
+        amm.version = AMM_INFO_VERSION_V2;
+        amm.status = AmmStatus::SwapOnly.into_u64();
+        amm.nonce = nonce as u64;
+        amm.coin_vault = Pubkey::new_unique();
+        amm.pc_vault = Pubkey::new_unique();
+        amm.open_orders = Pubkey::new_unique();
+        amm.target_orders = Pubkey::new_unique();
+        amm.market_program = Pubkey::new_unique();
+        amm.market = Pubkey::new_unique();
+        // no decimals on either side, the normalized amounts are the native ones
+        amm.sys_decimal_value = 1;
+        amm.state_data.need_take_pnl_coin = 30;
+        amm.state_data.need_take_pnl_pc = 70;
+        amm.state_data.total_pnl_coin = 100;
+        amm.state_data.total_pnl_pc = 200;
+        let mut extension = AmmInfoExtension::default();
+        extension.auto_compound = 1;
+        let amm_data = [bytemuck::bytes_of(&amm), bytemuck::bytes_of(&extension)].concat();
+        let state_data = |amm_data: &Vec<u8>| {
+            let amm = bytemuck::from_bytes::<AmmInfo>(&amm_data[..std::mem::size_of::<AmmInfo>()]);
+            (
+                amm.state_data.need_take_pnl_coin,
+                amm.state_data.need_take_pnl_pc,
+                amm.state_data.total_pnl_coin,
+                amm.state_data.total_pnl_pc,
+            )
+        };
+
+        let mut target_orders = TargetOrders::default();
+        target_orders.calc_pnl_x = 1_000_000;
+        target_orders.calc_pnl_y = 1_000_000;
+
+        // a wrong vault is rejected before the pnl is compounded
+        let mut data = amm_data.clone();
+        assert_eq!(
+            withdraw_pnl_with_coin_vault(
+                &program_id,
+                &mut data,
+                &mut target_orders,
+                Pubkey::new_unique()
+            ),
+            Err(AmmError::InvalidCoinVault.into())
+        );
+        assert_eq!(state_data(&data), (30, 70, 100, 200));
+
+        // the pnl is compounded, need_take_pnl and total_pnl no longer count it
+        let mut data = amm_data.clone();
+        withdraw_pnl_with_coin_vault(&program_id, &mut data, &mut target_orders, amm.coin_vault)
+            .unwrap();
+        assert_eq!(state_data(&data), (0, 0, 70, 130));
+        let extension =
+            *bytemuck::from_bytes::<AmmInfoExtension>(&data[std::mem::size_of::<AmmInfo>()..]);
+        let compounded = (extension.pnl_compounded_coin, extension.pnl_compounded_pc);
+        assert_eq!(compounded, (30, 70));
+        // and the pnl baseline counts it as pool reserves, not growth to take pnl from
+        let calc_pnl = (target_orders.calc_pnl_x, target_orders.calc_pnl_y);
+        assert_eq!(calc_pnl, (1_000_070, 1_000_030));
+    }
+
+    // ===== CRANK REWARD TESTS =====
+
+    fn pay_crank_reward_in(
+        program_id: &Pubkey,
+        amm_data: &mut Vec<u8>,
+        crank_reward_mint: Pubkey,
+        cranks: &[(u64, u64)],
+    ) -> ProgramResult {
+        let crank_reward_pc = spl_token::state::Account {
+            mint: crank_reward_mint,
+            owner: Pubkey::new_unique(),
+            state: spl_token::state::AccountState::Initialized,
+            ..Default::default()
+        };
+        let mut keys = (0..5).map(|_| Pubkey::new_unique()).collect::<Vec<Pubkey>>();
+        keys[1] = spl_token::id();
+        let mut data = vec![Vec::new(); 5];
+        data[0] = std::mem::take(amm_data);
+        data[4] = vec![0u8; spl_token::state::Account::LEN];
+        crank_reward_pc.pack_into_slice(&mut data[4]);
+        let owners = [*program_id, *program_id, *program_id, keys[1], keys[1]];
+        let result = process_with_accounts(&keys, &mut data, &owners, None, |accounts| {
+            cranks.iter().try_for_each(|(epoch, slot)| {
+                pay_crank_reward(
+                    program_id,
+                    &accounts[0],
+                    &accounts[1],
+                    &accounts[2],
+                    &accounts[3],
+                    &accounts[4],
+                    *epoch,
+                    *slot,
+                )
+            })
+        });
+        *amm_data = std::mem::take(&mut data[0]);
+        result
+    }
+
+    #[test]
+    fn test_pay_crank_reward_bounded_by_unclaimed_pnl() {
+        let program_id = Pubkey::new_unique();
+        let pc_mint = Pubkey::new_unique();
+        let mut amm = AmmInfo::default();
+        amm.version = AMM_INFO_VERSION_V2;
+        amm.status = AmmStatus::SwapOnly.into_u64();
+        amm.pc_vault_mint = pc_mint;
+        amm.state_data.need_take_pnl_pc = 3;
+        let mut extension = AmmInfoExtension::default();
+        extension.crank_reward_pc = 10;
+        extension.crank_reward_epoch_cap = 100;
+        let amm_data = [bytemuck::bytes_of(&amm), bytemuck::bytes_of(&extension)].concat();
+        let paid_from_pnl = |amm_data: &Vec<u8>| {
+            let (amm, extension) = amm_data.split_at(std::mem::size_of::<AmmInfo>());
+            let amm = bytemuck::from_bytes::<AmmInfo>(amm);
+            let extension = bytemuck::from_bytes::<AmmInfoExtension>(extension);
+            (
+                amm.state_data.need_take_pnl_pc,
+                extension.pnl_claimed_pc,
+                extension.crank_reward_epoch_paid,
+            )
+        };
+
+        // the reward of 10 is cut down to the 3 pc of unclaimed pnl
+        let mut data = amm_data.clone();
+        pay_crank_reward_in(&program_id, &mut data, pc_mint, &[(1, 100)]).unwrap();
+        assert_eq!(paid_from_pnl(&data), (0, 3, 3));
+        // later cranks, in a new epoch too, have nothing left to pay from
+        pay_crank_reward_in(&program_id, &mut data, pc_mint, &[(1, 200), (2, 300)]).unwrap();
+        assert_eq!(paid_from_pnl(&data), (0, 3, 3));
+
+        // a crank reward account of another mint is rejected before anything is paid
+        let mut data = amm_data.clone();
+        assert_eq!(
+            pay_crank_reward_in(&program_id, &mut data, Pubkey::new_unique(), &[(1, 100)]),
+            Err(AmmError::InvalidPCMint.into())
+        );
+        assert_eq!(paid_from_pnl(&data), (3, 0, 0));
+    }
+
     // ===== EXECUTE TRANSFER HOOK TESTS =====
 
     #[test]